@@ -7,8 +7,11 @@ use chrono::Utc;
 use lopdf::Document;
 use serde_json::{json, Value};
 
-use hrt_shared::convert::convert_hormone;
-use hrt_shared::types::Hormone;
+use hrt_shared::convert::{
+    convert_batch as batch_convert, convert_item, hormone_unit_catalog, ConvertItem,
+};
+use hrt_shared::regimen::{optimize_regimen as optimize, RegimenRequest};
+use hrt_shared::types::HormoneUnits;
 
 use crate::storage::{
     content_type_from_ext, delete_bloodtest_pdf as delete_bloodtest_pdf_file, delete_photo,
@@ -89,44 +92,80 @@ pub async fn post_settings(body: Bytes) -> Response {
 }
 
 pub async fn convert(body: Bytes) -> Response {
-    let payload: Value = match serde_json::from_slice(&body) {
+    let item: ConvertItem = match serde_json::from_slice(&body) {
         Ok(value) => value,
-        Err(_) => return json_error("Conversion failed", StatusCode::INTERNAL_SERVER_ERROR),
+        Err(err) => {
+            return json_error(
+                &format!("Invalid JSON body: {err}"),
+                StatusCode::BAD_REQUEST,
+            )
+        }
     };
 
-    let value = payload.get("value").and_then(|v| v.as_f64());
-    let hormone_value = payload.get("hormone").and_then(|v| v.as_str());
-    let from_unit = payload.get("fromUnit").and_then(|v| v.as_str());
-    let to_unit = payload.get("toUnit").and_then(|v| v.as_str());
+    match convert_item(&item) {
+        Ok(converted) => {
+            let unit = item.toUnit.as_deref().unwrap_or_default().trim();
+            Json(json!({ "value": converted, "unit": unit })).into_response()
+        }
+        Err(err) => (
+            StatusCode::BAD_REQUEST,
+            Json(json!({ "error": err.message, "code": err.code, "field": err.field })),
+        )
+            .into_response(),
+    }
+}
 
-    let value = match value {
-        Some(value) if value.is_finite() => value,
-        _ => return json_error("Invalid value", StatusCode::BAD_REQUEST),
-    };
+const MAX_BATCH_ITEMS: usize = 1000;
 
-    let hormone_str = match hormone_value {
-        Some(value) => value,
-        None => return json_error("Conversion failed", StatusCode::BAD_REQUEST),
-    };
-    let from_unit = match from_unit {
-        Some(value) => value,
-        None => return json_error("Conversion failed", StatusCode::BAD_REQUEST),
+/// Accepts `{ "items": [...] }` or a bare array of `/api/convert` bodies.
+/// Failed items carry their own error; the request itself still succeeds.
+pub async fn convert_batch(body: Bytes) -> Response {
+    let payload: Value = match serde_json::from_slice(&body) {
+        Ok(value) => value,
+        Err(err) => {
+            return json_error(
+                &format!("Invalid JSON body: {err}"),
+                StatusCode::BAD_REQUEST,
+            )
+        }
     };
-    let to_unit = match to_unit {
-        Some(value) => value,
-        None => return json_error("Conversion failed", StatusCode::BAD_REQUEST),
+    let items = match payload {
+        Value::Array(items) => items,
+        Value::Object(mut map) => match map.remove("items") {
+            Some(Value::Array(items)) => items,
+            _ => return json_error("Missing field \"items\"", StatusCode::BAD_REQUEST),
+        },
+        _ => return json_error("Expected an array of items", StatusCode::BAD_REQUEST),
     };
+    if items.len() > MAX_BATCH_ITEMS {
+        return json_error(
+            &format!("At most {MAX_BATCH_ITEMS} items per request"),
+            StatusCode::PAYLOAD_TOO_LARGE,
+        );
+    }
 
-    let hormone: Hormone = match serde_json::from_value(Value::String(hormone_str.to_string())) {
+    let results = batch_convert(&items);
+    let failed = results.iter().filter(|r| r.error.is_some()).count();
+    Json(json!({
+        "results": results,
+        "converted": results.len() - failed,
+        "failed": failed,
+    }))
+    .into_response()
+}
+
+pub async fn convert_units() -> Response {
+    let units: Vec<&str> = HormoneUnits::ALL.iter().map(|unit| unit.symbol()).collect();
+    Json(json!({ "hormones": hormone_unit_catalog(), "units": units })).into_response()
+}
+
+pub async fn optimize_regimen(body: Bytes) -> Response {
+    let request: RegimenRequest = match serde_json::from_slice(&body) {
         Ok(value) => value,
         Err(err) => return json_error(&err.to_string(), StatusCode::BAD_REQUEST),
     };
-
-    match convert_hormone(value, hormone, from_unit, to_unit) {
-        Ok(converted) => {
-            let rounded = (converted * 1000.0).round() / 1000.0;
-            Json(json!({ "value": rounded, "unit": to_unit })).into_response()
-        }
+    match optimize(&request) {
+        Ok(options) => Json(json!({ "options": options })).into_response(),
         Err(err) => json_error(&err, StatusCode::BAD_REQUEST),
     }
 }
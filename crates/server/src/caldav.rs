@@ -0,0 +1,856 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashSet;
+use std::hash::{Hash, Hasher};
+
+use axum::body::{Body, Bytes};
+use axum::extract::{OriginalUri, Path, Query};
+use axum::http::{HeaderMap, Method, StatusCode};
+use axum::response::Response;
+use chrono::NaiveDateTime;
+use serde_json::Value;
+
+use hrt_shared::logic::record_scheduled_dose;
+
+use crate::ics::{
+    escape_text, scheduled_dose_label, scheduled_dose_times, to_ics_date_time, typed_data,
+    unreadable_data, IcsOptions, IcsQuery,
+};
+use crate::storage::{read_data_value, read_settings_value, write_data_value};
+
+const DAY_MS: i64 = 24 * 60 * 60 * 1000;
+const TODO_HORIZON_DAYS: i64 = 30;
+const CALENDAR_NAME: &str = "HRT Dose Check-off";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TodoUpdate {
+    pub uid: String,
+    /// A regimen id; older UIDs carry the medication type instead.
+    pub regimen: String,
+    pub scheduled_ms: i64,
+    pub completed: bool,
+    pub completed_ms: Option<i64>,
+}
+
+/// One scheduled dose as served from the collection.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DoseTodo {
+    pub uid: String,
+    pub summary: String,
+    pub scheduled_ms: i64,
+    /// When the dose was logged; `None` while it is still open.
+    pub completed_ms: Option<i64>,
+}
+
+async fn read_conf() -> Value {
+    match read_settings_value().await {
+        Ok(Some(value)) => value,
+        _ => serde_json::json!({}),
+    }
+}
+
+fn configured_secret(conf: &Value) -> String {
+    conf.get("icsSecret")
+        .and_then(|v| v.as_str())
+        .unwrap_or("")
+        .trim()
+        .to_string()
+}
+
+fn not_found() -> Response {
+    Response::builder()
+        .status(StatusCode::NOT_FOUND)
+        .body("Not found".into())
+        .unwrap()
+}
+
+fn plain(status: StatusCode, message: impl Into<Body>) -> Response {
+    Response::builder()
+        .status(status)
+        .body(message.into())
+        .unwrap()
+}
+
+async fn secret_matches(secret: &str) -> bool {
+    let configured = configured_secret(&read_conf().await);
+    !configured.is_empty() && secret.trim() == configured
+}
+
+async fn read_data() -> Result<Value, Response> {
+    match read_data_value().await {
+        Ok(Some(value)) => Ok(value),
+        Ok(None) => Ok(serde_json::json!({})),
+        Err(_) => Err(plain(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to read data",
+        )),
+    }
+}
+
+fn horizon_options(query: IcsQuery) -> IcsOptions {
+    let horizon_days = match query.horizonDays.and_then(|v| v.parse::<f64>().ok()) {
+        Some(value) if value > 0.0 => value as i64,
+        _ => TODO_HORIZON_DAYS,
+    };
+    IcsOptions {
+        horizon_days,
+        include_past: false,
+        now_ms: chrono::Utc::now().timestamp_millis(),
+    }
+}
+
+pub async fn get_public_todos(Query(query): Query<IcsQuery>) -> Response {
+    let conf = read_conf().await;
+    if !configured_secret(&conf).is_empty() {
+        return not_found();
+    }
+    build_todos(query).await
+}
+
+async fn build_todos(query: IcsQuery) -> Response {
+    let options = horizon_options(query);
+    let data = match read_data_value().await {
+        Ok(Some(value)) => value,
+        _ => serde_json::json!({}),
+    };
+    if let Err(err) = typed_data(&data, options.now_ms) {
+        return unreadable_data(err);
+    }
+
+    Response::builder()
+        .status(StatusCode::OK)
+        .header("Content-Type", "text/calendar; charset=utf-8")
+        .header("Cache-Control", "no-cache, no-store, must-revalidate")
+        .body(generate_todo_ics(&data, options).into())
+        .unwrap()
+}
+
+// With an ICS secret the feed is also a minimal CalDAV collection: clients
+// discover it with PROPFIND, sync with REPORT and compare ETags, and check a
+// dose off by PUTting the completed VTODO back to its resource. The public
+// feed stays a plain read-only GET because check-off writes dosage history.
+pub async fn todo_collection(
+    method: Method,
+    Path(secret): Path<String>,
+    Query(query): Query<IcsQuery>,
+    OriginalUri(uri): OriginalUri,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    if !secret_matches(&secret).await {
+        return not_found();
+    }
+    match method.as_str() {
+        "GET" | "HEAD" => return build_todos(query).await,
+        "OPTIONS" => return dav_options("OPTIONS, GET, HEAD, PROPFIND, REPORT"),
+        "PROPFIND" | "REPORT" => {}
+        _ => return plain(StatusCode::METHOD_NOT_ALLOWED, "Method not allowed"),
+    }
+
+    let data = match read_data().await {
+        Ok(data) => data,
+        Err(response) => return response,
+    };
+    let options = horizon_options(query);
+    if let Err(err) = typed_data(&data, options.now_ms) {
+        return unreadable_data(err);
+    }
+    let todos = dose_todos(&data, options);
+    let href = collection_href(uri.path());
+    let xml = if method.as_str() == "PROPFIND" {
+        let depth = headers
+            .get("Depth")
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or("0");
+        propfind_response(&href, &todos, depth.trim() != "0")
+    } else {
+        report_response(&href, &todos, &String::from_utf8_lossy(&body))
+    };
+    multistatus(xml)
+}
+
+pub async fn todo_resource(
+    method: Method,
+    Path((secret, resource)): Path<(String, String)>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    if !secret_matches(&secret).await {
+        return not_found();
+    }
+    match method.as_str() {
+        "GET" | "HEAD" => get_todo(&resource).await,
+        "PUT" => put_todo(&resource, &headers, &body).await,
+        "OPTIONS" => dav_options("OPTIONS, GET, HEAD, PUT"),
+        _ => plain(StatusCode::METHOD_NOT_ALLOWED, "Method not allowed"),
+    }
+}
+
+async fn get_todo(resource: &str) -> Response {
+    let data = match read_data().await {
+        Ok(data) => data,
+        Err(response) => return response,
+    };
+    let options = horizon_options(IcsQuery::default());
+    let uid = resource_uid(resource);
+    let Some(todo) = dose_todos(&data, options)
+        .into_iter()
+        .find(|todo| todo.uid == uid)
+    else {
+        return not_found();
+    };
+    Response::builder()
+        .status(StatusCode::OK)
+        .header("Content-Type", "text/calendar; charset=utf-8")
+        .header("ETag", todo_etag(&todo))
+        .body(todo_resource_ics(&todo).into())
+        .unwrap()
+}
+
+// Completing a task logs the dose. Other edits to an open task, such as a
+// changed alarm, are accepted without storing anything; logged doses are
+// edited in the app, not reopened from a task list.
+async fn put_todo(resource: &str, headers: &HeaderMap, body: &Bytes) -> Response {
+    let update = match parse_todo_update(&String::from_utf8_lossy(body), resource) {
+        Ok(update) => update,
+        Err(message) => return plain(StatusCode::UNPROCESSABLE_ENTITY, message),
+    };
+    let mut data = match read_data().await {
+        Ok(data) => data,
+        Err(response) => return response,
+    };
+
+    let options = horizon_options(IcsQuery::default());
+    let current = dose_todos(&data, options)
+        .into_iter()
+        .find(|todo| todo.uid == update.uid);
+    if let Some(expected) = headers.get("If-Match").and_then(|v| v.to_str().ok()) {
+        let matches = match &current {
+            Some(todo) => expected.trim() == "*" || expected.trim() == todo_etag(todo),
+            None => false,
+        };
+        if !matches {
+            return plain(StatusCode::PRECONDITION_FAILED, "Task has changed");
+        }
+    }
+
+    if !update.completed {
+        if current.is_some_and(|todo| todo.completed_ms.is_some()) {
+            return plain(
+                StatusCode::FORBIDDEN,
+                "Logged doses can only be changed in HRT Tracker",
+            );
+        }
+        return plain(StatusCode::NO_CONTENT, Body::empty());
+    }
+
+    match apply_completed_todo(&mut data, &update) {
+        Ok(true) => {
+            if write_data_value(&data).await.is_err() {
+                return plain(StatusCode::INTERNAL_SERVER_ERROR, "Failed to write data");
+            }
+        }
+        Ok(false) => {}
+        Err(message) => {
+            eprintln!("Could not log completed todo: {message}");
+            return plain(StatusCode::INTERNAL_SERVER_ERROR, message);
+        }
+    }
+
+    // The stored task is rendered from the dosage history rather than kept
+    // byte for byte, so no ETag is returned and clients fetch it again.
+    plain(StatusCode::NO_CONTENT, Body::empty())
+}
+
+fn dav_options(allow: &str) -> Response {
+    Response::builder()
+        .status(StatusCode::OK)
+        .header("DAV", "1, calendar-access")
+        .header("Allow", allow)
+        .body(Body::empty())
+        .unwrap()
+}
+
+fn multistatus(xml: String) -> Response {
+    Response::builder()
+        .status(StatusCode::MULTI_STATUS)
+        .header("Content-Type", "application/xml; charset=utf-8")
+        .body(xml.into())
+        .unwrap()
+}
+
+/// Upcoming doses in the horizon plus the scheduled doses already checked
+/// off within it, so a completed task stays listed as completed.
+pub fn dose_todos(data: &Value, options: IcsOptions) -> Vec<DoseTodo> {
+    let window = options.horizon_days * DAY_MS;
+    let horizon_end = options.now_ms + window;
+    let typed = match typed_data(data, options.now_ms) {
+        Ok(typed) => typed,
+        Err(err) => {
+            eprintln!(
+                "Todo feed: stored data could not be parsed, leaving out scheduled doses: {err}"
+            );
+            Default::default()
+        }
+    };
+
+    let logged: Vec<(String, i64)> = data
+        .get("dosageHistory")
+        .and_then(|v| v.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| {
+            let id = entry.get("id")?.as_str()?;
+            parse_todo_uid(id)?;
+            Some((id.to_string(), entry.get("date")?.as_f64()? as i64))
+        })
+        .collect();
+    let logged_at = |uid: &str| {
+        logged
+            .iter()
+            .find(|(id, _)| id == uid)
+            .map(|(_, date)| *date)
+    };
+
+    let summary_for = |regimen_id: &str| {
+        let label = typed
+            .regimens
+            .iter()
+            .find(|regimen| regimen.id == regimen_id)
+            .map(|regimen| {
+                let sched = serde_json::to_value(&regimen.schedule).unwrap_or_default();
+                scheduled_dose_label(regimen.schedule.medication_type(), &sched)
+            })
+            .unwrap_or_else(|| "dose".to_string());
+        format!("Take {}", label)
+    };
+
+    let mut todos: Vec<DoseTodo> = Vec::new();
+    let mut seen: HashSet<String> = HashSet::new();
+    for regimen in &typed.regimens {
+        for t in scheduled_dose_times(&typed, regimen, options.now_ms, horizon_end) {
+            let uid = todo_uid(&regimen.id, t);
+            seen.insert(uid.clone());
+            todos.push(DoseTodo {
+                completed_ms: logged_at(&uid),
+                summary: summary_for(&regimen.id),
+                scheduled_ms: t,
+                uid,
+            });
+        }
+    }
+    for (uid, date) in &logged {
+        let Some((regimen, t)) = parse_todo_uid(uid) else {
+            continue;
+        };
+        if t < options.now_ms - window || t > horizon_end || !seen.insert(uid.clone()) {
+            continue;
+        }
+        todos.push(DoseTodo {
+            uid: uid.clone(),
+            summary: summary_for(&regimen),
+            scheduled_ms: t,
+            completed_ms: Some(*date),
+        });
+    }
+    todos.sort_by(|a, b| a.scheduled_ms.cmp(&b.scheduled_ms).then(a.uid.cmp(&b.uid)));
+    todos
+}
+
+// DTSTAMP follows the dose rather than the request time so the bytes of a
+// resource, and with them its ETag, only change when its status does.
+fn todo_component(todo: &DoseTodo) -> String {
+    let stamp = todo.completed_ms.unwrap_or(todo.scheduled_ms);
+    let mut lines = vec![
+        "BEGIN:VTODO".to_string(),
+        format!("UID:{}", todo.uid),
+        format!("DTSTAMP:{}", to_ics_date_time(stamp)),
+        format!("DTSTART:{}", to_ics_date_time(todo.scheduled_ms)),
+        format!("DUE:{}", to_ics_date_time(todo.scheduled_ms)),
+        format!("SUMMARY:{}", escape_text(&todo.summary)),
+        "DESCRIPTION:Mark complete to log this dose in HRT Tracker.".to_string(),
+    ];
+    match todo.completed_ms {
+        Some(completed) => {
+            lines.push("STATUS:COMPLETED".to_string());
+            lines.push(format!("COMPLETED:{}", to_ics_date_time(completed)));
+            lines.push("PERCENT-COMPLETE:100".to_string());
+        }
+        None => lines.push("STATUS:NEEDS-ACTION".to_string()),
+    }
+    lines.push("CATEGORIES:HRT".to_string());
+    lines.push("END:VTODO".to_string());
+    lines.join("\r\n")
+}
+
+fn calendar(components: Vec<String>) -> String {
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "PRODID:-//HRT Tracker//EN".to_string(),
+        "VERSION:2.0".to_string(),
+        "CALSCALE:GREGORIAN".to_string(),
+        format!("X-WR-CALNAME:{}", CALENDAR_NAME),
+    ];
+    lines.extend(components);
+    lines.push("END:VCALENDAR".to_string());
+    lines.join("\r\n")
+}
+
+pub fn generate_todo_ics(data: &Value, options: IcsOptions) -> String {
+    calendar(
+        dose_todos(data, options)
+            .iter()
+            .map(todo_component)
+            .collect(),
+    )
+}
+
+fn todo_resource_ics(todo: &DoseTodo) -> String {
+    calendar(vec![todo_component(todo)])
+}
+
+fn hash_etag(value: impl Hash) -> String {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    format!("\"{:016x}\"", hasher.finish())
+}
+
+pub fn todo_etag(todo: &DoseTodo) -> String {
+    hash_etag(todo_resource_ics(todo))
+}
+
+fn collection_ctag(todos: &[DoseTodo]) -> String {
+    hash_etag(todos.iter().map(todo_etag).collect::<Vec<_>>())
+}
+
+fn collection_href(path: &str) -> String {
+    if path.ends_with('/') {
+        path.to_string()
+    } else {
+        format!("{}/", path)
+    }
+}
+
+fn resource_uid(resource: &str) -> String {
+    resource
+        .trim_end_matches(".ics")
+        .replace("%40", "@")
+        .to_string()
+}
+
+fn xml_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+fn xml_unescape(value: &str) -> String {
+    value
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
+
+fn multistatus_xml(responses: Vec<String>) -> String {
+    let mut xml = String::from(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<d:multistatus xmlns:d=\"DAV:\" xmlns:c=\"urn:ietf:params:xml:ns:caldav\" xmlns:cs=\"http://calendarserver.org/ns/\">\n",
+    );
+    for response in responses {
+        xml.push_str(&response);
+    }
+    xml.push_str("</d:multistatus>\n");
+    xml
+}
+
+fn found(href: &str, props: &str) -> String {
+    format!(
+        "<d:response><d:href>{}</d:href><d:propstat><d:prop>{}</d:prop><d:status>HTTP/1.1 200 OK</d:status></d:propstat></d:response>\n",
+        xml_escape(href),
+        props
+    )
+}
+
+fn todo_props(todo: &DoseTodo, with_data: bool) -> String {
+    let mut props = format!(
+        "<d:resourcetype/><d:getcontenttype>text/calendar; charset=utf-8; component=VTODO</d:getcontenttype><d:getetag>{}</d:getetag>",
+        xml_escape(&todo_etag(todo))
+    );
+    if with_data {
+        props.push_str(&format!(
+            "<c:calendar-data>{}</c:calendar-data>",
+            xml_escape(&todo_resource_ics(todo))
+        ));
+    }
+    props
+}
+
+/// Answers PROPFIND with the collection's own properties and, for depth 1,
+/// every task resource and its ETag. Requested properties are not filtered.
+pub fn propfind_response(collection: &str, todos: &[DoseTodo], depth_one: bool) -> String {
+    let mut responses = vec![found(
+        collection,
+        &format!(
+            "<d:resourcetype><d:collection/><c:calendar/></d:resourcetype><d:displayname>{}</d:displayname><c:supported-calendar-component-set><c:comp name=\"VTODO\"/></c:supported-calendar-component-set><cs:getctag>{}</cs:getctag>",
+            CALENDAR_NAME,
+            xml_escape(&collection_ctag(todos))
+        ),
+    )];
+    if depth_one {
+        for todo in todos {
+            responses.push(found(
+                &format!("{}{}.ics", collection, todo.uid),
+                &todo_props(todo, false),
+            ));
+        }
+    }
+    multistatus_xml(responses)
+}
+
+/// Answers a calendar-multiget with the requested tasks and any other
+/// REPORT, such as calendar-query, with every task in the collection.
+pub fn report_response(collection: &str, todos: &[DoseTodo], body: &str) -> String {
+    if !body.contains("calendar-multiget") {
+        return multistatus_xml(
+            todos
+                .iter()
+                .map(|todo| {
+                    found(
+                        &format!("{}{}.ics", collection, todo.uid),
+                        &todo_props(todo, true),
+                    )
+                })
+                .collect(),
+        );
+    }
+    let responses = report_hrefs(body)
+        .into_iter()
+        .map(|href| {
+            let resource = href.rsplit('/').next().unwrap_or("");
+            let uid = resource_uid(resource);
+            match todos.iter().find(|todo| todo.uid == uid) {
+                Some(todo) => found(&href, &todo_props(todo, true)),
+                None => format!(
+                    "<d:response><d:href>{}</d:href><d:status>HTTP/1.1 404 Not Found</d:status></d:response>\n",
+                    xml_escape(&href)
+                ),
+            }
+        })
+        .collect();
+    multistatus_xml(responses)
+}
+
+/// Collects the text of every `href` element, whatever its namespace prefix.
+fn report_hrefs(body: &str) -> Vec<String> {
+    let mut hrefs = Vec::new();
+    let mut rest = body;
+    while let Some(start) = rest.find('<') {
+        let after = &rest[start + 1..];
+        let Some(end) = after.find('>') else {
+            break;
+        };
+        let tag = after[..end].split_whitespace().next().unwrap_or("");
+        rest = &after[end + 1..];
+        if tag.starts_with('/') || tag.rsplit(':').next() != Some("href") {
+            continue;
+        }
+        let text = &rest[..rest.find('<').unwrap_or(rest.len())];
+        hrefs.push(xml_unescape(text.trim()));
+    }
+    hrefs
+}
+fn todo_uid(regimen_id: &str, t: i64) -> String {
+    format!("{}-{}-todo@hrt-tracker", regimen_id, t)
+}
+
+fn parse_todo_uid(uid: &str) -> Option<(String, i64)> {
+    let rest = uid.strip_suffix("@hrt-tracker")?.strip_suffix("-todo")?;
+    let (regimen, t) = rest.rsplit_once('-')?;
+    if regimen.is_empty() {
+        return None;
+    }
+    Some((regimen.to_string(), t.parse().ok()?))
+}
+
+fn parse_ics_date_time(value: &str) -> Option<i64> {
+    let trimmed = value.trim().trim_end_matches('Z');
+    NaiveDateTime::parse_from_str(trimmed, "%Y%m%dT%H%M%S")
+        .ok()
+        .map(|dt| dt.and_utc().timestamp_millis())
+}
+
+fn unfold_lines(text: &str) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for raw in text.split('\n') {
+        let line = raw.trim_end_matches('\r');
+        if let Some(rest) = line.strip_prefix(' ').or_else(|| line.strip_prefix('\t')) {
+            if let Some(last) = lines.last_mut() {
+                last.push_str(rest);
+                continue;
+            }
+        }
+        lines.push(line.to_string());
+    }
+    lines
+}
+
+pub fn parse_todo_update(body: &str, resource: &str) -> Result<TodoUpdate, String> {
+    let mut in_todo = false;
+    let mut uid: Option<String> = None;
+    let mut status: Option<String> = None;
+    let mut completed: Option<String> = None;
+    for line in unfold_lines(body) {
+        match line.as_str() {
+            "BEGIN:VTODO" => in_todo = true,
+            "END:VTODO" => break,
+            _ if in_todo => {
+                let Some((name, value)) = line.split_once(':') else {
+                    continue;
+                };
+                let name = name.split(';').next().unwrap_or("").to_ascii_uppercase();
+                match name.as_str() {
+                    "UID" => uid = Some(value.trim().to_string()),
+                    "STATUS" => status = Some(value.trim().to_ascii_uppercase()),
+                    "COMPLETED" => completed = Some(value.to_string()),
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+    }
+    let uid = uid.unwrap_or_else(|| resource_uid(resource));
+    let (regimen, scheduled_ms) =
+        parse_todo_uid(&uid).ok_or_else(|| format!("Unknown task UID: {uid}"))?;
+    Ok(TodoUpdate {
+        uid,
+        regimen,
+        scheduled_ms,
+        completed: status.as_deref() == Some("COMPLETED"),
+        completed_ms: completed.as_deref().and_then(parse_ics_date_time),
+    })
+}
+
+pub fn apply_completed_todo(data: &mut Value, todo: &TodoUpdate) -> Result<bool, String> {
+    let now_ms = chrono::Utc::now().timestamp_millis();
+    let mut typed =
+        typed_data(data, now_ms).map_err(|err| format!("Stored data could not be read: {err}"))?;
+    let date = todo.completed_ms.unwrap_or(todo.scheduled_ms);
+    if !record_scheduled_dose(&mut typed, &todo.regimen, date, Some(todo.uid.clone())) {
+        return Ok(false);
+    }
+
+    let regimens = serde_json::to_value(&typed.regimens).map_err(|e| e.to_string())?;
+    let plans = serde_json::to_value(&typed.plans).map_err(|e| e.to_string())?;
+    let history = serde_json::to_value(&typed.dosageHistory).map_err(|e| e.to_string())?;
+    let Some(obj) = data.as_object_mut() else {
+        return Err("Stored data is not an object".to_string());
+    };
+    for key in [
+        "injectableEstradiol",
+        "oralEstradiol",
+        "antiandrogen",
+        "progesterone",
+        "patch",
+        "gel",
+    ] {
+        obj.remove(key);
+    }
+    obj.insert("regimens".to_string(), regimens);
+    obj.insert("plans".to_string(), plans);
+    obj.insert("dosageHistory".to_string(), history);
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn sample_data(now: i64) -> Value {
+        json!({
+            "injectableEstradiol": {
+                "type": "Estradiol Valerate",
+                "dose": 4,
+                "unit": "mg",
+                "frequency": 7,
+                "vialId": "vial-1",
+                "syringeKind": "Insulin syringe",
+                "nextDoseDate": now + 86400000
+            },
+            "dosageHistory": []
+        })
+    }
+
+    #[test]
+    fn todo_feed_lists_upcoming_doses() {
+        let now = 1700000000000_i64;
+        let options = IcsOptions {
+            horizon_days: 14,
+            include_past: false,
+            now_ms: now,
+        };
+        let cal = generate_todo_ics(&sample_data(now), options);
+        assert!(cal.contains("BEGIN:VTODO"));
+        assert!(cal.contains(&format!(
+            "UID:injectableEstradiol-{}-todo@hrt-tracker",
+            now + 86400000
+        )));
+        assert!(cal.contains("SUMMARY:Take Injection: Estradiol Valerate 4 mg"));
+        assert!(cal.contains("STATUS:NEEDS-ACTION"));
+        assert_eq!(cal.matches("BEGIN:VTODO").count(), 2);
+    }
+
+    #[test]
+    fn parse_todo_update_reads_uid_and_completion() {
+        let body = "BEGIN:VCALENDAR\r\nBEGIN:VTODO\r\nUID:injectableEstradiol-1700086400000-to\r\n do@hrt-tracker\r\nSTATUS:COMPLETED\r\nCOMPLETED:20231116T120000Z\r\nEND:VTODO\r\nEND:VCALENDAR";
+        let todo = parse_todo_update(body, "ignored.ics").unwrap();
+        assert_eq!(todo.regimen, "injectableEstradiol");
+        assert_eq!(todo.scheduled_ms, 1700086400000);
+        assert_eq!(todo.completed_ms, Some(1700136000000));
+        assert!(todo.completed);
+    }
+
+    #[test]
+    fn parse_todo_update_reads_open_items_and_rejects_unknown_ones() {
+        let body = "BEGIN:VTODO\r\nUID:antiandrogen-1-todo@hrt-tracker\r\nSTATUS:NEEDS-ACTION\r\nEND:VTODO";
+        let todo = parse_todo_update(body, "x.ics").unwrap();
+        assert_eq!(todo.regimen, "antiandrogen");
+        assert!(!todo.completed);
+        let body = "BEGIN:VTODO\r\nUID:not-ours\r\nSTATUS:COMPLETED\r\nEND:VTODO";
+        assert!(parse_todo_update(body, "x.ics").is_err());
+    }
+
+    #[test]
+    fn parse_todo_update_falls_back_to_resource_name() {
+        let body = "BEGIN:VTODO\r\nSTATUS:COMPLETED\r\nEND:VTODO";
+        let todo = parse_todo_update(body, "progesterone-5-todo@hrt-tracker.ics").unwrap();
+        assert_eq!(todo.regimen, "progesterone");
+        assert_eq!(todo.scheduled_ms, 5);
+        assert_eq!(todo.completed_ms, None);
+    }
+
+    #[test]
+    fn apply_completed_todo_logs_schedule_defaults_once() {
+        let now = 1700000000000_i64;
+        let mut data = sample_data(now);
+        data["customField"] = json!("kept");
+        let todo = TodoUpdate {
+            uid: todo_uid("injectableEstradiol", now + 86400000),
+            regimen: "injectableEstradiol".to_string(),
+            scheduled_ms: now + 86400000,
+            completed: true,
+            completed_ms: None,
+        };
+        assert_eq!(apply_completed_todo(&mut data, &todo), Ok(true));
+        assert_eq!(apply_completed_todo(&mut data, &todo), Ok(false));
+
+        let history = data["dosageHistory"].as_array().unwrap();
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0]["medicationType"], "injectableEstradiol");
+        assert_eq!(history[0]["date"], now + 86400000);
+        assert_eq!(history[0]["vialId"], "vial-1");
+        assert_eq!(history[0]["syringeKind"], "Insulin syringe");
+        assert_eq!(data["customField"], "kept");
+        assert!(data.get("injectableEstradiol").is_none());
+        assert_eq!(data["regimens"][0]["id"], "injectableEstradiol");
+        assert_eq!(
+            data["regimens"][0]["schedule"]["nextDoseDate"],
+            now + 86400000 + 7 * DAY_MS
+        );
+    }
+
+    fn options(now: i64) -> IcsOptions {
+        IcsOptions {
+            horizon_days: 14,
+            include_past: false,
+            now_ms: now,
+        }
+    }
+
+    fn complete(data: &mut Value, scheduled: i64, completed: i64) {
+        let todo = TodoUpdate {
+            uid: todo_uid("injectableEstradiol", scheduled),
+            regimen: "injectableEstradiol".to_string(),
+            scheduled_ms: scheduled,
+            completed: true,
+            completed_ms: Some(completed),
+        };
+        assert_eq!(apply_completed_todo(data, &todo), Ok(true));
+    }
+
+    #[test]
+    fn checked_off_dose_stays_listed_as_completed() {
+        let now = 1700000000000_i64;
+        let first = now + 86400000;
+        let mut data = sample_data(now);
+        complete(&mut data, first, first + 3600000);
+
+        let todos = dose_todos(&data, options(now));
+        assert_eq!(todos.len(), 2);
+        assert_eq!(todos[0].uid, todo_uid("injectableEstradiol", first));
+        assert_eq!(todos[0].completed_ms, Some(first + 3600000));
+        // The next dose is counted from when this one was actually taken.
+        assert_eq!(todos[1].scheduled_ms, first + 3600000 + 7 * DAY_MS);
+        assert_eq!(todos[1].completed_ms, None);
+
+        let cal = generate_todo_ics(&data, options(now));
+        assert_eq!(cal.matches("STATUS:COMPLETED").count(), 1);
+        assert_eq!(cal.matches("STATUS:NEEDS-ACTION").count(), 1);
+        assert!(cal.contains(&format!("COMPLETED:{}", to_ics_date_time(first + 3600000))));
+    }
+
+    #[test]
+    fn propfind_lists_tasks_with_etags_that_change_on_completion() {
+        let now = 1700000000000_i64;
+        let first = now + 86400000;
+        let mut data = sample_data(now);
+        let before = dose_todos(&data, options(now));
+        let href = "/api/todos/secret/";
+
+        let xml = propfind_response(href, &before, false);
+        assert!(xml.contains("<d:href>/api/todos/secret/</d:href>"));
+        assert!(xml.contains("<c:calendar/>"));
+        assert!(xml.contains("<c:comp name=\"VTODO\"/>"));
+        assert!(!xml.contains(".ics</d:href>"));
+
+        let xml = propfind_response(href, &before, true);
+        for todo in &before {
+            assert!(xml.contains(&format!("<d:href>{href}{}.ics</d:href>", todo.uid)));
+            assert!(xml.contains(&format!("<d:getetag>{}</d:getetag>", todo_etag(todo))));
+        }
+        assert_eq!(dose_todos(&data, options(now)), before);
+
+        complete(&mut data, first, first);
+        let after = dose_todos(&data, options(now));
+        assert_eq!(after[0].uid, before[0].uid);
+        assert_ne!(todo_etag(&after[0]), todo_etag(&before[0]));
+        assert_ne!(collection_ctag(&after), collection_ctag(&before));
+    }
+
+    #[test]
+    fn report_returns_requested_or_all_tasks() {
+        let now = 1700000000000_i64;
+        let todos = dose_todos(&sample_data(now), options(now));
+        let href = "/api/todos/secret/";
+
+        let multiget = format!(
+            "<?xml version=\"1.0\"?><c:calendar-multiget xmlns:d=\"DAV:\" xmlns:c=\"urn:ietf:params:xml:ns:caldav\"><d:prop><d:getetag/><c:calendar-data/></d:prop><d:href>{href}{}.ics</d:href><D:href xmlns:D=\"DAV:\">{href}missing.ics</D:href></c:calendar-multiget>",
+            todos[0].uid.replace('@', "%40")
+        );
+        assert_eq!(
+            report_hrefs(&multiget),
+            vec![
+                format!("{href}{}.ics", todos[0].uid.replace('@', "%40")),
+                format!("{href}missing.ics"),
+            ]
+        );
+        let xml = report_response(href, &todos, &multiget);
+        assert_eq!(xml.matches("<c:calendar-data>").count(), 1);
+        assert!(xml.contains(&format!("UID:{}", todos[0].uid)));
+        assert!(xml.contains(&todo_etag(&todos[0])));
+        assert!(xml.contains("HTTP/1.1 404 Not Found"));
+
+        let query = "<c:calendar-query xmlns:c=\"urn:ietf:params:xml:ns:caldav\"><c:filter><c:comp-filter name=\"VCALENDAR\"><c:comp-filter name=\"VTODO\"/></c:comp-filter></c:filter></c:calendar-query>";
+        let xml = report_response(href, &todos, query);
+        assert_eq!(xml.matches("<c:calendar-data>").count(), todos.len());
+    }
+}
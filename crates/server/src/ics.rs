@@ -5,11 +5,21 @@ use chrono::{Datelike, TimeZone, Timelike};
 use serde::Deserialize;
 use serde_json::Value;
 
+use hrt_shared::inventory::{inventory_events, InventoryEvent};
+use hrt_shared::medications::builtin_medications;
+use hrt_shared::plans::{apply_due_plan_steps, projected_regimens, upcoming_plan_steps};
+use hrt_shared::safety::safety_alerts;
+use hrt_shared::schedules::{migrate_schedules_to_regimens, regimen_dose_times};
+use hrt_shared::transdermal::{
+    gel_dose_mg, patch_removal_date, patch_total_strength, worn_patches,
+};
+use hrt_shared::types::{DosageHistoryEntry, HrtData, Regimen};
+
 use crate::storage::{read_data_value, read_settings_value};
 
 const DAY_MS: i64 = 24 * 60 * 60 * 1000;
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Default, Deserialize)]
 #[allow(non_snake_case)]
 pub struct IcsQuery {
     pub horizonDays: Option<String>,
@@ -84,6 +94,9 @@ async fn build_ics(query: IcsQuery, conf: Value) -> Response {
         Err(_) => serde_json::json!({}),
     };
 
+    if let Err(err) = typed_data(&data, now_ms) {
+        return unreadable_data(err);
+    }
     let calendar = generate_ics(&data, &conf, options);
 
     Response::builder()
@@ -125,11 +138,28 @@ pub fn generate_ics(data: &Value, conf: &Value, options: IcsOptions) -> String {
                     .get("medicationType")
                     .and_then(|v| v.as_str())
                     .unwrap_or("medication");
-                let name = d.get("type").and_then(|v| v.as_str()).unwrap_or("");
-                let qty = d.get("dose").map(|v| v.to_string()).unwrap_or_default();
-                let unit = d.get("unit").and_then(|v| v.as_str()).unwrap_or("mg");
+                let name = d
+                    .get("type")
+                    .and_then(|v| v.as_str())
+                    .map(str::to_string)
+                    .or_else(|| catalog_medication_name(data, d))
+                    .unwrap_or_default();
+                let (qty, unit) = match d.get("strength").and_then(|v| v.as_f64()) {
+                    Some(strength) => {
+                        let quantity = d.get("patchQuantity").and_then(|v| v.as_f64());
+                        (
+                            patch_total_strength(strength, quantity).to_string(),
+                            "µg/day",
+                        )
+                    }
+                    None => (
+                        d.get("dose").map(|v| v.to_string()).unwrap_or_default(),
+                        d.get("unit").and_then(|v| v.as_str()).unwrap_or("mg"),
+                    ),
+                };
                 let site = d
                     .get("injectionSite")
+                    .or_else(|| d.get("site"))
                     .and_then(|v| v.as_str())
                     .map(|s| format!("; Site: {}", s))
                     .unwrap_or_default();
@@ -139,14 +169,10 @@ pub fn generate_ics(data: &Value, conf: &Value, options: IcsOptions) -> String {
                     .map(|s| format!("; Note: {}", s))
                     .unwrap_or_default();
                 let summary = format!(
-                    "{}: {} {} {}",
+                    "{}: {}",
                     summary_for_medication(medication_type),
-                    name,
-                    qty,
-                    unit
-                )
-                .trim()
-                .to_string();
+                    join_non_empty(&[&name, &qty, unit])
+                );
                 let desc = format!("Recorded dose{}{}", site, note).trim().to_string();
                 let uid = format!("{}-{}-history@hrt-tracker", medication_type, date);
                 events.push(make_event(
@@ -156,89 +182,90 @@ pub fn generate_ics(data: &Value, conf: &Value, options: IcsOptions) -> String {
                     Some(&desc),
                     options.now_ms,
                 ));
+                if let Some(removed) = d.get("removedAt").and_then(|v| v.as_i64()) {
+                    let uid = format!("patch-{}-removal@hrt-tracker", date);
+                    events.push(make_event(
+                        &uid,
+                        removed,
+                        "Patch removed",
+                        None,
+                        options.now_ms,
+                    ));
+                }
             }
         }
     }
 
-    let regimen_keys = [
-        "injectableEstradiol",
-        "oralEstradiol",
-        "antiandrogen",
-        "progesterone",
-    ];
-    for key in regimen_keys {
-        let sched = match data.get(key) {
-            Some(value) => value,
-            None => continue,
-        };
-        let freq_days = sched
-            .get("frequency")
-            .and_then(|v| v.as_f64())
-            .unwrap_or(0.0);
-        if freq_days <= 0.0 {
-            continue;
+    let typed = match typed_data(data, options.now_ms) {
+        Ok(typed) => typed,
+        Err(err) => {
+            eprintln!(
+                "ICS feed: stored data could not be parsed, leaving out schedules and reminders: {err}"
+            );
+            HrtData::default()
         }
-        let step = (freq_days * DAY_MS as f64) as i64;
-
-        let last_taken_dates: Vec<i64> = data
-            .get("dosageHistory")
-            .and_then(|v| v.as_array())
-            .map(|arr| {
-                arr.iter()
-                    .filter_map(|d| {
-                        if d.get("medicationType").and_then(|v| v.as_str()) == Some(key) {
-                            d.get("date").and_then(|v| v.as_i64())
-                        } else {
-                            None
-                        }
-                    })
-                    .collect()
-            })
-            .unwrap_or_default();
-
-        let mut t = if !last_taken_dates.is_empty() {
-            last_taken_dates.into_iter().max().unwrap() + step
-        } else if let Some(next) = sched.get("nextDoseDate").and_then(|v| v.as_i64()) {
-            next
-        } else {
-            continue;
-        };
-
-        let today_start_utc = utc_day_start(options.now_ms);
-        while t < today_start_utc {
-            t += step;
-        }
-
-        while t <= horizon_end {
-            let name = sched.get("type").and_then(|v| v.as_str()).unwrap_or("");
-            let qty = sched.get("dose").map(|v| v.to_string()).unwrap_or_default();
-            let unit = sched.get("unit").and_then(|v| v.as_str()).unwrap_or("mg");
-            let route = if key == "progesterone" {
-                sched
-                    .get("route")
-                    .and_then(|v| v.as_str())
-                    .map(|r| format!(" ({})", r))
-                    .unwrap_or_default()
-            } else {
-                String::new()
+    };
+    // Ended regimens for today's earlier doses, then what the plans project.
+    let regimens: Vec<Regimen> = typed
+        .regimens
+        .iter()
+        .filter(|regimen| !regimen.is_current())
+        .cloned()
+        .chain(projected_regimens(&typed))
+        .collect();
+    for regimen in &regimens {
+        let key = regimen.schedule.medication_type();
+        let sched = serde_json::to_value(&regimen.schedule).unwrap_or_default();
+        let freq_days = regimen.schedule.frequency();
+        for t in scheduled_dose_times(&typed, regimen, options.now_ms, horizon_end) {
+            let summary = format!("Scheduled {}", scheduled_dose_label(key, &sched));
+            let desc = match &regimen.recurrence {
+                Some(recurrence) => format!("Scheduled per regimen; {}.", recurrence.describe()),
+                None => format!("Scheduled per regimen; every {} day(s).", freq_days),
             };
-            let summary = format!(
-                "Scheduled {}{}: {} {} {}",
-                summary_for_medication(key),
-                route,
-                name,
-                qty,
-                unit
-            )
-            .trim()
-            .to_string();
-            let desc = format!("Scheduled per regimen; every {} day(s).", freq_days);
-            let uid = format!("{}-{}-scheduled@hrt-tracker", key, t);
+            let uid = format!("{}-{}-scheduled@hrt-tracker", regimen.id, t);
             events.push(make_event(&uid, t, &summary, Some(&desc), options.now_ms));
-            t += step;
+            if key == "patch" {
+                let wear_days = sched.get("wearDays").and_then(|v| v.as_f64());
+                let removal = patch_removal_date(t, wear_days.or(Some(freq_days)), None);
+                if removal <= horizon_end {
+                    let uid = format!("patch-{}-removal@hrt-tracker", t);
+                    let desc = "Remove the patch applied at the previous change.";
+                    events.push(make_event(
+                        &uid,
+                        removal,
+                        "Remove patch",
+                        Some(desc),
+                        options.now_ms,
+                    ));
+                }
+            }
         }
     }
 
+    for (plan, step) in upcoming_plan_steps(&typed, options.now_ms) {
+        if step.startDate > horizon_end {
+            continue;
+        }
+        let change = match &step.schedule {
+            Some(schedule) => {
+                let sched = serde_json::to_value(schedule).unwrap_or_default();
+                scheduled_dose_label(schedule.medication_type(), &sched)
+            }
+            None => "Stop".to_string(),
+        };
+        let summary = format!("Plan step ({}): {}", plan.name, change);
+        let desc = "The regimen changes to this step from here on.";
+        let uid = format!("{}-{}-plan@hrt-tracker", plan.id, step.startDate);
+        events.push(make_event(
+            &uid,
+            step.startDate,
+            &summary,
+            Some(desc),
+            options.now_ms,
+        ));
+    }
+
     if let Some(true) = conf
         .get("enableBloodTestSchedule")
         .and_then(|v| v.as_bool())
@@ -273,6 +300,113 @@ pub fn generate_ics(data: &Value, conf: &Value, options: IcsOptions) -> String {
         }
     }
 
+    let today_start_utc = utc_day_start(options.now_ms);
+    for event in inventory_events(&typed) {
+        let (uid, start, summary, desc) = match &event {
+            InventoryEvent::VialUseBy {
+                vial_id,
+                label,
+                date,
+            } => {
+                if *date < today_start_utc && !options.include_past {
+                    continue;
+                }
+                (
+                    format!("vial-{}-useby@hrt-tracker", vial_id),
+                    *date,
+                    format!("Vial use-by: {}", label),
+                    format!("{} reaches its use-by date.", label),
+                )
+            }
+            InventoryEvent::VialProjectedEmpty {
+                vial_id,
+                label,
+                date,
+                remaining_ml,
+                doses_left,
+                ..
+            } => (
+                format!("vial-{}-empty@hrt-tracker", vial_id),
+                (*date).max(today_start_utc),
+                format!("Vial projected empty: {}", label),
+                format!(
+                    "About {:.2} mL left, enough for {} more scheduled dose(s).",
+                    remaining_ml, doses_left
+                ),
+            ),
+            InventoryEvent::LowPillStock {
+                regimen_id,
+                medication_type,
+                date,
+                runs_out,
+                remaining,
+            } => (
+                format!("{}-pillstock@hrt-tracker", regimen_id),
+                (*date).max(today_start_utc),
+                format!(
+                    "Low pill stock: {}",
+                    summary_for_medication(medication_type)
+                ),
+                format!(
+                    "{} pill(s) left; runs out around {}.",
+                    remaining,
+                    to_ics_date(*runs_out)
+                ),
+            ),
+        };
+        if start > horizon_end {
+            continue;
+        }
+        events.push(make_event(
+            &uid,
+            start,
+            &summary,
+            Some(&desc),
+            options.now_ms,
+        ));
+    }
+
+    for (patch, removal) in worn_patches(&typed, options.now_ms) {
+        if removal > horizon_end {
+            continue;
+        }
+        let DosageHistoryEntry::Patch { date, site, .. } = patch else {
+            continue;
+        };
+        let uid = format!("patch-{}-removal@hrt-tracker", date);
+        let desc = match site {
+            Some(site) => format!("Patch applied to {}.", site.label()),
+            None => "Patch applied earlier.".to_string(),
+        };
+        events.push(make_event(
+            &uid,
+            removal,
+            "Remove patch",
+            Some(&desc),
+            options.now_ms,
+        ));
+    }
+
+    for alert in safety_alerts(&typed) {
+        // Past alerts show on today unless the feed includes past events.
+        let start = if options.include_past {
+            alert.date()
+        } else {
+            alert.date().max(today_start_utc)
+        };
+        if start > horizon_end {
+            continue;
+        }
+        let uid = format!("safety-{}@hrt-tracker", alert.id());
+        events.push(make_event(
+            &uid,
+            start,
+            &alert.summary(),
+            Some(&alert.description()),
+            options.now_ms,
+        ));
+    }
+
     let mut lines = vec![
         "BEGIN:VCALENDAR".to_string(),
         "PRODID:-//HRT Tracker//EN".to_string(),
@@ -288,12 +422,113 @@ pub fn generate_ics(data: &Value, conf: &Value, options: IcsOptions) -> String {
     lines.join("\r\n")
 }
 
-fn summary_for_medication(medication_type: &str) -> &str {
+/// Parses stored data, moving single per-class schedules into regimens and
+/// applying plan steps due by `now_ms`.
+pub(crate) fn typed_data(data: &Value, now_ms: i64) -> Result<HrtData, serde_json::Error> {
+    let mut typed = serde_json::from_value::<HrtData>(data.clone())?;
+    migrate_schedules_to_regimens(&mut typed);
+    apply_due_plan_steps(&mut typed, now_ms);
+    Ok(typed)
+}
+
+/// A feed built from data that does not parse would look valid but be
+/// missing every schedule, so fail loudly instead.
+pub(crate) fn unreadable_data(err: serde_json::Error) -> Response {
+    eprintln!("Stored data could not be parsed: {err}");
+    Response::builder()
+        .status(StatusCode::INTERNAL_SERVER_ERROR)
+        .body("Stored data could not be read".into())
+        .unwrap()
+}
+
+pub(crate) fn scheduled_dose_times(
+    data: &HrtData,
+    regimen: &Regimen,
+    now_ms: i64,
+    horizon_end: i64,
+) -> Vec<i64> {
+    regimen_dose_times(data, regimen, utc_day_start(now_ms), horizon_end)
+}
+
+pub(crate) fn scheduled_dose_label(key: &str, sched: &Value) -> String {
+    if key == "patch" {
+        let strength = sched
+            .get("strength")
+            .and_then(|v| v.as_f64())
+            .unwrap_or(0.0);
+        let quantity = sched.get("patchQuantity").and_then(|v| v.as_f64());
+        return format!(
+            "{}: {} µg/day",
+            summary_for_medication(key),
+            patch_total_strength(strength, quantity)
+        );
+    }
+    let name = sched.get("type").and_then(|v| v.as_str()).unwrap_or("");
+    let qty = match sched.get("dose").and_then(|v| v.as_f64()) {
+        Some(dose) if key == "gel" => gel_dose_mg(
+            dose,
+            sched.get("pumps").and_then(|v| v.as_f64()),
+            sched.get("mgPerPump").and_then(|v| v.as_f64()),
+        )
+        .to_string(),
+        Some(dose) => dose.to_string(),
+        None => String::new(),
+    };
+    let unit = sched.get("unit").and_then(|v| v.as_str()).unwrap_or("mg");
+    let route = if key == "progesterone" {
+        sched
+            .get("route")
+            .and_then(|v| v.as_str())
+            .map(|r| format!(" ({})", r))
+            .unwrap_or_default()
+    } else {
+        String::new()
+    };
+    format!(
+        "{}{}: {}",
+        summary_for_medication(key),
+        route,
+        join_non_empty(&[name, &qty, unit])
+    )
+}
+
+fn join_non_empty(parts: &[&str]) -> String {
+    parts
+        .iter()
+        .filter(|part| !part.is_empty())
+        .copied()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// Name of the catalog entry a generic "medication" dose refers to.
+fn catalog_medication_name(data: &Value, dose: &Value) -> Option<String> {
+    let id = dose.get("medicationId")?.as_str()?;
+    let user_entry = || {
+        data.get("medications")?
+            .as_array()?
+            .iter()
+            .find(|med| med.get("id").and_then(|v| v.as_str()) == Some(id))?
+            .get("name")?
+            .as_str()
+            .map(str::to_string)
+    };
+    user_entry().or_else(|| {
+        builtin_medications()
+            .into_iter()
+            .find(|med| med.id == id)
+            .map(|med| med.name)
+    })
+}
+
+pub(crate) fn summary_for_medication(medication_type: &str) -> &str {
     match medication_type {
         "injectableEstradiol" => "Injection",
         "oralEstradiol" => "Oral Estradiol",
         "antiandrogen" => "Antiandrogen",
         "progesterone" => "Progesterone",
+        "patch" => "Estradiol Patch",
+        "gel" => "Estradiol Gel",
         _ => "Medication",
     }
 }
@@ -322,7 +557,7 @@ fn make_event(
     lines.join("\r\n")
 }
 
-fn escape_text(text: &str) -> String {
+pub(crate) fn escape_text(text: &str) -> String {
     text.replace('\\', "\\\\")
         .replace(';', "\\;")
         .replace(',', "\\,")
@@ -330,7 +565,7 @@ fn escape_text(text: &str) -> String {
         .replace('\n', "\\n")
 }
 
-fn to_ics_date_time(ms: i64) -> String {
+pub(crate) fn to_ics_date_time(ms: i64) -> String {
     let dt = chrono::Utc
         .timestamp_millis_opt(ms)
         .single()
@@ -346,6 +581,14 @@ fn to_ics_date_time(ms: i64) -> String {
     )
 }
 
+fn to_ics_date(ms: i64) -> String {
+    let dt = chrono::Utc
+        .timestamp_millis_opt(ms)
+        .single()
+        .unwrap_or_else(chrono::Utc::now);
+    format!("{:04}-{:02}-{:02}", dt.year(), dt.month(), dt.day())
+}
+
 fn add_months_utc(ms: i64, months: i64) -> i64 {
     let dt = chrono::Utc
         .timestamp_millis_opt(ms)
@@ -382,7 +625,7 @@ fn set_local_morning_10(ms: i64) -> i64 {
     dt.timestamp_millis()
 }
 
-fn utc_day_start(ms: i64) -> i64 {
+pub(crate) fn utc_day_start(ms: i64) -> i64 {
     let dt = chrono::Utc
         .timestamp_millis_opt(ms)
         .single()
@@ -415,6 +658,13 @@ mod tests {
         assert!(cal.contains("PRODID:-//HRT Tracker//EN"));
     }
 
+    #[test]
+    fn typed_data_reports_unparseable_data() {
+        let data = json!({ "dosageHistory": "not a list" });
+        assert!(typed_data(&data, 1700000000000).is_err());
+        assert!(typed_data(&json!({}), 1700000000000).is_ok());
+    }
+
     #[test]
     fn generate_ics_includes_dosage_history() {
         let data = json!({
@@ -481,6 +731,45 @@ mod tests {
         assert!(cal.contains("Scheduled Injection"), "should contain scheduled events");
     }
 
+    #[test]
+    fn generate_ics_includes_patch_removals() {
+        let now = 1700000000000_i64;
+        let data = json!({
+            "patch": {
+                "strength": 50,
+                "patchQuantity": 2,
+                "frequency": 3.5,
+                "nextDoseDate": now + 86400000
+            },
+            "dosageHistory": [{
+                "date": now - 86400000,
+                "medicationType": "patch",
+                "strength": 50,
+                "patchQuantity": 2,
+                "wearDays": 3.5,
+                "site": "Hip left"
+            }]
+        });
+        let conf = json!({});
+        let options = IcsOptions {
+            horizon_days: 14,
+            include_past: false,
+            now_ms: now,
+        };
+        let cal = generate_ics(&data, &conf, options);
+        assert!(cal.contains("SUMMARY:Scheduled Estradiol Patch: 100 µg/day"));
+        let worn_removal = now - 86400000 + 302400000;
+        assert!(cal.contains(&format!("UID:patch-{}-removal@hrt-tracker", now - 86400000)));
+        assert!(cal.contains(&format!("DTSTART:{}", to_ics_date_time(worn_removal))));
+        assert!(cal.contains("DESCRIPTION:Patch applied to Hip left."));
+        let scheduled = cal.matches("SUMMARY:Scheduled Estradiol Patch").count();
+        let removals = cal.matches("SUMMARY:Remove patch").count();
+        assert!(
+            removals >= scheduled,
+            "{scheduled} changes, {removals} removals"
+        );
+    }
+
     #[test]
     fn generate_ics_includes_blood_test_schedule() {
         let now = 1700000000000_i64;
@@ -522,6 +811,32 @@ mod tests {
         assert!(cal.contains("Oral Estradiol"), "should use oral estradiol summary");
     }
 
+    #[test]
+    fn generate_ics_names_catalog_medications() {
+        let now = 1700000000000_i64;
+        let data = json!({
+            "medications": [{
+                "id": "med-1",
+                "name": "Leuprorelin",
+                "class": "gnrhAgonist",
+                "route": "injection",
+                "unit": "mg"
+            }],
+            "dosageHistory": [
+                {"date": now, "medicationType": "medication", "medicationId": "med-1", "dose": 3.75, "unit": "mg"},
+                {"date": now - 1000, "medicationType": "medication", "medicationId": "builtin:finasteride", "dose": 1, "unit": "mg"}
+            ]
+        });
+        let options = IcsOptions {
+            horizon_days: 30,
+            include_past: true,
+            now_ms: now + 1000,
+        };
+        let cal = generate_ics(&data, &json!({}), options);
+        assert!(cal.contains("Medication: Leuprorelin 3.75 mg"), "{cal}");
+        assert!(cal.contains("Medication: Finasteride 1 mg"), "{cal}");
+    }
+
     #[test]
     fn generate_ics_progesterone_includes_route() {
         let now = 1700000000000_i64;
@@ -599,4 +914,251 @@ mod tests {
         assert!(cal.contains("Injection"), "should have injection events");
         assert!(cal.contains("Antiandrogen"), "should have antiandrogen events");
     }
+
+    #[test]
+    fn generate_ics_includes_inventory_events() {
+        let now = 1700000000000_i64;
+        let data = json!({
+            "injectableEstradiol": {
+                "type": "Estradiol Valerate",
+                "dose": 4,
+                "unit": "mg",
+                "frequency": 7,
+                "vialId": "v1",
+                "nextDoseDate": now + 86400000
+            },
+            "antiandrogen": {
+                "type": "Spironolactone",
+                "dose": 50,
+                "unit": "mg",
+                "frequency": 1,
+                "nextDoseDate": now + 86400000,
+                "pillStock": 20,
+                "pillStockDate": now
+            },
+            "vials": [{
+                "id": "v1",
+                "esterKind": "Estradiol Valerate",
+                "concentrationMgPerMl": 40,
+                "volumeMl": 1,
+                "useBy": now + 86400000 * 30,
+                "createdAt": now
+            }]
+        });
+        let conf = json!({});
+        let options = IcsOptions {
+            horizon_days: 120,
+            include_past: false,
+            now_ms: now,
+        };
+        let cal = generate_ics(&data, &conf, options);
+        assert!(cal.contains("UID:vial-v1-useby@hrt-tracker"));
+        assert!(cal.contains("SUMMARY:Vial projected empty: Estradiol Valerate"));
+        assert!(cal.contains("enough for 10 more scheduled dose(s)"));
+        assert!(cal.contains("SUMMARY:Low pill stock: Antiandrogen"));
+    }
+
+    #[test]
+    fn generate_ics_schedules_each_regimen_within_its_dates() {
+        let now = 1700000000000_i64;
+        let regimen = |id: &str, kind: &str, dose: i64, end: Option<i64>| {
+            json!({
+                "id": id,
+                "startDate": now - 86400000 * 30,
+                "endDate": end,
+                "schedule": {
+                    "medicationType": "antiandrogen",
+                    "type": kind,
+                    "dose": dose,
+                    "unit": "mg",
+                    "frequency": 1,
+                    "nextDoseDate": now + 3600000
+                }
+            })
+        };
+        let data = json!({
+            "regimens": [
+                regimen("spiro-old", "Spironolactone", 50, Some(now - 86400000)),
+                regimen("spiro", "Spironolactone", 100, None),
+                regimen("fin", "Finasteride", 1, Some(now + 86400000 * 2))
+            ]
+        });
+        let options = IcsOptions {
+            horizon_days: 7,
+            include_past: false,
+            now_ms: now,
+        };
+        let cal = generate_ics(&data, &json!({}), options);
+        assert!(!cal.contains("Spironolactone 50 mg"));
+        assert_eq!(
+            cal.matches("SUMMARY:Scheduled Antiandrogen: Spironolactone 100 mg")
+                .count(),
+            7
+        );
+        assert_eq!(
+            cal.matches("SUMMARY:Scheduled Antiandrogen: Finasteride 1 mg")
+                .count(),
+            2
+        );
+        assert!(cal.contains(&format!("UID:fin-{}-scheduled@hrt-tracker", now + 3600000)));
+    }
+
+    #[test]
+    fn generate_ics_shows_upcoming_plan_steps() {
+        let now = 1700000000000_i64;
+        let schedule = |dose: i64| {
+            json!({
+                "medicationType": "injectableEstradiol",
+                "type": "Estradiol Valerate",
+                "dose": dose,
+                "unit": "mg",
+                "frequency": 7,
+                "nextDoseDate": now + 3600000
+            })
+        };
+        let data = json!({
+            "regimens": [{
+                "id": "ev",
+                "startDate": now - 86400000 * 30,
+                "schedule": schedule(2)
+            }],
+            "plans": [{
+                "id": "titrate",
+                "name": "Titrate EV",
+                "regimenId": "ev",
+                "steps": [
+                    { "startDate": now - 86400000, "schedule": schedule(3) },
+                    { "startDate": now + 86400000 * 14, "schedule": schedule(4) }
+                ]
+            }]
+        });
+        let options = IcsOptions {
+            horizon_days: 28,
+            include_past: false,
+            now_ms: now,
+        };
+        let cal = generate_ics(&data, &json!({}), options);
+        assert!(!cal.contains("Estradiol Valerate 2 mg"));
+        assert_eq!(
+            cal.matches("SUMMARY:Scheduled Injection: Estradiol Valerate 3 mg")
+                .count(),
+            2
+        );
+        assert_eq!(
+            cal.matches("SUMMARY:Scheduled Injection: Estradiol Valerate 4 mg")
+                .count(),
+            2
+        );
+        assert!(cal.contains(&format!(
+            "UID:titrate-{}-plan@hrt-tracker",
+            now + 86400000 * 14
+        )));
+        assert!(cal.contains("SUMMARY:Plan step (Titrate EV): Injection: Estradiol Valerate 4 mg"));
+    }
+
+    #[test]
+    fn generate_ics_follows_regimen_recurrence() {
+        let now = 1700000000000_i64;
+        let data = json!({
+            "regimens": [{
+                "id": "ev",
+                "startDate": now - 86400000 * 30,
+                "schedule": {
+                    "medicationType": "injectableEstradiol",
+                    "type": "Estradiol Valerate",
+                    "dose": 4,
+                    "unit": "mg",
+                    "frequency": 7,
+                    "nextDoseDate": now
+                },
+                "recurrence": { "weekdays": [6], "timesOfDay": [540] }
+            }]
+        });
+        let options = IcsOptions {
+            horizon_days: 14,
+            include_past: false,
+            now_ms: now,
+        };
+        let cal = generate_ics(&data, &json!({}), options);
+        assert_eq!(cal.matches("-scheduled@hrt-tracker").count(), 2);
+        assert!(cal.contains("DESCRIPTION:Scheduled per regimen\\; Sun\\; at 09:00."));
+    }
+
+    #[test]
+    fn generate_ics_includes_safety_alerts() {
+        let now = 1700000000000_i64;
+        let data = json!({
+            "antiandrogen": {
+                "type": "Spironolactone",
+                "dose": 100,
+                "unit": "mg",
+                "frequency": 1,
+                "nextDoseDate": now
+            },
+            "bloodTests": [{
+                "date": now - 86400000 * 30,
+                "labResults": [
+                    { "name": "Potassium", "value": 5.6, "unit": "mmol/L" },
+                    { "name": "Creatinine", "value": 0.9, "unit": "mg/dL" }
+                ]
+            }]
+        });
+        let options = IcsOptions {
+            horizon_days: 90,
+            include_past: false,
+            now_ms: now,
+        };
+        let cal = generate_ics(&data, &json!({}), options);
+        assert!(cal.contains("SUMMARY:Potassium high"));
+        assert!(cal.contains("UID:safety-spironolactone-potassium-due@hrt-tracker"));
+        assert!(cal.contains("SUMMARY:Check kidney function"));
+    }
+
+    #[test]
+    fn generate_ics_keeps_past_alert_dates_when_including_past() {
+        let now = 1700000000000_i64;
+        let tested = now - 86400000 * 30;
+        let data = json!({
+            "antiandrogen": {
+                "type": "Spironolactone",
+                "dose": 100,
+                "unit": "mg",
+                "frequency": 1,
+                "nextDoseDate": now
+            },
+            "bloodTests": [{
+                "date": tested,
+                "labResults": [{ "name": "Potassium", "value": 5.6, "unit": "mmol/L" }]
+            }]
+        });
+        let options = IcsOptions {
+            horizon_days: 90,
+            include_past: true,
+            now_ms: now,
+        };
+        let cal = generate_ics(&data, &json!({}), options);
+        let uid = format!("UID:safety-spironolactone-potassium-{tested}-range@hrt-tracker");
+        let event = cal.split("BEGIN:VEVENT").find(|e| e.contains(&uid)).unwrap();
+        assert!(event.contains(&format!("DTSTART:{}", to_ics_date_time(tested))));
+    }
+
+    #[test]
+    fn generate_ics_inventory_respects_horizon() {
+        let now = 1700000000000_i64;
+        let data = json!({
+            "vials": [{
+                "id": "v1",
+                "useBy": now + 86400000 * 400,
+                "createdAt": now
+            }]
+        });
+        let conf = json!({});
+        let options = IcsOptions {
+            horizon_days: 30,
+            include_past: false,
+            now_ms: now,
+        };
+        let cal = generate_ics(&data, &conf, options);
+        assert!(!cal.contains("Vial use-by"));
+    }
 }
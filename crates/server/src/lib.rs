@@ -1,3 +1,4 @@
 pub mod api;
+pub mod caldav;
 pub mod ics;
 pub mod storage;
@@ -1,7 +1,7 @@
 use axum::http::{HeaderValue, Method};
-use axum::routing::{get, post};
+use axum::routing::{any, get, post};
 use axum::Router;
-use hrt_server::{api, ics, storage};
+use hrt_server::{api, caldav, ics, storage};
 use tower_http::cors::{AllowOrigin, Any, CorsLayer};
 
 #[tokio::main]
@@ -21,7 +21,7 @@ async fn main() {
 
     let cors = CorsLayer::new()
         .allow_origin(AllowOrigin::list(origins))
-        .allow_methods([Method::GET, Method::POST, Method::DELETE])
+        .allow_methods([Method::GET, Method::POST, Method::PUT, Method::DELETE])
         .allow_headers(Any);
 
     let app = Router::new()
@@ -32,6 +32,9 @@ async fn main() {
             get(api::get_settings).post(api::post_settings),
         )
         .route("/api/convert", post(api::convert))
+        .route("/api/convert/batch", post(api::convert_batch))
+        .route("/api/convert/units", get(api::convert_units))
+        .route("/api/regimen/optimize", post(api::optimize_regimen))
         .route("/api/ics", get(ics::get_public_ics))
         .route("/api/ics/:secret", get(ics::get_secret_ics))
         .route(
@@ -47,7 +50,16 @@ async fn main() {
             "/api/bloodtest-pdf/:filename",
             get(api::get_bloodtest_pdf).delete(api::delete_bloodtest_pdf),
         )
-        .layer(cors);
+        .layer(cors)
+        // CalDAV clients send OPTIONS to discover the collection, which the
+        // CORS layer would otherwise answer as a preflight.
+        .merge(
+            Router::new()
+                .route("/api/todos", get(caldav::get_public_todos))
+                .route("/api/todos/:secret", any(caldav::todo_collection))
+                .route("/api/todos/:secret/", any(caldav::todo_collection))
+                .route("/api/todos/:secret/:resource", any(caldav::todo_resource)),
+        );
 
     let addr = std::env::var("HRT_SERVER_ADDR").unwrap_or_else(|_| "127.0.0.1:4200".to_string());
     let listener = tokio::net::TcpListener::bind(&addr)
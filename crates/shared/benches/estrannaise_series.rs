@@ -0,0 +1,46 @@
+//! Compares point-by-point `e2_multidose_3c` with `e2_multidose_series` on
+//! three years of weekly injections sampled every 6 hours, as the Estrannaise
+//! chart does. Run with `cargo bench -p hrt-shared`.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use hrt_shared::estrannaise::{e2_multidose_3c, e2_multidose_series};
+use hrt_shared::types::EstrannaiseModel;
+
+fn time<F: FnMut() -> f64>(label: &str, runs: u32, mut f: F) -> Duration {
+    let start = Instant::now();
+    let mut sink = 0.0;
+    for _ in 0..runs {
+        sink += f();
+    }
+    let elapsed = start.elapsed() / runs;
+    black_box(sink);
+    println!("{label:<12} {elapsed:>12.2?} per chart");
+    elapsed
+}
+
+fn main() {
+    let weeks = 156;
+    let times: Vec<f64> = (0..weeks).map(|w| w as f64 * 7.0).collect();
+    let doses = vec![4.0; weeks];
+    let models = vec![EstrannaiseModel::EvIm; weeks];
+    let ts: Vec<f64> = (0..weeks * 7 * 4).map(|i| i as f64 * 0.25).collect();
+
+    let pointwise = time("pointwise", 3, || {
+        ts.iter()
+            .map(|t| e2_multidose_3c(*t, &doses, &times, &models, 1.0, false))
+            .sum()
+    });
+    let series = time("series", 3, || {
+        e2_multidose_series(&ts, &doses, &times, &models, 1.0, false)
+            .iter()
+            .sum()
+    });
+    println!(
+        "speedup      {:>11.1}x ({} points, {} doses)",
+        pointwise.as_secs_f64() / series.as_secs_f64(),
+        ts.len(),
+        weeks
+    );
+}
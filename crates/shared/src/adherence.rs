@@ -0,0 +1,439 @@
+#![allow(non_snake_case)]
+
+use std::collections::BTreeMap;
+
+use chrono::NaiveDate;
+
+use crate::recurrence::local_date;
+use crate::schedules::{entry_date, recurrence_minute};
+use crate::types::{DosageHistoryEntry, HrtData, Regimen, RegimenSchedule, UnixTime};
+
+const HOUR_MS: i64 = 60 * 60 * 1000;
+const DAY_MS: i64 = 24 * HOUR_MS;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DoseStatus {
+    OnTime,
+    Late,
+    Missed,
+}
+
+/// A dose a regimen called for, and the logged dose that covered it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DoseSlot {
+    pub regimenId: String,
+    pub due: UnixTime,
+    pub taken: Option<UnixTime>,
+    pub status: DoseStatus,
+}
+
+impl DoseSlot {
+    /// Negative when the dose was taken early.
+    pub fn lateness(&self) -> Option<i64> {
+        self.taken.map(|taken| taken - self.due)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DayAdherence {
+    pub onTime: usize,
+    pub late: usize,
+    pub missed: usize,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AdherenceReport {
+    /// Slots that are settled, oldest first. A slot whose window is still
+    /// open and has no dose yet is left out.
+    pub slots: Vec<DoseSlot>,
+    /// Bonus doses, and doses logged with no slot left to cover.
+    pub extra: usize,
+}
+
+/// Upper bounds of the lateness buckets, with their labels.
+const LATENESS_BUCKETS: [(i64, &str); 5] = [
+    (-HOUR_MS, "Early"),
+    (HOUR_MS, "Within an hour"),
+    (6 * HOUR_MS, "1–6 h late"),
+    (DAY_MS, "6–24 h late"),
+    (3 * DAY_MS, "1–3 days late"),
+];
+
+impl AdherenceReport {
+    pub fn count(&self, status: DoseStatus) -> usize {
+        self.slots
+            .iter()
+            .filter(|slot| slot.status == status)
+            .count()
+    }
+
+    /// Share of slots that were covered by a dose, in percent.
+    pub fn taken_percent(&self) -> Option<f64> {
+        let taken = self.slots.len() - self.count(DoseStatus::Missed);
+        percent(taken, self.slots.len())
+    }
+
+    pub fn on_time_percent(&self) -> Option<f64> {
+        percent(self.count(DoseStatus::OnTime), self.slots.len())
+    }
+
+    /// Current and longest runs of slots without a missed dose.
+    pub fn streaks(&self) -> (usize, usize) {
+        let mut current = 0;
+        let mut longest = 0;
+        for slot in &self.slots {
+            if slot.status == DoseStatus::Missed {
+                current = 0;
+            } else {
+                current += 1;
+                longest = longest.max(current);
+            }
+        }
+        (current, longest)
+    }
+
+    /// How late the taken doses were, as (label, count).
+    pub fn lateness_buckets(&self) -> Vec<(&'static str, usize)> {
+        let mut counts = vec![0; LATENESS_BUCKETS.len() + 1];
+        for lateness in self.slots.iter().filter_map(DoseSlot::lateness) {
+            let bucket = LATENESS_BUCKETS
+                .iter()
+                .position(|(bound, _)| lateness < *bound)
+                .unwrap_or(LATENESS_BUCKETS.len());
+            counts[bucket] += 1;
+        }
+        LATENESS_BUCKETS
+            .iter()
+            .map(|(_, label)| *label)
+            .chain(["Over 3 days late"])
+            .zip(counts)
+            .collect()
+    }
+
+    /// Slot outcomes per local day of the due time.
+    pub fn by_day(&self) -> BTreeMap<NaiveDate, DayAdherence> {
+        let mut days: BTreeMap<NaiveDate, DayAdherence> = BTreeMap::new();
+        for slot in &self.slots {
+            let day = days.entry(local_date(slot.due)).or_default();
+            match slot.status {
+                DoseStatus::OnTime => day.onTime += 1,
+                DoseStatus::Late => day.late += 1,
+                DoseStatus::Missed => day.missed += 1,
+            }
+        }
+        days
+    }
+
+    /// Slots that were missed, most recent first.
+    pub fn missed(&self) -> Vec<&DoseSlot> {
+        let mut missed: Vec<&DoseSlot> = self
+            .slots
+            .iter()
+            .filter(|slot| slot.status == DoseStatus::Missed)
+            .collect();
+        missed.reverse();
+        missed
+    }
+}
+
+fn percent(part: usize, total: usize) -> Option<f64> {
+    (total > 0).then(|| part as f64 * 100.0 / total as f64)
+}
+
+/// How early a dose may be logged and still cover its slot.
+fn early_window(step: i64) -> i64 {
+    (step / 4).clamp(HOUR_MS, DAY_MS).min(step / 2)
+}
+
+/// How late a dose may be logged and still count as on time.
+fn on_time_grace(step: i64) -> i64 {
+    (step / 10).clamp(HOUR_MS, DAY_MS)
+}
+
+fn is_bonus_dose(schedule: &RegimenSchedule, entry: &DosageHistoryEntry) -> bool {
+    match (schedule, entry) {
+        (
+            RegimenSchedule::InjectableEstradiol(s),
+            DosageHistoryEntry::InjectableEstradiol {
+                kind, bonusDose, ..
+            },
+        ) => *kind == s.kind && bonusDose.unwrap_or(false),
+        _ => false,
+    }
+}
+
+/// Rebuilds the dose slots of one regimen up to `now` and matches logged
+/// doses to them. Each slot takes the first dose logged between a little
+/// before it and the start of the next slot's window. Slots start with the
+/// regimen, so doses missed before the first logged one count. Fixed-interval
+/// schedules restart from a late dose, as recording one moves the next
+/// dose date.
+pub fn regimen_adherence(data: &HrtData, regimen: &Regimen, now: UnixTime) -> AdherenceReport {
+    let mut report = AdherenceReport::default();
+    let freq_days = regimen.schedule.frequency();
+    let step = (freq_days * DAY_MS as f64) as i64;
+    if !freq_days.is_finite() || step <= 0 {
+        return report;
+    }
+    let early = early_window(step);
+    let grace = on_time_grace(step);
+    let end = regimen.endDate.map_or(now, |end| end.min(now));
+    let in_regimen = |date: UnixTime| date >= regimen.startDate && date < end;
+
+    report.extra = data
+        .dosageHistory
+        .iter()
+        .filter(|entry| is_bonus_dose(&regimen.schedule, entry) && in_regimen(entry_date(entry)))
+        .count();
+    let mut doses: Vec<UnixTime> = data
+        .dosageHistory
+        .iter()
+        .filter(|entry| regimen.schedule.matches_entry(entry))
+        .map(entry_date)
+        .filter(|date| in_regimen(*date))
+        .collect();
+    doses.sort_unstable();
+
+    let minute = recurrence_minute(data, regimen);
+    let next_slot = |due: UnixTime, taken: Option<UnixTime>| match &regimen.recurrence {
+        Some(recurrence) => recurrence.next_after(minute, due + 1),
+        None => Some(taken.unwrap_or(due) + step),
+    };
+    // Logging a dose moves the next dose date past it, so it only marks
+    // the first dose while nothing has been logged.
+    let first_due = match doses.first() {
+        Some(_) => regimen.startDate,
+        None => regimen
+            .schedule
+            .next_dose_date()
+            .map_or(regimen.startDate, |next| next.max(regimen.startDate)),
+    };
+    let mut due = match &regimen.recurrence {
+        Some(recurrence) => recurrence.next_after(minute, first_due),
+        None => Some(first_due),
+    };
+
+    let mut remaining = doses.into_iter().peekable();
+    while let Some(slot) = due.filter(|slot| *slot <= end) {
+        while remaining.next_if(|dose| *dose < slot - early).is_some() {
+            report.extra += 1;
+        }
+        let window_end = next_slot(slot, None).unwrap_or(slot + step) - early;
+        let taken = remaining.next_if(|dose| *dose < window_end);
+        let status = match taken {
+            Some(taken) if taken - slot <= grace => DoseStatus::OnTime,
+            Some(_) => DoseStatus::Late,
+            None if window_end > now => break,
+            None => DoseStatus::Missed,
+        };
+        report.slots.push(DoseSlot {
+            regimenId: regimen.id.clone(),
+            due: slot,
+            taken,
+            status,
+        });
+        due = next_slot(slot, taken);
+    }
+    report.extra += remaining.count();
+    report
+}
+
+/// Adherence across every regimen, current and past.
+pub fn adherence_report(data: &HrtData, now: UnixTime) -> AdherenceReport {
+    let mut report = AdherenceReport::default();
+    for regimen in &data.regimens {
+        let regimen_report = regimen_adherence(data, regimen, now);
+        report.slots.extend(regimen_report.slots);
+        report.extra += regimen_report.extra;
+    }
+    report.slots.sort_by_key(|slot| slot.due);
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{
+        AntiandrogenSchedule, Antiandrogens, HormoneUnits, InjectableEstradiols,
+        InjectableSchedule, Recurrence,
+    };
+    use chrono::{Local, TimeZone};
+
+    const BASE: UnixTime = 1_700_000_000_000;
+
+    fn injection(date: UnixTime, bonus: bool) -> DosageHistoryEntry {
+        DosageHistoryEntry::InjectableEstradiol {
+            date,
+            id: None,
+            kind: InjectableEstradiols::Valerate,
+            dose: 4.0,
+            unit: HormoneUnits::Mg,
+            note: None,
+            bonusDose: bonus.then_some(true),
+            injectionSite: None,
+            vialId: None,
+            subVialId: None,
+            syringeKind: None,
+            needleLength: None,
+            needleGauge: None,
+            photos: None,
+            pkModelId: None,
+        }
+    }
+
+    fn spiro(date: UnixTime) -> DosageHistoryEntry {
+        DosageHistoryEntry::Antiandrogen {
+            date,
+            id: None,
+            kind: Antiandrogens::Spiro,
+            dose: 50.0,
+            unit: HormoneUnits::Mg,
+            note: None,
+        }
+    }
+
+    fn at(day: u32, hour: u32, minute: u32) -> UnixTime {
+        Local
+            .with_ymd_and_hms(2025, 3, day, hour, minute, 0)
+            .unwrap()
+            .timestamp_millis()
+    }
+
+    fn statuses(report: &AdherenceReport) -> Vec<DoseStatus> {
+        report.slots.iter().map(|slot| slot.status).collect()
+    }
+
+    #[test]
+    fn weekly_injections_restart_from_a_late_dose() {
+        let mut data = HrtData::default();
+        data.regimens.push(Regimen {
+            id: "e2".to_string(),
+            startDate: BASE,
+            endDate: None,
+            schedule: RegimenSchedule::InjectableEstradiol(InjectableSchedule {
+                kind: InjectableEstradiols::Valerate,
+                dose: 4.0,
+                unit: HormoneUnits::Mg,
+                frequency: 7.0,
+                vialId: None,
+                subVialId: None,
+                syringeKind: None,
+                needleLength: None,
+                needleGauge: None,
+                nextDoseDate: None,
+            }),
+            recurrence: None,
+        });
+        data.dosageHistory = vec![
+            injection(BASE, false),
+            injection(BASE + 3 * DAY_MS, true),
+            injection(BASE + 7 * DAY_MS + HOUR_MS, false),
+            injection(BASE + 16 * DAY_MS + HOUR_MS, false),
+        ];
+
+        let report = adherence_report(&data, BASE + 32 * DAY_MS);
+        use DoseStatus::*;
+        assert_eq!(statuses(&report), vec![OnTime, OnTime, Late, Missed]);
+        assert_eq!(report.slots[3].due, BASE + 23 * DAY_MS + HOUR_MS);
+        assert_eq!(report.slots[2].lateness(), Some(2 * DAY_MS));
+        assert_eq!(report.extra, 1);
+        assert_eq!(report.taken_percent(), Some(75.0));
+        assert_eq!(report.streaks(), (0, 3));
+    }
+
+    #[test]
+    fn doses_missed_before_the_first_logged_one_count() {
+        let mut data = HrtData::default();
+        data.regimens.push(Regimen {
+            id: "e2".to_string(),
+            startDate: BASE,
+            endDate: None,
+            schedule: RegimenSchedule::InjectableEstradiol(InjectableSchedule {
+                kind: InjectableEstradiols::Valerate,
+                dose: 4.0,
+                unit: HormoneUnits::Mg,
+                frequency: 7.0,
+                vialId: None,
+                subVialId: None,
+                syringeKind: None,
+                needleLength: None,
+                needleGauge: None,
+                nextDoseDate: Some(BASE + 14 * DAY_MS + HOUR_MS),
+            }),
+            recurrence: None,
+        });
+        data.dosageHistory = vec![injection(BASE + 7 * DAY_MS + HOUR_MS, false)];
+
+        let report = adherence_report(&data, BASE + 10 * DAY_MS);
+        use DoseStatus::*;
+        assert_eq!(statuses(&report), vec![Missed, OnTime]);
+        assert_eq!(report.slots[0].due, BASE);
+        assert_eq!(report.streaks(), (1, 1));
+    }
+
+    #[test]
+    fn twice_daily_rule_counts_each_time_of_day() {
+        let mut data = HrtData::default();
+        data.regimens.push(Regimen {
+            id: "spiro".to_string(),
+            startDate: at(10, 0, 0),
+            endDate: None,
+            schedule: RegimenSchedule::Antiandrogen(AntiandrogenSchedule {
+                kind: Antiandrogens::Spiro,
+                dose: 50.0,
+                unit: HormoneUnits::Mg,
+                frequency: 0.5,
+                nextDoseDate: None,
+                pillStock: None,
+                pillStockDate: None,
+            }),
+            recurrence: Some(Recurrence {
+                timesOfDay: vec![8 * 60, 20 * 60],
+                ..Default::default()
+            }),
+        });
+        data.dosageHistory = vec![
+            spiro(at(10, 8, 5)),
+            spiro(at(10, 20, 0)),
+            spiro(at(11, 8, 30)),
+            spiro(at(11, 9, 0)),
+        ];
+
+        // The 08:00 slot on the 12th is still open and not counted yet.
+        let report = adherence_report(&data, at(12, 7, 0));
+        use DoseStatus::*;
+        assert_eq!(statuses(&report), vec![OnTime, OnTime, OnTime, Missed]);
+        assert_eq!(report.extra, 1);
+        assert_eq!(report.missed()[0].due, at(11, 20, 0));
+
+        let days = report.by_day();
+        let day = |d| days[&NaiveDate::from_ymd_opt(2025, 3, d).unwrap()];
+        assert_eq!(day(10).onTime, 2);
+        assert_eq!((day(11).onTime, day(11).missed), (1, 1));
+    }
+
+    #[test]
+    fn lateness_is_bucketed() {
+        let slot = |lateness: i64| DoseSlot {
+            regimenId: String::new(),
+            due: BASE,
+            taken: Some(BASE + lateness),
+            status: DoseStatus::Late,
+        };
+        let report = AdherenceReport {
+            slots: vec![
+                slot(-2 * HOUR_MS),
+                slot(0),
+                slot(2 * HOUR_MS),
+                slot(4 * DAY_MS),
+            ],
+            extra: 0,
+        };
+        let counts: Vec<usize> = report
+            .lateness_buckets()
+            .into_iter()
+            .map(|(_, count)| count)
+            .collect();
+        assert_eq!(counts, vec![1, 1, 1, 0, 0, 1]);
+    }
+}
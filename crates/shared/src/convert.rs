@@ -1,12 +1,18 @@
+#![allow(non_snake_case)]
+
 use std::collections::HashMap;
 
-use crate::types::Hormone;
+use serde::{Deserialize, Serialize};
+
+use crate::quantity::UnitDimension;
+use crate::types::{Hormone, HormoneUnits};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum BaseUnit {
     G,
     Mol,
     L,
+    Iu,
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -21,32 +27,80 @@ struct UnitRatio {
     denominator: UnitSingle,
 }
 
-fn molar_masses() -> HashMap<Hormone, f64> {
+const HORMONES: [Hormone; 25] = {
     use Hormone::*;
-    HashMap::from([
-        (Cholesterol, 386.65),
-        (Testosterone, 288.431),
-        (Dihydrotestosterone, 290.447),
-        (Dehydroepiandrosterone, 288.424),
-        (Estrone, 270.336),
-        (Estradiol, 272.38),
-        (Estriol, 288.387),
-        (Estetrol, 304.386),
-        (Progesterone, 314.469),
-        (Aldosterone, 360.45),
-        (Androstenedione, 286.415),
-        (Cortisol, 362.46),
-        (Gonadorelin, 1182.311),
-        (FollicleStimulatingHormone, 30000.0),
-        (LuteinisingHormone, 33000.0),
-        (ThyroidStimulatingHormone, 28000.0),
-        (SexHormoneBindingGlobulin, 43700.0),
-        (Prolactin, 22892.0),
-        (Thyroxine, 776.87),
-        (Triiodothyronine, 650.977),
-        (VitaminD3, 384.64),
-        (VitaminB12, 1355.388),
-    ])
+    [
+        Cholesterol,
+        Testosterone,
+        Dihydrotestosterone,
+        Dehydroepiandrosterone,
+        Estrone,
+        Estradiol,
+        Estriol,
+        Estetrol,
+        Progesterone,
+        Aldosterone,
+        Androstenedione,
+        Cortisol,
+        Gonadorelin,
+        FollicleStimulatingHormone,
+        LuteinisingHormone,
+        ThyroidStimulatingHormone,
+        SexHormoneBindingGlobulin,
+        Prolactin,
+        Thyroxine,
+        Triiodothyronine,
+        VitaminD3,
+        VitaminB12,
+        Creatinine,
+        Glucose,
+        Triglycerides,
+    ]
+};
+
+/// Molar mass in g/mol. Every hormone has one, so mass and molar
+/// concentrations always convert.
+pub const fn molar_mass(hormone: &Hormone) -> f64 {
+    use Hormone::*;
+    match hormone {
+        Cholesterol => 386.65,
+        Testosterone => 288.431,
+        Dihydrotestosterone => 290.447,
+        Dehydroepiandrosterone => 288.424,
+        Estrone => 270.336,
+        Estradiol => 272.38,
+        Estriol => 288.387,
+        Estetrol => 304.386,
+        Progesterone => 314.469,
+        Aldosterone => 360.45,
+        Androstenedione => 286.415,
+        Cortisol => 362.46,
+        Gonadorelin => 1182.311,
+        FollicleStimulatingHormone => 30000.0,
+        LuteinisingHormone => 33000.0,
+        ThyroidStimulatingHormone => 28000.0,
+        SexHormoneBindingGlobulin => 43700.0,
+        Prolactin => 22892.0,
+        Thyroxine => 776.87,
+        Triiodothyronine => 650.977,
+        VitaminD3 => 384.64,
+        VitaminB12 => 1355.388,
+        Creatinine => 113.12,
+        Glucose => 180.156,
+        // Triolein, the mass labs use to convert triglycerides.
+        Triglycerides => 885.7,
+    }
+}
+
+// International units per gram from WHO reference preparations. Glycoprotein
+// hormones without a mass standard (FSH, LH, TSH) only convert between IU
+// units.
+pub(crate) fn iu_per_gram(hormone: &Hormone) -> Option<f64> {
+    match hormone {
+        // WHO 3rd IS 84/500: 1 ng/mL = 21.2 mIU/L.
+        Hormone::Prolactin => Some(21_200.0),
+        _ => None,
+    }
 }
 
 fn prefix_exponents() -> HashMap<&'static str, i32> {
@@ -91,6 +145,11 @@ fn parse_unit_single(token: &str) -> Result<UnitSingle, String> {
 
     let (base, base_len) = if t.ends_with("mol") {
         (BaseUnit::Mol, 3)
+    } else if t.ends_with("IU") {
+        (BaseUnit::Iu, 2)
+    } else if t.ends_with('U') {
+        // U/L is used interchangeably with IU/L for gonadotropins.
+        (BaseUnit::Iu, 1)
     } else if t.ends_with('g') {
         (BaseUnit::G, 1)
     } else if t.ends_with('L') {
@@ -122,11 +181,11 @@ fn parse_unit_ratio(unit: &str) -> Result<UnitRatio, String> {
     let numerator = parse_unit_single(num_raw)?;
     let denominator = parse_unit_single(den_raw)?;
 
-    let num_ok = matches!(numerator.base, BaseUnit::G | BaseUnit::Mol);
+    let num_ok = matches!(numerator.base, BaseUnit::G | BaseUnit::Mol | BaseUnit::Iu);
     let den_ok = matches!(denominator.base, BaseUnit::L);
     if !(num_ok && den_ok) {
         return Err(format!(
-            "Only mass/volume or mol/volume units are supported (got \"{}\")",
+            "Only mass, mol or IU per volume units are supported (got \"{}\")",
             unit
         ));
     }
@@ -146,16 +205,21 @@ fn convert_core(
     let prefix_calc =
         (from.numerator.exp + to.denominator.exp) - (from.denominator.exp + to.numerator.exp);
 
-    let mass = molar_masses()
-        .get(&hormone)
-        .copied()
-        .ok_or_else(|| format!("Missing molar mass for {:?}", hormone))?;
+    let mass = molar_mass(&hormone);
+    let iu = || {
+        iu_per_gram(&hormone).ok_or_else(|| format!("No IU reference standard for {:?}", hormone))
+    };
 
     let base_factor = match (from.numerator.base, to.numerator.base) {
         (BaseUnit::Mol, BaseUnit::G) => mass,
         (BaseUnit::G, BaseUnit::Mol) => 1.0 / mass,
-        (BaseUnit::Mol, BaseUnit::Mol) => 1.0,
-        (BaseUnit::G, BaseUnit::G) => 1.0,
+        (BaseUnit::Mol, BaseUnit::Mol)
+        | (BaseUnit::G, BaseUnit::G)
+        | (BaseUnit::Iu, BaseUnit::Iu) => 1.0,
+        (BaseUnit::G, BaseUnit::Iu) => iu()?,
+        (BaseUnit::Iu, BaseUnit::G) => 1.0 / iu()?,
+        (BaseUnit::Mol, BaseUnit::Iu) => mass * iu()?,
+        (BaseUnit::Iu, BaseUnit::Mol) => 1.0 / (mass * iu()?),
         _ => {
             return Err(format!(
                 "Unsupported base conversion from {:?} to {:?}",
@@ -179,6 +243,262 @@ pub fn convert_hormone(
     convert_core(value, hormone, from, to)
 }
 
+pub fn convert_hormone_units(
+    value: f64,
+    hormone: Hormone,
+    from_unit: &HormoneUnits,
+    to_unit: &HormoneUnits,
+) -> Result<f64, String> {
+    convert_hormone(value, hormone, from_unit.symbol(), to_unit.symbol())
+}
+
+const HORMONE_ALIASES: [(&str, Hormone); 11] = [
+    ("E2", Hormone::Estradiol),
+    ("T", Hormone::Testosterone),
+    ("P4", Hormone::Progesterone),
+    ("FSH", Hormone::FollicleStimulatingHormone),
+    ("LH", Hormone::LuteinisingHormone),
+    ("TSH", Hormone::ThyroidStimulatingHormone),
+    ("PRL", Hormone::Prolactin),
+    ("SHBG", Hormone::SexHormoneBindingGlobulin),
+    ("DHT", Hormone::Dihydrotestosterone),
+    ("DHEA", Hormone::Dehydroepiandrosterone),
+    ("E1", Hormone::Estrone),
+];
+
+// Hormones reported by labs in international units.
+fn measured_in_iu(hormone: &Hormone) -> bool {
+    matches!(
+        hormone,
+        Hormone::FollicleStimulatingHormone
+            | Hormone::LuteinisingHormone
+            | Hormone::ThyroidStimulatingHormone
+            | Hormone::Prolactin
+    )
+}
+
+pub fn hormone_name(hormone: &Hormone) -> String {
+    match serde_json::to_value(hormone) {
+        Ok(serde_json::Value::String(name)) => name,
+        _ => format!("{:?}", hormone),
+    }
+}
+
+/// Parses a hormone by its full name or the short name used for blood test
+/// fields (e.g. "E2", "FSH", "SHBG"), ignoring case.
+pub fn parse_hormone(name: &str) -> Option<Hormone> {
+    let name = name.trim();
+    HORMONES
+        .iter()
+        .find(|hormone| hormone_name(hormone).eq_ignore_ascii_case(name))
+        .or_else(|| {
+            HORMONE_ALIASES
+                .iter()
+                .find(|(alias, _)| alias.eq_ignore_ascii_case(name))
+                .map(|(_, hormone)| hormone)
+        })
+        .cloned()
+}
+
+/// One entry of a conversion request. Fields are optional so that a missing
+/// field can be reported per item instead of rejecting the whole body.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ConvertItem {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<serde_json::Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value: Option<serde_json::Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hormone: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fromUnit: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub toUnit: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ConvertErrorCode {
+    InvalidItem,
+    MissingField,
+    InvalidValue,
+    UnknownHormone,
+    InvalidUnit,
+    IncompatibleUnits,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ConvertError {
+    pub code: ConvertErrorCode,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub field: Option<String>,
+    pub message: String,
+}
+
+impl ConvertError {
+    fn new(code: ConvertErrorCode, field: Option<&str>, message: String) -> Self {
+        Self {
+            code,
+            field: field.map(str::to_string),
+            message,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ConvertOutcome {
+    pub index: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub unit: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<ConvertError>,
+}
+
+fn required<'a>(field: &str, value: Option<&'a str>) -> Result<&'a str, ConvertError> {
+    value
+        .map(str::trim)
+        .filter(|v| !v.is_empty())
+        .ok_or_else(|| {
+            ConvertError::new(
+                ConvertErrorCode::MissingField,
+                Some(field),
+                format!("Missing field \"{}\"", field),
+            )
+        })
+}
+
+/// Converts one item, rounding to three decimals like `/api/convert`.
+pub fn convert_item(item: &ConvertItem) -> Result<f64, ConvertError> {
+    let value = match &item.value {
+        None | Some(serde_json::Value::Null) => {
+            return Err(ConvertError::new(
+                ConvertErrorCode::MissingField,
+                Some("value"),
+                "Missing field \"value\"".to_string(),
+            ))
+        }
+        Some(serde_json::Value::Number(number)) => number.as_f64(),
+        Some(serde_json::Value::String(text)) => text.trim().parse::<f64>().ok(),
+        Some(_) => None,
+    };
+    let value = value.filter(|v| v.is_finite()).ok_or_else(|| {
+        ConvertError::new(
+            ConvertErrorCode::InvalidValue,
+            Some("value"),
+            "Invalid value".to_string(),
+        )
+    })?;
+    let hormone_str = required("hormone", item.hormone.as_deref())?;
+    let from_unit = required("fromUnit", item.fromUnit.as_deref())?;
+    let to_unit = required("toUnit", item.toUnit.as_deref())?;
+
+    let hormone = parse_hormone(hormone_str).ok_or_else(|| {
+        ConvertError::new(
+            ConvertErrorCode::UnknownHormone,
+            Some("hormone"),
+            format!("Unknown hormone \"{}\"", hormone_str),
+        )
+    })?;
+    let from = parse_unit_ratio(from_unit)
+        .map_err(|err| ConvertError::new(ConvertErrorCode::InvalidUnit, Some("fromUnit"), err))?;
+    let to = parse_unit_ratio(to_unit)
+        .map_err(|err| ConvertError::new(ConvertErrorCode::InvalidUnit, Some("toUnit"), err))?;
+    let converted = convert_core(value, hormone, from, to)
+        .map_err(|err| ConvertError::new(ConvertErrorCode::IncompatibleUnits, None, err))?;
+    Ok((converted * 1000.0).round() / 1000.0)
+}
+
+/// Converts each raw JSON item independently; items that are not objects of
+/// the `ConvertItem` shape fail with `InvalidItem`.
+pub fn convert_batch(items: &[serde_json::Value]) -> Vec<ConvertOutcome> {
+    items
+        .iter()
+        .enumerate()
+        .map(|(index, raw)| {
+            let item = match serde_json::from_value::<ConvertItem>(raw.clone()) {
+                Ok(item) if raw.is_object() => item,
+                Ok(_) | Err(_) => {
+                    return ConvertOutcome {
+                        index,
+                        id: None,
+                        value: None,
+                        unit: None,
+                        error: Some(ConvertError::new(
+                            ConvertErrorCode::InvalidItem,
+                            None,
+                            "Item must be an object with value, hormone, fromUnit and toUnit"
+                                .to_string(),
+                        )),
+                    };
+                }
+            };
+            let (value, unit, error) = match convert_item(&item) {
+                Ok(value) => (
+                    Some(value),
+                    item.toUnit.as_ref().map(|u| u.trim().to_string()),
+                    None,
+                ),
+                Err(err) => (None, None, Some(err)),
+            };
+            ConvertOutcome {
+                index,
+                id: item.id,
+                value,
+                unit,
+                error,
+            }
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HormoneUnitInfo {
+    pub name: String,
+    pub aliases: Vec<String>,
+    pub molarMass: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub iuPerGram: Option<f64>,
+    /// Unit symbols from `HormoneUnits` that convert to each other for this
+    /// hormone. Other SI-prefixed g, mol or IU per volume units also parse.
+    pub units: Vec<String>,
+}
+
+/// Every hormone the converter knows, with its molar mass and units.
+pub fn hormone_unit_catalog() -> Vec<HormoneUnitInfo> {
+    HORMONES
+        .iter()
+        .map(|hormone| {
+            let iu = iu_per_gram(hormone);
+            let units = HormoneUnits::ALL
+                .iter()
+                .filter(|unit| match unit.dimension() {
+                    UnitDimension::MassConcentration | UnitDimension::MolarConcentration => {
+                        !measured_in_iu(hormone) || iu.is_some()
+                    }
+                    UnitDimension::Activity => measured_in_iu(hormone),
+                    UnitDimension::Mass => false,
+                })
+                .map(|unit| unit.symbol().to_string())
+                .collect();
+            HormoneUnitInfo {
+                name: hormone_name(hormone),
+                aliases: HORMONE_ALIASES
+                    .iter()
+                    .filter(|(_, aliased)| aliased == hormone)
+                    .map(|(alias, _)| alias.to_string())
+                    .collect(),
+                molarMass: molar_mass(hormone),
+                iuPerGram: iu,
+                units,
+            }
+        })
+        .collect()
+}
+
 pub fn convert_estradiol(value: f64, from_unit: &str, to_unit: &str) -> Result<f64, String> {
     convert_hormone(value, Hormone::Estradiol, from_unit, to_unit)
 }
@@ -303,6 +623,168 @@ mod tests {
         assert!((r1 - r2).abs() < f64::EPSILON);
     }
 
+    #[test]
+    fn gonadotropin_iu_units() {
+        let fsh = Hormone::FollicleStimulatingHormone;
+        assert_eq!(
+            convert_hormone(5000.0, fsh.clone(), "mIU/L", "mIU/mL").unwrap(),
+            5.0
+        );
+        let u_l = convert_hormone(5.0, fsh.clone(), "U/L", "mIU/mL").unwrap();
+        assert!((u_l - 5.0).abs() < 1e-9, "got {u_l}");
+        let iu_l = convert_hormone(5.0, Hormone::LuteinisingHormone, "IU/L", "U/L").unwrap();
+        assert!((iu_l - 5.0).abs() < 1e-9, "got {iu_l}");
+        // No mass standard for FSH.
+        assert!(convert_hormone(5.0, fsh, "mIU/mL", "ng/mL").is_err());
+    }
+
+    #[test]
+    fn prolactin_who_standard() {
+        let miu = convert_hormone(10.0, Hormone::Prolactin, "ng/mL", "mIU/L").unwrap();
+        assert!((miu - 212.0).abs() < 1e-6, "got {miu}");
+        let ng = convert_hormone(212.0, Hormone::Prolactin, "mIU/L", "ng/mL").unwrap();
+        assert!((ng - 10.0).abs() < 1e-6, "got {ng}");
+        let ug_l = convert_hormone(212.0, Hormone::Prolactin, "mIU/L", "ug/L").unwrap();
+        assert!((ug_l - 10.0).abs() < 1e-6, "got {ug_l}");
+        let pmol = convert_hormone(212.0, Hormone::Prolactin, "mIU/L", "pmol/L").unwrap();
+        assert!((pmol - 10_000.0 / 22.892).abs() < 1e-3, "got {pmol}");
+    }
+
+    #[test]
+    fn iu_and_non_molar_errors() {
+        assert!(convert_hormone(1.0, Hormone::Estradiol, "mIU/mL", "pg/mL").is_err());
+        assert!(convert_hormone_units(
+            1.0,
+            Hormone::Estradiol,
+            &HormoneUnits::Mg,
+            &HormoneUnits::E2PgMl
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn hormone_units_match_string_conversion() {
+        let typed = convert_hormone_units(
+            100.0,
+            Hormone::Testosterone,
+            &HormoneUnits::TNgDl,
+            &HormoneUnits::TNmolL,
+        )
+        .unwrap();
+        let text = convert_testosterone(100.0, "ng/dL", "nmol/L").unwrap();
+        assert_eq!(typed, text);
+    }
+
+    #[test]
+    fn parse_hormone_accepts_names_and_aliases() {
+        assert_eq!(parse_hormone("Estradiol"), Some(Hormone::Estradiol));
+        assert_eq!(
+            parse_hormone("Sex hormone-binding globulin"),
+            Some(Hormone::SexHormoneBindingGlobulin)
+        );
+        assert_eq!(
+            parse_hormone("fsh"),
+            Some(Hormone::FollicleStimulatingHormone)
+        );
+        assert_eq!(
+            parse_hormone(" SHBG "),
+            Some(Hormone::SexHormoneBindingGlobulin)
+        );
+        assert_eq!(parse_hormone("unobtanium"), None);
+    }
+
+    fn item(value: serde_json::Value, hormone: &str, from: &str, to: &str) -> ConvertItem {
+        ConvertItem {
+            id: None,
+            value: Some(value),
+            hormone: Some(hormone.to_string()),
+            fromUnit: Some(from.to_string()),
+            toUnit: Some(to.to_string()),
+        }
+    }
+
+    #[test]
+    fn convert_item_reports_structured_errors() {
+        let ok = item(serde_json::json!(100), "E2", "pg/mL", "pmol/L");
+        assert_eq!(convert_item(&ok), Ok(367.134));
+        let text = item(serde_json::json!("100"), "Estradiol", "pg/mL", "pmol/L");
+        assert_eq!(convert_item(&text), Ok(367.134));
+
+        let missing = ConvertItem {
+            toUnit: None,
+            ..ok.clone()
+        };
+        let err = convert_item(&missing).unwrap_err();
+        assert_eq!(err.code, ConvertErrorCode::MissingField);
+        assert_eq!(err.field.as_deref(), Some("toUnit"));
+
+        let err = convert_item(&item(serde_json::json!("abc"), "E2", "pg/mL", "pmol/L"));
+        assert_eq!(err.unwrap_err().code, ConvertErrorCode::InvalidValue);
+        let err = convert_item(&item(serde_json::json!(1), "E9", "pg/mL", "pmol/L"));
+        assert_eq!(err.unwrap_err().code, ConvertErrorCode::UnknownHormone);
+        let err = convert_item(&item(serde_json::json!(1), "E2", "pg", "pmol/L")).unwrap_err();
+        assert_eq!(err.code, ConvertErrorCode::InvalidUnit);
+        assert_eq!(err.field.as_deref(), Some("fromUnit"));
+        let err = convert_item(&item(serde_json::json!(1), "FSH", "mIU/mL", "ng/mL"));
+        assert_eq!(err.unwrap_err().code, ConvertErrorCode::IncompatibleUnits);
+    }
+
+    #[test]
+    fn convert_batch_keeps_order_and_ids() {
+        let mut first = item(serde_json::json!(1), "T", "nmol/L", "ng/dL");
+        first.id = Some(serde_json::json!("t"));
+        let items = vec![
+            serde_json::to_value(first).unwrap(),
+            serde_json::to_value(item(serde_json::json!(1), "nope", "nmol/L", "ng/dL")).unwrap(),
+            serde_json::json!("E2 100 pg/mL"),
+        ];
+        let results = convert_batch(&items);
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0].id, Some(serde_json::json!("t")));
+        assert_eq!(results[0].value, Some(28.843));
+        assert_eq!(results[0].unit.as_deref(), Some("ng/dL"));
+        assert!(results[0].error.is_none());
+        assert_eq!(results[1].index, 1);
+        assert!(results[1].value.is_none());
+        assert_eq!(
+            results[1].error.as_ref().map(|e| e.code.clone()),
+            Some(ConvertErrorCode::UnknownHormone)
+        );
+        assert_eq!(
+            results[2].error.as_ref().map(|e| e.code.clone()),
+            Some(ConvertErrorCode::InvalidItem)
+        );
+    }
+
+    #[test]
+    fn catalog_lists_units_that_convert() {
+        let catalog = hormone_unit_catalog();
+        assert_eq!(catalog.len(), HORMONES.len());
+        for info in &catalog {
+            let hormone = parse_hormone(&info.name).unwrap();
+            for from in &info.units {
+                for to in &info.units {
+                    assert!(
+                        convert_hormone(1.0, hormone.clone(), from, to).is_ok(),
+                        "{} {from} -> {to}",
+                        info.name
+                    );
+                }
+            }
+        }
+        let fsh = catalog
+            .iter()
+            .find(|h| h.aliases.contains(&"FSH".to_string()));
+        assert_eq!(fsh.unwrap().units, vec!["mIU/mL", "mIU/L", "U/L"]);
+        let e2 = catalog.iter().find(|h| h.name == "Estradiol").unwrap();
+        assert_eq!(e2.molarMass, 272.38);
+        assert!(e2.units.contains(&"pmol/L".to_string()));
+        assert!(!e2.units.contains(&"mIU/L".to_string()));
+        let prolactin = catalog.iter().find(|h| h.name == "Prolactin").unwrap();
+        assert_eq!(prolactin.iuPerGram, Some(21_200.0));
+        assert!(prolactin.units.contains(&"ng/mL".to_string()));
+    }
+
     #[test]
     fn ng_ml_to_pg_ml_is_thousand() {
         let result = convert_estradiol(1.0, "ng/mL", "pg/mL").unwrap();
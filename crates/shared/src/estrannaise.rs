@@ -1,9 +1,41 @@
 use std::collections::HashMap;
 
-use crate::types::EstrannaiseModel;
+use crate::medications::{find_medication, medication_model};
+use crate::quantity::{Mass, Measure, TypedUnit};
+use crate::transdermal::{patch_model, patch_total_strength, patch_wear_days, worn_patch_model};
+use crate::types::{
+    CustomPkModel, DosageHistoryEntry, EstrannaiseModel, HormoneUnits, HrtData,
+    InjectableEstradiols, InjectableSchedule, MedicationClass, OralEstradiolRoutes, OralEstradiols,
+    PkModelParams, UnixTime,
+};
 
 pub type PKParams = (f64, f64, f64, f64);
 
+// Patch doses are mcg/day of delivered estradiol, as in estrannaise; the depot
+// is removed at the end of the wear period. These are the nominal wear periods
+// of the two fits; `Patch` models carry their own.
+const PATCH_TW_WEAR_DAYS: f64 = 3.5;
+const PATCH_OW_WEAR_DAYS: f64 = 7.0;
+
+// How long after the last dose a model stays worth charting.
+const DEFAULT_WASHOUT_DAYS: f64 = 30.0;
+const PEP_WASHOUT_DAYS: f64 = 210.0;
+const MAX_WASHOUT_DAYS: f64 = 365.0;
+
+// Bounds for user-defined model parameters; well outside every built-in model.
+const MAX_CUSTOM_D: f64 = 100_000.0;
+const MAX_CUSTOM_RATE: f64 = 1_000.0;
+const MIN_CUSTOM_RATE: f64 = 1e-4;
+
+// Population variability used for uncertainty bands, as coefficients of
+// variation on the dose scale and on the rate constants.
+const DOSE_SCALE_CV: f64 = 0.35;
+const RATE_CV: f64 = 0.2;
+const UNCERTAINTY_SEED: u64 = 0x5eed_e2e2;
+
+pub const UNCERTAINTY_SAMPLES: usize = 50;
+pub const UNCERTAINTY_COVERAGE: f64 = 0.9;
+
 pub fn pk_parameters() -> HashMap<EstrannaiseModel, PKParams> {
     use EstrannaiseModel::*;
     HashMap::from([
@@ -13,8 +45,25 @@ pub fn pk_parameters() -> HashMap<EstrannaiseModel, PKParams> {
         (EbIm, (1893.1, 0.67, 61.5, 4.34)),
         (EUnIm, (471.5, 0.01729, 6.528, 2.285)),
         (EUnCasubq, (16.15, 0.046, 0.022, 0.101)),
+        // Polyestradiol phosphate is not in estrannaise. Fitted to a single
+        // 80 mg IM dose plateauing near 250 pg/mL from the first to the
+        // second week (Stege et al. 1996) and its ~70 day elimination
+        // half-life (Gunnarsson & Norlén 1988).
+        (PepIm, (320.0, 0.5, 0.0099, 1.0)),
         (PatchTw, (16.792, 0.283, 5.592, 4.3)),
         (PatchOw, (59.481, 0.107, 7.842, 5.193)),
+        // Oral and transdermal routes have no estrannaise fit. Oral: 2 mg/day
+        // micronized E2 at steady state peaks near 60 pg/mL about 6 h after
+        // the dose and troughs near 35 pg/mL, with a 13-20 h terminal
+        // half-life (Kuhl 2005).
+        (E2Oral, (26.0, 20.0, 10.0, 1.04)),
+        // Sublingual: a single 0.25 mg dose peaks near 415 pg/mL about 1 h
+        // after the dose and is back near baseline within 12 h (Price et
+        // al. 1997).
+        (E2Sublingual, (3276.0, 70.0, 100.0, 24.0)),
+        // Gel: 1.5 mg/day averages near 80 pg/mL with a flat daily profile
+        // from the skin reservoir (Kuhl 2005).
+        (E2Gel, (640.0, 0.8, 10.0, 12.0)),
     ])
 }
 
@@ -108,14 +157,63 @@ fn e2_curve_3c(
     }
 }
 
-fn pk_functions(conversion_factor: f64) -> HashMap<EstrannaiseModel, Box<dyn Fn(f64, f64) -> f64>> {
-    let params = pk_parameters();
-    let mut map: HashMap<EstrannaiseModel, Box<dyn Fn(f64, f64) -> f64>> = HashMap::new();
+fn e2_patch_3c(t: f64, dose: f64, d: f64, k1: f64, k2: f64, k3: f64, wear: f64) -> f64 {
+    if t <= wear {
+        return e2_curve_3c(t, dose, d, k1, k2, k3, 0.0, 0.0, false, 1.0);
+    }
+    let ds = if (k1 - k2).abs() < f64::EPSILON {
+        dose * d * k1 * wear * f64::exp(-k1 * wear)
+    } else {
+        dose * d * k1 / (k2 - k1) * (f64::exp(-k1 * wear) - f64::exp(-k2 * wear))
+    };
+    let d2 = e2_curve_3c(wear, dose, d, k1, k2, k3, 0.0, 0.0, false, 1.0);
+    e2_curve_3c(t - wear, 0.0, d, k1, k2, k3, ds, d2, false, 1.0)
+}
+
+// Built-in parameters plus those of any custom and worn patch models in
+// `models`.
+fn pk_parameters_for(models: &[EstrannaiseModel]) -> HashMap<EstrannaiseModel, PKParams> {
+    let mut params = pk_parameters();
+    for model in models {
+        match model {
+            EstrannaiseModel::Custom(p) => {
+                params.insert(model.clone(), (p.d, p.k1, p.k2, p.k3));
+            }
+            EstrannaiseModel::Patch(wear) => {
+                let fit = params[&patch_model(wear.wearDays)];
+                params.insert(model.clone(), fit);
+            }
+            _ => {}
+        }
+    }
+    params
+}
+
+type PkFunctions = HashMap<EstrannaiseModel, Box<dyn Fn(f64, f64) -> f64>>;
+
+fn pk_functions(models: &[EstrannaiseModel], conversion_factor: f64) -> PkFunctions {
+    pk_functions_from(pk_parameters_for(models), conversion_factor)
+}
+
+fn pk_functions_from(
+    params: HashMap<EstrannaiseModel, PKParams>,
+    conversion_factor: f64,
+) -> PkFunctions {
+    let mut map: PkFunctions = HashMap::new();
 
     for (model, (d, k1, k2, k3)) in params {
-        map.insert(
-            model,
-            Box::new(move |t: f64, dose: f64| {
+        let func: Box<dyn Fn(f64, f64) -> f64> = match model {
+            EstrannaiseModel::PatchTw | EstrannaiseModel::PatchOw | EstrannaiseModel::Patch(_) => {
+                let wear = match &model {
+                    EstrannaiseModel::Patch(wear) => wear.wearDays,
+                    EstrannaiseModel::PatchTw => PATCH_TW_WEAR_DAYS,
+                    _ => PATCH_OW_WEAR_DAYS,
+                };
+                Box::new(move |t: f64, dose: f64| {
+                    e2_patch_3c(t, conversion_factor * dose, d, k1, k2, k3, wear)
+                })
+            }
+            _ => Box::new(move |t: f64, dose: f64| {
                 e2_curve_3c(
                     t,
                     conversion_factor * dose,
@@ -129,7 +227,8 @@ fn pk_functions(conversion_factor: f64) -> HashMap<EstrannaiseModel, Box<dyn Fn(
                     1.0,
                 )
             }),
-        );
+        };
+        map.insert(model, func);
     }
 
     map
@@ -143,6 +242,19 @@ pub fn e2_multidose_3c(
     conversion_factor: f64,
     intervals: bool,
 ) -> f64 {
+    e2_multidose_series(&[t], doses, times, models, conversion_factor, intervals)[0]
+}
+
+/// `e2_multidose_3c` at every point of `ts`. The model functions are built
+/// once, so prefer this for charts and other many-point evaluations.
+pub fn e2_multidose_series(
+    ts: &[f64],
+    doses: &[f64],
+    times: &[f64],
+    models: &[EstrannaiseModel],
+    conversion_factor: f64,
+    intervals: bool,
+) -> Vec<f64> {
     let mut computed_times = times.to_vec();
     if intervals && !times.is_empty() {
         let mut sum = -times[0];
@@ -155,30 +267,342 @@ pub fn e2_multidose_3c(
             .collect();
     }
 
+    let functions = pk_functions(models, conversion_factor);
+    let resolved = resolve_doses(doses, &computed_times, models, &functions);
+    let sorted = is_sorted(&resolved);
+    ts.iter().map(|t| level_at(&resolved, sorted, *t)).collect()
+}
+
+type ResolvedDose<'a> = (f64, f64, &'a dyn Fn(f64, f64) -> f64);
+
+// Each dose with its time and model function; doses without either are dropped.
+fn resolve_doses<'a>(
+    doses: &[f64],
+    times: &[f64],
+    models: &[EstrannaiseModel],
+    functions: &'a PkFunctions,
+) -> Vec<ResolvedDose<'a>> {
+    doses
+        .iter()
+        .enumerate()
+        .filter_map(|(idx, dose)| {
+            let func = functions.get(models.get(idx)?)?;
+            Some((*times.get(idx)?, *dose, func.as_ref()))
+        })
+        .collect()
+}
+
+fn is_sorted(resolved: &[ResolvedDose]) -> bool {
+    resolved.windows(2).all(|w| w[0].0 <= w[1].0)
+}
+
+// Doses after `t` contribute exactly zero; with sorted dose times they can be
+// skipped without changing the summation order.
+fn level_at(resolved: &[ResolvedDose], sorted: bool, t: f64) -> f64 {
+    let end = if sorted {
+        resolved.partition_point(|(time, _, _)| *time <= t)
+    } else {
+        resolved.len()
+    };
     let mut total = 0.0;
-    let functions = pk_functions(conversion_factor);
+    for (time, dose, func) in &resolved[..end] {
+        total += func(t - time, *dose);
+    }
+    total
+}
 
-    for (idx, dose) in doses.iter().enumerate() {
-        let model = match models.get(idx) {
-            Some(m) => m,
-            None => continue,
-        };
-        let t_offset = match computed_times.get(idx) {
-            Some(value) => t - *value,
-            None => continue,
-        };
-        if let Some(func) = functions.get(model) {
-            total += func(t_offset, *dose);
+/// Closed-form steady-state level `t` days into a dosing cycle. Only available
+/// for plain three-compartment models; patches and sublingual return `None`.
+pub fn e2_steady_state(
+    t: f64,
+    dose: f64,
+    interval_days: f64,
+    model: &EstrannaiseModel,
+    conversion_factor: f64,
+) -> Option<f64> {
+    match model {
+        EstrannaiseModel::PatchTw
+        | EstrannaiseModel::PatchOw
+        | EstrannaiseModel::Patch(_)
+        | EstrannaiseModel::E2Sublingual => None,
+        _ => {
+            let (d, k1, k2, k3) = *pk_parameters_for(std::slice::from_ref(model)).get(model)?;
+            let value = e2_steady_state_3c(t, conversion_factor * dose, interval_days, d, k1, k2, k3);
+            value.is_finite().then_some(value)
         }
     }
+}
 
-    total
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct E2Band {
+    pub lower: Vec<f64>,
+    pub median: Vec<f64>,
+    pub upper: Vec<f64>,
+}
+
+// SplitMix64; seeded so bands are stable between redraws.
+struct Rng(u64);
+
+impl Rng {
+    fn next_f64(&mut self) -> f64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^= z >> 31;
+        ((z >> 11) as f64 + 0.5) / (1u64 << 53) as f64
+    }
+
+    fn normal(&mut self) -> f64 {
+        let u1 = self.next_f64();
+        let u2 = self.next_f64();
+        (-2.0 * u1.ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos()
+    }
+
+    // Median-preserving log-normal multiplier.
+    fn log_normal(&mut self, cv: f64) -> f64 {
+        let sigma = (1.0 + cv * cv).ln().sqrt();
+        (sigma * self.normal()).exp()
+    }
+}
+
+/// Monte Carlo percentile band of `e2_multidose_3c` over population variability
+/// in the PK parameters. `ts` and `times` are in days, as for `e2_multidose_3c`.
+pub fn e2_multidose_band(
+    ts: &[f64],
+    doses: &[f64],
+    times: &[f64],
+    models: &[EstrannaiseModel],
+    conversion_factor: f64,
+    samples: usize,
+    coverage: f64,
+) -> E2Band {
+    if ts.is_empty() || samples == 0 {
+        return E2Band::default();
+    }
+    let mut rng = Rng(UNCERTAINTY_SEED);
+    let mut curves: Vec<Vec<f64>> = vec![Vec::with_capacity(samples); ts.len()];
+    for _ in 0..samples {
+        // One draw per sample: a person's physiology applies to every dose.
+        let d_mult = rng.log_normal(DOSE_SCALE_CV);
+        let k1_mult = rng.log_normal(RATE_CV);
+        let k2_mult = rng.log_normal(RATE_CV);
+        let k3_mult = rng.log_normal(RATE_CV);
+        let params = pk_parameters_for(models)
+            .into_iter()
+            .map(|(model, (d, k1, k2, k3))| {
+                (model, (d * d_mult, k1 * k1_mult, k2 * k2_mult, k3 * k3_mult))
+            })
+            .collect();
+        let functions = pk_functions_from(params, conversion_factor);
+        let resolved = resolve_doses(doses, times, models, &functions);
+        let sorted = is_sorted(&resolved);
+        for (idx, t) in ts.iter().enumerate() {
+            curves[idx].push(level_at(&resolved, sorted, *t));
+        }
+    }
+
+    let coverage = coverage.clamp(0.0, 1.0);
+    let last = (samples - 1) as f64;
+    let lower_idx = (last * (1.0 - coverage) / 2.0).round() as usize;
+    let upper_idx = (last * (1.0 + coverage) / 2.0).round() as usize;
+    let median_idx = (last / 2.0).round() as usize;
+    let mut band = E2Band::default();
+    for mut values in curves {
+        values.sort_by(f64::total_cmp);
+        band.lower.push(values[lower_idx]);
+        band.median.push(values[median_idx]);
+        band.upper.push(values[upper_idx]);
+    }
+    band
+}
+
+pub fn injectable_model(kind: &InjectableEstradiols) -> Option<EstrannaiseModel> {
+    match kind {
+        InjectableEstradiols::Benzoate => Some(EstrannaiseModel::EbIm),
+        InjectableEstradiols::Valerate => Some(EstrannaiseModel::EvIm),
+        InjectableEstradiols::Enanthate => Some(EstrannaiseModel::EEnIm),
+        InjectableEstradiols::Cypionate => Some(EstrannaiseModel::EcIm),
+        InjectableEstradiols::Undecylate => Some(EstrannaiseModel::EUnIm),
+        InjectableEstradiols::PolyestradiolPhosphate => Some(EstrannaiseModel::PepIm),
+    }
+}
+
+pub fn washout_days(model: &EstrannaiseModel) -> f64 {
+    match model {
+        EstrannaiseModel::PepIm => PEP_WASHOUT_DAYS,
+        // Five half-lives of the slowest compartment.
+        EstrannaiseModel::Custom(p) => {
+            let slowest = p.k1.min(p.k2).min(p.k3);
+            (5.0 * std::f64::consts::LN_2 / slowest).clamp(DEFAULT_WASHOUT_DAYS, MAX_WASHOUT_DAYS)
+        }
+        _ => DEFAULT_WASHOUT_DAYS,
+    }
+}
+
+pub fn validate_custom_pk_model(model: &CustomPkModel) -> Result<(), String> {
+    if model.name.trim().is_empty() {
+        return Err("Model name is required".to_string());
+    }
+    if !(model.d.is_finite() && model.d > 0.0 && model.d <= MAX_CUSTOM_D) {
+        return Err(format!("D must be between 0 and {}", MAX_CUSTOM_D));
+    }
+    for (label, k) in [("k1", model.k1), ("k2", model.k2), ("k3", model.k3)] {
+        if !(k.is_finite() && (MIN_CUSTOM_RATE..=MAX_CUSTOM_RATE).contains(&k)) {
+            return Err(format!(
+                "{} must be between {} and {} per day",
+                label, MIN_CUSTOM_RATE, MAX_CUSTOM_RATE
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Looks up a user-defined model by id. Invalid models resolve to `None` so
+/// callers fall back to the built-in model.
+pub fn custom_model(data: &HrtData, id: &str) -> Option<EstrannaiseModel> {
+    let model = data.pkModels.iter().find(|model| model.id == id)?;
+    validate_custom_pk_model(model).ok()?;
+    Some(EstrannaiseModel::Custom(PkModelParams {
+        d: model.d,
+        k1: model.k1,
+        k2: model.k2,
+        k3: model.k3,
+    }))
+}
+
+// A dose's own model wins over its vial's.
+fn selected_custom_model(
+    data: &HrtData,
+    pk_model_id: Option<&String>,
+    vial_id: Option<&String>,
+) -> Option<EstrannaiseModel> {
+    let vial_model = || {
+        let vial_id = vial_id?;
+        data.vials
+            .iter()
+            .find(|vial| &vial.id == vial_id)?
+            .pkModelId
+            .as_ref()
+    };
+    pk_model_id
+        .or_else(vial_model)
+        .and_then(|id| custom_model(data, id))
+}
+
+pub fn injectable_schedule_model(
+    data: &HrtData,
+    schedule: &InjectableSchedule,
+) -> Option<EstrannaiseModel> {
+    selected_custom_model(data, None, schedule.vialId.as_ref())
+        .or_else(|| injectable_model(&schedule.kind))
+}
+
+pub fn oral_model(route: Option<&OralEstradiolRoutes>) -> EstrannaiseModel {
+    match route {
+        Some(OralEstradiolRoutes::Sublingual) => EstrannaiseModel::E2Sublingual,
+        _ => EstrannaiseModel::E2Oral,
+    }
+}
+
+// Oral models take mg of estradiol, so esters are scaled by their E2 content.
+pub fn oral_e2_fraction(kind: &OralEstradiols) -> Option<f64> {
+    match kind {
+        OralEstradiols::Hemihydrate => Some(1.0),
+        OralEstradiols::Valerate => Some(272.38 / 356.5),
+        OralEstradiols::Premarin => None,
+    }
+}
+
+/// Unit a model's doses are given in: patches take delivered µg/day,
+/// everything else mg.
+pub fn model_dose_unit(model: &EstrannaiseModel) -> Mass {
+    match model {
+        EstrannaiseModel::PatchTw | EstrannaiseModel::PatchOw | EstrannaiseModel::Patch(_) => {
+            Mass::Mcg
+        }
+        _ => Mass::Mg,
+    }
+}
+
+pub fn estradiol_dose_events(data: &HrtData) -> Vec<(UnixTime, f64, EstrannaiseModel)> {
+    let mut events: Vec<_> = data
+        .dosageHistory
+        .iter()
+        .filter_map(|entry| match entry {
+            DosageHistoryEntry::InjectableEstradiol {
+                date,
+                kind,
+                dose,
+                vialId,
+                pkModelId,
+                ..
+            } => selected_custom_model(data, pkModelId.as_ref(), vialId.as_ref())
+                .or_else(|| injectable_model(kind))
+                .map(|model| (*date, *dose, model)),
+            DosageHistoryEntry::OralEstradiol {
+                date,
+                kind,
+                route,
+                dose,
+                unit,
+                pillQuantity,
+                pkModelId,
+                ..
+            } if *unit == HormoneUnits::Mg => {
+                let fraction = oral_e2_fraction(kind)?;
+                let qty = pillQuantity.filter(|q| *q > 0.0).unwrap_or(1.0);
+                let model = selected_custom_model(data, pkModelId.as_ref(), None)
+                    .unwrap_or_else(|| oral_model(route.as_ref()));
+                Some((*date, dose * qty * fraction, model))
+            }
+            DosageHistoryEntry::Patch {
+                date,
+                strength,
+                patchQuantity,
+                wearDays,
+                removedAt,
+                ..
+            } => {
+                let model = worn_patch_model(patch_wear_days(*date, *wearDays, *removedAt));
+                Some((
+                    *date,
+                    patch_total_strength(*strength, *patchQuantity),
+                    model,
+                ))
+            }
+            DosageHistoryEntry::Gel {
+                date, dose, unit, ..
+            } if *unit == HormoneUnits::Mg => Some((*date, *dose, EstrannaiseModel::E2Gel)),
+            DosageHistoryEntry::Medication {
+                date,
+                medicationId,
+                dose,
+                unit,
+                ..
+            } => {
+                let med = find_medication(data, medicationId)?;
+                if med.class != MedicationClass::Estrogen {
+                    return None;
+                }
+                let model = medication_model(data, &med)?;
+                let TypedUnit::Mass(unit) = unit.typed() else {
+                    return None;
+                };
+                let dose = Measure::new(*dose, unit).to(model_dose_unit(&model)).value;
+                Some((*date, dose, model))
+            }
+            _ => None,
+        })
+        .collect();
+    events.sort_by_key(|(date, _, _)| *date);
+    events
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::types::EstrannaiseModel;
+    use crate::types::{CustomPkRoute, EstrannaiseModel, Medication, MedicationRoute, Vial};
 
     #[test]
     fn pk_parameters_has_all_models() {
@@ -189,9 +613,13 @@ mod tests {
         assert!(params.contains_key(&EstrannaiseModel::EbIm));
         assert!(params.contains_key(&EstrannaiseModel::EUnIm));
         assert!(params.contains_key(&EstrannaiseModel::EUnCasubq));
+        assert!(params.contains_key(&EstrannaiseModel::PepIm));
         assert!(params.contains_key(&EstrannaiseModel::PatchTw));
         assert!(params.contains_key(&EstrannaiseModel::PatchOw));
-        assert_eq!(params.len(), 8);
+        assert!(params.contains_key(&EstrannaiseModel::E2Oral));
+        assert!(params.contains_key(&EstrannaiseModel::E2Sublingual));
+        assert!(params.contains_key(&EstrannaiseModel::E2Gel));
+        assert_eq!(params.len(), 12);
     }
 
     #[test]
@@ -286,4 +714,519 @@ mod tests {
             assert!(result > 0.0, "{:?} produced non-positive value: {result}", model);
         }
     }
+
+    // (hours after the last dose, pg/mL) every 15 minutes for `hours` after
+    // the last of `count` daily doses.
+    fn after_last_dose(
+        model: EstrannaiseModel,
+        dose: f64,
+        count: usize,
+        hours: f64,
+    ) -> Vec<(f64, f64)> {
+        let times: Vec<f64> = (0..count).map(|day| day as f64).collect();
+        let doses = vec![dose; count];
+        let models = vec![model; count];
+        let last = times[count - 1];
+        (0..=(hours * 4.0) as usize)
+            .map(|i| {
+                let h = i as f64 / 4.0;
+                let level = e2_multidose_3c(last + h / 24.0, &doses, &times, &models, 1.0, false);
+                (h, level)
+            })
+            .collect()
+    }
+
+    // (Tmax in hours, Cmax, trough) of a profile.
+    fn peak_and_trough(profile: &[(f64, f64)]) -> (f64, f64, f64) {
+        let (tmax, cmax) =
+            profile
+                .iter()
+                .cloned()
+                .fold((0.0, 0.0), |best, p| if p.1 > best.1 { p } else { best });
+        let trough = profile.iter().map(|p| p.1).fold(f64::INFINITY, f64::min);
+        (tmax, cmax, trough)
+    }
+
+    fn assert_near(what: &str, actual: f64, published: f64, tolerance: f64) {
+        assert!(
+            (actual - published).abs() <= published * tolerance,
+            "{what}: model {actual}, published {published} ±{}%",
+            tolerance * 100.0
+        );
+    }
+
+    // The published points are the ones cited on `pk_parameters`. Levels
+    // must match within 25%, times to peak within 2 h (1/2 h sublingually).
+    const LEVEL_TOLERANCE: f64 = 0.25;
+
+    #[test]
+    fn oral_matches_published_points() {
+        let profile = after_last_dose(EstrannaiseModel::E2Oral, 2.0, 14, 24.0);
+        let (tmax, cmax, trough) = peak_and_trough(&profile);
+        assert_near("oral Cmax", cmax, 60.0, LEVEL_TOLERANCE);
+        assert!((tmax - 6.0).abs() <= 2.0, "oral Tmax {tmax} h");
+        assert_near("oral trough", trough, 35.0, LEVEL_TOLERANCE);
+    }
+
+    #[test]
+    fn sublingual_matches_published_points() {
+        let profile = after_last_dose(EstrannaiseModel::E2Sublingual, 0.25, 1, 12.0);
+        let (tmax, cmax, trough) = peak_and_trough(&profile);
+        assert_near("sublingual Cmax", cmax, 415.0, LEVEL_TOLERANCE);
+        assert!((tmax - 1.0).abs() <= 0.5, "sublingual Tmax {tmax} h");
+        assert!(trough < cmax * 0.1, "sublingual at 12 h: {trough}");
+    }
+
+    #[test]
+    fn gel_matches_published_points() {
+        let profile = after_last_dose(EstrannaiseModel::E2Gel, 1.5, 14, 24.0);
+        let mean = profile.iter().map(|p| p.1).sum::<f64>() / profile.len() as f64;
+        assert_near("gel average", mean, 80.0, LEVEL_TOLERANCE);
+        let (_, cmax, trough) = peak_and_trough(&profile);
+        assert!(cmax / trough < 2.0, "gel should be flat: {trough}..{cmax}");
+    }
+
+    #[test]
+    fn patch_level_falls_after_removal() {
+        let model = [EstrannaiseModel::PatchTw];
+        let worn = e2_multidose_3c(3.0, &[100.0], &[0.0], &model, 1.0, false);
+        let removed = e2_multidose_3c(4.5, &[100.0], &[0.0], &model, 1.0, false);
+        let later = e2_multidose_3c(6.0, &[100.0], &[0.0], &model, 1.0, false);
+        assert!(worn > removed && removed > later, "{worn} {removed} {later}");
+        assert!(later < worn * 0.1, "level should wash out after removal: {later}");
+    }
+
+    #[test]
+    fn patch_releases_for_its_actual_wear() {
+        let at = |wear: f64, t: f64| {
+            e2_multidose_3c(t, &[100.0], &[0.0], &[worn_patch_model(wear)], 1.0, false)
+        };
+        // Same fit as the nominal model when worn the nominal time.
+        let nominal = e2_multidose_3c(
+            4.5,
+            &[100.0],
+            &[0.0],
+            &[EstrannaiseModel::PatchTw],
+            1.0,
+            false,
+        );
+        assert!((at(3.5, 4.5) - nominal).abs() < 1e-9);
+        // Removed early: the level is already falling before 3.5 days.
+        assert!(at(2.0, 3.0) < at(3.5, 3.0) * 0.5);
+        // Worn past its week: still absorbing after day 7.
+        assert!(at(10.0, 8.0) > at(7.0, 8.0) * 2.0);
+    }
+
+    #[test]
+    fn estradiol_dose_events_covers_oral_routes() {
+        let oral = |date, kind, route, unit, pills| DosageHistoryEntry::OralEstradiol {
+            date,
+            id: None,
+            kind,
+            route,
+            dose: 2.0,
+            unit,
+            pillQuantity: pills,
+            note: None,
+            pkModelId: None,
+        };
+        let history = vec![
+            oral(3, OralEstradiols::Valerate, None, HormoneUnits::Mg, None),
+            oral(
+                1,
+                OralEstradiols::Hemihydrate,
+                Some(OralEstradiolRoutes::Sublingual),
+                HormoneUnits::Mg,
+                Some(2.0),
+            ),
+            oral(2, OralEstradiols::Premarin, None, HormoneUnits::Mg, None),
+            oral(4, OralEstradiols::Hemihydrate, None, HormoneUnits::NgMl, None),
+            DosageHistoryEntry::InjectableEstradiol {
+                date: 0,
+                id: None,
+                kind: InjectableEstradiols::PolyestradiolPhosphate,
+                dose: 80.0,
+                unit: HormoneUnits::Mg,
+                note: None,
+                bonusDose: None,
+                injectionSite: None,
+                vialId: None,
+                subVialId: None,
+                syringeKind: None,
+                needleLength: None,
+                needleGauge: None,
+                photos: None,
+                pkModelId: None,
+            },
+        ];
+        let data = HrtData {
+            dosageHistory: history,
+            ..Default::default()
+        };
+        let events = estradiol_dose_events(&data);
+        assert_eq!(events.len(), 3);
+        assert_eq!(events[0], (0, 80.0, EstrannaiseModel::PepIm));
+        assert_eq!(events[1], (1, 4.0, EstrannaiseModel::E2Sublingual));
+        assert_eq!(events[2].0, 3);
+        assert!((events[2].1 - 2.0 * 272.38 / 356.5).abs() < 1e-9);
+        assert_eq!(events[2].2, EstrannaiseModel::E2Oral);
+    }
+
+    #[test]
+    fn estradiol_dose_events_include_catalog_estrogens() {
+        let medication = |id: &str, class, pk_model: Option<&str>| Medication {
+            id: id.to_string(),
+            name: id.to_string(),
+            class,
+            route: MedicationRoute::Other,
+            unit: HormoneUnits::Mg,
+            pkModelId: pk_model.map(str::to_string),
+        };
+        let dose = |date, medication_id: &str| DosageHistoryEntry::Medication {
+            date,
+            id: None,
+            medicationId: medication_id.to_string(),
+            dose: 0.1,
+            unit: HormoneUnits::Mg,
+            note: None,
+        };
+        let data = HrtData {
+            medications: vec![
+                medication("patch", MedicationClass::Estrogen, Some("patch tw")),
+                medication("unmodelled", MedicationClass::Estrogen, None),
+                medication("raloxifene", MedicationClass::Serm, Some("E2 oral")),
+            ],
+            dosageHistory: vec![
+                dose(0, "patch"),
+                dose(1, "unmodelled"),
+                dose(2, "raloxifene"),
+                dose(3, "missing"),
+            ],
+            ..Default::default()
+        };
+        let events = estradiol_dose_events(&data);
+        assert_eq!(events, vec![(0, 100.0, EstrannaiseModel::PatchTw)]);
+    }
+
+    #[test]
+    fn catalog_patch_matches_builtin_patch() {
+        let level = |history: Vec<DosageHistoryEntry>| {
+            let data = HrtData {
+                medications: vec![Medication {
+                    id: "patch".to_string(),
+                    name: "Patch".to_string(),
+                    class: MedicationClass::Estrogen,
+                    route: MedicationRoute::Transdermal,
+                    unit: HormoneUnits::Mcg,
+                    pkModelId: Some("patch tw".to_string()),
+                }],
+                dosageHistory: history,
+                ..Default::default()
+            };
+            let events = estradiol_dose_events(&data);
+            assert_eq!(events.len(), 1);
+            let (_, dose, model) = &events[0];
+            [1.0, 3.0, 4.5].map(|t| {
+                e2_multidose_3c(t, &[*dose], &[0.0], std::slice::from_ref(model), 1.0, false)
+            })
+        };
+        let catalog = |dose, unit| DosageHistoryEntry::Medication {
+            date: 0,
+            id: None,
+            medicationId: "patch".to_string(),
+            dose,
+            unit,
+            note: None,
+        };
+        let builtin = level(vec![DosageHistoryEntry::Patch {
+            date: 0,
+            id: None,
+            strength: 100.0,
+            patchQuantity: None,
+            wearDays: Some(3.5),
+            removedAt: None,
+            site: None,
+            note: None,
+        }]);
+        for entry in [
+            catalog(100.0, HormoneUnits::Mcg),
+            catalog(0.1, HormoneUnits::Mg),
+        ] {
+            let catalog = level(vec![entry]);
+            for (a, b) in catalog.iter().zip(&builtin) {
+                assert!((a - b).abs() < 1e-9, "{a} vs {b}");
+            }
+        }
+    }
+
+    #[test]
+    fn estradiol_dose_events_include_patches_and_gel() {
+        let day = 24 * 60 * 60 * 1000;
+        let patch = |date, wear_days, removed_at| DosageHistoryEntry::Patch {
+            date,
+            id: None,
+            strength: 50.0,
+            patchQuantity: Some(2.0),
+            wearDays: wear_days,
+            removedAt: removed_at,
+            site: None,
+            note: None,
+        };
+        let data = HrtData {
+            dosageHistory: vec![
+                patch(0, Some(3.5), None),
+                patch(day, Some(3.5), Some(8 * day)),
+                DosageHistoryEntry::Gel {
+                    date: 2 * day,
+                    id: None,
+                    dose: 1.5,
+                    unit: HormoneUnits::Mg,
+                    pumps: Some(2.0),
+                    note: None,
+                },
+            ],
+            ..Default::default()
+        };
+        let events = estradiol_dose_events(&data);
+        assert_eq!(
+            events,
+            vec![
+                (0, 100.0, worn_patch_model(3.5)),
+                (day, 100.0, worn_patch_model(7.0)),
+                (2 * day, 1.5, EstrannaiseModel::E2Gel),
+            ]
+        );
+    }
+
+    fn custom(id: &str, params: PKParams) -> CustomPkModel {
+        CustomPkModel {
+            id: id.to_string(),
+            name: format!("Model {}", id),
+            route: CustomPkRoute::Injection,
+            d: params.0,
+            k1: params.1,
+            k2: params.2,
+            k3: params.3,
+        }
+    }
+
+    fn injection(date: i64, vial: Option<&str>, pk_model: Option<&str>) -> DosageHistoryEntry {
+        DosageHistoryEntry::InjectableEstradiol {
+            date,
+            id: None,
+            kind: InjectableEstradiols::Valerate,
+            dose: 4.0,
+            unit: HormoneUnits::Mg,
+            note: None,
+            bonusDose: None,
+            injectionSite: None,
+            vialId: vial.map(str::to_string),
+            subVialId: None,
+            syringeKind: None,
+            needleLength: None,
+            needleGauge: None,
+            photos: None,
+            pkModelId: pk_model.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn custom_model_with_builtin_parameters_matches_builtin() {
+        let ev = pk_parameters()[&EstrannaiseModel::EvIm];
+        let model = EstrannaiseModel::Custom(PkModelParams {
+            d: ev.0,
+            k1: ev.1,
+            k2: ev.2,
+            k3: ev.3,
+        });
+        let ts: Vec<f64> = (0..40).map(|i| i as f64 * 0.5).collect();
+        let builtin = e2_multidose_series(
+            &ts,
+            &[4.0, 4.0],
+            &[0.0, 7.0],
+            &[EstrannaiseModel::EvIm, EstrannaiseModel::EvIm],
+            1.0,
+            false,
+        );
+        let mixed = e2_multidose_series(
+            &ts,
+            &[4.0, 4.0],
+            &[0.0, 7.0],
+            &[EstrannaiseModel::EvIm, model.clone()],
+            1.0,
+            false,
+        );
+        assert_eq!(builtin, mixed);
+        assert_eq!(
+            e2_steady_state(1.0, 4.0, 7.0, &model, 1.0),
+            e2_steady_state(1.0, 4.0, 7.0, &EstrannaiseModel::EvIm, 1.0)
+        );
+        assert_eq!(washout_days(&model), DEFAULT_WASHOUT_DAYS);
+    }
+
+    #[test]
+    fn dose_events_resolve_custom_models_from_dose_then_vial() {
+        let mut vial = Vial {
+            id: "v".to_string(),
+            esterKind: None,
+            suspensionOil: None,
+            otherIngredients: None,
+            batchNumber: None,
+            source: None,
+            concentrationMgPerMl: None,
+            isSpent: None,
+            spentAt: None,
+            useBy: None,
+            volumeMl: None,
+            pkModelId: Some("slow".to_string()),
+            createdAt: 0,
+            subVials: Vec::new(),
+        };
+        let data = HrtData {
+            dosageHistory: vec![
+                injection(0, Some("v"), None),
+                injection(1, Some("v"), Some("fast")),
+                injection(2, None, Some("missing")),
+            ],
+            vials: vec![vial.clone()],
+            pkModels: vec![
+                custom("slow", (200.0, 0.05, 1.0, 0.3)),
+                custom("fast", (900.0, 0.5, 20.0, 2.0)),
+            ],
+            ..Default::default()
+        };
+        let events = estradiol_dose_events(&data);
+        let params = |(d, k1, k2, k3)| EstrannaiseModel::Custom(PkModelParams { d, k1, k2, k3 });
+        assert_eq!(events[0].2, params((200.0, 0.05, 1.0, 0.3)));
+        assert_eq!(events[1].2, params((900.0, 0.5, 20.0, 2.0)));
+        assert_eq!(events[2].2, EstrannaiseModel::EvIm);
+
+        vial.pkModelId = None;
+        let schedule = InjectableSchedule {
+            kind: InjectableEstradiols::Valerate,
+            dose: 4.0,
+            unit: HormoneUnits::Mg,
+            frequency: 7.0,
+            vialId: Some("v".to_string()),
+            subVialId: None,
+            syringeKind: None,
+            needleLength: None,
+            needleGauge: None,
+            nextDoseDate: None,
+        };
+        assert_eq!(
+            injectable_schedule_model(&data, &schedule),
+            Some(params((200.0, 0.05, 1.0, 0.3)))
+        );
+        let data = HrtData {
+            vials: vec![vial],
+            ..data
+        };
+        assert_eq!(
+            injectable_schedule_model(&data, &schedule),
+            Some(EstrannaiseModel::EvIm)
+        );
+    }
+
+    #[test]
+    fn custom_model_validation() {
+        assert!(validate_custom_pk_model(&custom("a", (478.0, 0.236, 4.85, 1.24))).is_ok());
+        let mut model = custom("a", (478.0, 0.236, 4.85, 1.24));
+        model.name = "  ".to_string();
+        assert!(validate_custom_pk_model(&model).is_err());
+        for bad in [
+            (0.0, 0.2, 4.0, 1.0),
+            (f64::NAN, 0.2, 4.0, 1.0),
+            (478.0, -0.2, 4.0, 1.0),
+            (478.0, 0.2, f64::INFINITY, 1.0),
+            (478.0, 0.2, 4.0, 5000.0),
+        ] {
+            assert!(
+                validate_custom_pk_model(&custom("a", bad)).is_err(),
+                "{:?}",
+                bad
+            );
+        }
+        let data = HrtData {
+            pkModels: vec![custom("bad", (0.0, 0.2, 4.0, 1.0))],
+            ..Default::default()
+        };
+        assert!(custom_model(&data, "bad").is_none());
+    }
+
+    #[test]
+    fn pep_matches_published_points() {
+        let model = [EstrannaiseModel::PepIm];
+        let at = |day: f64| e2_multidose_3c(day, &[80.0], &[0.0], &model, 1.0, false);
+        assert_near("PEP at one week", at(7.0), 250.0, LEVEL_TOLERANCE);
+        assert_near("PEP at two weeks", at(14.0), 250.0, LEVEL_TOLERANCE);
+        // ~70 day elimination half-life
+        assert_near(
+            "PEP half-life ratio",
+            at(77.0) / at(7.0),
+            0.5,
+            LEVEL_TOLERANCE,
+        );
+        assert!(at(washout_days(&model[0])) < at(7.0) * 0.15);
+        assert!(washout_days(&model[0]) > washout_days(&EstrannaiseModel::EvIm));
+    }
+
+    #[test]
+    fn band_brackets_deterministic_curve() {
+        let model = EstrannaiseModel::EvIm;
+        let times = [0.0, 7.0, 14.0];
+        let doses = [4.0; 3];
+        let models = [model.clone(), model.clone(), model];
+        let ts: Vec<f64> = (1..20).map(f64::from).collect();
+        let band = e2_multidose_band(&ts, &doses, &times, &models, 1.0, 200, 0.9);
+        assert_eq!(band.lower.len(), ts.len());
+        for (idx, t) in ts.iter().enumerate() {
+            let point = e2_multidose_3c(*t, &doses, &times, &models, 1.0, false);
+            assert!(band.lower[idx] < point && point < band.upper[idx], "t={t}");
+            assert!((band.median[idx] - point).abs() / point < 0.25, "t={t}");
+        }
+    }
+
+    #[test]
+    fn band_widens_with_coverage_and_is_stable() {
+        let ts = [3.0];
+        let args = ([4.0], [0.0], [EstrannaiseModel::EvIm]);
+        let narrow = e2_multidose_band(&ts, &args.0, &args.1, &args.2, 1.0, 200, 0.5);
+        let wide = e2_multidose_band(&ts, &args.0, &args.1, &args.2, 1.0, 200, 0.95);
+        assert!(wide.lower[0] < narrow.lower[0] && wide.upper[0] > narrow.upper[0]);
+        let again = e2_multidose_band(&ts, &args.0, &args.1, &args.2, 1.0, 200, 0.95);
+        assert_eq!(wide, again);
+    }
+
+    #[test]
+    fn band_empty_inputs() {
+        let band = e2_multidose_band(&[], &[4.0], &[0.0], &[EstrannaiseModel::EvIm], 1.0, 50, 0.9);
+        assert!(band.lower.is_empty());
+        let band = e2_multidose_band(&[1.0], &[], &[], &[], 1.0, 50, 0.9);
+        assert_eq!(band.upper, vec![0.0]);
+    }
+
+    #[test]
+    fn series_matches_pointwise_evaluation() {
+        let models = [
+            EstrannaiseModel::EvIm,
+            EstrannaiseModel::PatchTw,
+            EstrannaiseModel::E2Sublingual,
+            EstrannaiseModel::EcIm,
+        ];
+        let doses = [4.0, 100.0, 2.0, 5.0];
+        let times = [0.0, 1.5, 2.25, 7.0];
+        let ts: Vec<f64> = (-4..80).map(|i| i as f64 * 0.25).collect();
+        let series = e2_multidose_series(&ts, &doses, &times, &models, 3.6713, false);
+        assert_eq!(series.len(), ts.len());
+        for (t, value) in ts.iter().zip(&series) {
+            let single = e2_multidose_3c(*t, &doses, &times, &models, 3.6713, false);
+            assert_eq!(*value, single, "t={t}");
+        }
+        let intervals = e2_multidose_series(&ts, &doses, &[0.0, 1.5, 0.75, 4.75], &models, 1.0, true);
+        let absolute = e2_multidose_series(&ts, &doses, &times, &models, 1.0, false);
+        for (a, b) in intervals.iter().zip(&absolute) {
+            assert!((a - b).abs() < 1e-9);
+        }
+    }
 }
@@ -0,0 +1,199 @@
+#![allow(non_snake_case)]
+
+use serde::{Deserialize, Serialize};
+
+use crate::quantity::{BloodAnalyte, MassConcentration, Measure, MolarConcentration, Quantity};
+use crate::types::{BloodTest, Hormone, HormoneUnits};
+
+/// Albumin assumed by the Vermeulen calculation when none was measured.
+pub const DEFAULT_ALBUMIN_G_DL: f64 = 4.3;
+
+const ALBUMIN_G_PER_MOL: f64 = 69_000.0;
+/// Association constants (L/mol) from Vermeulen et al. 1999.
+const K_SHBG_T: f64 = 1.0e9;
+const K_ALBUMIN_T: f64 = 3.6e4;
+/// Association constants (L/mol) from Södergård et al. 1982.
+const K_SHBG_E2: f64 = 6.8e8;
+const K_ALBUMIN_E2: f64 = 4.21e4;
+
+/// Levels calculated from the total hormones, SHBG and albumin of a test.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CalculatedLevels {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub freeAndrogenIndex: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub freeTestosterone: Option<Quantity>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bioavailableTestosterone: Option<Quantity>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub freeEstradiol: Option<Quantity>,
+    /// True when the default albumin was used instead of a measured one.
+    pub albuminAssumed: bool,
+}
+
+impl CalculatedLevels {
+    pub fn is_empty(&self) -> bool {
+        self.freeAndrogenIndex.is_none()
+            && self.freeTestosterone.is_none()
+            && self.bioavailableTestosterone.is_none()
+            && self.freeEstradiol.is_none()
+    }
+}
+
+/// 100 × total T / SHBG, both in nmol/L.
+pub fn free_androgen_index(total_t_nmol_l: f64, shbg_nmol_l: f64) -> Option<f64> {
+    (shbg_nmol_l > 0.0 && total_t_nmol_l >= 0.0).then(|| 100.0 * total_t_nmol_l / shbg_nmol_l)
+}
+
+fn albumin_mol_l(albumin_g_dl: f64) -> f64 {
+    albumin_g_dl * 10.0 / ALBUMIN_G_PER_MOL
+}
+
+/// Vermeulen free testosterone in nmol/L. Solves the binding equilibrium of T
+/// with SHBG and albumin for the free fraction.
+pub fn vermeulen_free_testosterone(
+    total_t_nmol_l: f64,
+    shbg_nmol_l: f64,
+    albumin_g_dl: f64,
+) -> Option<f64> {
+    if total_t_nmol_l < 0.0 || shbg_nmol_l < 0.0 || albumin_g_dl <= 0.0 {
+        return None;
+    }
+    let total_t = total_t_nmol_l * 1e-9;
+    let shbg = shbg_nmol_l * 1e-9;
+    let n = 1.0 + K_ALBUMIN_T * albumin_mol_l(albumin_g_dl);
+    let a = n * K_SHBG_T;
+    let b = n + K_SHBG_T * (shbg - total_t);
+    let free = (-b + (b * b + 4.0 * a * total_t).sqrt()) / (2.0 * a);
+    free.is_finite().then_some(free * 1e9)
+}
+
+/// Free plus albumin-bound testosterone in nmol/L.
+pub fn bioavailable_testosterone(
+    total_t_nmol_l: f64,
+    shbg_nmol_l: f64,
+    albumin_g_dl: f64,
+) -> Option<f64> {
+    let free = vermeulen_free_testosterone(total_t_nmol_l, shbg_nmol_l, albumin_g_dl)?;
+    Some(free * (1.0 + K_ALBUMIN_T * albumin_mol_l(albumin_g_dl)))
+}
+
+/// Free estradiol in pmol/L. SHBG already occupied by testosterone is taken
+/// out before E2 binding; E2 itself is too dilute to saturate SHBG.
+pub fn free_estradiol(
+    total_e2_pmol_l: f64,
+    shbg_nmol_l: f64,
+    albumin_g_dl: f64,
+    total_t_nmol_l: Option<f64>,
+) -> Option<f64> {
+    if total_e2_pmol_l < 0.0 || shbg_nmol_l < 0.0 || albumin_g_dl <= 0.0 {
+        return None;
+    }
+    let shbg_bound_t = match total_t_nmol_l {
+        Some(total_t) => {
+            let free_t = vermeulen_free_testosterone(total_t, shbg_nmol_l, albumin_g_dl)?;
+            total_t - free_t * (1.0 + K_ALBUMIN_T * albumin_mol_l(albumin_g_dl))
+        }
+        None => 0.0,
+    };
+    let free_shbg = (shbg_nmol_l - shbg_bound_t).max(0.0) * 1e-9;
+    let bound_ratio = 1.0 + K_ALBUMIN_E2 * albumin_mol_l(albumin_g_dl) + K_SHBG_E2 * free_shbg;
+    Some(total_e2_pmol_l / bound_ratio)
+}
+
+/// Everything that can be calculated from `test`, converting its stored units.
+/// Free and bioavailable T are given in ng/dL, free E2 in pg/mL.
+pub fn calculate_levels(test: &BloodTest) -> CalculatedLevels {
+    let total_t = test.level_in(BloodAnalyte::Testosterone, HormoneUnits::TNmolL);
+    let shbg = test.level_in(BloodAnalyte::Shbg, HormoneUnits::TNmolL);
+    let total_e2 = test.level_in(BloodAnalyte::Estradiol, HormoneUnits::E2PmolL);
+    let measured_albumin = test
+        .albuminLevel
+        .filter(|albumin| albumin.is_finite() && *albumin > 0.0);
+    let albumin = measured_albumin.unwrap_or(DEFAULT_ALBUMIN_G_DL);
+
+    let Some(shbg) = shbg.filter(|shbg| *shbg > 0.0) else {
+        return CalculatedLevels::default();
+    };
+    let ng_dl = |nmol_l: f64| {
+        Measure::new(nmol_l, MolarConcentration::NmolL)
+            .to_mass(&Hormone::Testosterone, MassConcentration::NgDl)
+            .quantity()
+    };
+    CalculatedLevels {
+        freeAndrogenIndex: total_t.and_then(|t| free_androgen_index(t, shbg)),
+        freeTestosterone: total_t
+            .and_then(|t| vermeulen_free_testosterone(t, shbg, albumin))
+            .map(ng_dl),
+        bioavailableTestosterone: total_t
+            .and_then(|t| bioavailable_testosterone(t, shbg, albumin))
+            .map(ng_dl),
+        freeEstradiol: total_e2
+            .and_then(|e2| free_estradiol(e2, shbg, albumin, total_t))
+            .map(|free| {
+                Measure::new(free, MolarConcentration::PmolL)
+                    .to_mass(&Hormone::Estradiol, MassConcentration::PgMl)
+                    .quantity()
+            }),
+        albuminAssumed: measured_albumin.is_none(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fai_is_ratio_of_t_to_shbg() {
+        assert_eq!(free_androgen_index(15.0, 40.0), Some(37.5));
+        assert_eq!(free_androgen_index(1.0, 0.0), None);
+    }
+
+    #[test]
+    fn vermeulen_matches_reference_values() {
+        // A typical male panel gives about 2% free T (~270 pmol/L).
+        let free = vermeulen_free_testosterone(15.0, 40.0, 4.3).unwrap();
+        assert!((free - 0.2736).abs() < 0.001, "got {free}");
+        let bio = bioavailable_testosterone(15.0, 40.0, 4.3).unwrap();
+        assert!((bio - 6.41).abs() < 0.05, "got {bio}");
+        assert_eq!(vermeulen_free_testosterone(0.0, 40.0, 4.3), Some(0.0));
+    }
+
+    #[test]
+    fn free_e2_falls_with_shbg() {
+        let low_shbg = free_estradiol(500.0, 20.0, 4.3, Some(1.0)).unwrap();
+        let high_shbg = free_estradiol(500.0, 120.0, 4.3, Some(1.0)).unwrap();
+        assert!(high_shbg < low_shbg);
+        // Free E2 is normally 1–3% of the total.
+        let fraction = low_shbg / 500.0;
+        assert!((0.01..0.03).contains(&fraction), "got {fraction}");
+    }
+
+    #[test]
+    fn calculates_from_test_units() {
+        let test = BloodTest {
+            estradiol: Some(Quantity::new(150.0, HormoneUnits::E2PgMl)),
+            testosterone: Some(Quantity::new(432.6, HormoneUnits::TNgDl)),
+            shbg: Some(Quantity::new(40.0, HormoneUnits::TNmolL)),
+            ..BloodTest::default()
+        };
+        let levels = calculate_levels(&test);
+        assert!(levels.albuminAssumed);
+        let fai = levels.freeAndrogenIndex.unwrap();
+        assert!((fai - 37.5).abs() < 0.1, "got {fai}");
+        let free_t = levels.freeTestosterone.unwrap();
+        assert_eq!(free_t.unit, HormoneUnits::TNgDl);
+        assert!((free_t.value - 7.89).abs() < 0.05, "got {}", free_t.value);
+        assert!(levels.freeEstradiol.unwrap().value < 150.0 * 0.03);
+
+        let measured = calculate_levels(&BloodTest {
+            albuminLevel: Some(3.5),
+            ..test.clone()
+        });
+        assert!(!measured.albuminAssumed);
+        assert!(measured.freeTestosterone.unwrap().value > free_t.value);
+
+        let no_shbg = BloodTest { shbg: None, ..test };
+        assert!(calculate_levels(&no_shbg).is_empty());
+    }
+}
@@ -0,0 +1,506 @@
+use crate::schedules::{current_injectable, current_regimens, last_regimen_dose};
+use crate::types::{DosageHistoryEntry, HormoneUnits, HrtData, RegimenSchedule, UnixTime};
+
+const DAY_MS: i64 = 24 * 60 * 60 * 1000;
+
+pub const LOW_STOCK_WARNING_DAYS: f64 = 7.0;
+
+/// Sub-vial fills are recorded in insulin-syringe units, which the dose
+/// form also assumes are U-100 (100 units per mL).
+pub const SYRINGE_UNITS_PER_ML: f64 = 100.0;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum InventoryEvent {
+    VialUseBy {
+        vial_id: String,
+        label: String,
+        date: UnixTime,
+    },
+    VialProjectedEmpty {
+        vial_id: String,
+        sub_vial_id: Option<String>,
+        label: String,
+        date: UnixTime,
+        remaining_ml: f64,
+        doses_left: i64,
+    },
+    LowPillStock {
+        regimen_id: String,
+        medication_type: &'static str,
+        date: UnixTime,
+        runs_out: UnixTime,
+        remaining: f64,
+    },
+}
+
+impl InventoryEvent {
+    pub fn date(&self) -> UnixTime {
+        match self {
+            InventoryEvent::VialUseBy { date, .. }
+            | InventoryEvent::VialProjectedEmpty { date, .. }
+            | InventoryEvent::LowPillStock { date, .. } => *date,
+        }
+    }
+}
+
+fn entry_date(entry: &DosageHistoryEntry) -> UnixTime {
+    match entry {
+        DosageHistoryEntry::InjectableEstradiol { date, .. }
+        | DosageHistoryEntry::OralEstradiol { date, .. }
+        | DosageHistoryEntry::Antiandrogen { date, .. }
+        | DosageHistoryEntry::Progesterone { date, .. }
+        | DosageHistoryEntry::Patch { date, .. }
+        | DosageHistoryEntry::Gel { date, .. }
+        | DosageHistoryEntry::Medication { date, .. } => *date,
+    }
+}
+
+fn entry_pill_quantity(entry: &DosageHistoryEntry) -> f64 {
+    let qty = match entry {
+        DosageHistoryEntry::OralEstradiol { pillQuantity, .. }
+        | DosageHistoryEntry::Progesterone { pillQuantity, .. } => *pillQuantity,
+        _ => None,
+    };
+    qty.filter(|value| value.is_finite() && *value > 0.0)
+        .unwrap_or(1.0)
+}
+
+fn vial_label(data: &HrtData, vial_id: &str) -> String {
+    data.vials
+        .iter()
+        .find(|vial| vial.id == vial_id)
+        .map(|vial| {
+            let ester = vial.esterKind.clone().unwrap_or_else(|| "Vial".to_string());
+            match vial.batchNumber.as_deref() {
+                Some(batch) if !batch.trim().is_empty() => format!("{} ({})", ester, batch),
+                _ => ester,
+            }
+        })
+        .unwrap_or_else(|| "Vial".to_string())
+}
+
+// First dose still to come: one interval after the last logged dose, or the
+// schedule's nextDoseDate when nothing has been logged yet.
+fn next_dose_anchor(data: &HrtData, schedule: &RegimenSchedule, step_ms: i64) -> Option<UnixTime> {
+    last_regimen_dose(data, schedule)
+        .map(|last| last + step_ms)
+        .or(schedule.next_dose_date())
+}
+
+pub fn vial_use_by_events(data: &HrtData) -> Vec<InventoryEvent> {
+    data.vials
+        .iter()
+        .filter(|vial| !vial.isSpent.unwrap_or(false))
+        .filter_map(|vial| {
+            vial.useBy.map(|date| InventoryEvent::VialUseBy {
+                vial_id: vial.id.clone(),
+                label: vial_label(data, &vial.id),
+                date,
+            })
+        })
+        .collect()
+}
+
+pub fn vial_remaining_ml(data: &HrtData, vial_id: &str, sub_vial_id: Option<&str>) -> Option<f64> {
+    let vial = data.vials.iter().find(|vial| vial.id == vial_id)?;
+    let conc = vial.concentrationMgPerMl.filter(|conc| *conc > 0.0)?;
+    let sub = sub_vial_id.and_then(|id| vial.subVials.iter().find(|sub| sub.id == id));
+    let initial_ml = match sub {
+        Some(sub) => sub.initialIu? / SYRINGE_UNITS_PER_ML,
+        None => vial.volumeMl?,
+    };
+    let used_ml: f64 = data
+        .dosageHistory
+        .iter()
+        .filter_map(|entry| match entry {
+            DosageHistoryEntry::InjectableEstradiol {
+                dose,
+                unit,
+                vialId,
+                subVialId,
+                ..
+            } if *unit == HormoneUnits::Mg => {
+                let matches = match sub {
+                    Some(sub) => subVialId.as_deref() == Some(sub.id.as_str()),
+                    None => vialId.as_deref() == Some(vial_id),
+                };
+                if matches && dose.is_finite() && *dose > 0.0 {
+                    Some(dose / conc)
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        })
+        .sum();
+    Some((initial_ml - used_ml).max(0.0))
+}
+
+pub fn projected_vial_empty(data: &HrtData) -> Option<InventoryEvent> {
+    let schedule = current_injectable(data)?;
+    let vial_id = schedule.vialId.as_deref()?;
+    if schedule.unit != HormoneUnits::Mg || schedule.dose <= 0.0 || schedule.frequency <= 0.0 {
+        return None;
+    }
+    let conc = data
+        .vials
+        .iter()
+        .find(|vial| vial.id == vial_id)?
+        .concentrationMgPerMl
+        .filter(|conc| *conc > 0.0)?;
+    let sub_vial_id = schedule.subVialId.as_deref();
+    let remaining_ml = vial_remaining_ml(data, vial_id, sub_vial_id)?;
+    let dose_ml = schedule.dose / conc;
+    let doses_left = (remaining_ml / dose_ml + 1e-9).floor() as i64;
+    let step_ms = (schedule.frequency * DAY_MS as f64) as i64;
+    let anchor = next_dose_anchor(
+        data,
+        &RegimenSchedule::InjectableEstradiol(schedule.clone()),
+        step_ms,
+    )?;
+    Some(InventoryEvent::VialProjectedEmpty {
+        vial_id: vial_id.to_string(),
+        sub_vial_id: sub_vial_id.map(str::to_string),
+        label: vial_label(data, vial_id),
+        date: anchor + doses_left * step_ms,
+        remaining_ml,
+        doses_left,
+    })
+}
+
+pub fn low_pill_stock_events(data: &HrtData, warning_days: f64) -> Vec<InventoryEvent> {
+    let mut events = Vec::new();
+    for regimen in current_regimens(data) {
+        let (frequency, stock, stock_date) = match &regimen.schedule {
+            RegimenSchedule::OralEstradiol(s) => (s.frequency, s.pillStock, s.pillStockDate),
+            RegimenSchedule::Antiandrogen(s) => (s.frequency, s.pillStock, s.pillStockDate),
+            RegimenSchedule::Progesterone(s) => (s.frequency, s.pillStock, s.pillStockDate),
+            _ => continue,
+        };
+        let Some(stock) = stock else {
+            continue;
+        };
+        if frequency <= 0.0 || !stock.is_finite() {
+            continue;
+        }
+        let entries: Vec<&DosageHistoryEntry> = data
+            .dosageHistory
+            .iter()
+            .filter(|entry| regimen.schedule.matches_entry(entry))
+            .collect();
+        let taken: f64 = entries
+            .iter()
+            .filter(|entry| stock_date.map(|d| entry_date(entry) >= d).unwrap_or(false))
+            .map(|entry| entry_pill_quantity(entry))
+            .sum();
+        let per_dose = entries
+            .iter()
+            .max_by_key(|entry| entry_date(entry))
+            .map(|entry| entry_pill_quantity(entry))
+            .unwrap_or(1.0);
+        let remaining = (stock - taken).max(0.0);
+        let step_ms = (frequency * DAY_MS as f64) as i64;
+        let Some(anchor) = next_dose_anchor(data, &regimen.schedule, step_ms) else {
+            continue;
+        };
+        let doses_left = (remaining / per_dose + 1e-9).floor() as i64;
+        let runs_out = anchor + doses_left * step_ms;
+        events.push(InventoryEvent::LowPillStock {
+            regimen_id: regimen.id.clone(),
+            medication_type: regimen.schedule.medication_type(),
+            date: runs_out - (warning_days * DAY_MS as f64) as i64,
+            runs_out,
+            remaining,
+        });
+    }
+    events
+}
+
+pub fn inventory_events(data: &HrtData) -> Vec<InventoryEvent> {
+    let mut events = vial_use_by_events(data);
+    events.extend(projected_vial_empty(data));
+    events.extend(low_pill_stock_events(data, LOW_STOCK_WARNING_DAYS));
+    events.sort_by_key(InventoryEvent::date);
+    events
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schedules::add_regimen;
+    use crate::types::*;
+
+    const T0: i64 = 1700000000000;
+
+    fn make_vial(id: &str, volume_ml: Option<f64>) -> Vial {
+        Vial {
+            id: id.to_string(),
+            esterKind: Some("Estradiol Valerate".to_string()),
+            suspensionOil: None,
+            otherIngredients: None,
+            batchNumber: Some("B42".to_string()),
+            source: None,
+            concentrationMgPerMl: Some(40.0),
+            isSpent: None,
+            spentAt: None,
+            useBy: Some(T0 + 90 * DAY_MS),
+            volumeMl: volume_ml,
+            createdAt: T0,
+            subVials: Vec::new(),
+            pkModelId: None,
+        }
+    }
+
+    fn make_injection(date: i64, dose: f64, vial_id: &str) -> DosageHistoryEntry {
+        DosageHistoryEntry::InjectableEstradiol {
+            date,
+            id: None,
+            kind: InjectableEstradiols::Valerate,
+            dose,
+            unit: HormoneUnits::Mg,
+            note: None,
+            bonusDose: None,
+            injectionSite: None,
+            vialId: Some(vial_id.to_string()),
+            subVialId: None,
+            syringeKind: None,
+            needleLength: None,
+            needleGauge: None,
+            photos: None,
+            pkModelId: None,
+        }
+    }
+
+    fn make_oral(date: i64) -> DosageHistoryEntry {
+        DosageHistoryEntry::OralEstradiol {
+            date,
+            id: None,
+            kind: OralEstradiols::Hemihydrate,
+            route: None,
+            dose: 2.0,
+            unit: HormoneUnits::Mg,
+            pillQuantity: None,
+            note: None,
+            pkModelId: None,
+        }
+    }
+
+    fn make_schedule(vial_id: Option<&str>) -> InjectableSchedule {
+        InjectableSchedule {
+            kind: InjectableEstradiols::Valerate,
+            dose: 4.0,
+            unit: HormoneUnits::Mg,
+            frequency: 7.0,
+            vialId: vial_id.map(str::to_string),
+            subVialId: None,
+            syringeKind: None,
+            needleLength: None,
+            needleGauge: None,
+            nextDoseDate: Some(T0),
+        }
+    }
+
+    #[test]
+    fn use_by_events_skip_spent_vials() {
+        let mut data = HrtData::default();
+        data.vials.push(make_vial("a", None));
+        let mut spent = make_vial("b", None);
+        spent.isSpent = Some(true);
+        data.vials.push(spent);
+        let events = vial_use_by_events(&data);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].date(), T0 + 90 * DAY_MS);
+        match &events[0] {
+            InventoryEvent::VialUseBy { vial_id, label, .. } => {
+                assert_eq!(vial_id, "a");
+                assert_eq!(label, "Estradiol Valerate (B42)");
+            }
+            other => panic!("unexpected event {:?}", other),
+        }
+    }
+
+    #[test]
+    fn remaining_ml_subtracts_logged_doses() {
+        let mut data = HrtData::default();
+        data.vials.push(make_vial("a", Some(10.0)));
+        data.dosageHistory.push(make_injection(T0, 4.0, "a"));
+        data.dosageHistory
+            .push(make_injection(T0 + 7 * DAY_MS, 4.0, "a"));
+        data.dosageHistory.push(make_injection(T0, 4.0, "other"));
+        let remaining = vial_remaining_ml(&data, "a", None).unwrap();
+        assert!((remaining - 9.8).abs() < 1e-9);
+    }
+
+    #[test]
+    fn remaining_ml_uses_sub_vial_initial_iu() {
+        let mut data = HrtData::default();
+        let mut vial = make_vial("a", Some(10.0));
+        vial.subVials.push(SubVial {
+            id: "s1".to_string(),
+            personalNumber: "1".to_string(),
+            createdAt: T0,
+            notes: None,
+            initialIu: Some(300.0),
+        });
+        data.vials.push(vial);
+        let mut entry = make_injection(T0, 8.0, "a");
+        if let DosageHistoryEntry::InjectableEstradiol { subVialId, .. } = &mut entry {
+            *subVialId = Some("s1".to_string());
+        }
+        data.dosageHistory.push(entry);
+        let remaining = vial_remaining_ml(&data, "a", Some("s1")).unwrap();
+        assert!((remaining - 2.8).abs() < 1e-9);
+    }
+
+    #[test]
+    fn remaining_ml_requires_known_volume() {
+        let mut data = HrtData::default();
+        data.vials.push(make_vial("a", None));
+        assert!(vial_remaining_ml(&data, "a", None).is_none());
+    }
+
+    #[test]
+    fn projected_empty_counts_whole_doses_from_next_dose() {
+        let mut data = HrtData::default();
+        // 1 mL at 40 mg/mL covers ten 4 mg doses.
+        data.vials.push(make_vial("a", Some(1.0)));
+        add_regimen(
+            &mut data,
+            RegimenSchedule::InjectableEstradiol(make_schedule(Some("a"))),
+            0,
+        );
+        let event = projected_vial_empty(&data).unwrap();
+        assert_eq!(event.date(), T0 + 10 * 7 * DAY_MS);
+        match event {
+            InventoryEvent::VialProjectedEmpty { doses_left, .. } => assert_eq!(doses_left, 10),
+            other => panic!("unexpected event {:?}", other),
+        }
+    }
+
+    #[test]
+    fn projected_empty_anchors_on_last_logged_dose() {
+        let mut data = HrtData::default();
+        data.vials.push(make_vial("a", Some(1.0)));
+        add_regimen(
+            &mut data,
+            RegimenSchedule::InjectableEstradiol(make_schedule(Some("a"))),
+            0,
+        );
+        data.dosageHistory.push(make_injection(T0, 4.0, "a"));
+        let event = projected_vial_empty(&data).unwrap();
+        assert_eq!(event.date(), T0 + 7 * DAY_MS + 9 * 7 * DAY_MS);
+    }
+
+    #[test]
+    fn projected_empty_requires_schedule_vial() {
+        let mut data = HrtData::default();
+        data.vials.push(make_vial("a", Some(1.0)));
+        add_regimen(
+            &mut data,
+            RegimenSchedule::InjectableEstradiol(make_schedule(None)),
+            0,
+        );
+        assert!(projected_vial_empty(&data).is_none());
+    }
+
+    #[test]
+    fn low_pill_stock_warns_before_running_out() {
+        let mut data = HrtData::default();
+        let schedule = RegimenSchedule::Antiandrogen(AntiandrogenSchedule {
+            kind: Antiandrogens::Spiro,
+            dose: 50.0,
+            unit: HormoneUnits::Mg,
+            frequency: 1.0,
+            nextDoseDate: Some(T0),
+            pillStock: Some(30.0),
+            pillStockDate: Some(T0),
+        });
+        add_regimen(&mut data, schedule, 0);
+        let events = low_pill_stock_events(&data, 7.0);
+        assert_eq!(events.len(), 1);
+        match &events[0] {
+            InventoryEvent::LowPillStock {
+                regimen_id,
+                medication_type,
+                date,
+                runs_out,
+                remaining,
+            } => {
+                assert!(regimen_id.starts_with("antiandrogen-"));
+                assert_eq!(*medication_type, "antiandrogen");
+                assert_eq!(*runs_out, T0 + 30 * DAY_MS);
+                assert_eq!(*date, T0 + 23 * DAY_MS);
+                assert_eq!(*remaining, 30.0);
+            }
+            other => panic!("unexpected event {:?}", other),
+        }
+    }
+
+    #[test]
+    fn low_pill_stock_subtracts_doses_since_count() {
+        let mut data = HrtData::default();
+        let schedule = RegimenSchedule::OralEstradiol(OralSchedule {
+            kind: OralEstradiols::Hemihydrate,
+            route: None,
+            dose: 2.0,
+            unit: HormoneUnits::Mg,
+            frequency: 1.0,
+            nextDoseDate: None,
+            pillStock: Some(20.0),
+            pillStockDate: Some(T0),
+        });
+        add_regimen(&mut data, schedule, 0);
+        for day in 0..3 {
+            let mut entry = make_oral(T0 + day * DAY_MS);
+            if let DosageHistoryEntry::OralEstradiol { pillQuantity, .. } = &mut entry {
+                *pillQuantity = Some(2.0);
+            }
+            data.dosageHistory.push(entry);
+        }
+        // Logged before the count was taken; should not be subtracted.
+        data.dosageHistory.push(make_oral(T0 - DAY_MS));
+        let events = low_pill_stock_events(&data, 0.0);
+        match &events[0] {
+            InventoryEvent::LowPillStock {
+                runs_out,
+                remaining,
+                ..
+            } => {
+                assert_eq!(*remaining, 14.0);
+                assert_eq!(*runs_out, T0 + 3 * DAY_MS + 7 * DAY_MS);
+            }
+            other => panic!("unexpected event {:?}", other),
+        }
+    }
+
+    #[test]
+    fn low_pill_stock_ignores_schedules_without_stock() {
+        let mut data = HrtData::default();
+        let schedule = RegimenSchedule::OralEstradiol(OralSchedule {
+            kind: OralEstradiols::Hemihydrate,
+            route: None,
+            dose: 2.0,
+            unit: HormoneUnits::Mg,
+            frequency: 1.0,
+            nextDoseDate: Some(T0),
+            pillStock: None,
+            pillStockDate: None,
+        });
+        add_regimen(&mut data, schedule, 0);
+        assert!(low_pill_stock_events(&data, 7.0).is_empty());
+    }
+
+    #[test]
+    fn inventory_events_are_sorted_by_date() {
+        let mut data = HrtData::default();
+        data.vials.push(make_vial("a", Some(1.0)));
+        add_regimen(
+            &mut data,
+            RegimenSchedule::InjectableEstradiol(make_schedule(Some("a"))),
+            0,
+        );
+        let events = inventory_events(&data);
+        assert_eq!(events.len(), 2);
+        assert!(events[0].date() <= events[1].date());
+    }
+}
@@ -0,0 +1,398 @@
+#![allow(non_snake_case)]
+
+use serde::{Deserialize, Serialize};
+
+use crate::convert::convert_hormone;
+use crate::ranges::RangeFlag;
+use crate::types::{Hormone, HrtData, UnixTime};
+
+/// A result that has no dedicated field on `BloodTest`, e.g. potassium or ALT.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct LabResult {
+    pub name: String,
+    pub value: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unit: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub referenceLow: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub referenceHigh: Option<f64>,
+    /// Flag printed by the lab. Takes precedence over the reference range.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub flag: Option<RangeFlag>,
+}
+
+impl LabResult {
+    /// The lab's flag, or one derived from the reference range.
+    pub fn effective_flag(&self) -> Option<RangeFlag> {
+        if self.flag.is_some() {
+            return self.flag;
+        }
+        if self.referenceLow.is_none() && self.referenceHigh.is_none() {
+            return None;
+        }
+        if self.referenceLow.is_some_and(|low| self.value < low) {
+            Some(RangeFlag::Low)
+        } else if self.referenceHigh.is_some_and(|high| self.value > high) {
+            Some(RangeFlag::High)
+        } else {
+            Some(RangeFlag::InRange)
+        }
+    }
+}
+
+/// A commonly monitored analyte, with the names labs print for it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LabAnalyte {
+    pub name: &'static str,
+    /// Lowercase names to match, most specific first.
+    pub aliases: &'static [&'static str],
+    pub unit: &'static str,
+    /// Set when results in other units can be converted via the molar mass.
+    pub hormone: Option<Hormone>,
+}
+
+const fn analyte(
+    name: &'static str,
+    aliases: &'static [&'static str],
+    unit: &'static str,
+    hormone: Option<Hormone>,
+) -> LabAnalyte {
+    LabAnalyte {
+        name,
+        aliases,
+        unit,
+        hormone,
+    }
+}
+
+pub const LAB_ANALYTES: [LabAnalyte; 20] = [
+    analyte("Potassium", &["potassium", "k+"], "mmol/L", None),
+    analyte("Sodium", &["sodium", "na+"], "mmol/L", None),
+    analyte(
+        "Creatinine",
+        &["creatinine"],
+        "mg/dL",
+        Some(Hormone::Creatinine),
+    ),
+    analyte("eGFR", &["egfr", "estimated gfr"], "mL/min/1.73m²", None),
+    analyte(
+        "ALT",
+        &["alanine aminotransferase", "alt", "sgpt"],
+        "U/L",
+        None,
+    ),
+    analyte(
+        "AST",
+        &["aspartate aminotransferase", "ast", "sgot"],
+        "U/L",
+        None,
+    ),
+    analyte(
+        "LDL cholesterol",
+        &["ldl cholesterol", "ldl-c", "ldl"],
+        "mg/dL",
+        Some(Hormone::Cholesterol),
+    ),
+    analyte(
+        "HDL cholesterol",
+        &["hdl cholesterol", "hdl-c", "hdl"],
+        "mg/dL",
+        Some(Hormone::Cholesterol),
+    ),
+    analyte(
+        "Total cholesterol",
+        &["total cholesterol", "cholesterol"],
+        "mg/dL",
+        Some(Hormone::Cholesterol),
+    ),
+    analyte(
+        "Triglycerides",
+        &["triglycerides", "triglyceride"],
+        "mg/dL",
+        Some(Hormone::Triglycerides),
+    ),
+    analyte(
+        "HbA1c",
+        &[
+            "hba1c",
+            "hemoglobin a1c",
+            "haemoglobin a1c",
+            "glycated hemoglobin",
+        ],
+        "%",
+        None,
+    ),
+    analyte(
+        "Hematocrit",
+        &["hematocrit", "haematocrit", "hct"],
+        "%",
+        None,
+    ),
+    analyte(
+        "Hemoglobin",
+        &["hemoglobin", "haemoglobin", "hgb"],
+        "g/dL",
+        None,
+    ),
+    analyte(
+        "Glucose",
+        &["fasting glucose", "glucose"],
+        "mg/dL",
+        Some(Hormone::Glucose),
+    ),
+    analyte(
+        "TSH",
+        &[
+            "thyroid stimulating hormone",
+            "thyroid-stimulating hormone",
+            "tsh",
+        ],
+        "mIU/L",
+        Some(Hormone::ThyroidStimulatingHormone),
+    ),
+    analyte(
+        "Free T4",
+        &["free thyroxine", "free t4", "ft4"],
+        "ng/dL",
+        Some(Hormone::Thyroxine),
+    ),
+    analyte(
+        "Free T3",
+        &["free triiodothyronine", "free t3", "ft3"],
+        "pg/mL",
+        Some(Hormone::Triiodothyronine),
+    ),
+    analyte(
+        "Vitamin D",
+        &["25-hydroxyvitamin d", "25-oh vitamin d", "vitamin d"],
+        "ng/mL",
+        Some(Hormone::VitaminD3),
+    ),
+    analyte(
+        "Vitamin B12",
+        &["vitamin b12", "cobalamin", "b12"],
+        "pg/mL",
+        Some(Hormone::VitaminB12),
+    ),
+    analyte(
+        "DHT",
+        &["dihydrotestosterone", "dht"],
+        "ng/dL",
+        Some(Hormone::Dihydrotestosterone),
+    ),
+];
+
+/// Looks up a known analyte by its name or one of its aliases.
+pub fn find_lab_analyte(name: &str) -> Option<&'static LabAnalyte> {
+    let name = name.trim().to_lowercase();
+    LAB_ANALYTES
+        .iter()
+        .find(|analyte| analyte.name.to_lowercase() == name || analyte.aliases.contains(&&*name))
+}
+
+/// The catalog name for known analytes, otherwise the trimmed input.
+pub fn canonical_lab_name(name: &str) -> String {
+    find_lab_analyte(name)
+        .map(|analyte| analyte.name.to_string())
+        .unwrap_or_else(|| name.trim().to_string())
+}
+
+fn same_analyte(a: &str, b: &str) -> bool {
+    canonical_lab_name(a).eq_ignore_ascii_case(&canonical_lab_name(b))
+}
+
+/// Names of every recorded lab result, deduplicated and sorted.
+pub fn recorded_lab_names(data: &HrtData) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    for result in data.bloodTests.iter().flat_map(|test| &test.labResults) {
+        let name = canonical_lab_name(&result.name);
+        if !name.is_empty() && !names.iter().any(|n| n.eq_ignore_ascii_case(&name)) {
+            names.push(name);
+        }
+    }
+    names.sort_by_key(|name| name.to_lowercase());
+    names
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LabResultPoint {
+    pub date: UnixTime,
+    pub value: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub flag: Option<RangeFlag>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LabResultSeries {
+    pub name: String,
+    pub unit: String,
+    pub points: Vec<LabResultPoint>,
+}
+
+/// All results for `name` in date order, in a single unit. Known analytes use
+/// the catalog unit where they can be converted; others use the unit of the
+/// latest result and skip results recorded in a different unit.
+pub fn lab_result_series(data: &HrtData, name: &str) -> LabResultSeries {
+    let mut results: Vec<(UnixTime, &LabResult)> = data
+        .bloodTests
+        .iter()
+        .flat_map(|test| {
+            test.labResults
+                .iter()
+                .filter(|result| same_analyte(&result.name, name) && result.value.is_finite())
+                .map(move |result| (test.date, result))
+        })
+        .collect();
+    results.sort_by_key(|(date, _)| *date);
+
+    let known = find_lab_analyte(name);
+    let latest_unit = results
+        .last()
+        .and_then(|(_, result)| result.unit.clone())
+        .unwrap_or_default();
+    let unit = match known {
+        Some(analyte) if analyte.hormone.is_some() => analyte.unit.to_string(),
+        _ => latest_unit,
+    };
+    let hormone = known.and_then(|analyte| analyte.hormone.clone());
+
+    let points = results
+        .into_iter()
+        .filter_map(|(date, result)| {
+            let from = result.unit.as_deref().unwrap_or(&unit);
+            let value = if from.eq_ignore_ascii_case(&unit) {
+                result.value
+            } else {
+                convert_hormone(result.value, hormone.clone()?, from, &unit).ok()?
+            };
+            Some(LabResultPoint {
+                date,
+                value,
+                flag: result.effective_flag(),
+            })
+        })
+        .collect();
+
+    LabResultSeries {
+        name: canonical_lab_name(name),
+        unit,
+        points,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::BloodTest;
+
+    fn result(name: &str, value: f64, unit: &str) -> LabResult {
+        LabResult {
+            name: name.to_string(),
+            value,
+            unit: Some(unit.to_string()),
+            ..LabResult::default()
+        }
+    }
+
+    #[test]
+    fn finds_analytes_by_alias() {
+        assert_eq!(find_lab_analyte(" SGPT ").unwrap().name, "ALT");
+        assert_eq!(canonical_lab_name("hba1c"), "HbA1c");
+        assert_eq!(canonical_lab_name(" Ferritin "), "Ferritin");
+    }
+
+    #[test]
+    fn flags_from_range_unless_lab_flagged() {
+        let mut potassium = LabResult {
+            referenceLow: Some(3.5),
+            referenceHigh: Some(5.1),
+            ..result("Potassium", 5.6, "mmol/L")
+        };
+        assert_eq!(potassium.effective_flag(), Some(RangeFlag::High));
+        potassium.flag = Some(RangeFlag::InRange);
+        assert_eq!(potassium.effective_flag(), Some(RangeFlag::InRange));
+        assert_eq!(result("ALT", 20.0, "U/L").effective_flag(), None);
+    }
+
+    #[test]
+    fn series_converts_known_units() {
+        let data = HrtData {
+            bloodTests: vec![
+                BloodTest {
+                    date: 2,
+                    labResults: vec![result("25-OH vitamin D", 75.0, "nmol/L")],
+                    ..BloodTest::default()
+                },
+                BloodTest {
+                    date: 1,
+                    labResults: vec![
+                        result("Vitamin D", 20.0, "ng/mL"),
+                        result("Potassium", 4.0, "mmol/L"),
+                    ],
+                    ..BloodTest::default()
+                },
+            ],
+            ..HrtData::default()
+        };
+        assert_eq!(recorded_lab_names(&data), vec!["Potassium", "Vitamin D"]);
+        let series = lab_result_series(&data, "vitamin d");
+        assert_eq!(series.unit, "ng/mL");
+        assert_eq!(series.points.len(), 2);
+        assert_eq!(series.points[0].value, 20.0);
+        assert!((series.points[1].value - 28.85).abs() < 0.05);
+    }
+
+    #[test]
+    fn series_converts_molar_chemistry_units() {
+        let data = HrtData {
+            bloodTests: vec![
+                BloodTest {
+                    date: 1,
+                    labResults: vec![
+                        result("Creatinine", 80.0, "µmol/L"),
+                        result("Glucose", 5.0, "mmol/L"),
+                        result("Triglycerides", 1.2, "mmol/L"),
+                    ],
+                    ..BloodTest::default()
+                },
+                BloodTest {
+                    date: 2,
+                    labResults: vec![result("Creatinine", 0.9, "mg/dL")],
+                    ..BloodTest::default()
+                },
+            ],
+            ..HrtData::default()
+        };
+        let series = lab_result_series(&data, "Creatinine");
+        assert_eq!(series.unit, "mg/dL");
+        assert_eq!(series.points.len(), 2);
+        assert!((series.points[0].value - 0.905).abs() < 0.001);
+        let glucose = lab_result_series(&data, "glucose");
+        assert!((glucose.points[0].value - 90.1).abs() < 0.1);
+        let triglycerides = lab_result_series(&data, "triglycerides");
+        assert!((triglycerides.points[0].value - 106.3).abs() < 0.1);
+    }
+
+    #[test]
+    fn series_skips_unconvertible_units() {
+        let data = HrtData {
+            bloodTests: vec![
+                BloodTest {
+                    date: 1,
+                    labResults: vec![result("Ferritin", 80.0, "pmol/L")],
+                    ..BloodTest::default()
+                },
+                BloodTest {
+                    date: 2,
+                    labResults: vec![result("Ferritin", 40.0, "ng/mL")],
+                    ..BloodTest::default()
+                },
+            ],
+            ..HrtData::default()
+        };
+        let series = lab_result_series(&data, "Ferritin");
+        assert_eq!(series.unit, "ng/mL");
+        assert_eq!(series.points.len(), 1);
+    }
+}
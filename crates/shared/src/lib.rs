@@ -1,4 +1,19 @@
+pub mod adherence;
 pub mod convert;
 pub mod estrannaise;
+pub mod free_hormones;
+pub mod inventory;
+pub mod lab_results;
 pub mod logic;
+pub mod medication_pk;
+pub mod medications;
+pub mod plans;
+pub mod quantity;
+pub mod ranges;
+pub mod recurrence;
+pub mod regimen;
+pub mod safety;
+pub mod schedules;
+pub mod transdermal;
 pub mod types;
+pub mod whatif;
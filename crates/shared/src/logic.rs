@@ -1,39 +1,44 @@
 use chrono::{Local, TimeZone, Timelike};
 
-use crate::estrannaise::e2_multidose_3c;
+use crate::estrannaise::{e2_multidose_3c, e2_multidose_series, estradiol_dose_events};
+use crate::quantity::BloodAnalyte;
+use crate::schedules::{
+    current_injectable, entry_date, find_regimen, following_dose, last_regimen_dose,
+    next_regimen_dose,
+};
+use crate::transdermal::{gel_dose_mg, next_patch_site};
 use crate::types::{
-    DosageHistoryEntry, EstrannaiseModel, HormoneUnits, HrtData, InjectableEstradiols,
-    ProgesteroneSchedule, UnixTime,
+    DosageHistoryEntry, EstrannaiseModel, HormoneUnits, HrtData, RegimenSchedule, UnixTime,
 };
 
 const DAY_MS: i64 = 24 * 60 * 60 * 1000;
 
-fn map_estrannaise_model(kind: &InjectableEstradiols) -> Option<EstrannaiseModel> {
-    match kind {
-        InjectableEstradiols::Benzoate => Some(EstrannaiseModel::EbIm),
-        InjectableEstradiols::Valerate => Some(EstrannaiseModel::EvIm),
-        InjectableEstradiols::Enanthate => Some(EstrannaiseModel::EEnIm),
-        InjectableEstradiols::Cypionate => Some(EstrannaiseModel::EcIm),
-        InjectableEstradiols::Undecylate => Some(EstrannaiseModel::EUnIm),
-        InjectableEstradiols::PolyestradiolPhosphate => None,
-    }
+const FIT_MAX_SHIFT_DAYS: f64 = 2.0;
+const FIT_SHIFT_STEP_DAYS: f64 = 1.0 / 24.0;
+
+/// Individual correction to the population PK curve, fitted to blood tests.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PkFit {
+    /// Multiplier on the predicted level (the fitted equivalent of a fudge factor).
+    pub scale: f64,
+    /// Positive values mean the measured curve lags the model.
+    pub time_shift_days: f64,
+    pub rmse_pg_ml: f64,
+    pub samples: usize,
 }
 
 pub fn predict_e2_pg_ml(data: &HrtData, date: i64) -> Option<f64> {
-    let mut dose_history: Vec<_> = data
-        .dosageHistory
-        .iter()
-        .filter_map(|entry| match entry {
-            DosageHistoryEntry::InjectableEstradiol {
-                date, kind, dose, ..
-            } => Some((*date, kind.clone(), *dose)),
-            _ => None,
-        })
-        .collect();
-    if dose_history.is_empty() {
-        return None;
-    }
-    dose_history.sort_by_key(|(date, _, _)| *date);
+    let dose_history = estradiol_dose_events(data);
+    predict_from_events(&dose_history, date)
+}
+
+pub fn predict_e2_pg_ml_fitted(data: &HrtData, date: i64, fit: &PkFit) -> Option<f64> {
+    let dose_history = estradiol_dose_events(data);
+    let shift_ms = (fit.time_shift_days * DAY_MS as f64) as i64;
+    predict_from_events(&dose_history, date - shift_ms).map(|value| value * fit.scale)
+}
+
+fn predict_from_events(dose_history: &[(UnixTime, f64, EstrannaiseModel)], date: i64) -> Option<f64> {
     let start_date = dose_history.first().map(|(date, _, _)| *date)?;
     if date < start_date {
         return None;
@@ -41,15 +46,10 @@ pub fn predict_e2_pg_ml(data: &HrtData, date: i64) -> Option<f64> {
     let mut time_map = Vec::new();
     let mut dose_map = Vec::new();
     let mut model_map = Vec::new();
-    for (dose_date, kind, dose) in dose_history {
-        if let Some(model) = map_estrannaise_model(&kind) {
-            time_map.push((dose_date - start_date) as f64 / DAY_MS as f64);
-            dose_map.push(dose);
-            model_map.push(model);
-        }
-    }
-    if model_map.is_empty() {
-        return None;
+    for (dose_date, dose, model) in dose_history {
+        time_map.push((dose_date - start_date) as f64 / DAY_MS as f64);
+        dose_map.push(*dose);
+        model_map.push(model.clone());
     }
     let t = (date - start_date) as f64 / DAY_MS as f64;
     let predicted = e2_multidose_3c(t, &dose_map, &time_map, &model_map, 1.0, false);
@@ -60,6 +60,73 @@ pub fn predict_e2_pg_ml(data: &HrtData, date: i64) -> Option<f64> {
     }
 }
 
+/// Least-squares fit of a scale and time shift to the estradiol blood tests
+/// taken after the first dose. A single test only determines the scale.
+pub fn fit_pk_parameters(data: &HrtData) -> Option<PkFit> {
+    let dose_history = estradiol_dose_events(data);
+    let start_date = dose_history.first().map(|(date, _, _)| *date)?;
+    let samples: Vec<(i64, f64)> = data
+        .bloodTests
+        .iter()
+        .filter(|test| test.date > start_date)
+        .filter_map(|test| {
+            test.level_in(BloodAnalyte::Estradiol, HormoneUnits::E2PgMl)
+                .map(|value| (test.date, value))
+        })
+        .collect();
+    if samples.is_empty() {
+        return None;
+    }
+
+    let max_steps = if samples.len() > 1 {
+        (FIT_MAX_SHIFT_DAYS / FIT_SHIFT_STEP_DAYS).round() as i64
+    } else {
+        0
+    };
+    // Try small shifts first so ties keep the least surprising fit.
+    let shifts = (0..=max_steps).flat_map(|step| {
+        if step == 0 {
+            vec![0.0]
+        } else {
+            let shift = step as f64 * FIT_SHIFT_STEP_DAYS;
+            vec![shift, -shift]
+        }
+    });
+
+    let day = |date: i64| (date - start_date) as f64 / DAY_MS as f64;
+    let time_map: Vec<f64> = dose_history.iter().map(|(date, _, _)| day(*date)).collect();
+    let dose_map: Vec<f64> = dose_history.iter().map(|(_, dose, _)| *dose).collect();
+    let model_map: Vec<_> = dose_history.iter().map(|(_, _, model)| model.clone()).collect();
+
+    let mut best: Option<PkFit> = None;
+    for shift in shifts {
+        let shift_ms = (shift * DAY_MS as f64) as i64;
+        let ts: Vec<f64> = samples.iter().map(|(date, _)| day(date - shift_ms)).collect();
+        let predicted = e2_multidose_series(&ts, &dose_map, &time_map, &model_map, 1.0, false);
+        let pairs: Vec<(f64, f64)> = predicted
+            .into_iter()
+            .zip(samples.iter().map(|(_, measured)| *measured))
+            .collect();
+        let pp: f64 = pairs.iter().map(|(p, _)| p * p).sum();
+        if pp <= 0.0 {
+            continue;
+        }
+        let pm: f64 = pairs.iter().map(|(p, m)| p * m).sum();
+        let scale = (pm / pp).max(0.0);
+        let sse: f64 = pairs.iter().map(|(p, m)| (m - p * scale).powi(2)).sum();
+        let rmse = (sse / pairs.len() as f64).sqrt();
+        if best.map(|fit| rmse < fit.rmse_pg_ml - 1e-9).unwrap_or(true) {
+            best = Some(PkFit {
+                scale,
+                time_shift_days: shift,
+                rmse_pg_ml: rmse,
+                samples: pairs.len(),
+            });
+        }
+    }
+    best
+}
+
 pub fn migrate_blood_tests_fudge_factor(data: &mut HrtData) -> bool {
     if data.bloodTests.is_empty() {
         return false;
@@ -74,16 +141,9 @@ pub fn migrate_blood_tests_fudge_factor(data: &mut HrtData) -> bool {
         if test.fudgeFactor.is_some() {
             continue;
         }
-        let Some(estradiol_level) = test.estradiolLevel else {
+        let Some(measured) = test.level_in(BloodAnalyte::Estradiol, HormoneUnits::E2PgMl) else {
             continue;
         };
-        let measured = match test.estradiolUnit {
-            Some(HormoneUnits::E2PmolL) => estradiol_level / 3.671,
-            _ => estradiol_level,
-        };
-        if !measured.is_finite() {
-            continue;
-        }
         let predicted = predicted_values
             .get(idx)
             .and_then(|value| *value)
@@ -105,7 +165,7 @@ pub fn migrate_blood_tests_fudge_factor(data: &mut HrtData) -> bool {
 }
 
 pub fn snap_to_next_injection_boundary(data: &HrtData, ts: UnixTime) -> UnixTime {
-    let inj = match &data.injectableEstradiol {
+    let inj = match current_injectable(data) {
         Some(inj) => inj,
         None => return ts,
     };
@@ -116,29 +176,8 @@ pub fn snap_to_next_injection_boundary(data: &HrtData, ts: UnixTime) -> UnixTime
         return ts;
     };
 
-    let mut reference: Option<i64> = None;
-    let last_taken_dates: Vec<i64> = data
-        .dosageHistory
-        .iter()
-        .filter_map(|d| match d {
-            DosageHistoryEntry::InjectableEstradiol {
-                date, bonusDose, ..
-            } => {
-                if bonusDose.unwrap_or(false) {
-                    None
-                } else {
-                    Some(*date)
-                }
-            }
-            _ => None,
-        })
-        .collect();
-
-    if !last_taken_dates.is_empty() {
-        reference = Some(*last_taken_dates.iter().max().unwrap());
-    } else if let Some(next) = inj.nextDoseDate {
-        reference = Some(next);
-    }
+    let reference = last_regimen_dose(data, &RegimenSchedule::InjectableEstradiol(inj.clone()))
+        .or(inj.nextDoseDate);
 
     let reference = match reference {
         Some(value) => value,
@@ -169,17 +208,7 @@ pub fn backfill_scheduled_doses(data: &mut HrtData) {
         return;
     }
 
-    fn process_schedule<T>(
-        schedule: &mut Option<T>,
-        medication_type: &str,
-        dosage_history: &[DosageHistoryEntry],
-    ) where
-        T: ScheduleFields,
-    {
-        let schedule = match schedule.as_mut() {
-            Some(value) => value,
-            None => return,
-        };
+    fn process_schedule(schedule: &mut RegimenSchedule, dosage_history: &[DosageHistoryEntry]) {
         let interval_days = schedule.frequency();
         if interval_days <= 0.0 {
             return;
@@ -191,39 +220,13 @@ pub fn backfill_scheduled_doses(data: &mut HrtData) {
 
         let mut next_time = schedule.next_dose_date();
 
-        let last_taken_dates: Vec<i64> = dosage_history
+        let last_taken = dosage_history
             .iter()
-            .filter_map(|d| match d {
-                DosageHistoryEntry::InjectableEstradiol {
-                    date, bonusDose, ..
-                } if medication_type == "injectableEstradiol" => {
-                    if bonusDose.unwrap_or(false) {
-                        None
-                    } else {
-                        Some(*date)
-                    }
-                }
-                DosageHistoryEntry::OralEstradiol { date, .. }
-                    if medication_type == "oralEstradiol" =>
-                {
-                    Some(*date)
-                }
-                DosageHistoryEntry::Antiandrogen { date, .. }
-                    if medication_type == "antiandrogen" =>
-                {
-                    Some(*date)
-                }
-                DosageHistoryEntry::Progesterone { date, .. }
-                    if medication_type == "progesterone" =>
-                {
-                    Some(*date)
-                }
-                _ => None,
-            })
-            .collect();
+            .filter(|d| schedule.matches_entry(d))
+            .map(entry_date)
+            .max();
 
-        if !last_taken_dates.is_empty() {
-            let last_taken = *last_taken_dates.iter().max().unwrap();
+        if let Some(last_taken) = last_taken {
             let next_after_last = last_taken + interval_ms;
             if next_time.is_none_or(|t| t < next_after_last) {
                 next_time = Some(next_after_last);
@@ -249,18 +252,135 @@ pub fn backfill_scheduled_doses(data: &mut HrtData) {
         schedule.set_next_dose_date(next_time);
     }
 
-    process_schedule(
-        &mut data.injectableEstradiol,
-        "injectableEstradiol",
-        &data.dosageHistory,
-    );
-    process_schedule(
-        &mut data.oralEstradiol,
-        "oralEstradiol",
-        &data.dosageHistory,
-    );
-    process_schedule(&mut data.antiandrogen, "antiandrogen", &data.dosageHistory);
-    process_schedule(&mut data.progesterone, "progesterone", &data.dosageHistory);
+    // Recurring regimens move to their first slot from today on.
+    let today_start_ms = day_start_ms(Local::now().timestamp_millis());
+    let recurring: Vec<(String, UnixTime)> = data
+        .regimens
+        .iter()
+        .filter(|r| r.is_current() && r.recurrence.is_some())
+        .filter_map(|r| Some((r.id.clone(), next_regimen_dose(data, r, today_start_ms)?)))
+        .collect();
+
+    for regimen in data.regimens.iter_mut().filter(|r| r.is_current()) {
+        if regimen.recurrence.is_none() {
+            process_schedule(&mut regimen.schedule, &data.dosageHistory);
+        } else if let Some((_, next)) = recurring.iter().find(|(id, _)| *id == regimen.id) {
+            regimen.schedule.set_next_dose_date(*next);
+        }
+    }
+}
+
+/// Logs a dose of a regimen with its scheduled defaults. `regimen` is a
+/// regimen id or, for the first current regimen of a class, a medication type.
+pub fn record_scheduled_dose(
+    data: &mut HrtData,
+    regimen: &str,
+    date: UnixTime,
+    id: Option<String>,
+) -> bool {
+    if let Some(id) = id.as_deref() {
+        if data.dosageHistory.iter().any(|entry| entry_id(entry) == Some(id)) {
+            return false;
+        }
+    }
+    let Some(regimen_id) = find_regimen(data, regimen).map(|r| r.id.clone()) else {
+        return false;
+    };
+    let site = next_patch_site(data);
+    let next_dose = find_regimen(data, &regimen_id).and_then(|r| following_dose(data, r, date));
+    let Some(regimen) = data.regimens.iter_mut().find(|r| r.id == regimen_id) else {
+        return false;
+    };
+    let record = match &mut regimen.schedule {
+        RegimenSchedule::InjectableEstradiol(cfg) => {
+            if next_dose.is_some() {
+                cfg.nextDoseDate = next_dose;
+            }
+            DosageHistoryEntry::InjectableEstradiol {
+                date,
+                id,
+                kind: cfg.kind.clone(),
+                dose: cfg.dose,
+                unit: cfg.unit.clone(),
+                note: None,
+                bonusDose: None,
+                injectionSite: None,
+                vialId: cfg.vialId.clone(),
+                subVialId: cfg.subVialId.clone(),
+                syringeKind: cfg.syringeKind.clone(),
+                needleLength: cfg.needleLength.clone(),
+                needleGauge: cfg.needleGauge.clone(),
+                photos: None,
+                pkModelId: None,
+            }
+        }
+        RegimenSchedule::OralEstradiol(cfg) => DosageHistoryEntry::OralEstradiol {
+            date,
+            id,
+            kind: cfg.kind.clone(),
+            route: cfg.route.clone(),
+            dose: cfg.dose,
+            unit: cfg.unit.clone(),
+            pillQuantity: Some(1.0),
+            note: None,
+            pkModelId: None,
+        },
+        RegimenSchedule::Antiandrogen(cfg) => DosageHistoryEntry::Antiandrogen {
+            date,
+            id,
+            kind: cfg.kind.clone(),
+            dose: cfg.dose,
+            unit: cfg.unit.clone(),
+            note: None,
+        },
+        RegimenSchedule::Progesterone(cfg) => DosageHistoryEntry::Progesterone {
+            date,
+            id,
+            kind: cfg.kind.clone(),
+            route: cfg.route.clone(),
+            dose: cfg.dose,
+            unit: cfg.unit.clone(),
+            pillQuantity: Some(1.0),
+            note: None,
+        },
+        RegimenSchedule::Patch(cfg) => {
+            if next_dose.is_some() {
+                cfg.nextDoseDate = next_dose;
+            }
+            DosageHistoryEntry::Patch {
+                date,
+                id,
+                strength: cfg.strength,
+                patchQuantity: cfg.patchQuantity,
+                wearDays: Some(cfg.wearDays.unwrap_or(cfg.frequency)),
+                removedAt: None,
+                site: Some(site),
+                note: None,
+            }
+        }
+        RegimenSchedule::Gel(cfg) => DosageHistoryEntry::Gel {
+            date,
+            id,
+            dose: gel_dose_mg(cfg.dose, cfg.pumps, cfg.mgPerPump),
+            unit: cfg.unit.clone(),
+            pumps: cfg.pumps,
+            note: None,
+        },
+    };
+    data.dosageHistory.push(record);
+    true
+}
+
+fn entry_id(entry: &DosageHistoryEntry) -> Option<&str> {
+    match entry {
+        DosageHistoryEntry::InjectableEstradiol { id, .. }
+        | DosageHistoryEntry::OralEstradiol { id, .. }
+        | DosageHistoryEntry::Antiandrogen { id, .. }
+        | DosageHistoryEntry::Progesterone { id, .. }
+        | DosageHistoryEntry::Patch { id, .. }
+        | DosageHistoryEntry::Gel { id, .. }
+        | DosageHistoryEntry::Medication { id, .. } => id.as_deref(),
+    }
 }
 
 fn day_start_ms(ms: i64) -> i64 {
@@ -276,63 +396,12 @@ fn day_start_ms(ms: i64) -> i64 {
     start.map(|d| d.timestamp_millis()).unwrap_or(ms)
 }
 
-trait ScheduleFields {
-    fn frequency(&self) -> f64;
-    fn next_dose_date(&self) -> Option<i64>;
-    fn set_next_dose_date(&mut self, ts: i64);
-}
-
-impl ScheduleFields for crate::types::InjectableSchedule {
-    fn frequency(&self) -> f64 {
-        self.frequency
-    }
-    fn next_dose_date(&self) -> Option<i64> {
-        self.nextDoseDate
-    }
-    fn set_next_dose_date(&mut self, ts: i64) {
-        self.nextDoseDate = Some(ts);
-    }
-}
-
-impl ScheduleFields for crate::types::OralSchedule {
-    fn frequency(&self) -> f64 {
-        self.frequency
-    }
-    fn next_dose_date(&self) -> Option<i64> {
-        self.nextDoseDate
-    }
-    fn set_next_dose_date(&mut self, ts: i64) {
-        self.nextDoseDate = Some(ts);
-    }
-}
-
-impl ScheduleFields for crate::types::AntiandrogenSchedule {
-    fn frequency(&self) -> f64 {
-        self.frequency
-    }
-    fn next_dose_date(&self) -> Option<i64> {
-        self.nextDoseDate
-    }
-    fn set_next_dose_date(&mut self, ts: i64) {
-        self.nextDoseDate = Some(ts);
-    }
-}
-
-impl ScheduleFields for ProgesteroneSchedule {
-    fn frequency(&self) -> f64 {
-        self.frequency
-    }
-    fn next_dose_date(&self) -> Option<i64> {
-        self.nextDoseDate
-    }
-    fn set_next_dose_date(&mut self, ts: i64) {
-        self.nextDoseDate = Some(ts);
-    }
-}
-
 #[cfg(test)]
+#[allow(clippy::field_reassign_with_default)]
 mod tests {
     use super::*;
+    use crate::quantity::Quantity;
+    use crate::schedules::migrate_schedules_to_regimens;
     use crate::types::*;
 
     fn make_injectable_entry(date: i64, kind: InjectableEstradiols, dose: f64) -> DosageHistoryEntry {
@@ -351,6 +420,7 @@ mod tests {
             needleLength: None,
             needleGauge: None,
             photos: None,
+            pkModelId: None,
         }
     }
 
@@ -359,10 +429,12 @@ mod tests {
             date,
             id: None,
             kind: OralEstradiols::Hemihydrate,
+            route: None,
             dose: 2.0,
             unit: HormoneUnits::Mg,
             pillQuantity: None,
             note: None,
+            pkModelId: None,
         }
     }
 
@@ -373,10 +445,11 @@ mod tests {
     }
 
     #[test]
-    fn predict_e2_returns_none_for_oral_only() {
+    fn predict_e2_includes_oral_doses() {
         let mut data = HrtData::default();
         data.dosageHistory.push(make_oral_entry(1700000000000));
-        assert!(predict_e2_pg_ml(&data, 1700100000000).is_none());
+        let e2 = predict_e2_pg_ml(&data, 1700000000000 + 8 * 3600 * 1000).unwrap();
+        assert!(e2 > 0.0, "oral dose should contribute: {e2}");
     }
 
     #[test]
@@ -407,15 +480,16 @@ mod tests {
     }
 
     #[test]
-    fn predict_e2_returns_none_for_polyestradiol_phosphate_only() {
+    fn predict_e2_includes_polyestradiol_phosphate() {
         let mut data = HrtData::default();
         data.dosageHistory.push(make_injectable_entry(
             1700000000000,
             InjectableEstradiols::PolyestradiolPhosphate,
             40.0,
         ));
-        // PEP has no estrannaise model
-        assert!(predict_e2_pg_ml(&data, 1700100000000).is_none());
+        // PEP is still releasing estradiol months after the injection
+        let e2 = predict_e2_pg_ml(&data, 1700000000000 + 90 * DAY_MS).unwrap();
+        assert!(e2 > 10.0, "PEP should still contribute: {e2}");
     }
 
     #[test]
@@ -458,24 +532,20 @@ mod tests {
         ));
         data.bloodTests.push(BloodTest {
             date: 1700000000000 + 3 * DAY_MS,
-            estradiolLevel: Some(200.0),
-            estradiolUnit: Some(HormoneUnits::E2PgMl),
+            estradiol: Some(Quantity::new(200.0, HormoneUnits::E2PgMl)),
             fudgeFactor: Some(1.5),
-            testLevel: None,
-            testUnit: None,
-            progesteroneLevel: None,
-            progesteroneUnit: None,
-            fshLevel: None,
-            fshUnit: None,
-            lhLevel: None,
-            lhUnit: None,
-            prolactinLevel: None,
-            prolactinUnit: None,
-            shbgLevel: None,
-            shbgUnit: None,
+            testosterone: None,
+            progesterone: None,
+            fsh: None,
+            lh: None,
+            prolactin: None,
+            shbg: None,
             freeAndrogenIndex: None,
+            albuminLevel: None,
             estrannaiseNumber: None,
             notes: None,
+            lab: None,
+            labResults: Vec::new(),
             estrogenType: None,
             pdfFiles: None,
         });
@@ -495,24 +565,20 @@ mod tests {
         ));
         data.bloodTests.push(BloodTest {
             date: dose_time + 3 * DAY_MS,
-            estradiolLevel: Some(200.0),
-            estradiolUnit: Some(HormoneUnits::E2PgMl),
+            estradiol: Some(Quantity::new(200.0, HormoneUnits::E2PgMl)),
             fudgeFactor: None,
-            testLevel: None,
-            testUnit: None,
-            progesteroneLevel: None,
-            progesteroneUnit: None,
-            fshLevel: None,
-            fshUnit: None,
-            lhLevel: None,
-            lhUnit: None,
-            prolactinLevel: None,
-            prolactinUnit: None,
-            shbgLevel: None,
-            shbgUnit: None,
+            testosterone: None,
+            progesterone: None,
+            fsh: None,
+            lh: None,
+            prolactin: None,
+            shbg: None,
             freeAndrogenIndex: None,
+            albuminLevel: None,
             estrannaiseNumber: None,
             notes: None,
+            lab: None,
+            labResults: Vec::new(),
             estrogenType: None,
             pdfFiles: None,
         });
@@ -533,24 +599,20 @@ mod tests {
         ));
         data.bloodTests.push(BloodTest {
             date: dose_time + 3 * DAY_MS,
-            estradiolLevel: Some(734.26), // ~200 pg/mL in pmol/L
-            estradiolUnit: Some(HormoneUnits::E2PmolL),
+            estradiol: Some(Quantity::new(734.26, HormoneUnits::E2PmolL)), // ~200 pg/mL in pmol/L
             fudgeFactor: None,
-            testLevel: None,
-            testUnit: None,
-            progesteroneLevel: None,
-            progesteroneUnit: None,
-            fshLevel: None,
-            fshUnit: None,
-            lhLevel: None,
-            lhUnit: None,
-            prolactinLevel: None,
-            prolactinUnit: None,
-            shbgLevel: None,
-            shbgUnit: None,
+            testosterone: None,
+            progesterone: None,
+            fsh: None,
+            lh: None,
+            prolactin: None,
+            shbg: None,
             freeAndrogenIndex: None,
+            albuminLevel: None,
             estrannaiseNumber: None,
             notes: None,
+            lab: None,
+            labResults: Vec::new(),
             estrogenType: None,
             pdfFiles: None,
         });
@@ -559,6 +621,95 @@ mod tests {
         assert!(ff > 0.0, "fudge factor should be positive: {ff}");
     }
 
+    fn make_e2_test(date: i64, level: f64) -> BloodTest {
+        BloodTest {
+            date,
+            estradiol: Some(Quantity::new(level, HormoneUnits::E2PgMl)),
+            fudgeFactor: None,
+            testosterone: None,
+            progesterone: None,
+            fsh: None,
+            lh: None,
+            prolactin: None,
+            shbg: None,
+            freeAndrogenIndex: None,
+            albuminLevel: None,
+            estrannaiseNumber: None,
+            notes: None,
+            lab: None,
+            labResults: Vec::new(),
+            estrogenType: None,
+            pdfFiles: None,
+        }
+    }
+
+    fn weekly_valerate(weeks: i64) -> HrtData {
+        let mut data = HrtData::default();
+        for week in 0..weeks {
+            data.dosageHistory.push(make_injectable_entry(
+                1700000000000 + week * 7 * DAY_MS,
+                InjectableEstradiols::Valerate,
+                4.0,
+            ));
+        }
+        data
+    }
+
+    #[test]
+    fn fit_pk_parameters_none_without_tests() {
+        assert!(fit_pk_parameters(&weekly_valerate(4)).is_none());
+    }
+
+    #[test]
+    fn fit_pk_parameters_single_test_fits_scale_only() {
+        let mut data = weekly_valerate(4);
+        let date = 1700000000000 + 20 * DAY_MS;
+        let predicted = predict_e2_pg_ml(&data, date).unwrap();
+        data.bloodTests.push(make_e2_test(date, predicted * 0.8));
+        let fit = fit_pk_parameters(&data).unwrap();
+        assert_eq!(fit.time_shift_days, 0.0);
+        assert!((fit.scale - 0.8).abs() < 1e-6, "scale {}", fit.scale);
+        assert!(fit.rmse_pg_ml < 1e-6);
+    }
+
+    #[test]
+    fn fit_pk_parameters_ignores_tests_before_the_first_dose() {
+        let mut data = weekly_valerate(4);
+        let date = 1700000000000 + 20 * DAY_MS;
+        let predicted = predict_e2_pg_ml(&data, date).unwrap();
+        data.bloodTests
+            .push(make_e2_test(1700000000000 - 10 * DAY_MS, 30.0));
+        data.bloodTests.push(make_e2_test(date, predicted * 0.8));
+        let fit = fit_pk_parameters(&data).unwrap();
+        assert_eq!(fit.samples, 1);
+        assert!((fit.scale - 0.8).abs() < 1e-6, "scale {}", fit.scale);
+    }
+
+    #[test]
+    fn fit_pk_parameters_recovers_scale_and_shift() {
+        let mut data = weekly_valerate(6);
+        let truth = PkFit {
+            scale: 1.4,
+            time_shift_days: 0.5,
+            rmse_pg_ml: 0.0,
+            samples: 0,
+        };
+        // Peaks and troughs across several cycles pin down the shift.
+        for hours in [36, 7 * 24, 9 * 24 + 12, 14 * 24, 22 * 24, 28 * 24, 31 * 24] {
+            let date = 1700000000000 + hours * 3600 * 1000;
+            let level = predict_e2_pg_ml_fitted(&data, date, &truth).unwrap();
+            data.bloodTests.push(make_e2_test(date, level));
+        }
+        let fit = fit_pk_parameters(&data).unwrap();
+        assert_eq!(fit.samples, 7);
+        assert!((fit.scale - 1.4).abs() < 0.01, "scale {}", fit.scale);
+        assert!((fit.time_shift_days - 0.5).abs() < 0.05, "shift {}", fit.time_shift_days);
+        let date = 1700000000000 + 25 * DAY_MS;
+        let fitted = predict_e2_pg_ml_fitted(&data, date, &fit).unwrap();
+        let expected = predict_e2_pg_ml_fitted(&data, date, &truth).unwrap();
+        assert!((fitted - expected).abs() / expected < 0.01);
+    }
+
     #[test]
     fn snap_returns_ts_when_no_injectable() {
         let data = HrtData::default();
@@ -581,6 +732,7 @@ mod tests {
             needleGauge: None,
             nextDoseDate: None,
         });
+        migrate_schedules_to_regimens(&mut data);
         let ts = 1700000000000_i64;
         assert_eq!(snap_to_next_injection_boundary(&data, ts), ts);
     }
@@ -601,6 +753,7 @@ mod tests {
             needleGauge: None,
             nextDoseDate: None,
         });
+        migrate_schedules_to_regimens(&mut data);
         data.dosageHistory.push(make_injectable_entry(
             dose_time,
             InjectableEstradiols::Valerate,
@@ -639,8 +792,9 @@ mod tests {
             needleGauge: None,
             nextDoseDate: None,
         });
+        migrate_schedules_to_regimens(&mut data);
         backfill_scheduled_doses(&mut data);
-        assert!(data.injectableEstradiol.as_ref().unwrap().nextDoseDate.is_none());
+        assert!(data.regimens[0].schedule.next_dose_date().is_none());
     }
 
     #[test]
@@ -664,11 +818,201 @@ mod tests {
             InjectableEstradiols::Valerate,
             4.0,
         ));
+        migrate_schedules_to_regimens(&mut data);
         backfill_scheduled_doses(&mut data);
-        let next = data.injectableEstradiol.as_ref().unwrap().nextDoseDate;
+        let next = data.regimens[0].schedule.next_dose_date();
         assert!(next.is_some(), "should have set nextDoseDate");
         let next = next.unwrap();
         // Should be dose_time + 7 days
         assert!(next > dose_time, "next dose should be after last dose");
     }
+
+    #[test]
+    fn record_scheduled_dose_uses_schedule_defaults() {
+        let mut data = HrtData {
+            injectableEstradiol: Some(InjectableSchedule {
+                kind: InjectableEstradiols::Valerate,
+                dose: 4.0,
+                unit: HormoneUnits::Mg,
+                frequency: 7.0,
+                vialId: Some("vial-1".to_string()),
+                subVialId: None,
+                syringeKind: Some("Insulin syringe".to_string()),
+                needleLength: None,
+                needleGauge: Some("29G".to_string()),
+                nextDoseDate: None,
+            }),
+            ..Default::default()
+        };
+        migrate_schedules_to_regimens(&mut data);
+        let date = 1700000000000_i64;
+        assert!(record_scheduled_dose(
+            &mut data,
+            "injectableEstradiol",
+            date,
+            Some("todo-1".to_string())
+        ));
+        match &data.dosageHistory[0] {
+            DosageHistoryEntry::InjectableEstradiol {
+                date: entry_date,
+                id,
+                dose,
+                vialId,
+                syringeKind,
+                needleGauge,
+                ..
+            } => {
+                assert_eq!(*entry_date, date);
+                assert_eq!(id.as_deref(), Some("todo-1"));
+                assert_eq!(*dose, 4.0);
+                assert_eq!(vialId.as_deref(), Some("vial-1"));
+                assert_eq!(syringeKind.as_deref(), Some("Insulin syringe"));
+                assert_eq!(needleGauge.as_deref(), Some("29G"));
+            }
+            other => panic!("unexpected entry {:?}", other),
+        }
+        assert_eq!(
+            data.regimens[0].schedule.next_dose_date(),
+            Some(date + 7 * DAY_MS)
+        );
+    }
+
+    #[test]
+    fn record_scheduled_dose_follows_weekday_recurrence() {
+        use chrono::{Datelike, Weekday};
+        let mut data = HrtData {
+            injectableEstradiol: Some(InjectableSchedule {
+                kind: InjectableEstradiols::Valerate,
+                dose: 4.0,
+                unit: HormoneUnits::Mg,
+                frequency: 7.0,
+                vialId: None,
+                subVialId: None,
+                syringeKind: None,
+                needleLength: None,
+                needleGauge: None,
+                nextDoseDate: None,
+            }),
+            ..Default::default()
+        };
+        migrate_schedules_to_regimens(&mut data);
+        data.regimens[0].recurrence = Some(Recurrence {
+            weekdays: vec![6],
+            timesOfDay: vec![9 * 60],
+            ..Default::default()
+        });
+        let date = 1700000000000_i64;
+        assert!(record_scheduled_dose(
+            &mut data,
+            "injectableEstradiol",
+            date,
+            None
+        ));
+        let next = data.regimens[0].schedule.next_dose_date().unwrap();
+        let next_local = Local.timestamp_millis_opt(next).unwrap();
+        assert_eq!(next_local.weekday(), Weekday::Sun);
+        assert_eq!((next_local.hour(), next_local.minute()), (9, 0));
+        assert!(next > date && next <= date + 7 * DAY_MS);
+    }
+
+    #[test]
+    fn record_scheduled_dose_skips_duplicate_ids() {
+        let mut data = HrtData {
+            antiandrogen: Some(AntiandrogenSchedule {
+                kind: Antiandrogens::Spiro,
+                dose: 50.0,
+                unit: HormoneUnits::Mg,
+                frequency: 1.0,
+                nextDoseDate: None,
+                pillStock: None,
+                pillStockDate: None,
+            }),
+            ..Default::default()
+        };
+        migrate_schedules_to_regimens(&mut data);
+        let id = Some("todo-2".to_string());
+        assert!(record_scheduled_dose(&mut data, "antiandrogen", 1, id.clone()));
+        assert!(!record_scheduled_dose(&mut data, "antiandrogen", 2, id));
+        assert_eq!(data.dosageHistory.len(), 1);
+    }
+
+    #[test]
+    fn record_scheduled_patch_rotates_site() {
+        let mut data = HrtData {
+            patch: Some(PatchSchedule {
+                strength: 100.0,
+                patchQuantity: None,
+                frequency: 3.5,
+                wearDays: None,
+                nextDoseDate: None,
+            }),
+            ..Default::default()
+        };
+        migrate_schedules_to_regimens(&mut data);
+        assert!(record_scheduled_dose(&mut data, "patch", 1, None));
+        assert!(record_scheduled_dose(&mut data, "patch", 2, None));
+        let sites: Vec<_> = data
+            .dosageHistory
+            .iter()
+            .map(|entry| match entry {
+                DosageHistoryEntry::Patch { site, wearDays, .. } => {
+                    assert_eq!(*wearDays, Some(3.5));
+                    site.clone()
+                }
+                other => panic!("unexpected entry {:?}", other),
+            })
+            .collect();
+        assert_eq!(
+            sites,
+            vec![
+                Some(PatchSites::AbdomenRight),
+                Some(PatchSites::AbdomenLeft)
+            ]
+        );
+        assert_eq!(
+            data.regimens[0].schedule.next_dose_date(),
+            Some(2 + 7 * DAY_MS / 2)
+        );
+    }
+
+    #[test]
+    fn record_scheduled_dose_picks_regimen_by_id() {
+        let mut data = HrtData::default();
+        let schedule = |kind, dose| {
+            RegimenSchedule::Antiandrogen(AntiandrogenSchedule {
+                kind,
+                dose,
+                unit: HormoneUnits::Mg,
+                frequency: 1.0,
+                nextDoseDate: None,
+                pillStock: None,
+                pillStockDate: None,
+            })
+        };
+        crate::schedules::add_regimen(&mut data, schedule(Antiandrogens::Spiro, 50.0), 0);
+        let fin =
+            crate::schedules::add_regimen(&mut data, schedule(Antiandrogens::Finasteride, 1.0), 0);
+        assert!(record_scheduled_dose(&mut data, &fin, 1, None));
+        assert!(record_scheduled_dose(&mut data, "antiandrogen", 2, None));
+        let kinds: Vec<_> = data
+            .dosageHistory
+            .iter()
+            .map(|entry| match entry {
+                DosageHistoryEntry::Antiandrogen { kind, .. } => kind.clone(),
+                other => panic!("unexpected entry {:?}", other),
+            })
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![Antiandrogens::Finasteride, Antiandrogens::Spiro]
+        );
+    }
+
+    #[test]
+    fn record_scheduled_dose_requires_schedule() {
+        let mut data = HrtData::default();
+        assert!(!record_scheduled_dose(&mut data, "progesterone", 1, None));
+        assert!(!record_scheduled_dose(&mut data, "unknown", 1, None));
+        assert!(data.dosageHistory.is_empty());
+    }
 }
@@ -0,0 +1,317 @@
+use crate::types::{Antiandrogens, DosageHistoryEntry, HormoneUnits, ProgesteroneRoutes, UnixTime};
+
+const DAY_MS: f64 = 24.0 * 60.0 * 60.0 * 1000.0;
+
+/// One-compartment first-order absorption (Bateman) model. `amplitude` is the
+/// output per mg absorbed; `ka` and `ke` are per day.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BatemanParams {
+    pub amplitude: f64,
+    pub ka: f64,
+    pub ke: f64,
+}
+
+impl BatemanParams {
+    fn from_half_lives(amplitude: f64, absorption_hours: f64, elimination_hours: f64) -> Self {
+        Self {
+            amplitude,
+            ka: std::f64::consts::LN_2 * 24.0 / absorption_hours,
+            ke: std::f64::consts::LN_2 * 24.0 / elimination_hours,
+        }
+    }
+
+    pub fn level(&self, t_days: f64, dose: f64) -> f64 {
+        if t_days < 0.0 || dose <= 0.0 {
+            return 0.0;
+        }
+        let Self { amplitude, ka, ke } = *self;
+        if (ka - ke).abs() < f64::EPSILON {
+            return amplitude * dose * ka * t_days * f64::exp(-ke * t_days);
+        }
+        amplitude * dose * ka / (ka - ke) * (f64::exp(-ke * t_days) - f64::exp(-ka * t_days))
+    }
+
+    /// Days from a single dose to its peak.
+    pub fn time_to_peak(&self) -> f64 {
+        if (self.ka - self.ke).abs() < f64::EPSILON {
+            1.0 / self.ka
+        } else {
+            (self.ka / self.ke).ln() / (self.ka - self.ke)
+        }
+    }
+}
+
+/// Serum progesterone in ng/mL per mg of micronized progesterone. Tuned to
+/// published averages: oral 100 mg peaks near 10 ng/mL at ~3 h with a short
+/// apparent half-life; rectal use peaks later (~6 h), higher and longer.
+pub fn progesterone_params(route: &ProgesteroneRoutes) -> BatemanParams {
+    match route {
+        ProgesteroneRoutes::Oral => BatemanParams {
+            amplitude: 0.13,
+            ka: 20.0,
+            ke: 2.08,
+        },
+        ProgesteroneRoutes::Boofed => BatemanParams {
+            amplitude: 0.216,
+            ka: 8.0,
+            ke: 1.386,
+        },
+    }
+}
+
+/// Amount of drug in the body (mg) from absorption and elimination
+/// half-lives. Spironolactone follows its active metabolite canrenone.
+pub fn antiandrogen_params(kind: &Antiandrogens) -> BatemanParams {
+    match kind {
+        Antiandrogens::CPA => BatemanParams::from_half_lives(0.88, 1.0, 38.0),
+        Antiandrogens::Spiro => BatemanParams::from_half_lives(0.9, 1.5, 16.5),
+        Antiandrogens::Bica => BatemanParams::from_half_lives(1.0, 12.0, 140.0),
+        Antiandrogens::Finasteride => BatemanParams::from_half_lives(0.65, 0.5, 6.0),
+    }
+}
+
+// `events` must be in date order; doses after `t` contribute nothing.
+fn superpose(events: &[(UnixTime, f64, BatemanParams)], ts: &[UnixTime]) -> Vec<f64> {
+    ts.iter()
+        .map(|t| {
+            let end = events.partition_point(|(date, _, _)| date <= t);
+            events[..end]
+                .iter()
+                .map(|(date, dose, params)| params.level((t - date) as f64 / DAY_MS, *dose))
+                .sum()
+        })
+        .collect()
+}
+
+/// Times to evaluate a dose series at when plotting from `from` to `to`: an
+/// even grid of `points` plus each dose and its peak, so short half-lives show
+/// their real swing instead of aliasing against the grid. Doses are thinned
+/// to at most `points` when more fall in range.
+fn sample_times(
+    events: &[(UnixTime, f64, BatemanParams)],
+    from: UnixTime,
+    to: UnixTime,
+    points: usize,
+) -> Vec<UnixTime> {
+    if from >= to || points == 0 {
+        return Vec::new();
+    }
+    let step = ((to - from) / points as i64).max(1);
+    let mut ts: Vec<UnixTime> = (0..=points as i64)
+        .map(|i| from + i * step)
+        .filter(|t| *t <= to)
+        .collect();
+    let in_range: Vec<_> = events
+        .iter()
+        .filter(|(date, _, _)| *date >= from && *date <= to)
+        .collect();
+    let stride = in_range.len().div_ceil(points).max(1);
+    for (date, _, params) in in_range.into_iter().step_by(stride) {
+        let peak = date + (params.time_to_peak() * DAY_MS) as i64;
+        ts.push(*date);
+        if peak <= to {
+            ts.push(peak);
+        }
+    }
+    ts.sort_unstable();
+    ts.dedup();
+    ts
+}
+
+fn progesterone_events(history: &[DosageHistoryEntry]) -> Vec<(UnixTime, f64, BatemanParams)> {
+    let mut events: Vec<_> = history
+        .iter()
+        .filter_map(|entry| match entry {
+            DosageHistoryEntry::Progesterone {
+                date,
+                route,
+                dose,
+                unit,
+                pillQuantity,
+                ..
+            } if *unit == HormoneUnits::Mg => {
+                let qty = pillQuantity.filter(|q| *q > 0.0).unwrap_or(1.0);
+                Some((*date, dose * qty, progesterone_params(route)))
+            }
+            _ => None,
+        })
+        .collect();
+    events.sort_by_key(|(date, _, _)| *date);
+    events
+}
+
+fn antiandrogen_events(
+    history: &[DosageHistoryEntry],
+    kind: &Antiandrogens,
+) -> Vec<(UnixTime, f64, BatemanParams)> {
+    let params = antiandrogen_params(kind);
+    let mut events: Vec<_> = history
+        .iter()
+        .filter_map(|entry| match entry {
+            DosageHistoryEntry::Antiandrogen {
+                date,
+                kind: entry_kind,
+                dose,
+                unit,
+                ..
+            } if entry_kind == kind && *unit == HormoneUnits::Mg => Some((*date, *dose, params)),
+            _ => None,
+        })
+        .collect();
+    events.sort_by_key(|(date, _, _)| *date);
+    events
+}
+
+/// Modeled serum progesterone (ng/mL) at each time in `ts`.
+pub fn progesterone_series(history: &[DosageHistoryEntry], ts: &[UnixTime]) -> Vec<f64> {
+    superpose(&progesterone_events(history), ts)
+}
+
+/// Plotting times for `progesterone_series`; see `sample_times`.
+pub fn progesterone_sample_times(
+    history: &[DosageHistoryEntry],
+    from: UnixTime,
+    to: UnixTime,
+    points: usize,
+) -> Vec<UnixTime> {
+    sample_times(&progesterone_events(history), from, to, points)
+}
+
+/// Modeled amount (mg) of one antiandrogen in the body at each time in `ts`.
+pub fn antiandrogen_series(
+    history: &[DosageHistoryEntry],
+    kind: &Antiandrogens,
+    ts: &[UnixTime],
+) -> Vec<f64> {
+    superpose(&antiandrogen_events(history, kind), ts)
+}
+
+/// Plotting times for `antiandrogen_series`; see `sample_times`.
+pub fn antiandrogen_sample_times(
+    history: &[DosageHistoryEntry],
+    kind: &Antiandrogens,
+    from: UnixTime,
+    to: UnixTime,
+    points: usize,
+) -> Vec<UnixTime> {
+    sample_times(&antiandrogen_events(history, kind), from, to, points)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Progesterones;
+
+    const HOUR: i64 = 60 * 60 * 1000;
+
+    fn progesterone(date: i64, route: ProgesteroneRoutes, dose: f64) -> DosageHistoryEntry {
+        DosageHistoryEntry::Progesterone {
+            date,
+            id: None,
+            kind: Progesterones::Micronized,
+            route,
+            dose,
+            unit: HormoneUnits::Mg,
+            pillQuantity: None,
+            note: None,
+        }
+    }
+
+    fn antiandrogen(date: i64, kind: Antiandrogens, dose: f64) -> DosageHistoryEntry {
+        DosageHistoryEntry::Antiandrogen {
+            date,
+            id: None,
+            kind,
+            dose,
+            unit: HormoneUnits::Mg,
+            note: None,
+        }
+    }
+
+    #[test]
+    fn oral_progesterone_matches_reference() {
+        let params = progesterone_params(&ProgesteroneRoutes::Oral);
+        let tmax_h = params.time_to_peak() * 24.0;
+        assert!((2.0..4.0).contains(&tmax_h), "tmax {tmax_h}");
+        let cmax = params.level(params.time_to_peak(), 100.0);
+        assert!((8.0..12.0).contains(&cmax), "cmax {cmax}");
+    }
+
+    #[test]
+    fn rectal_progesterone_peaks_later_and_higher() {
+        let oral = progesterone_params(&ProgesteroneRoutes::Oral);
+        let rectal = progesterone_params(&ProgesteroneRoutes::Boofed);
+        assert!(rectal.time_to_peak() > oral.time_to_peak());
+        assert!(
+            rectal.level(rectal.time_to_peak(), 100.0) > oral.level(oral.time_to_peak(), 100.0)
+        );
+        assert!(rectal.level(1.0, 100.0) > oral.level(1.0, 100.0));
+    }
+
+    #[test]
+    fn progesterone_series_superposes_and_uses_pill_count() {
+        let history = vec![
+            progesterone(0, ProgesteroneRoutes::Oral, 100.0),
+            DosageHistoryEntry::Progesterone {
+                date: 24 * HOUR,
+                id: None,
+                kind: Progesterones::Micronized,
+                route: ProgesteroneRoutes::Oral,
+                dose: 100.0,
+                unit: HormoneUnits::Mg,
+                pillQuantity: Some(2.0),
+                note: None,
+            },
+        ];
+        let series = progesterone_series(&history, &[-HOUR, 3 * HOUR, 27 * HOUR]);
+        assert_eq!(series[0], 0.0);
+        let params = progesterone_params(&ProgesteroneRoutes::Oral);
+        let expected = params.level(27.0 / 24.0, 100.0) + params.level(3.0 / 24.0, 200.0);
+        assert!((series[2] - expected).abs() < 1e-9);
+        assert!(series[2] > series[1]);
+    }
+
+    #[test]
+    fn antiandrogen_half_lives_order() {
+        // Bicalutamide lingers for weeks, spironolactone for about a day.
+        let day = 24 * HOUR;
+        let at = |kind: Antiandrogens, t: i64| {
+            antiandrogen_series(&[antiandrogen(0, kind.clone(), 50.0)], &kind, &[t])[0]
+        };
+        assert!(at(Antiandrogens::Bica, 14 * day) > at(Antiandrogens::CPA, 14 * day));
+        assert!(at(Antiandrogens::CPA, 3 * day) > at(Antiandrogens::Spiro, 3 * day));
+        let spiro = antiandrogen_params(&Antiandrogens::Spiro);
+        let peak = spiro.level(spiro.time_to_peak(), 100.0);
+        let later = spiro.level(spiro.time_to_peak() + 16.5 / 24.0, 100.0);
+        assert!((later / peak - 0.5).abs() < 0.1, "ratio {}", later / peak);
+    }
+
+    #[test]
+    fn antiandrogen_series_filters_kind() {
+        let history = vec![
+            antiandrogen(0, Antiandrogens::Spiro, 100.0),
+            antiandrogen(0, Antiandrogens::CPA, 12.5),
+        ];
+        let spiro = antiandrogen_series(&history, &Antiandrogens::Spiro, &[6 * HOUR])[0];
+        let expected = antiandrogen_params(&Antiandrogens::Spiro).level(0.25, 100.0);
+        assert!((spiro - expected).abs() < 1e-9);
+    }
+
+    #[test]
+    fn sample_times_hit_every_daily_peak() {
+        let day = 24 * HOUR;
+        let history: Vec<_> = (0..180)
+            .map(|d| progesterone(d * day, ProgesteroneRoutes::Oral, 100.0))
+            .collect();
+        let ts = progesterone_sample_times(&history, 0, 180 * day, 400);
+        assert!(ts.len() <= 3 * 400 + 1 && ts.windows(2).all(|w| w[0] < w[1]));
+
+        let params = progesterone_params(&ProgesteroneRoutes::Oral);
+        let peak_offset = (params.time_to_peak() * DAY_MS) as i64;
+        assert!((0..180).all(|d| ts.binary_search(&(d * day + peak_offset)).is_ok()));
+        let levels = progesterone_series(&history, &ts);
+        let single_peak = params.level(params.time_to_peak(), 100.0);
+        let daily_peaks = levels.iter().filter(|level| **level >= single_peak).count();
+        assert!(daily_peaks >= 179, "{daily_peaks}");
+    }
+}
@@ -0,0 +1,479 @@
+#![allow(non_snake_case)]
+
+use crate::estrannaise::custom_model;
+use crate::transdermal::{patch_model, patch_wear_days};
+use crate::types::{
+    Antiandrogens, DosageHistoryEntry, EstrannaiseModel, HormoneUnits, HrtData,
+    InjectableEstradiols, Medication, MedicationClass, MedicationRoute, OralEstradiolRoutes,
+    OralEstradiols, ProgesteroneRoutes, Progesterones,
+};
+
+/// Ids of built-in catalog entries start with this, so they never clash with
+/// user-defined ones.
+pub const BUILTIN_ID_PREFIX: &str = "builtin:";
+
+pub const PATCH_MEDICATION_ID: &str = "builtin:patch";
+pub const GEL_MEDICATION_ID: &str = "builtin:gel";
+
+/// Built-in estrannaise models a catalog entry can refer to by name.
+pub const BUILTIN_PK_MODELS: [&str; 12] = [
+    "EB im",
+    "EV im",
+    "EEn im",
+    "EC im",
+    "EUn im",
+    "EUn casubq",
+    "PEP im",
+    "patch tw",
+    "patch ow",
+    "E2 oral",
+    "E2 sl",
+    "E2 gel",
+];
+
+impl MedicationClass {
+    pub const ALL: [MedicationClass; 7] = [
+        MedicationClass::Estrogen,
+        MedicationClass::Antiandrogen,
+        MedicationClass::Progestogen,
+        MedicationClass::GnrhAgonist,
+        MedicationClass::FiveAlphaReductaseInhibitor,
+        MedicationClass::Serm,
+        MedicationClass::Other,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            MedicationClass::Estrogen => "Estrogen",
+            MedicationClass::Antiandrogen => "Antiandrogen",
+            MedicationClass::Progestogen => "Progestogen",
+            MedicationClass::GnrhAgonist => "GnRH agonist",
+            MedicationClass::FiveAlphaReductaseInhibitor => "5α-reductase inhibitor",
+            MedicationClass::Serm => "SERM",
+            MedicationClass::Other => "Other",
+        }
+    }
+
+    pub fn from_label(label: &str) -> Option<MedicationClass> {
+        MedicationClass::ALL
+            .into_iter()
+            .find(|class| class.label() == label)
+    }
+}
+
+impl MedicationRoute {
+    pub const ALL: [MedicationRoute; 9] = [
+        MedicationRoute::Injection,
+        MedicationRoute::Oral,
+        MedicationRoute::Sublingual,
+        MedicationRoute::Rectal,
+        MedicationRoute::Transdermal,
+        MedicationRoute::Gel,
+        MedicationRoute::Implant,
+        MedicationRoute::Nasal,
+        MedicationRoute::Other,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            MedicationRoute::Injection => "Injection",
+            MedicationRoute::Oral => "Oral",
+            MedicationRoute::Sublingual => "Sublingual",
+            MedicationRoute::Rectal => "Rectal",
+            MedicationRoute::Transdermal => "Transdermal patch",
+            MedicationRoute::Gel => "Gel",
+            MedicationRoute::Implant => "Implant",
+            MedicationRoute::Nasal => "Nasal",
+            MedicationRoute::Other => "Other",
+        }
+    }
+
+    pub fn from_label(label: &str) -> Option<MedicationRoute> {
+        MedicationRoute::ALL
+            .into_iter()
+            .find(|route| route.label() == label)
+    }
+}
+
+fn builtin(
+    id: &str,
+    name: &str,
+    class: MedicationClass,
+    route: MedicationRoute,
+    pk_model: Option<&str>,
+) -> Medication {
+    // Patch strengths are delivered µg/day.
+    let unit = if route == MedicationRoute::Transdermal {
+        HormoneUnits::Mcg
+    } else {
+        HormoneUnits::Mg
+    };
+    Medication {
+        id: format!("{}{}", BUILTIN_ID_PREFIX, id),
+        name: name.to_string(),
+        class,
+        route,
+        unit,
+        pkModelId: pk_model.map(str::to_string),
+    }
+}
+
+pub fn injectable_medication_id(kind: &InjectableEstradiols) -> String {
+    let id = match kind {
+        InjectableEstradiols::Benzoate => "injectable-eb",
+        InjectableEstradiols::Cypionate => "injectable-ec",
+        InjectableEstradiols::Enanthate => "injectable-een",
+        InjectableEstradiols::Undecylate => "injectable-eun",
+        InjectableEstradiols::Valerate => "injectable-ev",
+        InjectableEstradiols::PolyestradiolPhosphate => "injectable-pep",
+    };
+    format!("{}{}", BUILTIN_ID_PREFIX, id)
+}
+
+pub fn oral_medication_id(kind: &OralEstradiols) -> String {
+    let id = match kind {
+        OralEstradiols::Hemihydrate => "oral-e2",
+        OralEstradiols::Valerate => "oral-ev",
+        OralEstradiols::Premarin => "oral-premarin",
+    };
+    format!("{}{}", BUILTIN_ID_PREFIX, id)
+}
+
+pub fn antiandrogen_medication_id(kind: &Antiandrogens) -> String {
+    let id = match kind {
+        Antiandrogens::CPA => "cpa",
+        Antiandrogens::Spiro => "spironolactone",
+        Antiandrogens::Bica => "bicalutamide",
+        Antiandrogens::Finasteride => "finasteride",
+    };
+    format!("{}{}", BUILTIN_ID_PREFIX, id)
+}
+
+pub fn progesterone_medication_id(kind: &Progesterones) -> String {
+    let id = match kind {
+        Progesterones::Micronized => "progesterone",
+    };
+    format!("{}{}", BUILTIN_ID_PREFIX, id)
+}
+
+/// Catalog entries for every medication with a dedicated dosage variant.
+pub fn builtin_medications() -> Vec<Medication> {
+    use MedicationClass::*;
+    use MedicationRoute::*;
+    vec![
+        builtin(
+            "injectable-eb",
+            "Estradiol Benzoate",
+            Estrogen,
+            Injection,
+            Some("EB im"),
+        ),
+        builtin(
+            "injectable-ec",
+            "Estradiol Cypionate",
+            Estrogen,
+            Injection,
+            Some("EC im"),
+        ),
+        builtin(
+            "injectable-een",
+            "Estradiol Enanthate",
+            Estrogen,
+            Injection,
+            Some("EEn im"),
+        ),
+        builtin(
+            "injectable-eun",
+            "Estradiol Undecylate",
+            Estrogen,
+            Injection,
+            Some("EUn im"),
+        ),
+        builtin(
+            "injectable-ev",
+            "Estradiol Valerate",
+            Estrogen,
+            Injection,
+            Some("EV im"),
+        ),
+        builtin(
+            "injectable-pep",
+            "Polyestradiol Phosphate",
+            Estrogen,
+            Injection,
+            Some("PEP im"),
+        ),
+        builtin(
+            "oral-e2",
+            "Estradiol Hemihydrate",
+            Estrogen,
+            Oral,
+            Some("E2 oral"),
+        ),
+        builtin(
+            "oral-ev",
+            "Estradiol Valerate (oral)",
+            Estrogen,
+            Oral,
+            Some("E2 oral"),
+        ),
+        builtin("oral-premarin", "Premarin", Estrogen, Oral, None),
+        builtin(
+            "patch",
+            "Estradiol patch",
+            Estrogen,
+            Transdermal,
+            Some("patch tw"),
+        ),
+        builtin("gel", "Estradiol gel", Estrogen, Gel, Some("E2 gel")),
+        builtin("cpa", "Cyproterone Acetate", Antiandrogen, Oral, None),
+        builtin("spironolactone", "Spironolactone", Antiandrogen, Oral, None),
+        builtin("bicalutamide", "Bicalutamide", Antiandrogen, Oral, None),
+        builtin(
+            "finasteride",
+            "Finasteride",
+            FiveAlphaReductaseInhibitor,
+            Oral,
+            None,
+        ),
+        builtin(
+            "progesterone",
+            "Micronized Progesterone",
+            Progestogen,
+            Oral,
+            None,
+        ),
+    ]
+}
+
+/// The built-in entries followed by the user's own.
+pub fn medication_catalog(data: &HrtData) -> Vec<Medication> {
+    let mut catalog = builtin_medications();
+    catalog.extend(data.medications.iter().cloned());
+    catalog
+}
+
+pub fn find_medication(data: &HrtData, id: &str) -> Option<Medication> {
+    if id.starts_with(BUILTIN_ID_PREFIX) {
+        builtin_medications().into_iter().find(|med| med.id == id)
+    } else {
+        data.medications.iter().find(|med| med.id == id).cloned()
+    }
+}
+
+/// The catalog entry a dose was taken from. The route of oral estradiol and
+/// progesterone doses comes from the entry rather than the catalog.
+pub fn entry_medication(data: &HrtData, entry: &DosageHistoryEntry) -> Option<Medication> {
+    match entry {
+        DosageHistoryEntry::InjectableEstradiol { kind, .. } => {
+            find_medication(data, &injectable_medication_id(kind))
+        }
+        DosageHistoryEntry::OralEstradiol { kind, route, .. } => {
+            let mut med = find_medication(data, &oral_medication_id(kind))?;
+            if route == &Some(OralEstradiolRoutes::Sublingual) {
+                med.route = MedicationRoute::Sublingual;
+                if med.pkModelId.is_some() {
+                    med.pkModelId = Some("E2 sl".to_string());
+                }
+            }
+            Some(med)
+        }
+        DosageHistoryEntry::Antiandrogen { kind, .. } => {
+            find_medication(data, &antiandrogen_medication_id(kind))
+        }
+        DosageHistoryEntry::Progesterone { kind, route, .. } => {
+            let mut med = find_medication(data, &progesterone_medication_id(kind))?;
+            if route == &ProgesteroneRoutes::Boofed {
+                med.route = MedicationRoute::Rectal;
+            }
+            Some(med)
+        }
+        DosageHistoryEntry::Patch {
+            date,
+            wearDays,
+            removedAt,
+            ..
+        } => {
+            let mut med = find_medication(data, PATCH_MEDICATION_ID)?;
+            if patch_model(patch_wear_days(*date, *wearDays, *removedAt))
+                == EstrannaiseModel::PatchOw
+            {
+                med.pkModelId = Some("patch ow".to_string());
+            }
+            Some(med)
+        }
+        DosageHistoryEntry::Gel { .. } => find_medication(data, GEL_MEDICATION_ID),
+        DosageHistoryEntry::Medication { medicationId, .. } => find_medication(data, medicationId),
+    }
+}
+
+/// Display name of the medication a dose was taken from.
+pub fn entry_medication_name(data: &HrtData, entry: &DosageHistoryEntry) -> String {
+    match entry_medication(data, entry) {
+        Some(med) => med.name,
+        None => match entry {
+            DosageHistoryEntry::Medication { medicationId, .. } => medicationId.clone(),
+            _ => String::new(),
+        },
+    }
+}
+
+/// The estrannaise model for a catalog entry: a custom model id first, then a
+/// built-in model name.
+pub fn medication_model(data: &HrtData, med: &Medication) -> Option<EstrannaiseModel> {
+    let id = med.pkModelId.as_deref()?;
+    custom_model(data, id)
+        .or_else(|| serde_json::from_value(serde_json::Value::String(id.to_string())).ok())
+}
+
+pub fn validate_medication(med: &Medication) -> Result<(), String> {
+    if med.name.trim().is_empty() {
+        return Err("Medication name is required".to_string());
+    }
+    if med.id.trim().is_empty() || med.id.starts_with(BUILTIN_ID_PREFIX) {
+        return Err("Medication id is invalid".to_string());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::CustomPkModel;
+
+    fn gel() -> Medication {
+        Medication {
+            id: "gel".to_string(),
+            name: "Oestrogel".to_string(),
+            class: MedicationClass::Estrogen,
+            route: MedicationRoute::Gel,
+            unit: HormoneUnits::Mg,
+            pkModelId: Some("E2 gel".to_string()),
+        }
+    }
+
+    #[test]
+    fn builtin_entries_cover_every_enum_value() {
+        let data = HrtData::default();
+        let ids = builtin_medications()
+            .into_iter()
+            .map(|med| med.id)
+            .collect::<Vec<_>>();
+        for kind in [
+            InjectableEstradiols::Benzoate,
+            InjectableEstradiols::Cypionate,
+            InjectableEstradiols::Enanthate,
+            InjectableEstradiols::Undecylate,
+            InjectableEstradiols::Valerate,
+            InjectableEstradiols::PolyestradiolPhosphate,
+        ] {
+            let med = find_medication(&data, &injectable_medication_id(&kind)).unwrap();
+            assert!(medication_model(&data, &med).is_some(), "{}", med.name);
+        }
+        for kind in [
+            Antiandrogens::CPA,
+            Antiandrogens::Spiro,
+            Antiandrogens::Bica,
+            Antiandrogens::Finasteride,
+        ] {
+            assert!(ids.contains(&antiandrogen_medication_id(&kind)));
+        }
+        for kind in [
+            OralEstradiols::Hemihydrate,
+            OralEstradiols::Valerate,
+            OralEstradiols::Premarin,
+        ] {
+            assert!(ids.contains(&oral_medication_id(&kind)));
+        }
+        for id in [PATCH_MEDICATION_ID, GEL_MEDICATION_ID] {
+            let med = find_medication(&data, id).unwrap();
+            assert!(medication_model(&data, &med).is_some(), "{}", med.name);
+        }
+    }
+
+    #[test]
+    fn entries_resolve_to_catalog() {
+        let data = HrtData {
+            medications: vec![gel()],
+            ..HrtData::default()
+        };
+        let sublingual = DosageHistoryEntry::OralEstradiol {
+            date: 0,
+            id: None,
+            kind: OralEstradiols::Hemihydrate,
+            route: Some(OralEstradiolRoutes::Sublingual),
+            dose: 2.0,
+            unit: HormoneUnits::Mg,
+            pillQuantity: None,
+            note: None,
+            pkModelId: None,
+        };
+        let med = entry_medication(&data, &sublingual).unwrap();
+        assert_eq!(med.route, MedicationRoute::Sublingual);
+        assert_eq!(
+            medication_model(&data, &med),
+            Some(EstrannaiseModel::E2Sublingual)
+        );
+
+        let generic = |medication_id: &str| DosageHistoryEntry::Medication {
+            date: 0,
+            id: None,
+            medicationId: medication_id.to_string(),
+            dose: 1.5,
+            unit: HormoneUnits::Mg,
+            note: None,
+        };
+        assert_eq!(entry_medication_name(&data, &generic("gel")), "Oestrogel");
+        assert_eq!(entry_medication_name(&data, &generic("gone")), "gone");
+        assert_eq!(
+            entry_medication_name(&data, &generic("builtin:finasteride")),
+            "Finasteride"
+        );
+    }
+
+    #[test]
+    fn custom_models_take_precedence() {
+        let mut med = gel();
+        let mut data = HrtData {
+            pkModels: vec![CustomPkModel {
+                id: "E2 gel".to_string(),
+                name: "Slow gel".to_string(),
+                route: crate::types::CustomPkRoute::Oral,
+                d: 500.0,
+                k1: 0.5,
+                k2: 5.0,
+                k3: 10.0,
+            }],
+            ..HrtData::default()
+        };
+        assert!(matches!(
+            medication_model(&data, &med),
+            Some(EstrannaiseModel::Custom(_))
+        ));
+        data.pkModels.clear();
+        assert_eq!(medication_model(&data, &med), Some(EstrannaiseModel::E2Gel));
+        med.pkModelId = Some("custom".to_string());
+        assert_eq!(medication_model(&data, &med), None);
+    }
+
+    #[test]
+    fn labels_roundtrip() {
+        for class in MedicationClass::ALL {
+            assert_eq!(MedicationClass::from_label(class.label()), Some(class));
+        }
+        for route in MedicationRoute::ALL {
+            assert_eq!(MedicationRoute::from_label(route.label()), Some(route));
+        }
+    }
+
+    #[test]
+    fn validates_user_entries() {
+        assert!(validate_medication(&gel()).is_ok());
+        let mut med = gel();
+        med.name = " ".to_string();
+        assert!(validate_medication(&med).is_err());
+        let mut med = gel();
+        med.id = "builtin:gel".to_string();
+        assert!(validate_medication(&med).is_err());
+    }
+}
@@ -0,0 +1,248 @@
+use crate::schedules::{end_regimen, replace_regimen};
+use crate::types::{DosePlan, HrtData, PlanStep, Regimen, UnixTime};
+
+/// Steps not yet applied, in date order.
+pub fn pending_steps(plan: &DosePlan) -> Vec<&PlanStep> {
+    let mut steps: Vec<&PlanStep> = plan.steps.iter().filter(|step| !step.applied).collect();
+    steps.sort_by_key(|step| step.startDate);
+    steps
+}
+
+/// Starts every plan step dated at or before `now`. Each step replaces the
+/// regimen the plan drives, so the regimen history records the titration.
+pub fn apply_due_plan_steps(data: &mut HrtData, now: UnixTime) -> bool {
+    let mut changed = false;
+    for plan_idx in 0..data.plans.len() {
+        data.plans[plan_idx]
+            .steps
+            .sort_by_key(|step| step.startDate);
+        for step_idx in 0..data.plans[plan_idx].steps.len() {
+            let step = data.plans[plan_idx].steps[step_idx].clone();
+            if step.applied || step.startDate > now {
+                continue;
+            }
+            let target = data.plans[plan_idx].regimenId.clone();
+            let regimen_id = match step.schedule {
+                Some(mut schedule) => {
+                    let replaced = target
+                        .as_deref()
+                        .and_then(|id| data.regimens.iter().find(|r| r.id == id));
+                    if let Some(replaced) = replaced {
+                        if let Some((stock, date)) = replaced.schedule.pill_stock() {
+                            schedule.set_pill_stock(stock, date);
+                        }
+                    }
+                    if schedule.next_dose_date().is_none() {
+                        schedule.set_next_dose_date(step.startDate);
+                    }
+                    Some(replace_regimen(
+                        data,
+                        target.as_deref().unwrap_or(""),
+                        schedule,
+                        step.recurrence,
+                        step.startDate,
+                    ))
+                }
+                None => {
+                    if let Some(id) = target.as_deref() {
+                        end_regimen(data, id, step.startDate);
+                    }
+                    None
+                }
+            };
+            let plan = &mut data.plans[plan_idx];
+            plan.regimenId = regimen_id;
+            plan.steps[step_idx].applied = true;
+            changed = true;
+        }
+    }
+    changed
+}
+
+/// Current regimens with the pending plan steps laid over them: a regimen
+/// a plan will replace ends at the step, and each step adds a regimen that
+/// has not started yet. Projected regimens are identified as
+/// `{plan}-step-{startDate}`, so they keep their id as earlier steps apply.
+pub fn projected_regimens(data: &HrtData) -> Vec<Regimen> {
+    let mut regimens: Vec<Regimen> = data
+        .regimens
+        .iter()
+        .filter(|regimen| regimen.is_current())
+        .cloned()
+        .collect();
+    for plan in &data.plans {
+        let mut target = plan.regimenId.clone();
+        for step in pending_steps(plan) {
+            if let Some(replaced) = target
+                .as_deref()
+                .and_then(|id| regimens.iter_mut().find(|r| r.id == id && r.is_current()))
+            {
+                replaced.endDate = Some(step.startDate.max(replaced.startDate));
+            }
+            target = step.schedule.as_ref().map(|schedule| {
+                let mut schedule = schedule.clone();
+                if schedule.next_dose_date().is_none() {
+                    schedule.set_next_dose_date(step.startDate);
+                }
+                let id = format!("{}-step-{}", plan.id, step.startDate);
+                regimens.push(Regimen {
+                    id: id.clone(),
+                    startDate: step.startDate,
+                    endDate: None,
+                    schedule,
+                    recurrence: step.recurrence.clone(),
+                });
+                id
+            });
+        }
+    }
+    regimens
+}
+
+/// Pending steps dated after `from`, with their plan, in date order.
+pub fn upcoming_plan_steps(data: &HrtData, from: UnixTime) -> Vec<(&DosePlan, &PlanStep)> {
+    let mut steps: Vec<(&DosePlan, &PlanStep)> = data
+        .plans
+        .iter()
+        .flat_map(|plan| {
+            pending_steps(plan)
+                .into_iter()
+                .filter(move |step| step.startDate >= from)
+                .map(move |step| (plan, step))
+        })
+        .collect();
+    steps.sort_by_key(|(_, step)| step.startDate);
+    steps
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schedules::{add_regimen, current_regimens, regimen_dose_times};
+    use crate::types::{
+        AntiandrogenSchedule, Antiandrogens, HormoneUnits, InjectableEstradiols,
+        InjectableSchedule, RegimenSchedule,
+    };
+
+    const DAY: i64 = 24 * 60 * 60 * 1000;
+
+    fn valerate(dose: f64) -> RegimenSchedule {
+        RegimenSchedule::InjectableEstradiol(InjectableSchedule {
+            kind: InjectableEstradiols::Valerate,
+            dose,
+            unit: HormoneUnits::Mg,
+            frequency: 7.0,
+            vialId: None,
+            subVialId: None,
+            syringeKind: None,
+            needleLength: None,
+            needleGauge: None,
+            nextDoseDate: Some(0),
+        })
+    }
+
+    fn step(start: UnixTime, schedule: Option<RegimenSchedule>) -> PlanStep {
+        PlanStep {
+            startDate: start,
+            schedule,
+            recurrence: None,
+            applied: false,
+        }
+    }
+
+    fn titration() -> HrtData {
+        let mut data = HrtData::default();
+        let id = add_regimen(&mut data, valerate(2.0), 0);
+        data.plans.push(DosePlan {
+            id: "plan".to_string(),
+            name: "Titrate".to_string(),
+            regimenId: Some(id),
+            steps: vec![step(28 * DAY, Some(valerate(4.0)))],
+        });
+        data
+    }
+
+    #[test]
+    fn due_steps_replace_the_driven_regimen() {
+        let mut data = titration();
+        assert!(!apply_due_plan_steps(&mut data, 27 * DAY));
+        assert!(apply_due_plan_steps(&mut data, 28 * DAY));
+        assert!(!apply_due_plan_steps(&mut data, 29 * DAY));
+
+        assert_eq!(data.regimens[0].endDate, Some(28 * DAY));
+        let current: Vec<_> = current_regimens(&data).collect();
+        assert_eq!(current.len(), 1);
+        assert_eq!(current[0].schedule.dose(), 4.0);
+        assert_eq!(
+            data.plans[0].regimenId.as_deref(),
+            Some(current[0].id.as_str())
+        );
+    }
+
+    #[test]
+    fn projection_switches_dose_at_each_step() {
+        let data = titration();
+        let projected = projected_regimens(&data);
+        assert_eq!(projected.len(), 2);
+        assert_eq!(projected[0].endDate, Some(28 * DAY));
+        assert_eq!(projected[1].id, format!("plan-step-{}", 28 * DAY));
+
+        let before = regimen_dose_times(&data, &projected[0], 0, 40 * DAY);
+        let after = regimen_dose_times(&data, &projected[1], 0, 40 * DAY);
+        assert_eq!(before, vec![0, 7 * DAY, 14 * DAY, 21 * DAY]);
+        assert_eq!(after, vec![28 * DAY, 35 * DAY]);
+        assert_eq!(upcoming_plan_steps(&data, 0).len(), 1);
+    }
+
+    #[test]
+    fn projected_ids_survive_earlier_steps_applying() {
+        let mut data = titration();
+        data.plans[0].steps.push(step(56 * DAY, Some(valerate(6.0))));
+        let id = format!("plan-step-{}", 56 * DAY);
+        assert_eq!(projected_regimens(&data)[2].id, id);
+
+        apply_due_plan_steps(&mut data, 28 * DAY);
+        let projected = projected_regimens(&data);
+        assert_eq!(projected.len(), 2);
+        assert_eq!(projected[1].id, id);
+    }
+
+    #[test]
+    fn taper_ends_with_a_stop_step() {
+        let bica = RegimenSchedule::Antiandrogen(AntiandrogenSchedule {
+            kind: Antiandrogens::Bica,
+            dose: 50.0,
+            unit: HormoneUnits::Mg,
+            frequency: 1.0,
+            nextDoseDate: None,
+            pillStock: Some(20.0),
+            pillStockDate: Some(0),
+        });
+        let mut data = HrtData::default();
+        let id = add_regimen(&mut data, bica.clone(), 0);
+        data.plans.push(DosePlan {
+            id: "taper".to_string(),
+            name: "Taper bica".to_string(),
+            regimenId: Some(id),
+            steps: vec![
+                step(30 * DAY, None),
+                step(15 * DAY, Some(bica.with_dosing(25.0, 1.0))),
+            ],
+        });
+        let mut stale = bica.with_dosing(25.0, 1.0);
+        stale.set_pill_stock(Some(99.0), Some(0));
+        data.plans[0].steps[1].schedule = Some(stale);
+
+        assert!(apply_due_plan_steps(&mut data, 20 * DAY));
+        let current: Vec<_> = current_regimens(&data).collect();
+        assert_eq!(current[0].schedule.dose(), 25.0);
+        assert_eq!(
+            current[0].schedule.pill_stock(),
+            Some((Some(20.0), Some(0)))
+        );
+
+        assert!(apply_due_plan_steps(&mut data, 30 * DAY));
+        assert_eq!(current_regimens(&data).count(), 0);
+        assert_eq!(data.plans[0].regimenId, None);
+    }
+}
@@ -0,0 +1,624 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::convert::{iu_per_gram, molar_mass};
+use crate::types::{BloodTest, Hormone, HormoneUnits};
+
+/// What a unit measures. `Mass` units convert only among themselves, never
+/// to a concentration, and `Activity` only converts to mass or molar units
+/// for hormones with an IU reference standard.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnitDimension {
+    MassConcentration,
+    MolarConcentration,
+    Activity,
+    Mass,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MassConcentration {
+    PgMl,
+    NgMl,
+    NgDl,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MolarConcentration {
+    PmolL,
+    NmolL,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Activity {
+    MIuMl,
+    MIuL,
+    UL,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mass {
+    Mcg,
+    Mg,
+}
+
+/// A unit that converts to the other units of its dimension by a power of
+/// ten.
+pub trait ScaledUnit: Copy {
+    /// Power of ten of the dimension's base unit: g/L, mol/L, IU/L or g.
+    fn exponent(self) -> i32;
+    fn unit(self) -> HormoneUnits;
+}
+
+impl ScaledUnit for MassConcentration {
+    fn exponent(self) -> i32 {
+        match self {
+            MassConcentration::PgMl => -9,
+            MassConcentration::NgMl => -6,
+            MassConcentration::NgDl => -8,
+        }
+    }
+
+    fn unit(self) -> HormoneUnits {
+        match self {
+            MassConcentration::PgMl => HormoneUnits::E2PgMl,
+            MassConcentration::NgMl => HormoneUnits::NgMl,
+            MassConcentration::NgDl => HormoneUnits::TNgDl,
+        }
+    }
+}
+
+impl ScaledUnit for MolarConcentration {
+    fn exponent(self) -> i32 {
+        match self {
+            MolarConcentration::PmolL => -12,
+            MolarConcentration::NmolL => -9,
+        }
+    }
+
+    fn unit(self) -> HormoneUnits {
+        match self {
+            MolarConcentration::PmolL => HormoneUnits::E2PmolL,
+            MolarConcentration::NmolL => HormoneUnits::TNmolL,
+        }
+    }
+}
+
+impl ScaledUnit for Activity {
+    fn exponent(self) -> i32 {
+        match self {
+            Activity::MIuMl | Activity::UL => 0,
+            Activity::MIuL => -3,
+        }
+    }
+
+    fn unit(self) -> HormoneUnits {
+        match self {
+            Activity::MIuMl => HormoneUnits::MIuMl,
+            Activity::MIuL => HormoneUnits::MIuL,
+            Activity::UL => HormoneUnits::UL,
+        }
+    }
+}
+
+impl ScaledUnit for Mass {
+    fn exponent(self) -> i32 {
+        match self {
+            Mass::Mcg => -6,
+            Mass::Mg => -3,
+        }
+    }
+
+    fn unit(self) -> HormoneUnits {
+        match self {
+            Mass::Mcg => HormoneUnits::Mcg,
+            Mass::Mg => HormoneUnits::Mg,
+        }
+    }
+}
+
+/// A `HormoneUnits` split by dimension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TypedUnit {
+    MassConcentration(MassConcentration),
+    MolarConcentration(MolarConcentration),
+    Activity(Activity),
+    Mass(Mass),
+}
+
+impl HormoneUnits {
+    pub const ALL: [HormoneUnits; 10] = [
+        HormoneUnits::E2PgMl,
+        HormoneUnits::E2PmolL,
+        HormoneUnits::TNgDl,
+        HormoneUnits::TNmolL,
+        HormoneUnits::Mg,
+        HormoneUnits::Mcg,
+        HormoneUnits::NgMl,
+        HormoneUnits::MIuMl,
+        HormoneUnits::MIuL,
+        HormoneUnits::UL,
+    ];
+
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            HormoneUnits::E2PgMl => "pg/mL",
+            HormoneUnits::E2PmolL => "pmol/L",
+            HormoneUnits::TNgDl => "ng/dL",
+            HormoneUnits::TNmolL => "nmol/L",
+            HormoneUnits::Mg => "mg",
+            HormoneUnits::Mcg => "µg",
+            HormoneUnits::NgMl => "ng/mL",
+            HormoneUnits::MIuMl => "mIU/mL",
+            HormoneUnits::MIuL => "mIU/L",
+            HormoneUnits::UL => "U/L",
+        }
+    }
+
+    pub fn from_symbol(symbol: &str) -> Option<HormoneUnits> {
+        HormoneUnits::ALL
+            .into_iter()
+            .find(|unit| unit.symbol() == symbol)
+    }
+
+    pub fn typed(&self) -> TypedUnit {
+        match self {
+            HormoneUnits::E2PgMl => TypedUnit::MassConcentration(MassConcentration::PgMl),
+            HormoneUnits::NgMl => TypedUnit::MassConcentration(MassConcentration::NgMl),
+            HormoneUnits::TNgDl => TypedUnit::MassConcentration(MassConcentration::NgDl),
+            HormoneUnits::E2PmolL => TypedUnit::MolarConcentration(MolarConcentration::PmolL),
+            HormoneUnits::TNmolL => TypedUnit::MolarConcentration(MolarConcentration::NmolL),
+            HormoneUnits::MIuMl => TypedUnit::Activity(Activity::MIuMl),
+            HormoneUnits::MIuL => TypedUnit::Activity(Activity::MIuL),
+            HormoneUnits::UL => TypedUnit::Activity(Activity::UL),
+            HormoneUnits::Mg => TypedUnit::Mass(Mass::Mg),
+            HormoneUnits::Mcg => TypedUnit::Mass(Mass::Mcg),
+        }
+    }
+
+    pub fn dimension(&self) -> UnitDimension {
+        match self.typed() {
+            TypedUnit::MassConcentration(_) => UnitDimension::MassConcentration,
+            TypedUnit::MolarConcentration(_) => UnitDimension::MolarConcentration,
+            TypedUnit::Activity(_) => UnitDimension::Activity,
+            TypedUnit::Mass(_) => UnitDimension::Mass,
+        }
+    }
+}
+
+/// A value in a unit of a known dimension. Conversions within the dimension,
+/// and between mass and molar concentrations, cannot fail.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Measure<U> {
+    pub value: f64,
+    pub unit: U,
+}
+
+impl<U: ScaledUnit> Measure<U> {
+    pub fn new(value: f64, unit: U) -> Self {
+        Self { value, unit }
+    }
+
+    pub fn to(self, unit: U) -> Measure<U> {
+        Measure::new(rescale(self.value, self.unit, unit, 1.0), unit)
+    }
+
+    pub fn quantity(self) -> Quantity {
+        Quantity::new(self.value, self.unit.unit())
+    }
+}
+
+impl Measure<MassConcentration> {
+    pub fn to_molar(
+        self,
+        hormone: &Hormone,
+        unit: MolarConcentration,
+    ) -> Measure<MolarConcentration> {
+        let value = rescale(self.value, self.unit, unit, 1.0 / molar_mass(hormone));
+        Measure::new(value, unit)
+    }
+}
+
+impl Measure<MolarConcentration> {
+    pub fn to_mass(self, hormone: &Hormone, unit: MassConcentration) -> Measure<MassConcentration> {
+        let value = rescale(self.value, self.unit, unit, molar_mass(hormone));
+        Measure::new(value, unit)
+    }
+}
+
+// `value` in `from`, times `factor` between the two base units, in `to`.
+fn rescale(value: f64, from: impl ScaledUnit, to: impl ScaledUnit, factor: f64) -> f64 {
+    value * 10_f64.powi(from.exponent() - to.exponent()) * factor
+}
+
+/// Why a `Quantity` could not be converted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConversionError {
+    /// The units measure different things, e.g. a dose and a concentration.
+    Incompatible {
+        from: HormoneUnits,
+        to: HormoneUnits,
+    },
+    /// Converting IU to mass or moles needs a reference standard.
+    NoIuStandard(Hormone),
+}
+
+impl fmt::Display for ConversionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConversionError::Incompatible { from, to } => {
+                write!(f, "Cannot convert {} to {}", from.symbol(), to.symbol())
+            }
+            ConversionError::NoIuStandard(hormone) => {
+                write!(f, "No IU reference standard for {:?}", hormone)
+            }
+        }
+    }
+}
+
+/// A measured amount together with its unit.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Quantity {
+    pub value: f64,
+    pub unit: HormoneUnits,
+}
+
+impl Quantity {
+    pub fn new(value: f64, unit: HormoneUnits) -> Self {
+        Self { value, unit }
+    }
+
+    /// Converts to `unit`. Only conversions involving IU can fail for a
+    /// concentration; masses never convert to concentrations.
+    pub fn to(&self, hormone: Hormone, unit: HormoneUnits) -> Result<Quantity, ConversionError> {
+        use TypedUnit as T;
+        let value = self.value;
+        let iu = || iu_per_gram(&hormone).ok_or(ConversionError::NoIuStandard(hormone.clone()));
+        let value = match (self.unit.typed(), unit.typed()) {
+            (T::MassConcentration(from), T::MassConcentration(to)) => {
+                Measure::new(value, from).to(to).value
+            }
+            (T::MolarConcentration(from), T::MolarConcentration(to)) => {
+                Measure::new(value, from).to(to).value
+            }
+            (T::Activity(from), T::Activity(to)) => Measure::new(value, from).to(to).value,
+            (T::Mass(from), T::Mass(to)) => Measure::new(value, from).to(to).value,
+            (T::MassConcentration(from), T::MolarConcentration(to)) => {
+                Measure::new(value, from).to_molar(&hormone, to).value
+            }
+            (T::MolarConcentration(from), T::MassConcentration(to)) => {
+                Measure::new(value, from).to_mass(&hormone, to).value
+            }
+            (T::MassConcentration(from), T::Activity(to)) => rescale(value, from, to, iu()?),
+            (T::Activity(from), T::MassConcentration(to)) => rescale(value, from, to, 1.0 / iu()?),
+            (T::MolarConcentration(from), T::Activity(to)) => {
+                rescale(value, from, to, molar_mass(&hormone) * iu()?)
+            }
+            (T::Activity(from), T::MolarConcentration(to)) => {
+                rescale(value, from, to, 1.0 / (molar_mass(&hormone) * iu()?))
+            }
+            _ => {
+                return Err(ConversionError::Incompatible {
+                    from: self.unit.clone(),
+                    to: unit,
+                })
+            }
+        };
+        Ok(Quantity { value, unit })
+    }
+
+    /// The value in `unit`, or `None` when the units are incompatible.
+    pub fn value_in(&self, hormone: Hormone, unit: HormoneUnits) -> Option<f64> {
+        self.to(hormone, unit).ok().map(|q| q.value)
+    }
+}
+
+impl fmt::Display for Quantity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.value, self.unit.symbol())
+    }
+}
+
+/// The levels recorded on a `BloodTest`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum BloodAnalyte {
+    Estradiol,
+    Testosterone,
+    Progesterone,
+    Fsh,
+    Lh,
+    Prolactin,
+    Shbg,
+}
+
+impl BloodAnalyte {
+    pub const ALL: [BloodAnalyte; 7] = [
+        BloodAnalyte::Estradiol,
+        BloodAnalyte::Testosterone,
+        BloodAnalyte::Progesterone,
+        BloodAnalyte::Fsh,
+        BloodAnalyte::Lh,
+        BloodAnalyte::Prolactin,
+        BloodAnalyte::Shbg,
+    ];
+
+    pub fn hormone(&self) -> Hormone {
+        match self {
+            BloodAnalyte::Estradiol => Hormone::Estradiol,
+            BloodAnalyte::Testosterone => Hormone::Testosterone,
+            BloodAnalyte::Progesterone => Hormone::Progesterone,
+            BloodAnalyte::Fsh => Hormone::FollicleStimulatingHormone,
+            BloodAnalyte::Lh => Hormone::LuteinisingHormone,
+            BloodAnalyte::Prolactin => Hormone::Prolactin,
+            BloodAnalyte::Shbg => Hormone::SexHormoneBindingGlobulin,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            BloodAnalyte::Estradiol => "Estradiol",
+            BloodAnalyte::Testosterone => "Testosterone",
+            BloodAnalyte::Progesterone => "Progesterone",
+            BloodAnalyte::Fsh => "FSH",
+            BloodAnalyte::Lh => "LH",
+            BloodAnalyte::Prolactin => "Prolactin",
+            BloodAnalyte::Shbg => "SHBG",
+        }
+    }
+
+    /// Unit assumed when a stored level has no unit.
+    pub fn default_unit(&self) -> HormoneUnits {
+        match self {
+            BloodAnalyte::Estradiol => HormoneUnits::E2PgMl,
+            BloodAnalyte::Testosterone | BloodAnalyte::Progesterone | BloodAnalyte::Shbg => {
+                HormoneUnits::TNmolL
+            }
+            BloodAnalyte::Fsh | BloodAnalyte::Lh => HormoneUnits::UL,
+            BloodAnalyte::Prolactin => HormoneUnits::MIuL,
+        }
+    }
+
+    /// Whether a level of this analyte can be expressed in `unit`.
+    pub fn supports(&self, unit: &HormoneUnits) -> bool {
+        Quantity::new(1.0, self.default_unit())
+            .to(self.hormone(), unit.clone())
+            .is_ok()
+    }
+}
+
+impl BloodTest {
+    fn level_field(&mut self, analyte: BloodAnalyte) -> &mut Option<Quantity> {
+        match analyte {
+            BloodAnalyte::Estradiol => &mut self.estradiol,
+            BloodAnalyte::Testosterone => &mut self.testosterone,
+            BloodAnalyte::Progesterone => &mut self.progesterone,
+            BloodAnalyte::Fsh => &mut self.fsh,
+            BloodAnalyte::Lh => &mut self.lh,
+            BloodAnalyte::Prolactin => &mut self.prolactin,
+            BloodAnalyte::Shbg => &mut self.shbg,
+        }
+    }
+
+    /// The recorded level in its stored unit.
+    pub fn level(&self, analyte: BloodAnalyte) -> Option<Quantity> {
+        let level = match analyte {
+            BloodAnalyte::Estradiol => &self.estradiol,
+            BloodAnalyte::Testosterone => &self.testosterone,
+            BloodAnalyte::Progesterone => &self.progesterone,
+            BloodAnalyte::Fsh => &self.fsh,
+            BloodAnalyte::Lh => &self.lh,
+            BloodAnalyte::Prolactin => &self.prolactin,
+            BloodAnalyte::Shbg => &self.shbg,
+        };
+        level.clone()
+    }
+
+    /// The recorded level converted to `unit`.
+    pub fn level_in(&self, analyte: BloodAnalyte, unit: HormoneUnits) -> Option<f64> {
+        self.level(analyte)?.value_in(analyte.hormone(), unit)
+    }
+
+    pub fn set_level(&mut self, analyte: BloodAnalyte, quantity: Option<Quantity>) {
+        *self.level_field(analyte) = quantity;
+    }
+}
+
+/// Reads and writes a `BloodTest` level as the `<name>Level`/`<name>Unit`
+/// pair it is stored as. A level saved without a unit is read in the
+/// analyte's default unit; one that is not finite is dropped.
+pub(crate) mod stored_level {
+    macro_rules! stored_level {
+        ($name:ident, $level:literal, $unit:literal, $analyte:expr) => {
+            pub(crate) mod $name {
+                use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+                use crate::quantity::{BloodAnalyte, Quantity};
+                use crate::types::HormoneUnits;
+
+                #[derive(Serialize, Deserialize)]
+                struct Stored {
+                    #[serde(rename = $level, default, skip_serializing_if = "Option::is_none")]
+                    level: Option<f64>,
+                    #[serde(rename = $unit, default, skip_serializing_if = "Option::is_none")]
+                    unit: Option<HormoneUnits>,
+                }
+
+                pub(crate) fn serialize<S: Serializer>(
+                    level: &Option<Quantity>,
+                    serializer: S,
+                ) -> Result<S::Ok, S::Error> {
+                    Stored {
+                        level: level.as_ref().map(|q| q.value),
+                        unit: level.as_ref().map(|q| q.unit.clone()),
+                    }
+                    .serialize(serializer)
+                }
+
+                pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+                    deserializer: D,
+                ) -> Result<Option<Quantity>, D::Error> {
+                    let stored = Stored::deserialize(deserializer)?;
+                    let unit = stored.unit.unwrap_or_else(|| $analyte.default_unit());
+                    Ok(stored
+                        .level
+                        .filter(|value| value.is_finite())
+                        .map(|value| Quantity::new(value, unit)))
+                }
+            }
+        };
+    }
+
+    stored_level!(
+        estradiol,
+        "estradiolLevel",
+        "estradiolUnit",
+        BloodAnalyte::Estradiol
+    );
+    stored_level!(
+        testosterone,
+        "testLevel",
+        "testUnit",
+        BloodAnalyte::Testosterone
+    );
+    stored_level!(
+        progesterone,
+        "progesteroneLevel",
+        "progesteroneUnit",
+        BloodAnalyte::Progesterone
+    );
+    stored_level!(fsh, "fshLevel", "fshUnit", BloodAnalyte::Fsh);
+    stored_level!(lh, "lhLevel", "lhUnit", BloodAnalyte::Lh);
+    stored_level!(
+        prolactin,
+        "prolactinLevel",
+        "prolactinUnit",
+        BloodAnalyte::Prolactin
+    );
+    stored_level!(shbg, "shbgLevel", "shbgUnit", BloodAnalyte::Shbg);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn symbols_roundtrip() {
+        for unit in HormoneUnits::ALL {
+            assert_eq!(HormoneUnits::from_symbol(unit.symbol()), Some(unit.clone()));
+            let json = serde_json::to_string(&unit).unwrap();
+            assert_eq!(json, format!("\"{}\"", unit.symbol()));
+        }
+        assert_eq!(HormoneUnits::from_symbol("invalid"), None);
+    }
+
+    #[test]
+    fn quantity_converts_with_molar_mass() {
+        let e2 = Quantity::new(100.0, HormoneUnits::E2PgMl);
+        let pmol = e2.to(Hormone::Estradiol, HormoneUnits::E2PmolL).unwrap();
+        assert!((pmol.value - 367.13).abs() < 0.01, "got {}", pmol.value);
+        let back = pmol.to(Hormone::Estradiol, HormoneUnits::E2PgMl).unwrap();
+        assert!((back.value - 100.0).abs() < 1e-9);
+
+        let t = Quantity::new(1.0, HormoneUnits::TNmolL);
+        let ng_dl = t
+            .value_in(Hormone::Testosterone, HormoneUnits::TNgDl)
+            .unwrap();
+        assert!((ng_dl - 28.843).abs() < 0.001, "got {ng_dl}");
+    }
+
+    #[test]
+    fn quantity_rejects_incompatible_units() {
+        let dose = Quantity::new(4.0, HormoneUnits::Mg);
+        assert!(dose.to(Hormone::Estradiol, HormoneUnits::E2PgMl).is_err());
+        assert_eq!(
+            dose.value_in(Hormone::Estradiol, HormoneUnits::Mcg),
+            Some(4000.0)
+        );
+        let fsh = Quantity::new(5.0, HormoneUnits::UL);
+        assert!(fsh
+            .to(Hormone::FollicleStimulatingHormone, HormoneUnits::NgMl)
+            .is_err());
+        assert_eq!(
+            fsh.value_in(Hormone::FollicleStimulatingHormone, HormoneUnits::MIuMl),
+            Some(5.0)
+        );
+    }
+
+    #[test]
+    fn measures_convert_without_failing() {
+        let dose = Measure::new(4.0, Mass::Mg).to(Mass::Mcg);
+        assert_eq!(dose.quantity(), Quantity::new(4000.0, HormoneUnits::Mcg));
+        let t = Measure::new(1.0, MolarConcentration::NmolL)
+            .to_mass(&Hormone::Testosterone, MassConcentration::NgDl);
+        assert!((t.value - 28.8431).abs() < 1e-9, "got {}", t.value);
+        let back = t.to_molar(&Hormone::Testosterone, MolarConcentration::NmolL);
+        assert!((back.value - 1.0).abs() < 1e-12);
+        assert_eq!(
+            Measure::new(5000.0, Activity::MIuL).to(Activity::UL).value,
+            5.0
+        );
+    }
+
+    #[test]
+    fn blood_test_levels_convert() {
+        let mut test = BloodTest {
+            estradiol: Some(Quantity::new(200.0, HormoneUnits::E2PgMl)),
+            testosterone: Some(Quantity::new(1.0, HormoneUnits::TNgDl)),
+            ..BloodTest::default()
+        };
+        assert_eq!(
+            test.level(BloodAnalyte::Estradiol),
+            Some(Quantity::new(200.0, HormoneUnits::E2PgMl))
+        );
+        assert_eq!(
+            test.level_in(BloodAnalyte::Testosterone, HormoneUnits::TNgDl),
+            Some(1.0)
+        );
+        assert_eq!(test.level(BloodAnalyte::Shbg), None);
+
+        test.set_level(
+            BloodAnalyte::Prolactin,
+            Some(Quantity::new(10.0, HormoneUnits::NgMl)),
+        );
+        let miu = test
+            .level_in(BloodAnalyte::Prolactin, HormoneUnits::MIuL)
+            .unwrap();
+        assert!((miu - 212.0).abs() < 1e-6);
+        test.set_level(BloodAnalyte::Prolactin, None);
+        assert_eq!(test.prolactin, None);
+    }
+
+    #[test]
+    fn blood_test_levels_keep_stored_format() {
+        let json = r#"{"date":0,"estradiolLevel":200.0,"testLevel":1.5,"testUnit":"nmol/L"}"#;
+        let test: BloodTest = serde_json::from_str(json).unwrap();
+        assert_eq!(
+            test.estradiol,
+            Some(Quantity::new(200.0, HormoneUnits::E2PgMl))
+        );
+        assert_eq!(
+            test.testosterone,
+            Some(Quantity::new(1.5, HormoneUnits::TNmolL))
+        );
+        assert_eq!(test.shbg, None);
+
+        let saved = serde_json::to_value(&test).unwrap();
+        assert_eq!(saved["estradiolLevel"], 200.0);
+        assert_eq!(saved["estradiolUnit"], "pg/mL");
+        assert_eq!(saved["testUnit"], "nmol/L");
+        assert!(saved.get("shbgLevel").is_none());
+        assert!(saved.get("estradiol").is_none());
+        let reread: BloodTest = serde_json::from_value(saved).unwrap();
+        assert_eq!(reread, test);
+    }
+
+    #[test]
+    fn analyte_supported_units() {
+        assert!(BloodAnalyte::Estradiol.supports(&HormoneUnits::E2PmolL));
+        assert!(!BloodAnalyte::Estradiol.supports(&HormoneUnits::MIuL));
+        assert!(BloodAnalyte::Prolactin.supports(&HormoneUnits::NgMl));
+        assert!(!BloodAnalyte::Lh.supports(&HormoneUnits::NgMl));
+        assert!(!BloodAnalyte::Shbg.supports(&HormoneUnits::Mg));
+    }
+}
@@ -0,0 +1,339 @@
+#![allow(non_snake_case)]
+
+use serde::{Deserialize, Serialize};
+
+use crate::quantity::{BloodAnalyte, Quantity};
+use crate::types::{BloodTest, HormoneUnits, HrtData};
+
+/// An inclusive range for one analyte. A missing bound is open.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReferenceRange {
+    pub analyte: BloodAnalyte,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub low: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub high: Option<f64>,
+    pub unit: HormoneUnits,
+}
+
+/// Normal ranges printed by one lab.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LabRanges {
+    pub lab: String,
+    #[serde(default)]
+    pub ranges: Vec<ReferenceRange>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum RangeFlag {
+    Low,
+    InRange,
+    High,
+}
+
+impl RangeFlag {
+    pub fn is_out_of_range(&self) -> bool {
+        *self != RangeFlag::InRange
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RangeCheck {
+    pub range: ReferenceRange,
+    pub flag: RangeFlag,
+}
+
+/// A blood test level checked against the HRT target and the lab's range.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LevelAssessment {
+    pub analyte: BloodAnalyte,
+    pub level: Quantity,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target: Option<RangeCheck>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reference: Option<RangeCheck>,
+}
+
+impl LevelAssessment {
+    pub fn is_flagged(&self) -> bool {
+        self.target
+            .iter()
+            .chain(self.reference.iter())
+            .any(|check| check.flag.is_out_of_range())
+    }
+}
+
+impl ReferenceRange {
+    pub fn new(
+        analyte: BloodAnalyte,
+        low: Option<f64>,
+        high: Option<f64>,
+        unit: HormoneUnits,
+    ) -> Self {
+        Self {
+            analyte,
+            low,
+            high,
+            unit,
+        }
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.low.is_none() && self.high.is_none()
+    }
+
+    /// Flags `level` after converting it to the range's unit. `None` when the
+    /// range has no bounds or the units cannot be converted.
+    pub fn flag(&self, level: &Quantity) -> Option<RangeFlag> {
+        if self.is_open() {
+            return None;
+        }
+        let value = level.value_in(self.analyte.hormone(), self.unit.clone())?;
+        if self.low.is_some_and(|low| value < low) {
+            Some(RangeFlag::Low)
+        } else if self.high.is_some_and(|high| value > high) {
+            Some(RangeFlag::High)
+        } else {
+            Some(RangeFlag::InRange)
+        }
+    }
+
+    fn check(&self, level: &Quantity) -> Option<RangeCheck> {
+        self.flag(level).map(|flag| RangeCheck {
+            range: self.clone(),
+            flag,
+        })
+    }
+}
+
+/// Feminizing HRT targets from the Endocrine Society guideline (E2 100–200
+/// pg/mL, T below 50 ng/dL) and the usual upper limit for prolactin.
+pub fn default_target_ranges() -> Vec<ReferenceRange> {
+    vec![
+        ReferenceRange::new(
+            BloodAnalyte::Estradiol,
+            Some(100.0),
+            Some(200.0),
+            HormoneUnits::E2PgMl,
+        ),
+        ReferenceRange::new(
+            BloodAnalyte::Testosterone,
+            None,
+            Some(50.0),
+            HormoneUnits::TNgDl,
+        ),
+        ReferenceRange::new(
+            BloodAnalyte::Prolactin,
+            None,
+            Some(25.0),
+            HormoneUnits::NgMl,
+        ),
+    ]
+}
+
+/// The user's target for `analyte`, falling back to the built-in default. An
+/// override without bounds turns the target off.
+pub fn target_range(data: &HrtData, analyte: BloodAnalyte) -> Option<ReferenceRange> {
+    let range = data
+        .targetRanges
+        .iter()
+        .find(|range| range.analyte == analyte)
+        .cloned()
+        .or_else(|| {
+            default_target_ranges()
+                .into_iter()
+                .find(|range| range.analyte == analyte)
+        })?;
+    (!range.is_open()).then_some(range)
+}
+
+pub fn lab_range(data: &HrtData, lab: &str, analyte: BloodAnalyte) -> Option<ReferenceRange> {
+    let lab = lab.trim();
+    data.labRanges
+        .iter()
+        .find(|entry| entry.lab.trim().eq_ignore_ascii_case(lab))?
+        .ranges
+        .iter()
+        .find(|range| range.analyte == analyte && !range.is_open())
+        .cloned()
+}
+
+pub fn assess_level(
+    data: &HrtData,
+    test: &BloodTest,
+    analyte: BloodAnalyte,
+) -> Option<LevelAssessment> {
+    let level = test.level(analyte)?;
+    let target = target_range(data, analyte).and_then(|range| range.check(&level));
+    let reference = test
+        .lab
+        .as_deref()
+        .and_then(|lab| lab_range(data, lab, analyte))
+        .and_then(|range| range.check(&level));
+    Some(LevelAssessment {
+        analyte,
+        level,
+        target,
+        reference,
+    })
+}
+
+/// Every recorded level of `test`, in `BloodAnalyte::ALL` order.
+pub fn assess_blood_test(data: &HrtData, test: &BloodTest) -> Vec<LevelAssessment> {
+    BloodAnalyte::ALL
+        .iter()
+        .filter_map(|analyte| assess_level(data, test, *analyte))
+        .collect()
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RangeSummary {
+    pub analyte: BloodAnalyte,
+    pub tests: usize,
+    pub low: usize,
+    pub inRange: usize,
+    pub high: usize,
+    /// Levels outside the lab's own range.
+    pub outsideReference: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub latest: Option<LevelAssessment>,
+}
+
+/// Per-analyte counts of target flags across all blood tests.
+pub fn range_summary(data: &HrtData) -> Vec<RangeSummary> {
+    let mut tests: Vec<&BloodTest> = data.bloodTests.iter().collect();
+    tests.sort_by_key(|test| test.date);
+    BloodAnalyte::ALL
+        .iter()
+        .filter_map(|analyte| {
+            let assessments: Vec<LevelAssessment> = tests
+                .iter()
+                .filter_map(|test| assess_level(data, test, *analyte))
+                .collect();
+            if assessments.is_empty() {
+                return None;
+            }
+            let count = |flag: RangeFlag| {
+                assessments
+                    .iter()
+                    .filter(|a| a.target.as_ref().is_some_and(|check| check.flag == flag))
+                    .count()
+            };
+            Some(RangeSummary {
+                analyte: *analyte,
+                tests: assessments.len(),
+                low: count(RangeFlag::Low),
+                inRange: count(RangeFlag::InRange),
+                high: count(RangeFlag::High),
+                outsideReference: assessments
+                    .iter()
+                    .filter(|a| {
+                        a.reference
+                            .as_ref()
+                            .is_some_and(|check| check.flag.is_out_of_range())
+                    })
+                    .count(),
+                latest: assessments.last().cloned(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_at(date: i64, e2_pg_ml: f64) -> BloodTest {
+        BloodTest {
+            date,
+            estradiol: Some(Quantity::new(e2_pg_ml, HormoneUnits::E2PgMl)),
+            ..BloodTest::default()
+        }
+    }
+
+    #[test]
+    fn default_targets_flag_e2_and_t() {
+        let data = HrtData::default();
+        let test = BloodTest {
+            testosterone: Some(Quantity::new(80.0, HormoneUnits::TNgDl)),
+            ..test_at(0, 180.0)
+        };
+        let e2 = assess_level(&data, &test, BloodAnalyte::Estradiol).unwrap();
+        assert_eq!(e2.target.unwrap().flag, RangeFlag::InRange);
+        let t = assess_level(&data, &test, BloodAnalyte::Testosterone).unwrap();
+        assert_eq!(t.target.as_ref().unwrap().flag, RangeFlag::High);
+        assert!(t.is_flagged());
+        assert!(t.reference.is_none());
+    }
+
+    #[test]
+    fn ranges_convert_units() {
+        let data = HrtData::default();
+        // 300 pmol/L is about 82 pg/mL.
+        let test = BloodTest {
+            estradiol: Some(Quantity::new(300.0, HormoneUnits::E2PmolL)),
+            testosterone: Some(Quantity::new(1.0, HormoneUnits::TNmolL)),
+            ..BloodTest::default()
+        };
+        let e2 = assess_level(&data, &test, BloodAnalyte::Estradiol).unwrap();
+        assert_eq!(e2.target.unwrap().flag, RangeFlag::Low);
+        let t = assess_level(&data, &test, BloodAnalyte::Testosterone).unwrap();
+        assert_eq!(t.target.unwrap().flag, RangeFlag::InRange);
+    }
+
+    #[test]
+    fn user_overrides_and_lab_ranges() {
+        let mut data = HrtData {
+            targetRanges: vec![
+                ReferenceRange::new(
+                    BloodAnalyte::Estradiol,
+                    Some(200.0),
+                    Some(300.0),
+                    HormoneUnits::E2PgMl,
+                ),
+                ReferenceRange::new(BloodAnalyte::Testosterone, None, None, HormoneUnits::TNgDl),
+            ],
+            labRanges: vec![LabRanges {
+                lab: "Quest".to_string(),
+                ranges: vec![ReferenceRange::new(
+                    BloodAnalyte::Estradiol,
+                    Some(15.0),
+                    Some(350.0),
+                    HormoneUnits::E2PgMl,
+                )],
+            }],
+            ..HrtData::default()
+        };
+        let test = BloodTest {
+            lab: Some("quest ".to_string()),
+            testosterone: Some(Quantity::new(80.0, HormoneUnits::TNgDl)),
+            ..test_at(0, 180.0)
+        };
+        let e2 = assess_level(&data, &test, BloodAnalyte::Estradiol).unwrap();
+        assert_eq!(e2.target.unwrap().flag, RangeFlag::Low);
+        assert_eq!(e2.reference.unwrap().flag, RangeFlag::InRange);
+        // An open override turns the default T target off.
+        let t = assess_level(&data, &test, BloodAnalyte::Testosterone).unwrap();
+        assert!(t.target.is_none());
+        assert!(!t.is_flagged());
+
+        data.labRanges.clear();
+        let e2 = assess_level(&data, &test, BloodAnalyte::Estradiol).unwrap();
+        assert!(e2.reference.is_none());
+    }
+
+    #[test]
+    fn summary_counts_flags() {
+        let data = HrtData {
+            bloodTests: vec![test_at(2, 250.0), test_at(0, 50.0), test_at(1, 150.0)],
+            ..HrtData::default()
+        };
+        let summary = range_summary(&data);
+        assert_eq!(summary.len(), 1);
+        let e2 = &summary[0];
+        assert_eq!((e2.tests, e2.low, e2.inRange, e2.high), (3, 1, 1, 1));
+        assert_eq!(e2.latest.as_ref().unwrap().level.value, 250.0);
+        assert_eq!(e2.outsideReference, 0);
+    }
+}
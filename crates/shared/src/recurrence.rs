@@ -0,0 +1,393 @@
+use chrono::{Datelike, Duration, Local, NaiveDate, TimeZone, Timelike};
+use chrono_tz::Tz;
+
+use crate::types::{Recurrence, RecurrenceCycle, UnixTime};
+
+pub const WEEKDAY_LABELS: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+
+const MINUTES_PER_DAY: u32 = 24 * 60;
+/// How far ahead `next_after` looks before giving up on a rule that never fires.
+const MAX_SCAN_DAYS: i64 = 400;
+
+/// The zone a rule reads its days and times in.
+#[derive(Debug, Clone, Copy)]
+enum RuleZone {
+    Local,
+    Named(Tz),
+}
+
+impl RuleZone {
+    fn date(self, ms: UnixTime) -> NaiveDate {
+        match self {
+            RuleZone::Local => date_in(&Local, ms),
+            RuleZone::Named(tz) => date_in(&tz, ms),
+        }
+    }
+
+    fn minute_of_day(self, ms: UnixTime) -> u32 {
+        match self {
+            RuleZone::Local => minute_in(&Local, ms),
+            RuleZone::Named(tz) => minute_in(&tz, ms),
+        }
+    }
+
+    fn time(self, day: NaiveDate, minute: u32) -> Option<UnixTime> {
+        match self {
+            RuleZone::Local => time_in(&Local, day, minute),
+            RuleZone::Named(tz) => time_in(&tz, day, minute),
+        }
+    }
+}
+
+impl RecurrenceCycle {
+    fn is_on(&self, day: NaiveDate, zone: RuleZone) -> bool {
+        let period = self.onDays as i64 + self.offDays as i64;
+        if period == 0 {
+            return true;
+        }
+        let start = zone.date(self.startDate);
+        (day - start).num_days().rem_euclid(period) < self.onDays as i64
+    }
+}
+
+impl Recurrence {
+    fn zone(&self) -> RuleZone {
+        self.timeZone
+            .as_deref()
+            .and_then(|name| name.parse::<Tz>().ok())
+            .map_or(RuleZone::Local, RuleZone::Named)
+    }
+
+    pub fn is_dose_day(&self, day: NaiveDate) -> bool {
+        let weekday = day.weekday().num_days_from_monday() as u8;
+        (self.weekdays.is_empty() || self.weekdays.contains(&weekday))
+            && (self.monthDays.is_empty() || self.monthDays.contains(&(day.day() as u8)))
+            && self
+                .cycle
+                .as_ref()
+                .is_none_or(|cycle| cycle.is_on(day, self.zone()))
+    }
+
+    /// Minutes after midnight of `ms` in the rule's zone.
+    pub fn minute_of_day(&self, ms: UnixTime) -> u32 {
+        self.zone().minute_of_day(ms)
+    }
+
+    /// Sorted times of day, falling back to `default_minute` when none are set.
+    fn minutes(&self, default_minute: u32) -> Vec<u32> {
+        let mut minutes: Vec<u32> = self
+            .timesOfDay
+            .iter()
+            .copied()
+            .filter(|minute| *minute < MINUTES_PER_DAY)
+            .collect();
+        if minutes.is_empty() {
+            minutes.push(default_minute.min(MINUTES_PER_DAY - 1));
+        }
+        minutes.sort_unstable();
+        minutes.dedup();
+        minutes
+    }
+
+    /// Dose times from `from` to `until`, both inclusive.
+    pub fn occurrences(
+        &self,
+        default_minute: u32,
+        from: UnixTime,
+        until: UnixTime,
+    ) -> Vec<UnixTime> {
+        let mut times = Vec::new();
+        if until < from {
+            return times;
+        }
+        let zone = self.zone();
+        let minutes = self.minutes(default_minute);
+        let last_day = zone.date(until);
+        let mut day = zone.date(from);
+        while day <= last_day {
+            if self.is_dose_day(day) {
+                times.extend(
+                    minutes
+                        .iter()
+                        .filter_map(|minute| zone.time(day, *minute))
+                        .filter(|t| (from..=until).contains(t)),
+                );
+            }
+            day += Duration::days(1);
+        }
+        times
+    }
+
+    /// First dose time at or after `from`.
+    pub fn next_after(&self, default_minute: u32, from: UnixTime) -> Option<UnixTime> {
+        let zone = self.zone();
+        let minutes = self.minutes(default_minute);
+        let first_day = zone.date(from);
+        (0..MAX_SCAN_DAYS)
+            .map(|offset| first_day + Duration::days(offset))
+            .filter(|day| self.is_dose_day(*day))
+            .flat_map(|day| {
+                minutes
+                    .iter()
+                    .filter_map(move |minute| zone.time(day, *minute))
+            })
+            .find(|t| *t >= from)
+    }
+
+    /// Average days between doses over a year, for code that only
+    /// understands a fixed interval. `None` if the rule never fires.
+    pub fn mean_interval_days(&self) -> Option<f64> {
+        let first_day = self
+            .cycle
+            .as_ref()
+            .map(|cycle| self.zone().date(cycle.startDate))
+            .unwrap_or_else(|| NaiveDate::from_ymd_opt(2024, 1, 1).unwrap());
+        let dose_days = (0..365)
+            .filter(|offset| self.is_dose_day(first_day + Duration::days(*offset)))
+            .count();
+        let doses = dose_days * self.minutes(0).len();
+        (doses > 0).then(|| 365.0 / doses as f64)
+    }
+
+    pub fn describe(&self) -> String {
+        let mut parts = Vec::new();
+        if !self.weekdays.is_empty() {
+            let mut weekdays = self.weekdays.clone();
+            weekdays.sort_unstable();
+            weekdays.dedup();
+            parts.push(
+                weekdays
+                    .iter()
+                    .filter_map(|day| WEEKDAY_LABELS.get(*day as usize).copied())
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            );
+        }
+        if !self.monthDays.is_empty() {
+            parts.push(format!(
+                "days {} of the month",
+                format_day_list(&self.monthDays)
+            ));
+        }
+        if let Some(cycle) = &self.cycle {
+            parts.push(format!("{} days on, {} off", cycle.onDays, cycle.offDays));
+        }
+        if parts.is_empty() {
+            parts.push("daily".to_string());
+        }
+        if !self.timesOfDay.is_empty() {
+            let times: Vec<String> = self
+                .minutes(0)
+                .into_iter()
+                .map(format_time_of_day)
+                .collect();
+            parts.push(format!("at {}", times.join(", ")));
+        }
+        parts.join("; ")
+    }
+}
+
+pub fn local_date(ms: UnixTime) -> NaiveDate {
+    date_in(&Local, ms)
+}
+
+pub fn minute_of_day(ms: UnixTime) -> u32 {
+    minute_in(&Local, ms)
+}
+
+fn date_in<Z: TimeZone>(zone: &Z, ms: UnixTime) -> NaiveDate {
+    zone.timestamp_millis_opt(ms)
+        .single()
+        .map(|dt| dt.date_naive())
+        .unwrap_or_default()
+}
+
+fn minute_in<Z: TimeZone>(zone: &Z, ms: UnixTime) -> u32 {
+    zone.timestamp_millis_opt(ms)
+        .single()
+        .map(|dt| dt.hour() * 60 + dt.minute())
+        .unwrap_or(0)
+}
+
+/// Time `minute` minutes into `day` in `zone`; `None` inside a DST gap.
+fn time_in<Z: TimeZone>(zone: &Z, day: NaiveDate, minute: u32) -> Option<UnixTime> {
+    let naive = day.and_hms_opt(minute / 60, minute % 60, 0)?;
+    zone.from_local_datetime(&naive)
+        .earliest()
+        .map(|dt| dt.timestamp_millis())
+}
+
+pub fn format_time_of_day(minute: u32) -> String {
+    format!("{:02}:{:02}", minute / 60, minute % 60)
+}
+
+/// Parses "08:00, 20:30" into minutes after midnight.
+pub fn parse_times_of_day(input: &str) -> Option<Vec<u32>> {
+    input
+        .split([',', ' '])
+        .map(str::trim)
+        .filter(|part| !part.is_empty())
+        .map(|part| {
+            let (hours, minutes) = part.split_once(':').unwrap_or((part, "0"));
+            let hours: u32 = hours.trim().parse().ok()?;
+            let minutes: u32 = minutes.trim().parse().ok()?;
+            (hours < 24 && minutes < 60).then_some(hours * 60 + minutes)
+        })
+        .collect()
+}
+
+/// Parses "1-14, 20" into the listed days, each between 1 and `max`.
+pub fn parse_day_list(input: &str, max: u8) -> Option<Vec<u8>> {
+    let mut days = Vec::new();
+    for part in input
+        .split(',')
+        .map(str::trim)
+        .filter(|part| !part.is_empty())
+    {
+        let (first, last) = match part.split_once('-') {
+            Some((first, last)) => (first.trim().parse().ok()?, last.trim().parse().ok()?),
+            None => {
+                let day: u8 = part.parse().ok()?;
+                (day, day)
+            }
+        };
+        if first == 0 || last > max || first > last {
+            return None;
+        }
+        days.extend(first..=last);
+    }
+    days.sort_unstable();
+    days.dedup();
+    Some(days)
+}
+
+/// Formats days as `parse_day_list` reads them, joining runs into ranges.
+pub fn format_day_list(days: &[u8]) -> String {
+    let mut days = days.to_vec();
+    days.sort_unstable();
+    days.dedup();
+    let mut parts = Vec::new();
+    let mut iter = days.into_iter().peekable();
+    while let Some(first) = iter.next() {
+        let mut last = first;
+        while iter.peek() == Some(&(last + 1)) {
+            last += 1;
+            iter.next();
+        }
+        parts.push(if first == last {
+            first.to_string()
+        } else {
+            format!("{first}-{last}")
+        });
+    }
+    parts.join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(day: NaiveDate, minute: u32) -> UnixTime {
+        time_in(&Local, day, minute).unwrap()
+    }
+
+    #[test]
+    fn twice_daily_times_are_returned_in_order() {
+        let rule = Recurrence {
+            timesOfDay: vec![20 * 60, 8 * 60],
+            ..Default::default()
+        };
+        let day = NaiveDate::from_ymd_opt(2025, 3, 10).unwrap();
+        let times = rule.occurrences(0, at(day, 0), at(day, 23 * 60));
+        assert_eq!(times, vec![at(day, 8 * 60), at(day, 20 * 60)]);
+        assert_eq!(rule.mean_interval_days(), Some(0.5));
+    }
+
+    #[test]
+    fn weekly_rule_skips_to_the_chosen_weekday() {
+        // 2025-03-10 is a Monday.
+        let monday = NaiveDate::from_ymd_opt(2025, 3, 10).unwrap();
+        let sunday = NaiveDate::from_ymd_opt(2025, 3, 16).unwrap();
+        let rule = Recurrence {
+            weekdays: vec![6],
+            ..Default::default()
+        };
+        assert_eq!(
+            rule.next_after(9 * 60, at(monday, 0)),
+            Some(at(sunday, 9 * 60))
+        );
+        assert_eq!(rule.describe(), "Sun");
+    }
+
+    #[test]
+    fn month_days_and_cycles_limit_dose_days() {
+        let nights = Recurrence {
+            monthDays: parse_day_list("1-14", 31).unwrap(),
+            timesOfDay: vec![22 * 60],
+            ..Default::default()
+        };
+        assert!(nights.is_dose_day(NaiveDate::from_ymd_opt(2025, 4, 14).unwrap()));
+        assert!(!nights.is_dose_day(NaiveDate::from_ymd_opt(2025, 4, 15).unwrap()));
+        assert_eq!(nights.describe(), "days 1-14 of the month; at 22:00");
+
+        let start = NaiveDate::from_ymd_opt(2025, 1, 1).unwrap();
+        let cyclic = Recurrence {
+            cycle: Some(RecurrenceCycle {
+                onDays: 21,
+                offDays: 7,
+                startDate: at(start, 12 * 60),
+            }),
+            ..Default::default()
+        };
+        assert!(cyclic.is_dose_day(start + Duration::days(20)));
+        assert!(!cyclic.is_dose_day(start + Duration::days(21)));
+        assert!(cyclic.is_dose_day(start + Duration::days(28)));
+    }
+
+    #[test]
+    fn parses_and_formats_lists() {
+        assert_eq!(parse_times_of_day("08:00, 20:30"), Some(vec![480, 1230]));
+        assert_eq!(parse_times_of_day("25:00"), None);
+        assert_eq!(parse_day_list("3, 1-2", 31), Some(vec![1, 2, 3]));
+        assert_eq!(parse_day_list("0-4", 31), None);
+        assert_eq!(format_day_list(&[5, 1, 2, 3]), "1-3, 5");
+    }
+
+    #[test]
+    fn rules_follow_their_time_zone() {
+        use chrono::Utc;
+
+        let utc = |y, m, d, h, min| {
+            Utc.with_ymd_and_hms(y, m, d, h, min, 0)
+                .unwrap()
+                .timestamp_millis()
+        };
+        let morning = Recurrence {
+            timesOfDay: vec![8 * 60],
+            timeZone: Some("America/New_York".to_string()),
+            ..Default::default()
+        };
+        // EST before the March 9 switch, EDT after.
+        assert_eq!(
+            morning.next_after(0, utc(2025, 3, 7, 0, 0)),
+            Some(utc(2025, 3, 7, 13, 0))
+        );
+        assert_eq!(
+            morning.next_after(0, utc(2025, 3, 10, 0, 0)),
+            Some(utc(2025, 3, 10, 12, 0))
+        );
+        assert_eq!(morning.minute_of_day(utc(2025, 3, 10, 12, 0)), 8 * 60);
+
+        // 01:00 on a Sunday in Tokyo is still Saturday in UTC.
+        let sunday = Recurrence {
+            weekdays: vec![6],
+            timesOfDay: vec![60],
+            timeZone: Some("Asia/Tokyo".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            sunday.occurrences(0, utc(2025, 3, 10, 0, 0), utc(2025, 3, 17, 0, 0)),
+            vec![utc(2025, 3, 15, 16, 0)]
+        );
+    }
+}
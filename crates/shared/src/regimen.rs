@@ -0,0 +1,393 @@
+#![allow(non_snake_case)]
+
+use serde::{Deserialize, Serialize};
+
+use crate::estrannaise::{e2_multidose_3c, e2_steady_state, injectable_schedule_model};
+use crate::quantity::{MassConcentration, Measure, MolarConcentration};
+use crate::schedules::current_injectable;
+use crate::types::{EstrannaiseModel, Hormone, HormoneUnits, HrtData};
+
+// Long enough for the slowest esters (undecylate) to reach steady state.
+const STEADY_STATE_MIN_DAYS: f64 = 365.0;
+const STEADY_STATE_MIN_CYCLES: f64 = 20.0;
+const SAMPLES_PER_CYCLE: usize = 288;
+const DEFAULT_DOSE_STEP: f64 = 0.05;
+const DEFAULT_MAX_OPTIONS: usize = 10;
+pub const MIN_INTERVAL_DAYS: f64 = 0.25;
+pub const MAX_INTERVAL_DAYS: f64 = 90.0;
+pub const MAX_INTERVALS: usize = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SteadyState {
+    pub trough: f64,
+    pub peak: f64,
+    pub average: f64,
+    /// Days from a dose to the peak.
+    pub timeToPeak: f64,
+    /// Peak-trough fluctuation relative to the average.
+    pub fluctuation: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RegimenRequest {
+    pub model: EstrannaiseModel,
+    pub troughMin: f64,
+    pub troughMax: f64,
+    pub peakMin: f64,
+    pub peakMax: f64,
+    /// Unit of the targets and of the returned levels; pg/mL when omitted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unit: Option<HormoneUnits>,
+    /// Allowed dosing intervals in days.
+    pub intervals: Vec<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fudgeFactor: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub doseStep: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub maxOptions: Option<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RegimenOption {
+    pub dose: f64,
+    pub frequency: f64,
+    pub trough: f64,
+    pub peak: f64,
+    pub average: f64,
+    pub withinTarget: bool,
+    /// Normalized distance from the middle of both target ranges; lower is better.
+    pub score: f64,
+}
+
+/// Multiplier from pg/mL to `unit` for estradiol, or `None` when `unit` is
+/// not an estradiol concentration.
+pub fn e2_unit_factor(unit: &HormoneUnits) -> Option<f64> {
+    let pg_ml = Measure::new(1.0, MassConcentration::PgMl);
+    match unit {
+        HormoneUnits::E2PgMl => Some(1.0),
+        HormoneUnits::E2PmolL => Some(
+            pg_ml
+                .to_molar(&Hormone::Estradiol, MolarConcentration::PmolL)
+                .value,
+        ),
+        _ => None,
+    }
+}
+
+/// Trough, peak and time-averaged level once a repeated dose has settled.
+/// Uses the closed form where the model has one and simulates otherwise.
+/// Intervals outside `MIN_INTERVAL_DAYS..=MAX_INTERVAL_DAYS` give `None`.
+pub fn steady_state_levels(
+    model: &EstrannaiseModel,
+    dose: f64,
+    interval_days: f64,
+    conversion_factor: f64,
+) -> Option<SteadyState> {
+    if !((MIN_INTERVAL_DAYS..=MAX_INTERVAL_DAYS).contains(&interval_days) && dose.is_finite()) {
+        return None;
+    }
+    let offsets: Vec<f64> = (0..SAMPLES_PER_CYCLE)
+        .map(|i| interval_days * i as f64 / SAMPLES_PER_CYCLE as f64)
+        .collect();
+    let samples: Vec<f64> = if e2_steady_state(0.0, dose, interval_days, model, conversion_factor)
+        .is_some()
+    {
+        offsets
+            .iter()
+            .map(|t| {
+                e2_steady_state(*t, dose, interval_days, model, conversion_factor).unwrap_or(0.0)
+            })
+            .collect()
+    } else {
+        let cycles = (STEADY_STATE_MIN_DAYS / interval_days)
+            .max(STEADY_STATE_MIN_CYCLES)
+            .ceil() as usize;
+        let times: Vec<f64> = (0..cycles).map(|i| i as f64 * interval_days).collect();
+        let doses = vec![dose; cycles];
+        let models = vec![model.clone(); cycles];
+        let last = times[cycles - 1];
+        offsets
+            .iter()
+            .map(|t| e2_multidose_3c(last + t, &doses, &times, &models, conversion_factor, false))
+            .collect()
+    };
+
+    let trough = samples.iter().cloned().fold(f64::INFINITY, f64::min);
+    let (peak_idx, peak) =
+        samples
+            .iter()
+            .cloned()
+            .enumerate()
+            .fold((0, f64::NEG_INFINITY), |best, (idx, value)| {
+                if value > best.1 {
+                    (idx, value)
+                } else {
+                    best
+                }
+            });
+    let average = samples.iter().sum::<f64>() / samples.len() as f64;
+    let fluctuation = if average > 0.0 {
+        (peak - trough) / average
+    } else {
+        0.0
+    };
+    Some(SteadyState {
+        trough,
+        peak,
+        average,
+        timeToPeak: offsets[peak_idx],
+        fluctuation,
+    })
+}
+
+pub fn injectable_steady_state(data: &HrtData, conversion_factor: f64) -> Option<SteadyState> {
+    let schedule = current_injectable(data)?;
+    let model = injectable_schedule_model(data, schedule)?;
+    steady_state_levels(&model, schedule.dose, schedule.frequency, conversion_factor)
+}
+
+fn range_score(value: f64, min: f64, max: f64) -> f64 {
+    let center = (min + max) / 2.0;
+    let half = ((max - min) / 2.0).max(f64::EPSILON);
+    ((value - center) / half).powi(2)
+}
+
+/// Ranks dose/interval pairs by how well their steady state hits the target
+/// trough and peak ranges. Levels scale linearly with dose, so each interval
+/// only needs one steady-state simulation.
+pub fn optimize_regimen(request: &RegimenRequest) -> Result<Vec<RegimenOption>, String> {
+    if !(request.troughMin <= request.troughMax && request.peakMin <= request.peakMax) {
+        return Err("Target minimums must not exceed maximums".to_string());
+    }
+    if request.troughMax <= 0.0 || request.peakMax <= 0.0 {
+        return Err("Targets must be positive".to_string());
+    }
+    if request.intervals.is_empty() || request.intervals.len() > MAX_INTERVALS {
+        return Err(format!("Give between 1 and {MAX_INTERVALS} intervals"));
+    }
+    if let Some(interval) = request
+        .intervals
+        .iter()
+        .find(|interval| !(MIN_INTERVAL_DAYS..=MAX_INTERVAL_DAYS).contains(*interval))
+    {
+        return Err(format!(
+            "Interval {interval} is outside {MIN_INTERVAL_DAYS}-{MAX_INTERVAL_DAYS} days"
+        ));
+    }
+    let step = request
+        .doseStep
+        .filter(|step| step.is_finite() && *step > 0.0)
+        .unwrap_or(DEFAULT_DOSE_STEP);
+    let fudge = request
+        .fudgeFactor
+        .filter(|value| value.is_finite() && *value > 0.0)
+        .unwrap_or(1.0);
+    let unit = request.unit.as_ref().unwrap_or(&HormoneUnits::E2PgMl);
+    let factor = fudge
+        * e2_unit_factor(unit)
+            .ok_or_else(|| format!("{} is not an estradiol unit", unit.symbol()))?;
+
+    let mut options = Vec::new();
+    for &interval in &request.intervals {
+        let Some(unit) = steady_state_levels(&request.model, 1.0, interval, factor) else {
+            continue;
+        };
+        if unit.trough <= 0.0 || unit.peak <= 0.0 {
+            continue;
+        }
+        // Weighted least squares toward both range centers, in dose space.
+        let tc = (request.troughMin + request.troughMax) / 2.0;
+        let pc = (request.peakMin + request.peakMax) / 2.0;
+        let tw = 1.0
+            / ((request.troughMax - request.troughMin) / 2.0)
+                .max(f64::EPSILON)
+                .powi(2);
+        let pw = 1.0
+            / ((request.peakMax - request.peakMin) / 2.0)
+                .max(f64::EPSILON)
+                .powi(2);
+        let ideal = (tw * tc * unit.trough + pw * pc * unit.peak)
+            / (tw * unit.trough.powi(2) + pw * unit.peak.powi(2));
+        let dose = ((ideal / step).round() * step).max(step);
+
+        let trough = unit.trough * dose;
+        let peak = unit.peak * dose;
+        let withinTarget = (request.troughMin..=request.troughMax).contains(&trough)
+            && (request.peakMin..=request.peakMax).contains(&peak);
+        options.push(RegimenOption {
+            dose: (dose * 1000.0).round() / 1000.0,
+            frequency: interval,
+            trough,
+            peak,
+            average: unit.average * dose,
+            withinTarget,
+            score: range_score(trough, request.troughMin, request.troughMax)
+                + range_score(peak, request.peakMin, request.peakMax),
+        });
+    }
+
+    options.sort_by(|a, b| {
+        b.withinTarget
+            .cmp(&a.withinTarget)
+            .then(a.score.total_cmp(&b.score))
+    });
+    options.truncate(request.maxOptions.unwrap_or(DEFAULT_MAX_OPTIONS));
+    Ok(options)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::InjectableSchedule;
+
+    fn request(intervals: Vec<f64>) -> RegimenRequest {
+        RegimenRequest {
+            model: EstrannaiseModel::EvIm,
+            troughMin: 100.0,
+            troughMax: 200.0,
+            peakMin: 150.0,
+            peakMax: 400.0,
+            unit: None,
+            intervals,
+            fudgeFactor: None,
+            doseStep: None,
+            maxOptions: None,
+        }
+    }
+
+    #[test]
+    fn steady_state_scales_with_dose() {
+        let one = steady_state_levels(&EstrannaiseModel::EvIm, 1.0, 7.0, 1.0).unwrap();
+        let four = steady_state_levels(&EstrannaiseModel::EvIm, 4.0, 7.0, 1.0).unwrap();
+        assert!(one.trough < one.average && one.average < one.peak);
+        assert!((four.average - one.average * 4.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn closed_form_matches_simulation() {
+        // Patches have no closed form, so compare a 3C model against superposition.
+        let model = EstrannaiseModel::EvIm;
+        let state = steady_state_levels(&model, 4.0, 7.0, 1.0).unwrap();
+        let times: Vec<f64> = (0..60).map(|i| i as f64 * 7.0).collect();
+        let sim =
+            |t: f64| e2_multidose_3c(t, &[4.0; 60], &times, &vec![model.clone(); 60], 1.0, false);
+        let last = times[59];
+        assert!((state.trough - sim(last)).abs() / state.trough < 0.01);
+        let peak_sim = sim(last + state.timeToPeak);
+        assert!((state.peak - peak_sim).abs() / state.peak < 0.01);
+        assert!(
+            state.timeToPeak > 0.5 && state.timeToPeak < 4.0,
+            "tmax {}",
+            state.timeToPeak
+        );
+        let expected = (state.peak - state.trough) / state.average;
+        assert!((state.fluctuation - expected).abs() < 1e-9);
+    }
+
+    #[test]
+    fn patch_steady_state_uses_simulation() {
+        let state = steady_state_levels(&EstrannaiseModel::PatchOw, 100.0, 7.0, 1.0).unwrap();
+        assert!(state.peak > state.trough && state.trough >= 0.0);
+    }
+
+    #[test]
+    fn injectable_steady_state_from_schedule() {
+        let schedule = InjectableSchedule {
+            kind: crate::types::InjectableEstradiols::Cypionate,
+            dose: 5.0,
+            unit: HormoneUnits::Mg,
+            frequency: 7.0,
+            vialId: None,
+            subVialId: None,
+            syringeKind: None,
+            needleLength: None,
+            needleGauge: None,
+            nextDoseDate: None,
+        };
+        let mut data = HrtData::default();
+        crate::schedules::add_regimen(
+            &mut data,
+            crate::types::RegimenSchedule::InjectableEstradiol(schedule),
+            0,
+        );
+        let state = injectable_steady_state(&data, 1.0).unwrap();
+        let direct = steady_state_levels(&EstrannaiseModel::EcIm, 5.0, 7.0, 1.0).unwrap();
+        assert_eq!(state, direct);
+    }
+
+    #[test]
+    fn steady_state_rejects_bad_interval() {
+        assert!(steady_state_levels(&EstrannaiseModel::EvIm, 4.0, 0.0, 1.0).is_none());
+        assert!(steady_state_levels(&EstrannaiseModel::PatchOw, 100.0, 1e-9, 1.0).is_none());
+        assert!(steady_state_levels(&EstrannaiseModel::EvIm, 4.0, f64::NAN, 1.0).is_none());
+    }
+
+    #[test]
+    fn optimizer_rejects_bad_intervals() {
+        assert!(optimize_regimen(&request(vec![7.0, 1e-9])).is_err());
+        assert!(optimize_regimen(&request(vec![365.0])).is_err());
+        assert!(optimize_regimen(&request(vec![f64::NAN])).is_err());
+        assert!(optimize_regimen(&request(vec![])).is_err());
+        assert!(optimize_regimen(&request(vec![7.0; MAX_INTERVALS + 1])).is_err());
+    }
+
+    #[test]
+    fn optimizer_prefers_options_within_target() {
+        let options = optimize_regimen(&request(vec![3.5, 7.0, 14.0])).unwrap();
+        assert_eq!(options.len(), 3);
+        assert!(options[0].withinTarget);
+        // EV every 14 days swings too far to fit both ranges.
+        let fortnightly = options.iter().find(|o| o.frequency == 14.0).unwrap();
+        assert!(!fortnightly.withinTarget);
+        assert!(options
+            .windows(2)
+            .all(|w| w[0].withinTarget >= w[1].withinTarget));
+        let check = steady_state_levels(
+            &EstrannaiseModel::EvIm,
+            options[0].dose,
+            options[0].frequency,
+            1.0,
+        )
+        .unwrap();
+        assert!((check.trough - options[0].trough).abs() < 0.01);
+    }
+
+    #[test]
+    fn optimizer_accounts_for_fudge_and_unit() {
+        let base = optimize_regimen(&request(vec![7.0])).unwrap();
+        let mut req = request(vec![7.0]);
+        req.fudgeFactor = Some(2.0);
+        let fudged = optimize_regimen(&req).unwrap();
+        assert!((fudged[0].dose - base[0].dose / 2.0).abs() <= 0.05);
+
+        let mut req = request(vec![7.0]);
+        req.unit = Some(HormoneUnits::E2PmolL);
+        for value in [
+            &mut req.troughMin,
+            &mut req.troughMax,
+            &mut req.peakMin,
+            &mut req.peakMax,
+        ] {
+            *value *= e2_unit_factor(&HormoneUnits::E2PmolL).unwrap();
+        }
+        let pmol = optimize_regimen(&req).unwrap();
+        assert_eq!(pmol[0].dose, base[0].dose);
+    }
+
+    #[test]
+    fn optimizer_validates_targets() {
+        let mut req = request(vec![7.0]);
+        req.troughMin = 300.0;
+        assert!(optimize_regimen(&req).is_err());
+    }
+
+    #[test]
+    fn optimizer_rejects_non_estradiol_units() {
+        let mut req = request(vec![7.0]);
+        req.unit = Some(HormoneUnits::TNgDl);
+        assert!(optimize_regimen(&req).is_err());
+        req.unit = Some(HormoneUnits::E2PmolL);
+        assert!(optimize_regimen(&req).is_ok());
+    }
+}
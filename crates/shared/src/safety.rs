@@ -0,0 +1,472 @@
+use crate::lab_results::canonical_lab_name;
+use crate::quantity::BloodAnalyte;
+use crate::ranges::{assess_level, RangeFlag};
+use crate::schedules::current_regimens;
+use crate::types::{Antiandrogens, BloodTest, DosageHistoryEntry, HrtData, UnixTime};
+
+const DAY_MS: i64 = 24 * 60 * 60 * 1000;
+
+/// Reminders are shown this many days before a check falls due.
+pub const MONITORING_LEAD_DAYS: i64 = 14;
+
+/// A lab check that a medication needs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SafetyCheck {
+    Potassium,
+    Kidney,
+    Prolactin,
+    Liver,
+}
+
+impl SafetyCheck {
+    pub fn label(&self) -> &'static str {
+        match self {
+            SafetyCheck::Potassium => "potassium",
+            SafetyCheck::Kidney => "kidney function",
+            SafetyCheck::Prolactin => "prolactin",
+            SafetyCheck::Liver => "liver enzymes",
+        }
+    }
+
+    fn id(&self) -> &'static str {
+        match self {
+            SafetyCheck::Potassium => "potassium",
+            SafetyCheck::Kidney => "kidney",
+            SafetyCheck::Prolactin => "prolactin",
+            SafetyCheck::Liver => "liver",
+        }
+    }
+
+    /// Catalog names of the lab results that satisfy this check.
+    fn lab_names(&self) -> &'static [&'static str] {
+        match self {
+            SafetyCheck::Potassium => &["Potassium"],
+            SafetyCheck::Kidney => &["Creatinine", "eGFR"],
+            SafetyCheck::Prolactin => &[],
+            SafetyCheck::Liver => &["ALT", "AST"],
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MonitoringRule {
+    pub medication: Antiandrogens,
+    pub check: SafetyCheck,
+    pub interval_days: i64,
+}
+
+const fn rule(medication: Antiandrogens, check: SafetyCheck, interval_days: i64) -> MonitoringRule {
+    MonitoringRule {
+        medication,
+        check,
+        interval_days,
+    }
+}
+
+pub const MONITORING_RULES: [MonitoringRule; 5] = [
+    rule(Antiandrogens::Spiro, SafetyCheck::Potassium, 90),
+    rule(Antiandrogens::Spiro, SafetyCheck::Kidney, 90),
+    rule(Antiandrogens::CPA, SafetyCheck::Prolactin, 180),
+    rule(Antiandrogens::CPA, SafetyCheck::Liver, 180),
+    rule(Antiandrogens::Bica, SafetyCheck::Liver, 90),
+];
+
+/// Used when neither the lab nor a saved range flags a result.
+fn default_flag(name: &str, value: f64) -> Option<RangeFlag> {
+    let (low, high) = match name {
+        "Potassium" => (Some(3.5), Some(5.0)),
+        "eGFR" => (Some(60.0), None),
+        "ALT" | "AST" => (None, Some(40.0)),
+        _ => return None,
+    };
+    if low.is_some_and(|low| value < low) {
+        Some(RangeFlag::Low)
+    } else if high.is_some_and(|high| value > high) {
+        Some(RangeFlag::High)
+    } else {
+        Some(RangeFlag::InRange)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SafetyAlert {
+    MonitoringDue {
+        medication: Antiandrogens,
+        check: SafetyCheck,
+        last_checked: Option<UnixTime>,
+        due: UnixTime,
+    },
+    OutOfRange {
+        medication: Antiandrogens,
+        check: SafetyCheck,
+        date: UnixTime,
+        name: String,
+        value: f64,
+        unit: Option<String>,
+        flag: RangeFlag,
+    },
+}
+
+impl SafetyAlert {
+    pub fn date(&self) -> UnixTime {
+        match self {
+            SafetyAlert::MonitoringDue { due, .. } => *due,
+            SafetyAlert::OutOfRange { date, .. } => *date,
+        }
+    }
+
+    /// Stable identifier, used for calendar UIDs.
+    pub fn id(&self) -> String {
+        match self {
+            SafetyAlert::MonitoringDue {
+                medication, check, ..
+            } => format!("{}-{}-due", medication_id(medication), check.id()),
+            SafetyAlert::OutOfRange {
+                medication,
+                check,
+                date,
+                ..
+            } => format!(
+                "{}-{}-{}-range",
+                medication_id(medication),
+                check.id(),
+                date
+            ),
+        }
+    }
+
+    pub fn summary(&self) -> String {
+        match self {
+            SafetyAlert::MonitoringDue { check, .. } => {
+                format!("Check {}", check.label())
+            }
+            SafetyAlert::OutOfRange { name, flag, .. } => {
+                let side = if *flag == RangeFlag::Low {
+                    "low"
+                } else {
+                    "high"
+                };
+                format!("{} {}", name, side)
+            }
+        }
+    }
+
+    pub fn description(&self) -> String {
+        match self {
+            SafetyAlert::MonitoringDue {
+                medication,
+                check,
+                last_checked,
+                ..
+            } => match last_checked {
+                Some(_) => format!(
+                    "{} needs regular {} monitoring.",
+                    medication_name(medication),
+                    check.label()
+                ),
+                None => format!(
+                    "{} needs {} monitoring; no result recorded yet.",
+                    medication_name(medication),
+                    check.label()
+                ),
+            },
+            SafetyAlert::OutOfRange {
+                medication,
+                name,
+                value,
+                unit,
+                ..
+            } => format!(
+                "Latest {} was {}{} while taking {}.",
+                name,
+                value,
+                unit.as_deref()
+                    .map(|unit| format!(" {}", unit))
+                    .unwrap_or_default(),
+                medication_name(medication)
+            ),
+        }
+    }
+}
+
+fn medication_id(medication: &Antiandrogens) -> &'static str {
+    match medication {
+        Antiandrogens::CPA => "cpa",
+        Antiandrogens::Spiro => "spironolactone",
+        Antiandrogens::Bica => "bicalutamide",
+        Antiandrogens::Finasteride => "finasteride",
+    }
+}
+
+fn medication_name(medication: &Antiandrogens) -> &'static str {
+    match medication {
+        Antiandrogens::CPA => "Cyproterone acetate",
+        Antiandrogens::Spiro => "Spironolactone",
+        Antiandrogens::Bica => "Bicalutamide",
+        Antiandrogens::Finasteride => "Finasteride",
+    }
+}
+
+struct CheckResult {
+    name: String,
+    value: f64,
+    unit: Option<String>,
+    flag: Option<RangeFlag>,
+}
+
+/// Results on `test` that satisfy `check`, with their flags.
+fn check_results(data: &HrtData, test: &BloodTest, check: SafetyCheck) -> Vec<CheckResult> {
+    if check == SafetyCheck::Prolactin {
+        return assess_level(data, test, BloodAnalyte::Prolactin)
+            .map(|assessment| {
+                let flag = assessment
+                    .reference
+                    .as_ref()
+                    .or(assessment.target.as_ref())
+                    .map(|range| range.flag);
+                vec![CheckResult {
+                    name: "Prolactin".to_string(),
+                    value: assessment.level.value,
+                    unit: Some(assessment.level.unit.symbol().to_string()),
+                    flag,
+                }]
+            })
+            .unwrap_or_default();
+    }
+    test.labResults
+        .iter()
+        .filter_map(|result| {
+            let name = canonical_lab_name(&result.name);
+            check
+                .lab_names()
+                .contains(&name.as_str())
+                .then(|| CheckResult {
+                    flag: result
+                        .effective_flag()
+                        .or_else(|| default_flag(&name, result.value)),
+                    name,
+                    value: result.value,
+                    unit: result.unit.clone(),
+                })
+        })
+        .collect()
+}
+
+/// Monitoring reminders and out-of-range alerts for the active antiandrogens.
+/// Reminders carry their due date, which may be in the past or future.
+pub fn safety_alerts(data: &HrtData) -> Vec<SafetyAlert> {
+    let mut medications: Vec<(Antiandrogens, UnixTime)> = Vec::new();
+    for regimen in current_regimens(data) {
+        let Some(schedule) = regimen.schedule.as_antiandrogen() else {
+            continue;
+        };
+        if !medications.iter().any(|(kind, _)| *kind == schedule.kind) {
+            medications.push((schedule.kind.clone(), regimen.startDate));
+        }
+    }
+    let mut tests: Vec<&BloodTest> = data.bloodTests.iter().collect();
+    tests.sort_by_key(|test| std::cmp::Reverse(test.date));
+
+    let mut alerts = Vec::new();
+    for (medication, regimen_start) in medications {
+        alerts.extend(medication_alerts(data, &tests, medication, regimen_start));
+    }
+    alerts.sort_by_key(SafetyAlert::date);
+    alerts
+}
+
+fn medication_alerts(
+    data: &HrtData,
+    tests: &[&BloodTest],
+    medication: Antiandrogens,
+    regimen_start: UnixTime,
+) -> Vec<SafetyAlert> {
+    let mut alerts = Vec::new();
+    for rule in MONITORING_RULES
+        .iter()
+        .filter(|rule| rule.medication == medication)
+    {
+        let latest = tests.iter().find_map(|test| {
+            let results = check_results(data, test, rule.check);
+            (!results.is_empty()).then_some((test.date, results))
+        });
+        let last_checked = latest.as_ref().map(|(date, _)| *date);
+        let started = data
+            .dosageHistory
+            .iter()
+            .filter_map(|entry| match entry {
+                DosageHistoryEntry::Antiandrogen { date, kind, .. } if *kind == medication => {
+                    Some(*date)
+                }
+                _ => None,
+            })
+            .min();
+        let due = match (last_checked, started) {
+            (Some(date), _) => date + rule.interval_days * DAY_MS,
+            (None, Some(started)) => started,
+            (None, None) => regimen_start,
+        };
+        alerts.push(SafetyAlert::MonitoringDue {
+            medication: medication.clone(),
+            check: rule.check,
+            last_checked,
+            due,
+        });
+        if let Some((date, results)) = latest {
+            for result in results {
+                let Some(flag) = result.flag.filter(|flag| flag.is_out_of_range()) else {
+                    continue;
+                };
+                alerts.push(SafetyAlert::OutOfRange {
+                    medication: medication.clone(),
+                    check: rule.check,
+                    date,
+                    name: result.name,
+                    value: result.value,
+                    unit: result.unit,
+                    flag,
+                });
+            }
+        }
+    }
+    alerts
+}
+
+/// Alerts worth showing at `now`: every out-of-range result and reminders
+/// due within `MONITORING_LEAD_DAYS`.
+pub fn active_safety_alerts(data: &HrtData, now: UnixTime) -> Vec<SafetyAlert> {
+    safety_alerts(data)
+        .into_iter()
+        .filter(|alert| match alert {
+            SafetyAlert::MonitoringDue { due, .. } => *due <= now + MONITORING_LEAD_DAYS * DAY_MS,
+            SafetyAlert::OutOfRange { .. } => true,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lab_results::LabResult;
+    use crate::quantity::Quantity;
+    use crate::schedules::add_regimen;
+    use crate::types::{AntiandrogenSchedule, HormoneUnits, RegimenSchedule};
+
+    const T0: i64 = 1700000000000;
+
+    fn on_antiandrogen(kind: Antiandrogens) -> HrtData {
+        let mut data = HrtData::default();
+        let schedule = AntiandrogenSchedule {
+            kind,
+            dose: 100.0,
+            unit: HormoneUnits::Mg,
+            frequency: 1.0,
+            nextDoseDate: Some(T0),
+            pillStock: None,
+            pillStockDate: None,
+        };
+        add_regimen(&mut data, RegimenSchedule::Antiandrogen(schedule), 0);
+        data
+    }
+
+    fn test_with(date: i64, results: Vec<LabResult>) -> BloodTest {
+        BloodTest {
+            date,
+            labResults: results,
+            ..BloodTest::default()
+        }
+    }
+
+    fn result(name: &str, value: f64) -> LabResult {
+        LabResult {
+            name: name.to_string(),
+            value,
+            ..LabResult::default()
+        }
+    }
+
+    #[test]
+    fn no_alerts_without_antiandrogen() {
+        let mut data = HrtData::default();
+        data.bloodTests
+            .push(test_with(T0, vec![result("Potassium", 6.0)]));
+        assert!(safety_alerts(&data).is_empty());
+    }
+
+    #[test]
+    fn spironolactone_reminds_and_flags_potassium() {
+        let mut data = on_antiandrogen(Antiandrogens::Spiro);
+        data.bloodTests
+            .push(test_with(T0 - 200 * DAY_MS, vec![result("K+", 4.0)]));
+        data.bloodTests
+            .push(test_with(T0 - 10 * DAY_MS, vec![result("potassium", 5.4)]));
+
+        let alerts = safety_alerts(&data);
+        let potassium_due = alerts.iter().find_map(|alert| match alert {
+            SafetyAlert::MonitoringDue {
+                check: SafetyCheck::Potassium,
+                due,
+                ..
+            } => Some(*due),
+            _ => None,
+        });
+        assert_eq!(potassium_due, Some(T0 + 80 * DAY_MS));
+        let high = alerts
+            .iter()
+            .find(|alert| matches!(alert, SafetyAlert::OutOfRange { .. }))
+            .unwrap();
+        assert_eq!(high.summary(), "Potassium high");
+        assert_eq!(
+            high.id(),
+            format!("spironolactone-potassium-{}-range", T0 - 10 * DAY_MS)
+        );
+
+        // Kidney function was never checked, so it is due now.
+        let active = active_safety_alerts(&data, T0);
+        assert_eq!(active.len(), 2);
+        assert!(active
+            .iter()
+            .any(|alert| alert.summary() == "Check kidney function"));
+    }
+
+    #[test]
+    fn unchecked_reminder_is_due_from_regimen_start() {
+        let mut data = HrtData::default();
+        let schedule = AntiandrogenSchedule {
+            kind: Antiandrogens::Bica,
+            dose: 50.0,
+            unit: HormoneUnits::Mg,
+            frequency: 1.0,
+            nextDoseDate: None,
+            pillStock: None,
+            pillStockDate: None,
+        };
+        add_regimen(
+            &mut data,
+            RegimenSchedule::Antiandrogen(schedule),
+            T0 - 5 * DAY_MS,
+        );
+        let alerts = safety_alerts(&data);
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].date(), T0 - 5 * DAY_MS);
+    }
+
+    #[test]
+    fn cpa_uses_prolactin_ranges_and_liver_enzymes() {
+        let mut data = on_antiandrogen(Antiandrogens::CPA);
+        data.bloodTests.push(BloodTest {
+            prolactin: Some(Quantity::new(40.0, HormoneUnits::NgMl)),
+            ..test_with(T0, vec![result("ALT", 25.0)])
+        });
+        let alerts = safety_alerts(&data);
+        let flagged: Vec<String> = alerts
+            .iter()
+            .filter(|alert| matches!(alert, SafetyAlert::OutOfRange { .. }))
+            .map(SafetyAlert::summary)
+            .collect();
+        assert_eq!(flagged, vec!["Prolactin high"]);
+        assert!(active_safety_alerts(&data, T0)
+            .iter()
+            .all(|alert| matches!(alert, SafetyAlert::OutOfRange { .. })));
+    }
+}
@@ -0,0 +1,668 @@
+use crate::recurrence::minute_of_day;
+use crate::transdermal::gel_dose_mg;
+use crate::types::{
+    AntiandrogenSchedule, DosageHistoryEntry, GelSchedule, HrtData, InjectableSchedule,
+    OralEstradiolRoutes, OralSchedule, PatchSchedule, ProgesteroneSchedule, Recurrence, Regimen,
+    RegimenSchedule, UnixTime,
+};
+
+const DAY_MS: f64 = 24.0 * 60.0 * 60.0 * 1000.0;
+/// A dose logged up to this long before a recurring slot counts for it.
+const EARLY_DOSE_MS: UnixTime = 60 * 60 * 1000;
+
+impl RegimenSchedule {
+    pub fn medication_type(&self) -> &'static str {
+        match self {
+            RegimenSchedule::InjectableEstradiol(_) => "injectableEstradiol",
+            RegimenSchedule::OralEstradiol(_) => "oralEstradiol",
+            RegimenSchedule::Antiandrogen(_) => "antiandrogen",
+            RegimenSchedule::Progesterone(_) => "progesterone",
+            RegimenSchedule::Patch(_) => "patch",
+            RegimenSchedule::Gel(_) => "gel",
+        }
+    }
+
+    pub fn frequency(&self) -> f64 {
+        match self {
+            RegimenSchedule::InjectableEstradiol(s) => s.frequency,
+            RegimenSchedule::OralEstradiol(s) => s.frequency,
+            RegimenSchedule::Antiandrogen(s) => s.frequency,
+            RegimenSchedule::Progesterone(s) => s.frequency,
+            RegimenSchedule::Patch(s) => s.frequency,
+            RegimenSchedule::Gel(s) => s.frequency,
+        }
+    }
+
+    pub fn next_dose_date(&self) -> Option<UnixTime> {
+        match self {
+            RegimenSchedule::InjectableEstradiol(s) => s.nextDoseDate,
+            RegimenSchedule::OralEstradiol(s) => s.nextDoseDate,
+            RegimenSchedule::Antiandrogen(s) => s.nextDoseDate,
+            RegimenSchedule::Progesterone(s) => s.nextDoseDate,
+            RegimenSchedule::Patch(s) => s.nextDoseDate,
+            RegimenSchedule::Gel(s) => s.nextDoseDate,
+        }
+    }
+
+    pub fn set_next_dose_date(&mut self, ts: UnixTime) {
+        let next = match self {
+            RegimenSchedule::InjectableEstradiol(s) => &mut s.nextDoseDate,
+            RegimenSchedule::OralEstradiol(s) => &mut s.nextDoseDate,
+            RegimenSchedule::Antiandrogen(s) => &mut s.nextDoseDate,
+            RegimenSchedule::Progesterone(s) => &mut s.nextDoseDate,
+            RegimenSchedule::Patch(s) => &mut s.nextDoseDate,
+            RegimenSchedule::Gel(s) => &mut s.nextDoseDate,
+        };
+        *next = Some(ts);
+    }
+
+    pub fn is_estradiol(&self) -> bool {
+        !matches!(
+            self,
+            RegimenSchedule::Antiandrogen(_) | RegimenSchedule::Progesterone(_)
+        )
+    }
+
+    /// Whether a scheduled (non-bonus) dose of this medication was recorded
+    /// by `entry`. Concurrent regimens of one class are told apart by kind,
+    /// and oral estradiol also by route (unset meaning swallowed).
+    pub fn matches_entry(&self, entry: &DosageHistoryEntry) -> bool {
+        let oral_route = |route: &Option<OralEstradiolRoutes>| {
+            route.clone().unwrap_or(OralEstradiolRoutes::Oral)
+        };
+        match (self, entry) {
+            (
+                RegimenSchedule::InjectableEstradiol(s),
+                DosageHistoryEntry::InjectableEstradiol {
+                    kind, bonusDose, ..
+                },
+            ) => *kind == s.kind && !bonusDose.unwrap_or(false),
+            (
+                RegimenSchedule::OralEstradiol(s),
+                DosageHistoryEntry::OralEstradiol { kind, route, .. },
+            ) => *kind == s.kind && oral_route(route) == oral_route(&s.route),
+            (RegimenSchedule::Antiandrogen(s), DosageHistoryEntry::Antiandrogen { kind, .. }) => {
+                *kind == s.kind
+            }
+            (RegimenSchedule::Progesterone(s), DosageHistoryEntry::Progesterone { kind, .. }) => {
+                *kind == s.kind
+            }
+            (RegimenSchedule::Patch(_), DosageHistoryEntry::Patch { .. })
+            | (RegimenSchedule::Gel(_), DosageHistoryEntry::Gel { .. }) => true,
+            _ => false,
+        }
+    }
+
+    // The schedule without bookkeeping (next date, stock, injection
+    // supplies), so only real dose changes start a new regimen.
+    fn dosing(&self) -> RegimenSchedule {
+        let mut dosing = self.clone();
+        match &mut dosing {
+            RegimenSchedule::InjectableEstradiol(s) => {
+                s.vialId = None;
+                s.subVialId = None;
+                s.syringeKind = None;
+                s.needleLength = None;
+                s.needleGauge = None;
+                s.nextDoseDate = None;
+            }
+            RegimenSchedule::OralEstradiol(s) => {
+                s.nextDoseDate = None;
+                s.pillStock = None;
+                s.pillStockDate = None;
+            }
+            RegimenSchedule::Antiandrogen(s) => {
+                s.nextDoseDate = None;
+                s.pillStock = None;
+                s.pillStockDate = None;
+            }
+            RegimenSchedule::Progesterone(s) => {
+                s.nextDoseDate = None;
+                s.pillStock = None;
+                s.pillStockDate = None;
+            }
+            RegimenSchedule::Patch(s) => s.nextDoseDate = None,
+            RegimenSchedule::Gel(s) => s.nextDoseDate = None,
+        }
+        dosing
+    }
+
+    pub fn same_dosing(&self, other: &RegimenSchedule) -> bool {
+        self.dosing() == other.dosing()
+    }
+
+    /// Dose per administration; a patch's strength in µg/day.
+    pub fn dose(&self) -> f64 {
+        match self {
+            RegimenSchedule::InjectableEstradiol(s) => s.dose,
+            RegimenSchedule::OralEstradiol(s) => s.dose,
+            RegimenSchedule::Antiandrogen(s) => s.dose,
+            RegimenSchedule::Progesterone(s) => s.dose,
+            RegimenSchedule::Patch(s) => s.strength,
+            RegimenSchedule::Gel(s) => gel_dose_mg(s.dose, s.pumps, s.mgPerPump),
+        }
+    }
+
+    /// The same medication at another dose and interval.
+    pub fn with_dosing(&self, dose: f64, frequency: f64) -> RegimenSchedule {
+        let mut schedule = self.clone();
+        match &mut schedule {
+            RegimenSchedule::InjectableEstradiol(s) => (s.dose, s.frequency) = (dose, frequency),
+            RegimenSchedule::OralEstradiol(s) => (s.dose, s.frequency) = (dose, frequency),
+            RegimenSchedule::Antiandrogen(s) => (s.dose, s.frequency) = (dose, frequency),
+            RegimenSchedule::Progesterone(s) => (s.dose, s.frequency) = (dose, frequency),
+            RegimenSchedule::Patch(s) => (s.strength, s.frequency) = (dose, frequency),
+            RegimenSchedule::Gel(s) => {
+                // Pumps would override the new dose.
+                s.pumps = None;
+                (s.dose, s.frequency) = (dose, frequency);
+            }
+        }
+        schedule
+    }
+
+    /// Pill count and when it was taken, for pill-based schedules.
+    pub fn pill_stock(&self) -> Option<(Option<f64>, Option<UnixTime>)> {
+        match self {
+            RegimenSchedule::OralEstradiol(s) => Some((s.pillStock, s.pillStockDate)),
+            RegimenSchedule::Antiandrogen(s) => Some((s.pillStock, s.pillStockDate)),
+            RegimenSchedule::Progesterone(s) => Some((s.pillStock, s.pillStockDate)),
+            _ => None,
+        }
+    }
+
+    pub fn set_pill_stock(&mut self, stock: Option<f64>, date: Option<UnixTime>) {
+        match self {
+            RegimenSchedule::OralEstradiol(s) => (s.pillStock, s.pillStockDate) = (stock, date),
+            RegimenSchedule::Antiandrogen(s) => (s.pillStock, s.pillStockDate) = (stock, date),
+            RegimenSchedule::Progesterone(s) => (s.pillStock, s.pillStockDate) = (stock, date),
+            _ => {}
+        }
+    }
+
+    pub fn as_injectable(&self) -> Option<&InjectableSchedule> {
+        match self {
+            RegimenSchedule::InjectableEstradiol(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_oral(&self) -> Option<&OralSchedule> {
+        match self {
+            RegimenSchedule::OralEstradiol(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_antiandrogen(&self) -> Option<&AntiandrogenSchedule> {
+        match self {
+            RegimenSchedule::Antiandrogen(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_progesterone(&self) -> Option<&ProgesteroneSchedule> {
+        match self {
+            RegimenSchedule::Progesterone(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_patch(&self) -> Option<&PatchSchedule> {
+        match self {
+            RegimenSchedule::Patch(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_gel(&self) -> Option<&GelSchedule> {
+        match self {
+            RegimenSchedule::Gel(s) => Some(s),
+            _ => None,
+        }
+    }
+}
+
+impl Regimen {
+    pub fn is_active_at(&self, t: UnixTime) -> bool {
+        self.startDate <= t && self.endDate.is_none_or(|end| t < end)
+    }
+
+    /// Still followed, i.e. not ended.
+    pub fn is_current(&self) -> bool {
+        self.endDate.is_none()
+    }
+}
+
+/// Regimens that have not been ended, in the order they were added.
+pub fn current_regimens(data: &HrtData) -> impl Iterator<Item = &Regimen> {
+    data.regimens.iter().filter(|regimen| regimen.is_current())
+}
+
+pub fn current_injectable(data: &HrtData) -> Option<&InjectableSchedule> {
+    current_regimens(data).find_map(|regimen| regimen.schedule.as_injectable())
+}
+
+/// The regimen a scheduled-dose key refers to: a regimen id, or a medication
+/// type for the first current regimen of that type.
+pub fn find_regimen<'a>(data: &'a HrtData, key: &str) -> Option<&'a Regimen> {
+    data.regimens
+        .iter()
+        .find(|regimen| regimen.id == key)
+        .or_else(|| {
+            current_regimens(data).find(|regimen| regimen.schedule.medication_type() == key)
+        })
+}
+
+/// Most recent scheduled dose recorded for a regimen's medication.
+pub fn last_regimen_dose(data: &HrtData, schedule: &RegimenSchedule) -> Option<UnixTime> {
+    data.dosageHistory
+        .iter()
+        .filter(|entry| schedule.matches_entry(entry))
+        .map(entry_date)
+        .max()
+}
+
+/// Dose times of a regimen from `from` up to `until`, continuing the cadence
+/// of the last recorded dose and kept within the regimen's start and end.
+pub fn regimen_dose_times(
+    data: &HrtData,
+    regimen: &Regimen,
+    from: UnixTime,
+    until: UnixTime,
+) -> Vec<UnixTime> {
+    let until = regimen.endDate.map_or(until, |end| until.min(end - 1));
+    if let Some(recurrence) = &regimen.recurrence {
+        let start = from
+            .max(regimen.startDate)
+            .max(recurrence_start(data, regimen));
+        return recurrence.occurrences(recurrence_minute(data, regimen), start, until);
+    }
+    let freq_days = regimen.schedule.frequency();
+    let step = (freq_days * DAY_MS) as i64;
+    if !freq_days.is_finite() || step <= 0 {
+        return Vec::new();
+    }
+    let anchor = match last_regimen_dose(data, &regimen.schedule) {
+        Some(last) => Some(last + step),
+        None => regimen.schedule.next_dose_date(),
+    };
+    let Some(mut t) = anchor else {
+        return Vec::new();
+    };
+    let start = from.max(regimen.startDate);
+    if t < start {
+        t += (start - t + step - 1) / step * step;
+    }
+    let mut times = Vec::new();
+    while t <= until {
+        times.push(t);
+        t += step;
+    }
+    times
+}
+
+/// The first dose of a regimen due at or after `from`.
+pub fn next_regimen_dose(data: &HrtData, regimen: &Regimen, from: UnixTime) -> Option<UnixTime> {
+    let next = match &regimen.recurrence {
+        Some(recurrence) => {
+            let start = from
+                .max(regimen.startDate)
+                .max(recurrence_start(data, regimen));
+            recurrence.next_after(recurrence_minute(data, regimen), start)?
+        }
+        None => {
+            let step = (regimen.schedule.frequency() * DAY_MS) as i64;
+            *regimen_dose_times(data, regimen, from, from + step.max(0)).first()?
+        }
+    };
+    regimen.endDate.is_none_or(|end| next < end).then_some(next)
+}
+
+/// The dose that follows one taken at `date`, or `None` for a schedule
+/// without a usable interval.
+pub(crate) fn following_dose(
+    data: &HrtData,
+    regimen: &Regimen,
+    date: UnixTime,
+) -> Option<UnixTime> {
+    match &regimen.recurrence {
+        Some(recurrence) => {
+            recurrence.next_after(recurrence_minute(data, regimen), date + EARLY_DOSE_MS)
+        }
+        None => {
+            let freq_days = regimen.schedule.frequency();
+            (freq_days.is_finite() && freq_days > 0.0).then(|| date + (freq_days * DAY_MS) as i64)
+        }
+    }
+}
+
+/// Slots before this were taken already or precede the schedule.
+fn recurrence_start(data: &HrtData, regimen: &Regimen) -> UnixTime {
+    match last_regimen_dose(data, &regimen.schedule) {
+        Some(last) => last + EARLY_DOSE_MS,
+        None => regimen
+            .schedule
+            .next_dose_date()
+            .unwrap_or(regimen.startDate),
+    }
+}
+
+/// Time of day for recurrences without explicit times, taken from the
+/// schedule's anchor in the rule's zone.
+pub(crate) fn recurrence_minute(data: &HrtData, regimen: &Regimen) -> u32 {
+    let anchor = regimen
+        .schedule
+        .next_dose_date()
+        .or_else(|| last_regimen_dose(data, &regimen.schedule))
+        .unwrap_or(regimen.startDate);
+    match &regimen.recurrence {
+        Some(recurrence) => recurrence.minute_of_day(anchor),
+        None => minute_of_day(anchor),
+    }
+}
+
+pub(crate) fn entry_date(entry: &DosageHistoryEntry) -> UnixTime {
+    match entry {
+        DosageHistoryEntry::InjectableEstradiol { date, .. }
+        | DosageHistoryEntry::OralEstradiol { date, .. }
+        | DosageHistoryEntry::Antiandrogen { date, .. }
+        | DosageHistoryEntry::Progesterone { date, .. }
+        | DosageHistoryEntry::Patch { date, .. }
+        | DosageHistoryEntry::Gel { date, .. }
+        | DosageHistoryEntry::Medication { date, .. } => *date,
+    }
+}
+
+fn unique_regimen_id(data: &HrtData, medication_type: &str, at: UnixTime) -> String {
+    let base = format!("{}-{}", medication_type, at);
+    let mut id = base.clone();
+    let mut n = 1;
+    while data.regimens.iter().any(|regimen| regimen.id == id) {
+        n += 1;
+        id = format!("{}-{}", base, n);
+    }
+    id
+}
+
+/// Starts a regimen at `at` alongside the current ones and returns its id.
+pub fn add_regimen(data: &mut HrtData, schedule: RegimenSchedule, at: UnixTime) -> String {
+    let id = unique_regimen_id(data, schedule.medication_type(), at);
+    data.regimens.push(Regimen {
+        id: id.clone(),
+        startDate: at,
+        endDate: None,
+        schedule,
+        recurrence: None,
+    });
+    id
+}
+
+/// Saves an edited schedule. Bookkeeping edits update the regimen in place;
+/// a dose or recurrence change ends it at `at` and starts a new one, keeping
+/// the history. An unknown `id` adds a new regimen.
+pub fn replace_regimen(
+    data: &mut HrtData,
+    id: &str,
+    schedule: RegimenSchedule,
+    recurrence: Option<Recurrence>,
+    at: UnixTime,
+) -> String {
+    if let Some(existing) = data.regimens.iter_mut().find(|regimen| regimen.id == id) {
+        if existing.schedule.medication_type() == schedule.medication_type()
+            && (existing.startDate >= at
+                || (existing.schedule.same_dosing(&schedule) && existing.recurrence == recurrence))
+        {
+            existing.schedule = schedule;
+            existing.recurrence = recurrence;
+            return existing.id.clone();
+        }
+        existing.endDate = Some(at.max(existing.startDate));
+    }
+    let id = add_regimen(data, schedule, at);
+    if let Some(added) = data.regimens.last_mut() {
+        added.recurrence = recurrence;
+    }
+    id
+}
+
+pub fn end_regimen(data: &mut HrtData, id: &str, at: UnixTime) -> bool {
+    match data.regimens.iter_mut().find(|regimen| regimen.id == id) {
+        Some(regimen) => {
+            regimen.endDate = Some(at.max(regimen.startDate));
+            true
+        }
+        None => false,
+    }
+}
+
+/// Moves the single per-class schedules of older data into `regimens`. The
+/// medication type becomes the id, so calendar UIDs stay the same.
+pub fn migrate_schedules_to_regimens(data: &mut HrtData) -> bool {
+    let legacy = [
+        data.injectableEstradiol
+            .take()
+            .map(RegimenSchedule::InjectableEstradiol),
+        data.oralEstradiol
+            .take()
+            .map(RegimenSchedule::OralEstradiol),
+        data.antiandrogen.take().map(RegimenSchedule::Antiandrogen),
+        data.progesterone.take().map(RegimenSchedule::Progesterone),
+        data.patch.take().map(RegimenSchedule::Patch),
+        data.gel.take().map(RegimenSchedule::Gel),
+    ];
+    let mut migrated = false;
+    for schedule in legacy.into_iter().flatten() {
+        let start = data
+            .dosageHistory
+            .iter()
+            .filter(|entry| schedule.matches_entry(entry))
+            .map(entry_date)
+            .min()
+            .or(schedule.next_dose_date())
+            .unwrap_or(0);
+        let id = if data
+            .regimens
+            .iter()
+            .any(|regimen| regimen.id == schedule.medication_type())
+        {
+            unique_regimen_id(data, schedule.medication_type(), start)
+        } else {
+            schedule.medication_type().to_string()
+        };
+        data.regimens.push(Regimen {
+            id,
+            startDate: start,
+            endDate: None,
+            schedule,
+            recurrence: None,
+        });
+        migrated = true;
+    }
+    migrated
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{Antiandrogens, HormoneUnits, OralEstradiols};
+
+    const DAY: i64 = DAY_MS as i64;
+
+    fn daily(kind: Antiandrogens, dose: f64) -> AntiandrogenSchedule {
+        AntiandrogenSchedule {
+            kind,
+            dose,
+            unit: HormoneUnits::Mg,
+            frequency: 1.0,
+            nextDoseDate: Some(10 * DAY),
+            pillStock: None,
+            pillStockDate: None,
+        }
+    }
+
+    fn antiandrogen(kind: Antiandrogens, dose: f64) -> RegimenSchedule {
+        RegimenSchedule::Antiandrogen(daily(kind, dose))
+    }
+
+    fn antiandrogen_dose(date: UnixTime, kind: Antiandrogens) -> DosageHistoryEntry {
+        DosageHistoryEntry::Antiandrogen {
+            date,
+            id: None,
+            kind,
+            dose: 50.0,
+            unit: HormoneUnits::Mg,
+            note: None,
+        }
+    }
+
+    #[test]
+    fn migrates_legacy_schedules_with_type_ids() {
+        let mut data = HrtData {
+            dosageHistory: vec![
+                antiandrogen_dose(3 * DAY, Antiandrogens::Spiro),
+                antiandrogen_dose(2 * DAY, Antiandrogens::Spiro),
+            ],
+            ..HrtData::default()
+        };
+        data.antiandrogen = Some(daily(Antiandrogens::Spiro, 50.0));
+        assert!(migrate_schedules_to_regimens(&mut data));
+        assert!(data.antiandrogen.is_none());
+        assert_eq!(data.regimens.len(), 1);
+        assert_eq!(data.regimens[0].id, "antiandrogen");
+        assert_eq!(data.regimens[0].startDate, 2 * DAY);
+        assert!(!migrate_schedules_to_regimens(&mut data));
+    }
+
+    #[test]
+    fn dose_change_ends_regimen_and_keeps_history() {
+        let mut data = HrtData::default();
+        let id = add_regimen(&mut data, antiandrogen(Antiandrogens::Spiro, 50.0), DAY);
+
+        let mut restocked = antiandrogen(Antiandrogens::Spiro, 50.0);
+        restocked.set_next_dose_date(20 * DAY);
+        assert_eq!(
+            replace_regimen(&mut data, &id, restocked, None, 5 * DAY),
+            id
+        );
+        assert_eq!(data.regimens.len(), 1);
+
+        let new_id = replace_regimen(
+            &mut data,
+            &id,
+            antiandrogen(Antiandrogens::Spiro, 100.0),
+            None,
+            6 * DAY,
+        );
+        assert_ne!(new_id, id);
+        assert_eq!(data.regimens[0].endDate, Some(6 * DAY));
+        assert_eq!(data.regimens[1].startDate, 6 * DAY);
+        assert_eq!(current_regimens(&data).count(), 1);
+        assert!(data.regimens[0].is_active_at(5 * DAY));
+        assert!(!data.regimens[0].is_active_at(6 * DAY));
+    }
+
+    #[test]
+    fn concurrent_regimens_schedule_independently() {
+        let mut data = HrtData {
+            dosageHistory: vec![antiandrogen_dose(12 * DAY, Antiandrogens::Spiro)],
+            ..HrtData::default()
+        };
+        let spiro = add_regimen(&mut data, antiandrogen(Antiandrogens::Spiro, 50.0), 0);
+        let fin = add_regimen(&mut data, antiandrogen(Antiandrogens::Finasteride, 1.0), 0);
+        assert_ne!(spiro, fin);
+
+        let spiro_times = regimen_dose_times(&data, &data.regimens[0], 0, 15 * DAY);
+        assert_eq!(spiro_times, vec![13 * DAY, 14 * DAY, 15 * DAY]);
+        let fin_times = regimen_dose_times(&data, &data.regimens[1], 11 * DAY, 13 * DAY);
+        assert_eq!(fin_times, vec![11 * DAY, 12 * DAY, 13 * DAY]);
+
+        end_regimen(&mut data, &fin, 12 * DAY + 1);
+        let fin_times = regimen_dose_times(&data, &data.regimens[1], 11 * DAY, 13 * DAY);
+        assert_eq!(fin_times, vec![11 * DAY, 12 * DAY]);
+        assert_eq!(find_regimen(&data, "antiandrogen").unwrap().id, spiro);
+    }
+
+    #[test]
+    fn concurrent_oral_regimens_are_told_apart_by_route() {
+        let oral = |route: Option<OralEstradiolRoutes>| {
+            RegimenSchedule::OralEstradiol(OralSchedule {
+                kind: OralEstradiols::Hemihydrate,
+                route,
+                dose: 2.0,
+                unit: HormoneUnits::Mg,
+                frequency: 1.0,
+                nextDoseDate: Some(10 * DAY),
+                pillStock: None,
+                pillStockDate: None,
+            })
+        };
+        let sublingual_dose = DosageHistoryEntry::OralEstradiol {
+            date: 12 * DAY,
+            id: None,
+            kind: OralEstradiols::Hemihydrate,
+            route: Some(OralEstradiolRoutes::Sublingual),
+            dose: 2.0,
+            unit: HormoneUnits::Mg,
+            pillQuantity: Some(1.0),
+            note: None,
+            pkModelId: None,
+        };
+        let mut data = HrtData {
+            dosageHistory: vec![sublingual_dose],
+            ..HrtData::default()
+        };
+        add_regimen(&mut data, oral(None), 0);
+        add_regimen(&mut data, oral(Some(OralEstradiolRoutes::Sublingual)), 0);
+
+        assert_eq!(last_regimen_dose(&data, &data.regimens[0].schedule), None);
+        assert_eq!(
+            last_regimen_dose(&data, &data.regimens[1].schedule),
+            Some(12 * DAY)
+        );
+        let swallowed = regimen_dose_times(&data, &data.regimens[0], 11 * DAY, 13 * DAY);
+        assert_eq!(swallowed, vec![11 * DAY, 12 * DAY, 13 * DAY]);
+        let sublingual = regimen_dose_times(&data, &data.regimens[1], 11 * DAY, 13 * DAY);
+        assert_eq!(sublingual, vec![13 * DAY]);
+    }
+
+    #[test]
+    fn recurring_regimen_continues_after_the_slot_just_taken() {
+        use chrono::{Local, NaiveDate, TimeZone};
+        let on_day = |day: u32, hour: u32, minute: u32| {
+            let naive = NaiveDate::from_ymd_opt(2025, 6, day)
+                .unwrap()
+                .and_hms_opt(hour, minute, 0)
+                .unwrap();
+            Local
+                .from_local_datetime(&naive)
+                .unwrap()
+                .timestamp_millis()
+        };
+        let local = |hour: u32, minute: u32| on_day(2, hour, minute);
+        let mut data = HrtData {
+            dosageHistory: vec![antiandrogen_dose(local(8, 10), Antiandrogens::Spiro)],
+            ..HrtData::default()
+        };
+        let twice_daily = Recurrence {
+            timesOfDay: vec![8 * 60, 20 * 60],
+            ..Recurrence::default()
+        };
+        replace_regimen(
+            &mut data,
+            "",
+            antiandrogen(Antiandrogens::Spiro, 50.0),
+            Some(twice_daily),
+            0,
+        );
+        let regimen = &data.regimens[0];
+        assert_eq!(next_regimen_dose(&data, regimen, 0), Some(local(20, 0)));
+        let times = regimen_dose_times(&data, regimen, local(0, 0), local(23, 59));
+        assert_eq!(times, vec![local(20, 0)]);
+        // Taken a little early, the evening dose still counts for its slot.
+        assert_eq!(
+            following_dose(&data, regimen, local(19, 30)),
+            Some(on_day(3, 8, 0))
+        );
+    }
+}
@@ -0,0 +1,209 @@
+use crate::types::{
+    DosageHistoryEntry, EstrannaiseModel, HrtData, PatchSites, PatchWear, UnixTime,
+};
+
+const DAY_MS: f64 = 24.0 * 60.0 * 60.0 * 1000.0;
+
+/// Wear duration assumed when neither the entry nor the schedule sets one.
+pub const DEFAULT_PATCH_WEAR_DAYS: f64 = 3.5;
+
+impl PatchSites {
+    pub const ALL: [PatchSites; 10] = [
+        PatchSites::AbdomenRight,
+        PatchSites::AbdomenLeft,
+        PatchSites::HipRight,
+        PatchSites::HipLeft,
+        PatchSites::ButtockRight,
+        PatchSites::ButtockLeft,
+        PatchSites::ThighRight,
+        PatchSites::ThighLeft,
+        PatchSites::UpperArmRight,
+        PatchSites::UpperArmLeft,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            PatchSites::AbdomenRight => "Abdomen right",
+            PatchSites::AbdomenLeft => "Abdomen left",
+            PatchSites::HipRight => "Hip right",
+            PatchSites::HipLeft => "Hip left",
+            PatchSites::ButtockRight => "Buttock right",
+            PatchSites::ButtockLeft => "Buttock left",
+            PatchSites::ThighRight => "Thigh right",
+            PatchSites::ThighLeft => "Thigh left",
+            PatchSites::UpperArmRight => "Upper arm right",
+            PatchSites::UpperArmLeft => "Upper arm left",
+        }
+    }
+
+    pub fn from_label(label: &str) -> Option<PatchSites> {
+        PatchSites::ALL
+            .into_iter()
+            .find(|site| site.label() == label)
+    }
+}
+
+/// The estrannaise patch model closest to the wear duration: twice-weekly
+/// patches up to five days, once-weekly beyond that.
+pub fn patch_model(wear_days: f64) -> EstrannaiseModel {
+    if wear_days <= 5.0 {
+        EstrannaiseModel::PatchTw
+    } else {
+        EstrannaiseModel::PatchOw
+    }
+}
+
+/// Model for a patch worn `wear_days`, so release stops at the actual removal.
+pub fn worn_patch_model(wear_days: f64) -> EstrannaiseModel {
+    EstrannaiseModel::Patch(PatchWear {
+        wearDays: wear_days,
+    })
+}
+
+/// Wear duration of a patch: the recorded removal if any, otherwise the
+/// planned wear days.
+pub fn patch_wear_days(
+    applied: UnixTime,
+    wear_days: Option<f64>,
+    removed_at: Option<UnixTime>,
+) -> f64 {
+    if let Some(removed) = removed_at.filter(|removed| *removed > applied) {
+        return (removed - applied) as f64 / DAY_MS;
+    }
+    wear_days
+        .filter(|days| days.is_finite() && *days > 0.0)
+        .unwrap_or(DEFAULT_PATCH_WEAR_DAYS)
+}
+
+pub fn patch_removal_date(
+    applied: UnixTime,
+    wear_days: Option<f64>,
+    removed_at: Option<UnixTime>,
+) -> UnixTime {
+    applied + (patch_wear_days(applied, wear_days, removed_at) * DAY_MS).round() as i64
+}
+
+/// Total delivered estradiol in µg/day for `quantity` patches.
+pub fn patch_total_strength(strength: f64, quantity: Option<f64>) -> f64 {
+    strength * quantity.filter(|q| *q > 0.0).unwrap_or(1.0)
+}
+
+/// Gel dose in mg, from the number of pumps when the pump size is known.
+pub fn gel_dose_mg(dose: f64, pumps: Option<f64>, mg_per_pump: Option<f64>) -> f64 {
+    match (pumps, mg_per_pump) {
+        (Some(pumps), Some(per_pump)) if pumps > 0.0 && per_pump > 0.0 => pumps * per_pump,
+        _ => dose,
+    }
+}
+
+/// Patches that were on at `now` and have not been recorded as removed, with
+/// their expected removal time.
+pub fn worn_patches(data: &HrtData, now: UnixTime) -> Vec<(&DosageHistoryEntry, UnixTime)> {
+    data.dosageHistory
+        .iter()
+        .filter_map(|entry| match entry {
+            DosageHistoryEntry::Patch {
+                date,
+                wearDays,
+                removedAt: None,
+                ..
+            } if *date <= now => Some((entry, patch_removal_date(*date, *wearDays, None))),
+            _ => None,
+        })
+        .filter(|(_, removal)| *removal > now)
+        .collect()
+}
+
+/// The site that has gone longest without a patch, unused sites first.
+pub fn next_patch_site(data: &HrtData) -> PatchSites {
+    let last_used = |site: &PatchSites| {
+        data.dosageHistory
+            .iter()
+            .filter_map(|entry| match entry {
+                DosageHistoryEntry::Patch {
+                    date,
+                    site: Some(used),
+                    ..
+                } if used == site => Some(*date),
+                _ => None,
+            })
+            .max()
+    };
+    PatchSites::ALL
+        .into_iter()
+        .min_by_key(|site| last_used(site).unwrap_or(i64::MIN))
+        .unwrap_or(PatchSites::AbdomenRight)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn patch(
+        date: UnixTime,
+        site: Option<PatchSites>,
+        removed: Option<UnixTime>,
+    ) -> DosageHistoryEntry {
+        DosageHistoryEntry::Patch {
+            date,
+            id: None,
+            strength: 100.0,
+            patchQuantity: None,
+            wearDays: Some(3.5),
+            removedAt: removed,
+            site,
+            note: None,
+        }
+    }
+
+    #[test]
+    fn wear_prefers_recorded_removal() {
+        let day = DAY_MS as i64;
+        assert_eq!(patch_wear_days(0, Some(7.0), Some(4 * day)), 4.0);
+        assert_eq!(patch_wear_days(0, None, None), DEFAULT_PATCH_WEAR_DAYS);
+        assert_eq!(patch_removal_date(0, Some(3.5), None), 3 * day + day / 2);
+        assert_eq!(patch_model(3.5), EstrannaiseModel::PatchTw);
+        assert_eq!(patch_model(7.0), EstrannaiseModel::PatchOw);
+    }
+
+    #[test]
+    fn gel_dose_uses_pumps_when_known() {
+        assert_eq!(gel_dose_mg(1.0, Some(2.0), Some(0.75)), 1.5);
+        assert_eq!(gel_dose_mg(1.0, Some(2.0), None), 1.0);
+        assert_eq!(patch_total_strength(50.0, Some(2.0)), 100.0);
+    }
+
+    #[test]
+    fn rotates_to_least_recently_used_site() {
+        let mut data = HrtData {
+            dosageHistory: PatchSites::ALL
+                .into_iter()
+                .enumerate()
+                .map(|(i, site)| patch(i as i64 + 1, Some(site), Some(i as i64 + 2)))
+                .collect(),
+            ..HrtData::default()
+        };
+        assert_eq!(next_patch_site(&data), PatchSites::AbdomenRight);
+        data.dosageHistory
+            .push(patch(20, Some(PatchSites::AbdomenRight), None));
+        assert_eq!(next_patch_site(&data), PatchSites::AbdomenLeft);
+        data.dosageHistory.truncate(2);
+        assert_eq!(next_patch_site(&data), PatchSites::HipRight);
+    }
+
+    #[test]
+    fn worn_patches_skip_removed_and_expired() {
+        let day = DAY_MS as i64;
+        let data = HrtData {
+            dosageHistory: vec![
+                patch(0, None, None),
+                patch(day, None, Some(2 * day)),
+                patch(2 * day, None, None),
+            ],
+            ..HrtData::default()
+        };
+        let worn = worn_patches(&data, 4 * day);
+        assert_eq!(worn.len(), 1);
+        assert_eq!(worn[0].1, 5 * day + day / 2);
+    }
+}
@@ -2,6 +2,10 @@
 
 use serde::{Deserialize, Serialize};
 
+use crate::lab_results::LabResult;
+use crate::quantity::Quantity;
+use crate::ranges::{LabRanges, ReferenceRange};
+
 // https://transfemscience.org/misc/injectable-e2-simulator/
 // https://transfemscience.org/misc/
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
@@ -30,6 +34,14 @@ pub enum OralEstradiols {
     Premarin,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub enum OralEstradiolRoutes {
+    #[serde(rename = "Oral")]
+    Oral,
+    #[serde(rename = "Sublingual")]
+    Sublingual,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub enum Antiandrogens {
     #[serde(rename = "Cyproterone Acetate")]
@@ -84,6 +96,8 @@ pub enum HormoneUnits {
     TNmolL,
     #[serde(rename = "mg")]
     Mg,
+    #[serde(rename = "µg")]
+    Mcg,
     #[serde(rename = "ng/mL")]
     NgMl,
     #[serde(rename = "mIU/mL")]
@@ -124,6 +138,30 @@ pub enum InjectionSites {
     ButtockLeft,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub enum PatchSites {
+    #[serde(rename = "Abdomen right")]
+    AbdomenRight,
+    #[serde(rename = "Abdomen left")]
+    AbdomenLeft,
+    #[serde(rename = "Hip right")]
+    HipRight,
+    #[serde(rename = "Hip left")]
+    HipLeft,
+    #[serde(rename = "Buttock right")]
+    ButtockRight,
+    #[serde(rename = "Buttock left")]
+    ButtockLeft,
+    #[serde(rename = "Thigh right")]
+    ThighRight,
+    #[serde(rename = "Thigh left")]
+    ThighLeft,
+    #[serde(rename = "Upper arm right")]
+    UpperArmRight,
+    #[serde(rename = "Upper arm left")]
+    UpperArmLeft,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub enum SyringeKinds {
     #[serde(rename = "Regular syringe")]
@@ -168,6 +206,8 @@ pub enum DosageHistoryEntry {
         needleGauge: Option<String>,
         #[serde(skip_serializing_if = "Option::is_none")]
         photos: Option<Vec<DosagePhoto>>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pkModelId: Option<String>,
     },
     #[serde(rename = "oralEstradiol")]
     OralEstradiol {
@@ -176,12 +216,16 @@ pub enum DosageHistoryEntry {
         id: Option<String>,
         #[serde(rename = "type")]
         kind: OralEstradiols,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        route: Option<OralEstradiolRoutes>,
         dose: f64,
         unit: HormoneUnits,
         #[serde(skip_serializing_if = "Option::is_none")]
         pillQuantity: Option<f64>,
         #[serde(skip_serializing_if = "Option::is_none")]
         note: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pkModelId: Option<String>,
     },
     #[serde(rename = "antiandrogen")]
     Antiandrogen {
@@ -210,6 +254,50 @@ pub enum DosageHistoryEntry {
         #[serde(skip_serializing_if = "Option::is_none")]
         note: Option<String>,
     },
+    /// An estradiol patch. `date` is when it was applied.
+    #[serde(rename = "patch")]
+    Patch {
+        date: UnixTime,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        id: Option<String>,
+        /// Delivered estradiol in µg/day, per patch.
+        strength: f64,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        patchQuantity: Option<f64>,
+        /// Planned wear duration; `removedAt` records the actual removal.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        wearDays: Option<f64>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        removedAt: Option<UnixTime>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        site: Option<PatchSites>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        note: Option<String>,
+    },
+    #[serde(rename = "gel")]
+    Gel {
+        date: UnixTime,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        id: Option<String>,
+        dose: f64,
+        unit: HormoneUnits,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pumps: Option<f64>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        note: Option<String>,
+    },
+    /// A dose of a catalog medication without a dedicated variant.
+    #[serde(rename = "medication")]
+    Medication {
+        date: UnixTime,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        id: Option<String>,
+        medicationId: String,
+        dose: f64,
+        unit: HormoneUnits,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        note: Option<String>,
+    },
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -266,39 +354,29 @@ pub struct Measurement {
     pub braSize: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
 pub struct BloodTest {
     pub date: UnixTime,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub estradiolLevel: Option<f64>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub testLevel: Option<f64>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub estradiolUnit: Option<HormoneUnits>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub testUnit: Option<HormoneUnits>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub progesteroneLevel: Option<f64>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub progesteroneUnit: Option<HormoneUnits>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub fshLevel: Option<f64>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub fshUnit: Option<HormoneUnits>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub lhLevel: Option<f64>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub lhUnit: Option<HormoneUnits>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub prolactinLevel: Option<f64>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub prolactinUnit: Option<HormoneUnits>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub shbgLevel: Option<f64>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub shbgUnit: Option<HormoneUnits>,
+    /// Levels are stored as `estradiolLevel`/`estradiolUnit` style pairs.
+    #[serde(flatten, with = "crate::quantity::stored_level::estradiol")]
+    pub estradiol: Option<Quantity>,
+    #[serde(flatten, with = "crate::quantity::stored_level::testosterone")]
+    pub testosterone: Option<Quantity>,
+    #[serde(flatten, with = "crate::quantity::stored_level::progesterone")]
+    pub progesterone: Option<Quantity>,
+    #[serde(flatten, with = "crate::quantity::stored_level::fsh")]
+    pub fsh: Option<Quantity>,
+    #[serde(flatten, with = "crate::quantity::stored_level::lh")]
+    pub lh: Option<Quantity>,
+    #[serde(flatten, with = "crate::quantity::stored_level::prolactin")]
+    pub prolactin: Option<Quantity>,
+    #[serde(flatten, with = "crate::quantity::stored_level::shbg")]
+    pub shbg: Option<Quantity>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub freeAndrogenIndex: Option<f64>,
+    /// Serum albumin in g/dL, used for calculated free hormone levels.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub albuminLevel: Option<f64>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub estrannaiseNumber: Option<f64>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -309,6 +387,12 @@ pub struct BloodTest {
     pub estrogenType: Option<EstrogenType>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub pdfFiles: Option<Vec<String>>,
+    /// Name of the lab, used to look up its reference ranges.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lab: Option<String>,
+    /// Results without a dedicated field, e.g. potassium or lipids.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub labResults: Vec<LabResult>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -352,6 +436,10 @@ pub struct Vial {
     pub spentAt: Option<UnixTime>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub useBy: Option<UnixTime>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub volumeMl: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pkModelId: Option<String>,
     pub createdAt: UnixTime,
     #[serde(default)]
     pub subVials: Vec<SubVial>,
@@ -378,6 +466,68 @@ pub struct Settings {
     pub pdfPassword: Option<String>,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub enum CustomPkRoute {
+    #[serde(rename = "Injection")]
+    Injection,
+    #[serde(rename = "Oral")]
+    Oral,
+}
+
+/// A user-defined three-compartment model with the same parameters as the
+/// built-in estrannaise models.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CustomPkModel {
+    pub id: String,
+    pub name: String,
+    pub route: CustomPkRoute,
+    pub d: f64,
+    pub k1: f64,
+    pub k2: f64,
+    pub k3: f64,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "camelCase")]
+pub enum MedicationClass {
+    Estrogen,
+    Antiandrogen,
+    Progestogen,
+    GnrhAgonist,
+    FiveAlphaReductaseInhibitor,
+    Serm,
+    Other,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "camelCase")]
+pub enum MedicationRoute {
+    Injection,
+    Oral,
+    Sublingual,
+    Rectal,
+    Transdermal,
+    Gel,
+    Implant,
+    Nasal,
+    Other,
+}
+
+/// An entry in the medication catalog. Built-in medications are listed by
+/// `medications::builtin_medications`; `HrtData::medications` holds the
+/// user's own.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Medication {
+    pub id: String,
+    pub name: String,
+    pub class: MedicationClass,
+    pub route: MedicationRoute,
+    pub unit: HormoneUnits,
+    /// A custom PK model id or the name of a built-in estrannaise model.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pkModelId: Option<String>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct InjectableSchedule {
     #[serde(rename = "type")]
@@ -403,11 +553,17 @@ pub struct InjectableSchedule {
 pub struct OralSchedule {
     #[serde(rename = "type")]
     pub kind: OralEstradiols,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub route: Option<OralEstradiolRoutes>,
     pub dose: f64,
     pub unit: HormoneUnits,
     pub frequency: f64,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub nextDoseDate: Option<UnixTime>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pillStock: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pillStockDate: Option<UnixTime>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -419,6 +575,10 @@ pub struct AntiandrogenSchedule {
     pub frequency: f64,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub nextDoseDate: Option<UnixTime>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pillStock: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pillStockDate: Option<UnixTime>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -431,10 +591,132 @@ pub struct ProgesteroneSchedule {
     pub frequency: f64,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub nextDoseDate: Option<UnixTime>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pillStock: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pillStockDate: Option<UnixTime>,
+}
+
+/// Patch changes every `frequency` days, e.g. 3.5 for twice weekly.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PatchSchedule {
+    /// Delivered estradiol in µg/day, per patch.
+    pub strength: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub patchQuantity: Option<f64>,
+    pub frequency: f64,
+    /// Defaults to `frequency`, i.e. the old patch comes off as the new one
+    /// goes on.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub wearDays: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nextDoseDate: Option<UnixTime>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct GelSchedule {
+    pub dose: f64,
+    pub unit: HormoneUnits,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pumps: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mgPerPump: Option<f64>,
+    pub frequency: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nextDoseDate: Option<UnixTime>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "medicationType")]
+pub enum RegimenSchedule {
+    #[serde(rename = "injectableEstradiol")]
+    InjectableEstradiol(InjectableSchedule),
+    #[serde(rename = "oralEstradiol")]
+    OralEstradiol(OralSchedule),
+    #[serde(rename = "antiandrogen")]
+    Antiandrogen(AntiandrogenSchedule),
+    #[serde(rename = "progesterone")]
+    Progesterone(ProgesteroneSchedule),
+    #[serde(rename = "patch")]
+    Patch(PatchSchedule),
+    #[serde(rename = "gel")]
+    Gel(GelSchedule),
+}
+
+/// Repeats on calendar days in `timeZone` that pass every rule set here,
+/// once per time of day. Replaces the schedule's `frequency` interval when present.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct Recurrence {
+    /// 0 = Monday through 6 = Sunday.
+    #[serde(default)]
+    pub weekdays: Vec<u8>,
+    /// Days of the month, 1 to 31.
+    #[serde(default)]
+    pub monthDays: Vec<u8>,
+    /// Minutes after midnight in `timeZone`. Empty means one dose at the
+    /// time the schedule was anchored to.
+    #[serde(default)]
+    pub timesOfDay: Vec<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cycle: Option<RecurrenceCycle>,
+    /// IANA zone the days and times are read in, e.g. "Europe/Berlin". The
+    /// machine's local zone is used when unset or unknown.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeZone: Option<String>,
+}
+
+/// Alternating on and off phases, counted in days from `startDate`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RecurrenceCycle {
+    pub onDays: u32,
+    pub offDays: u32,
+    pub startDate: UnixTime,
+}
+
+/// A schedule and the period it was followed for. Dose changes end the old
+/// record and start a new one, so past regimens are kept.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Regimen {
+    pub id: String,
+    pub startDate: UnixTime,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub endDate: Option<UnixTime>,
+    pub schedule: RegimenSchedule,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub recurrence: Option<Recurrence>,
+}
+
+/// A titration or taper: dated steps that take over a regimen in turn.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DosePlan {
+    pub id: String,
+    pub name: String,
+    /// The regimen the plan drives; each applied step replaces it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub regimenId: Option<String>,
+    #[serde(default)]
+    pub steps: Vec<PlanStep>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PlanStep {
+    pub startDate: UnixTime,
+    /// `None` stops the medication, as at the end of a taper.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub schedule: Option<RegimenSchedule>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub recurrence: Option<Recurrence>,
+    #[serde(default)]
+    pub applied: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
 pub struct HrtData {
+    #[serde(default)]
+    pub regimens: Vec<Regimen>,
+    #[serde(default)]
+    pub plans: Vec<DosePlan>,
+    // Single schedules from before `regimens`; moved there on load.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub injectableEstradiol: Option<InjectableSchedule>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -443,6 +725,10 @@ pub struct HrtData {
     pub antiandrogen: Option<AntiandrogenSchedule>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub progesterone: Option<ProgesteroneSchedule>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub patch: Option<PatchSchedule>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gel: Option<GelSchedule>,
     #[serde(default)]
     pub bloodTests: Vec<BloodTest>,
     #[serde(default)]
@@ -453,6 +739,15 @@ pub struct HrtData {
     pub notes: Vec<DiaryEntry>,
     #[serde(default)]
     pub vials: Vec<Vial>,
+    #[serde(default)]
+    pub pkModels: Vec<CustomPkModel>,
+    #[serde(default)]
+    pub medications: Vec<Medication>,
+    /// Overrides for the built-in target ranges.
+    #[serde(default)]
+    pub targetRanges: Vec<ReferenceRange>,
+    #[serde(default)]
+    pub labRanges: Vec<LabRanges>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub settings: Option<Settings>,
 }
@@ -473,10 +768,80 @@ pub enum EstrannaiseModel {
     EUnIm,
     #[serde(rename = "EUn casubq")]
     EUnCasubq,
+    #[serde(rename = "PEP im")]
+    PepIm,
     #[serde(rename = "patch tw")]
     PatchTw,
     #[serde(rename = "patch ow")]
     PatchOw,
+    #[serde(rename = "E2 oral")]
+    E2Oral,
+    #[serde(rename = "E2 sl")]
+    E2Sublingual,
+    #[serde(rename = "E2 gel")]
+    E2Gel,
+    #[serde(rename = "custom")]
+    Custom(PkModelParams),
+    /// A patch with a known wear duration; it releases estradiol only until
+    /// removal, using the closer of the twice- and once-weekly fits.
+    #[serde(rename = "patch")]
+    Patch(PatchWear),
+}
+
+/// Parameters of a `Custom` model. Compared and hashed by bit pattern so the
+/// model can key the same maps as the built-in ones.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct PkModelParams {
+    pub d: f64,
+    pub k1: f64,
+    pub k2: f64,
+    pub k3: f64,
+}
+
+impl PkModelParams {
+    fn bits(&self) -> [u64; 4] {
+        [
+            self.d.to_bits(),
+            self.k1.to_bits(),
+            self.k2.to_bits(),
+            self.k3.to_bits(),
+        ]
+    }
+}
+
+impl PartialEq for PkModelParams {
+    fn eq(&self, other: &Self) -> bool {
+        self.bits() == other.bits()
+    }
+}
+
+impl Eq for PkModelParams {}
+
+impl std::hash::Hash for PkModelParams {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.bits().hash(state);
+    }
+}
+
+/// Wear duration of a `Patch` model, compared and hashed by bit pattern like
+/// `PkModelParams`.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct PatchWear {
+    pub wearDays: f64,
+}
+
+impl PartialEq for PatchWear {
+    fn eq(&self, other: &Self) -> bool {
+        self.wearDays.to_bits() == other.wearDays.to_bits()
+    }
+}
+
+impl Eq for PatchWear {}
+
+impl std::hash::Hash for PatchWear {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.wearDays.to_bits().hash(state);
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
@@ -525,4 +890,10 @@ pub enum Hormone {
     VitaminD3,
     #[serde(rename = "Vitamin B12")]
     VitaminB12,
+    #[serde(rename = "Creatinine")]
+    Creatinine,
+    #[serde(rename = "Glucose")]
+    Glucose,
+    #[serde(rename = "Triglycerides")]
+    Triglycerides,
 }
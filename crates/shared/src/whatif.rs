@@ -0,0 +1,289 @@
+#![allow(non_snake_case)]
+
+use serde::{Deserialize, Serialize};
+
+use crate::estrannaise::e2_multidose_series;
+use crate::types::{EstrannaiseModel, UnixTime};
+
+const DAY_MS: f64 = 24.0 * 60.0 * 60.0 * 1000.0;
+const PEAK_SAMPLES: usize = 96;
+
+pub type DoseEvent = (UnixTime, f64, EstrannaiseModel);
+
+/// A hypothetical change to the planned doses. `index` counts planned doses
+/// from the next one (0).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum DoseAdjustment {
+    Skip { index: usize },
+    Shift { index: usize, days: f64 },
+    Scale { index: usize, factor: f64 },
+    Add { date: UnixTime, dose: f64 },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CatchUpSuggestion {
+    /// Planned dose to change, counted like `DoseAdjustment` indexes.
+    pub index: usize,
+    pub date: UnixTime,
+    pub scheduledDose: f64,
+    pub suggestedDose: f64,
+    /// Levels just before the following dose, without and with the suggestion.
+    pub baselineTrough: f64,
+    pub simulatedTrough: f64,
+    /// True when the dose was limited to keep the peak under the baseline peak.
+    pub peakLimited: bool,
+}
+
+pub fn apply_dose_adjustments(
+    planned: &[DoseEvent],
+    adjustments: &[DoseAdjustment],
+) -> Vec<DoseEvent> {
+    let mut doses: Vec<Option<DoseEvent>> = planned.iter().cloned().map(Some).collect();
+    let fallback_model = planned.first().map(|(_, _, model)| model.clone());
+    let mut extra = Vec::new();
+    for adjustment in adjustments {
+        match adjustment {
+            DoseAdjustment::Skip { index } => {
+                if let Some(slot) = doses.get_mut(*index) {
+                    *slot = None;
+                }
+            }
+            DoseAdjustment::Shift { index, days } => {
+                if let Some(Some((date, _, _))) = doses.get_mut(*index) {
+                    *date += (days * DAY_MS) as i64;
+                }
+            }
+            DoseAdjustment::Scale { index, factor } => {
+                if let Some(Some((_, dose, _))) = doses.get_mut(*index) {
+                    *dose *= factor.max(0.0);
+                }
+            }
+            DoseAdjustment::Add { date, dose } => {
+                if let Some(model) = fallback_model.clone() {
+                    extra.push((*date, *dose, model));
+                }
+            }
+        }
+    }
+    let mut events: Vec<DoseEvent> = doses.into_iter().flatten().chain(extra).collect();
+    events.sort_by_key(|(date, _, _)| *date);
+    events
+}
+
+fn levels(events: &[DoseEvent], origin: UnixTime, dates: &[UnixTime], factor: f64) -> Vec<f64> {
+    let ts: Vec<f64> = dates
+        .iter()
+        .map(|date| (date - origin) as f64 / DAY_MS)
+        .collect();
+    let times: Vec<f64> = events
+        .iter()
+        .map(|(date, _, _)| (date - origin) as f64 / DAY_MS)
+        .collect();
+    let doses: Vec<f64> = events.iter().map(|(_, dose, _)| *dose).collect();
+    let models: Vec<EstrannaiseModel> = events.iter().map(|(_, _, model)| model.clone()).collect();
+    e2_multidose_series(&ts, &doses, &times, &models, factor, false)
+}
+
+/// Suggests a new amount for the first planned dose after every adjustment so
+/// the following trough returns to the baseline plan, without the peak going
+/// above the highest baseline level.
+pub fn suggest_catch_up(
+    history: &[DoseEvent],
+    planned: &[DoseEvent],
+    adjustments: &[DoseAdjustment],
+    conversion_factor: f64,
+) -> Option<CatchUpSuggestion> {
+    if adjustments.is_empty() {
+        return None;
+    }
+    let last_touched = adjustments
+        .iter()
+        .map(|adjustment| match adjustment {
+            DoseAdjustment::Skip { index }
+            | DoseAdjustment::Shift { index, .. }
+            | DoseAdjustment::Scale { index, .. } => *index as i64,
+            DoseAdjustment::Add { date, .. } => {
+                planned
+                    .iter()
+                    .filter(|(planned_date, _, _)| planned_date <= date)
+                    .count() as i64
+                    - 1
+            }
+        })
+        .max()?;
+    let index = (last_touched + 1) as usize;
+    let (date, scheduled_dose, model) = planned.get(index)?.clone();
+    let (next_date, _, _) = planned.get(index + 1)?.clone();
+
+    // Everything except the dose being tuned, which then scales linearly.
+    let mut without_tuned = adjustments.to_vec();
+    without_tuned.push(DoseAdjustment::Skip { index });
+    let baseline_events: Vec<DoseEvent> = history.iter().chain(planned).cloned().collect();
+    let other_events: Vec<DoseEvent> = history
+        .iter()
+        .cloned()
+        .chain(apply_dose_adjustments(planned, &without_tuned))
+        .collect();
+    let unit_event = [(date, 1.0, model)];
+
+    let origin = baseline_events.iter().map(|(date, _, _)| *date).min()?;
+    let trough_at = [next_date - 1];
+    let baseline_trough = levels(&baseline_events, origin, &trough_at, conversion_factor)[0];
+    let other_trough = levels(&other_events, origin, &trough_at, conversion_factor)[0];
+    let unit_trough = levels(&unit_event, origin, &trough_at, conversion_factor)[0];
+    if unit_trough <= 0.0 {
+        return None;
+    }
+    let target = (baseline_trough - other_trough) / unit_trough;
+
+    let window_start = planned.first()?.0;
+    let window_end = planned.last()?.0;
+    let step = ((window_end - window_start) / PEAK_SAMPLES as i64).max(1);
+    let window: Vec<UnixTime> = (0..=PEAK_SAMPLES as i64)
+        .map(|i| window_start + i * step)
+        .collect();
+    let baseline_peak = levels(&baseline_events, origin, &window, conversion_factor)
+        .into_iter()
+        .fold(0.0, f64::max);
+    let cycle_step = ((next_date - date) / PEAK_SAMPLES as i64).max(1);
+    let cycle: Vec<UnixTime> = (0..PEAK_SAMPLES as i64)
+        .map(|i| date + i * cycle_step)
+        .collect();
+    let other_cycle = levels(&other_events, origin, &cycle, conversion_factor);
+    let unit_cycle = levels(&unit_event, origin, &cycle, conversion_factor);
+    let cap = other_cycle
+        .iter()
+        .zip(&unit_cycle)
+        .filter(|(_, unit)| **unit > 1e-9)
+        .map(|(other, unit)| (baseline_peak - other) / unit)
+        .fold(f64::INFINITY, f64::min)
+        .max(0.0);
+
+    let peakLimited = target > cap;
+    let suggested = (target.min(cap).max(0.0) * 100.0).round() / 100.0;
+    Some(CatchUpSuggestion {
+        index,
+        date,
+        scheduledDose: scheduled_dose,
+        suggestedDose: suggested,
+        baselineTrough: baseline_trough,
+        simulatedTrough: other_trough + unit_trough * suggested,
+        peakLimited,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const START: i64 = 1_700_000_000_000;
+    const WEEK: i64 = 7 * 24 * 60 * 60 * 1000;
+
+    fn weekly(from: i64, count: i64) -> Vec<DoseEvent> {
+        (0..count)
+            .map(|i| (START + (from + i) * WEEK, 4.0, EstrannaiseModel::EvIm))
+            .collect()
+    }
+
+    #[test]
+    fn adjustments_skip_shift_scale_and_add() {
+        let planned = weekly(0, 4);
+        let adjusted = apply_dose_adjustments(
+            &planned,
+            &[
+                DoseAdjustment::Skip { index: 0 },
+                DoseAdjustment::Shift {
+                    index: 1,
+                    days: 2.0,
+                },
+                DoseAdjustment::Scale {
+                    index: 2,
+                    factor: 2.0,
+                },
+                DoseAdjustment::Add {
+                    date: START + 1,
+                    dose: 1.0,
+                },
+            ],
+        );
+        assert_eq!(adjusted.len(), 4);
+        assert_eq!(adjusted[0], (START + 1, 1.0, EstrannaiseModel::EvIm));
+        assert_eq!(adjusted[1].0, START + WEEK + 2 * 24 * 60 * 60 * 1000);
+        assert_eq!(adjusted[2].1, 8.0);
+        assert_eq!(adjusted[3], planned[3]);
+    }
+
+    #[test]
+    fn no_adjustments_no_suggestion() {
+        assert!(suggest_catch_up(&weekly(-8, 8), &weekly(0, 4), &[], 1.0).is_none());
+    }
+
+    #[test]
+    fn skipped_dose_is_caught_up_without_overshooting() {
+        let history = weekly(-12, 12);
+        let planned = weekly(0, 4);
+        let suggestion = suggest_catch_up(
+            &history,
+            &planned,
+            &[DoseAdjustment::Skip { index: 0 }],
+            1.0,
+        )
+        .unwrap();
+        assert_eq!(suggestion.index, 1);
+        assert!(suggestion.suggestedDose > 4.0, "{suggestion:?}");
+        assert!(suggestion.simulatedTrough <= suggestion.baselineTrough * 1.01);
+
+        let mut adjusted = apply_dose_adjustments(&planned, &[DoseAdjustment::Skip { index: 0 }]);
+        adjusted[0].1 = suggestion.suggestedDose;
+        let events: Vec<DoseEvent> = history.iter().cloned().chain(adjusted).collect();
+        let baseline: Vec<DoseEvent> = history.iter().cloned().chain(planned.clone()).collect();
+        let window: Vec<i64> = (0..=200).map(|i| START + i * 4 * WEEK / 200).collect();
+        let peak = |events: &[DoseEvent]| {
+            levels(events, history[0].0, &window, 1.0)
+                .into_iter()
+                .fold(0.0, f64::max)
+        };
+        assert!(peak(&events) <= peak(&baseline) * 1.01);
+    }
+
+    #[test]
+    fn doubled_dose_suggests_less_next_time() {
+        let history = weekly(-12, 12);
+        let planned = weekly(0, 4);
+        let suggestion = suggest_catch_up(
+            &history,
+            &planned,
+            &[DoseAdjustment::Scale {
+                index: 0,
+                factor: 2.0,
+            }],
+            1.0,
+        )
+        .unwrap();
+        assert!(suggestion.suggestedDose < 4.0, "{suggestion:?}");
+    }
+
+    #[test]
+    fn late_dose_suggestion_targets_following_dose() {
+        let history = weekly(-12, 12);
+        let planned = weekly(0, 4);
+        let suggestion = suggest_catch_up(
+            &history,
+            &planned,
+            &[DoseAdjustment::Shift {
+                index: 0,
+                days: 2.0,
+            }],
+            1.0,
+        )
+        .unwrap();
+        assert_eq!(suggestion.index, 1);
+        assert!(suggestion.suggestedDose <= 4.0, "{suggestion:?}");
+        assert!(
+            (suggestion.simulatedTrough - suggestion.baselineTrough).abs()
+                / suggestion.baselineTrough
+                < 0.05
+        );
+    }
+}
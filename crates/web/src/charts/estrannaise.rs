@@ -6,6 +6,9 @@ use plotters_canvas::CanvasBackend;
 use wasm_bindgen::JsCast;
 use web_sys::HtmlCanvasElement;
 
+use hrt_shared::logic::PkFit;
+use hrt_shared::whatif::CatchUpSuggestion;
+
 use crate::charts::{
     data_to_canvas_x, data_to_canvas_y, ChartTooltip, ViewZoom, CHART_MARGIN, CHART_X_LABEL,
     CHART_Y_LABEL,
@@ -22,6 +25,15 @@ pub struct EstrannaisePoint {
 pub struct EstrannaiseSeries {
     pub blended: Vec<EstrannaisePoint>,
     pub stepped: Vec<EstrannaisePoint>,
+    pub fitted: Vec<EstrannaisePoint>,
+    pub fit: Option<PkFit>,
+    /// (x, lower, upper) population-variability band around the blended line.
+    pub band: Vec<(f64, f64, f64)>,
+    /// Blended line with the what-if dose adjustments applied.
+    pub whatif: Vec<EstrannaisePoint>,
+    /// Upcoming forecast doses as (date, dose in mg of the scheduled drug).
+    pub planned_doses: Vec<(i64, f64)>,
+    pub catch_up: Option<CatchUpSuggestion>,
     pub blood: Vec<EstrannaisePoint>,
     pub forecast: Option<(f64, f64)>,
     pub step_split_x: Option<f64>,
@@ -152,6 +164,21 @@ pub fn draw_estrannaise_chart(canvas_id: &str, series: &EstrannaiseSeries, zoom:
             .ok();
     }
 
+    if series.band.len() > 1 {
+        let outline = series
+            .band
+            .iter()
+            .map(|(x, lower, _)| (*x, *lower))
+            .chain(series.band.iter().rev().map(|(x, _, upper)| (*x, *upper)))
+            .collect::<Vec<_>>();
+        chart
+            .draw_series(std::iter::once(Polygon::new(
+                outline,
+                RGBAColor(46, 134, 171, 0.18).filled(),
+            )))
+            .ok();
+    }
+
     if !series.blended.is_empty() {
         let blended_style = ShapeStyle::from(&RGBColor(46, 134, 171)).stroke_width(2);
         if let Some((split_x, _)) = series.forecast {
@@ -220,6 +247,23 @@ pub fn draw_estrannaise_chart(canvas_id: &str, series: &EstrannaiseSeries, zoom:
             }
         }
     }
+    if series.whatif.len() > 1 {
+        let whatif_style = ShapeStyle::from(&RGBColor(196, 167, 231)).stroke_width(2);
+        let line = series.whatif.iter().map(|p| (p.x, p.y)).collect::<Vec<_>>();
+        chart
+            .draw_series(std::iter::once(DashedPathElement::new(
+                line,
+                8,
+                4,
+                whatif_style,
+            )))
+            .ok();
+    }
+    if series.fitted.len() > 1 {
+        let fitted_style = ShapeStyle::from(&RGBColor(112, 201, 140)).stroke_width(2);
+        let line = series.fitted.iter().map(|p| (p.x, p.y));
+        chart.draw_series(LineSeries::new(line, fitted_style)).ok();
+    }
     for point in &series.blood {
         chart
             .draw_series(std::iter::once(Circle::new(
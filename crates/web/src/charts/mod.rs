@@ -31,6 +31,17 @@ pub fn chart_padding() -> (f64, f64, f64, f64) {
     )
 }
 
+/// `chart_padding` with room for a right-hand axis when `secondary_axis`.
+pub fn chart_padding_with(secondary_axis: bool) -> (f64, f64, f64, f64) {
+    let (left, top, right, bottom) = chart_padding();
+    let right = if secondary_axis {
+        right + CHART_Y_LABEL
+    } else {
+        right
+    };
+    (left, top, right, bottom)
+}
+
 pub fn clamp_zoom(domain_min: f64, domain_max: f64, new_min: f64, new_max: f64) -> ViewZoom {
     let full_span = domain_max - domain_min;
     let span = (new_max - new_min).max(1.0);
@@ -1,5 +1,6 @@
 use chrono::{Local, TimeZone};
 use leptos::window;
+use plotters::element::DashedPathElement;
 use plotters::prelude::*;
 use plotters_canvas::CanvasBackend;
 use wasm_bindgen::JsCast;
@@ -9,12 +10,25 @@ use crate::charts::{
     data_to_canvas_x, data_to_canvas_y, ChartTooltip, ViewZoom, CHART_MARGIN, CHART_X_LABEL,
     CHART_Y_LABEL,
 };
+use crate::estrannaise::{band_dates, cached_e2_band};
 use crate::utils::{
     convert_estradiol_to_display, convert_fsh_to_miu_ml, convert_lh_to_miu_ml,
-    convert_progesterone_to_ng_ml, convert_testosterone_to_ng_dl, fmt_blood_value, fmt_date_label,
-    hormone_unit_label,
+    convert_progesterone_to_ng_ml, convert_prolactin_to_miu_l, convert_testosterone_to_ng_dl,
+    effective_fai, fmt_blood_value, fmt_date_label, range_flag_note,
+};
+use hrt_shared::estrannaise::estradiol_dose_events;
+use hrt_shared::free_hormones::calculate_levels;
+use hrt_shared::lab_results::lab_result_series;
+use hrt_shared::medication_pk::{
+    antiandrogen_sample_times, antiandrogen_series, progesterone_sample_times, progesterone_series,
+};
+use hrt_shared::medications::entry_medication_name;
+use hrt_shared::quantity::{BloodAnalyte, Quantity};
+use hrt_shared::ranges::{assess_blood_test, RangeFlag};
+use hrt_shared::transdermal::patch_total_strength;
+use hrt_shared::types::{
+    Antiandrogens, BloodTest, DosageHistoryEntry, HormoneUnits, HrtData, Settings,
 };
-use hrt_shared::types::{BloodTest, DosageHistoryEntry, HormoneUnits, HrtData, Settings};
 
 fn inferred_fudge_factor(test: &BloodTest) -> Option<f64> {
     if let Some(value) = test.fudgeFactor {
@@ -23,18 +37,7 @@ fn inferred_fudge_factor(test: &BloodTest) -> Option<f64> {
         }
     }
 
-    let measured = test.estradiolLevel?;
-    if !measured.is_finite() {
-        return None;
-    }
-    let measured_pg_ml = if test.estradiolUnit == Some(HormoneUnits::E2PmolL) {
-        measured / 3.671
-    } else {
-        measured
-    };
-    if !measured_pg_ml.is_finite() {
-        return None;
-    }
+    let measured_pg_ml = test.level_in(BloodAnalyte::Estradiol, HormoneUnits::E2PgMl)?;
 
     let predicted = test
         .estrannaiseNumber
@@ -53,8 +56,21 @@ pub struct ViewChartPoint {
     pub y: f64,
     pub label: String,
     pub color: RGBColor,
+    /// Outside the target or lab range; drawn with a ring.
+    pub flagged: bool,
+}
+
+fn flagged_label(tooltip: String, note: Option<String>) -> (String, bool) {
+    match note {
+        Some(note) => (format!("{} · {}", tooltip, note), true),
+        None => (tooltip, false),
+    }
 }
 
+type ModelLine = (RGBColor, Vec<(f64, f64)>);
+/// Medication name, color and (x, % of peak) points.
+pub type LoadLine = (&'static str, RGBColor, Vec<(f64, f64)>);
+
 #[derive(Clone, Debug, Default, PartialEq)]
 pub struct ViewChartState {
     pub domain_min: f64,
@@ -65,6 +81,13 @@ pub struct ViewChartState {
     pub y_label: String,
     pub points: Vec<ViewChartPoint>,
     pub dosage_points: Vec<ViewChartPoint>,
+    /// (x, lower, upper) predicted estradiol range in display units.
+    pub e2_band: Vec<(f64, f64, f64)>,
+    /// Modeled progesterone (ng/mL) curves on the levels axis.
+    pub model_lines: Vec<ModelLine>,
+    /// Modeled antiandrogen body load as a percentage of each curve's own
+    /// peak, drawn against a secondary 0–100% axis.
+    pub load_lines: Vec<LoadLine>,
     pub first_dose: Option<i64>,
     pub use_days: bool,
     pub has_data: bool,
@@ -80,12 +103,19 @@ pub fn compute_view_chart_state(
     show_e2: bool,
     show_t: bool,
     show_prog: bool,
+    show_prog_model: bool,
+    show_aa_model: bool,
     show_fsh: bool,
     show_lh: bool,
     show_prolactin: bool,
     show_shbg: bool,
     show_fai: bool,
+    show_free_t: bool,
+    show_bio_t: bool,
+    show_free_e2: bool,
+    lab_result: &str,
     show_fudge_factor: bool,
+    e2_band: &[(i64, f64, f64)],
 ) -> ViewChartState {
     let now = js_sys::Date::now() as i64;
     let start_time = now - time_range_days * 24 * 60 * 60 * 1000;
@@ -100,7 +130,10 @@ pub fn compute_view_chart_state(
             DosageHistoryEntry::InjectableEstradiol { date, .. }
             | DosageHistoryEntry::OralEstradiol { date, .. }
             | DosageHistoryEntry::Antiandrogen { date, .. }
-            | DosageHistoryEntry::Progesterone { date, .. } => *date,
+            | DosageHistoryEntry::Progesterone { date, .. }
+            | DosageHistoryEntry::Patch { date, .. }
+            | DosageHistoryEntry::Gel { date, .. }
+            | DosageHistoryEntry::Medication { date, .. } => *date,
         })
         .min();
 
@@ -115,32 +148,49 @@ pub fn compute_view_chart_state(
     let mut all_values = Vec::new();
     let mut has_data = false;
 
-    for test in data.bloodTests.iter().filter(|t| t.date >= start_time) {
-        let x = if use_days {
-            (test.date - first_dose.unwrap_or(test.date)) as f64 / (24.0 * 60.0 * 60.0 * 1000.0)
+    let x_for_date = |date: i64| {
+        if use_days {
+            (date - first_dose.unwrap_or(date)) as f64 / (24.0 * 60.0 * 60.0 * 1000.0)
         } else {
-            test.date as f64
-        };
-        let date_label = fmt_date_label(test.date, axis_mode, first_dose);
-        let date_short = if use_days {
-            date_label.clone()
+            date as f64
+        }
+    };
+    let short_date = |date: i64| {
+        let date_label = fmt_date_label(date, axis_mode, first_dose);
+        if use_days {
+            date_label
         } else {
             Local
-                .timestamp_millis_opt(test.date)
+                .timestamp_millis_opt(date)
                 .single()
                 .map(|d| d.format("%b %d").to_string())
-                .unwrap_or_else(|| date_label.clone())
+                .unwrap_or(date_label)
+        }
+    };
+
+    for test in data.bloodTests.iter().filter(|t| t.date >= start_time) {
+        let assessments = assess_blood_test(data, test);
+        let flag_note = |analyte: BloodAnalyte| {
+            assessments
+                .iter()
+                .find(|assessment| assessment.analyte == analyte)
+                .and_then(range_flag_note)
         };
+        let x = x_for_date(test.date);
+        let date_short = short_date(test.date);
         if show_e2 {
-            if let Some(value) = test.estradiolLevel {
-                let raw_unit = test.estradiolUnit.clone().unwrap_or(display_unit.clone());
+            if let Some(Quantity {
+                value,
+                unit: raw_unit,
+            }) = test.estradiol.clone()
+            {
                 let plot_val = convert_estradiol_to_display(value, &raw_unit, &display_unit);
-                let unit_label = hormone_unit_label(&display_unit);
+                let unit_label = display_unit.symbol();
                 let tooltip = if raw_unit != display_unit {
                     format!(
                         "Estradiol: {} {} -> {} {} ({})",
                         fmt_blood_value(value),
-                        hormone_unit_label(&raw_unit),
+                        raw_unit.symbol(),
                         fmt_blood_value(plot_val),
                         unit_label,
                         date_short
@@ -153,10 +203,12 @@ pub fn compute_view_chart_state(
                         date_short
                     )
                 };
+                let (label, flagged) = flagged_label(tooltip, flag_note(BloodAnalyte::Estradiol));
                 points.push(ViewChartPoint {
                     x,
                     y: plot_val,
-                    label: tooltip,
+                    label,
+                    flagged,
                     color: RGBColor(70, 130, 180),
                 });
                 all_values.push(plot_val);
@@ -164,15 +216,18 @@ pub fn compute_view_chart_state(
             }
         }
         if show_t {
-            if let Some(value) = test.testLevel {
-                let raw_unit = test.testUnit.clone().unwrap_or(HormoneUnits::TNmolL);
+            if let Some(Quantity {
+                value,
+                unit: raw_unit,
+            }) = test.testosterone.clone()
+            {
                 let plot_val = convert_testosterone_to_ng_dl(value, &raw_unit);
                 let unit_label = "ng/dL";
                 let tooltip = if raw_unit != HormoneUnits::TNgDl {
                     format!(
                         "Testosterone: {} {} -> {} {} ({})",
                         fmt_blood_value(value),
-                        hormone_unit_label(&raw_unit),
+                        raw_unit.symbol(),
                         fmt_blood_value(plot_val),
                         unit_label,
                         date_short
@@ -185,10 +240,13 @@ pub fn compute_view_chart_state(
                         date_short
                     )
                 };
+                let (label, flagged) =
+                    flagged_label(tooltip, flag_note(BloodAnalyte::Testosterone));
                 points.push(ViewChartPoint {
                     x,
                     y: plot_val,
-                    label: tooltip,
+                    label,
+                    flagged,
                     color: RGBColor(220, 20, 60),
                 });
                 all_values.push(plot_val);
@@ -196,21 +254,24 @@ pub fn compute_view_chart_state(
             }
         }
         if show_prog {
-            if let Some(value) = test.progesteroneLevel {
-                let raw_unit = test
-                    .progesteroneUnit
-                    .clone()
-                    .unwrap_or(HormoneUnits::TNmolL);
+            if let Some(Quantity {
+                value,
+                unit: raw_unit,
+            }) = test.progesterone.clone()
+            {
                 let plot_val = convert_progesterone_to_ng_ml(value, &raw_unit);
                 let tooltip = format!(
                     "Progesterone: {} ng/mL ({})",
                     fmt_blood_value(plot_val),
                     date_short
                 );
+                let (label, flagged) =
+                    flagged_label(tooltip, flag_note(BloodAnalyte::Progesterone));
                 points.push(ViewChartPoint {
                     x,
                     y: plot_val,
-                    label: tooltip,
+                    label,
+                    flagged,
                     color: RGBColor(148, 0, 211),
                 });
                 all_values.push(plot_val);
@@ -218,14 +279,19 @@ pub fn compute_view_chart_state(
             }
         }
         if show_fsh {
-            if let Some(value) = test.fshLevel {
-                let raw_unit = test.fshUnit.clone().unwrap_or(HormoneUnits::UL);
+            if let Some(Quantity {
+                value,
+                unit: raw_unit,
+            }) = test.fsh.clone()
+            {
                 let plot_val = convert_fsh_to_miu_ml(value, &raw_unit);
                 let tooltip = format!("FSH: {} mIU/mL ({})", fmt_blood_value(plot_val), date_short);
+                let (label, flagged) = flagged_label(tooltip, flag_note(BloodAnalyte::Fsh));
                 points.push(ViewChartPoint {
                     x,
                     y: plot_val,
-                    label: tooltip,
+                    label,
+                    flagged,
                     color: RGBColor(34, 139, 34),
                 });
                 all_values.push(plot_val);
@@ -233,14 +299,19 @@ pub fn compute_view_chart_state(
             }
         }
         if show_lh {
-            if let Some(value) = test.lhLevel {
-                let raw_unit = test.lhUnit.clone().unwrap_or(HormoneUnits::UL);
+            if let Some(Quantity {
+                value,
+                unit: raw_unit,
+            }) = test.lh.clone()
+            {
                 let plot_val = convert_lh_to_miu_ml(value, &raw_unit);
                 let tooltip = format!("LH: {} mIU/mL ({})", fmt_blood_value(plot_val), date_short);
+                let (label, flagged) = flagged_label(tooltip, flag_note(BloodAnalyte::Lh));
                 points.push(ViewChartPoint {
                     x,
                     y: plot_val,
-                    label: tooltip,
+                    label,
+                    flagged,
                     color: RGBColor(0, 139, 139),
                 });
                 all_values.push(plot_val);
@@ -248,39 +319,48 @@ pub fn compute_view_chart_state(
             }
         }
         if show_prolactin {
-            if let Some(value) = test.prolactinLevel {
-                let raw_unit = test.prolactinUnit.clone().unwrap_or(HormoneUnits::MIuL);
-                let unit_label = hormone_unit_label(&raw_unit);
+            if let Some(Quantity {
+                value,
+                unit: raw_unit,
+            }) = test.prolactin.clone()
+            {
+                let plot_val = convert_prolactin_to_miu_l(value, &raw_unit);
                 let tooltip = format!(
-                    "Prolactin: {} {} ({})",
-                    fmt_blood_value(value),
-                    unit_label,
+                    "Prolactin: {} mIU/L ({})",
+                    fmt_blood_value(plot_val),
                     date_short
                 );
+                let (label, flagged) = flagged_label(tooltip, flag_note(BloodAnalyte::Prolactin));
                 points.push(ViewChartPoint {
                     x,
-                    y: value,
-                    label: tooltip,
+                    y: plot_val,
+                    label,
+                    flagged,
                     color: RGBColor(139, 69, 19),
                 });
-                all_values.push(value);
+                all_values.push(plot_val);
                 has_data = true;
             }
         }
         if show_shbg {
-            if let Some(value) = test.shbgLevel {
-                let raw_unit = test.shbgUnit.clone().unwrap_or(HormoneUnits::TNmolL);
-                let unit_label = hormone_unit_label(&raw_unit);
+            if let Some(Quantity {
+                value,
+                unit: raw_unit,
+            }) = test.shbg.clone()
+            {
+                let unit_label = raw_unit.symbol();
                 let tooltip = format!(
                     "SHBG: {} {} ({})",
                     fmt_blood_value(value),
                     unit_label,
                     date_short
                 );
+                let (label, flagged) = flagged_label(tooltip, flag_note(BloodAnalyte::Shbg));
                 points.push(ViewChartPoint {
                     x,
                     y: value,
-                    label: tooltip,
+                    label,
+                    flagged,
                     color: RGBColor(255, 20, 147),
                 });
                 all_values.push(value);
@@ -288,18 +368,58 @@ pub fn compute_view_chart_state(
             }
         }
         if show_fai {
-            if let Some(value) = test.freeAndrogenIndex {
-                let tooltip = format!("FAI: {} ({})", fmt_blood_value(value), date_short);
+            if let Some(value) = effective_fai(test) {
+                let source = if test.freeAndrogenIndex.is_some_and(|fai| fai > 0.0) {
+                    ""
+                } else {
+                    " (calc)"
+                };
+                let tooltip = format!("FAI{}: {} ({})", source, fmt_blood_value(value), date_short);
                 points.push(ViewChartPoint {
                     x,
                     y: value,
                     label: tooltip,
                     color: RGBColor(0, 0, 0),
+                    flagged: false,
                 });
                 all_values.push(value);
                 has_data = true;
             }
         }
+        if show_free_t || show_bio_t || show_free_e2 {
+            let levels = calculate_levels(test);
+            let calculated = [
+                (show_free_t, "Free T", levels.freeTestosterone, RGBColor(178, 34, 34)),
+                (
+                    show_bio_t,
+                    "Bioavailable T",
+                    levels.bioavailableTestosterone,
+                    RGBColor(205, 92, 92),
+                ),
+                (show_free_e2, "Free E2", levels.freeEstradiol, RGBColor(100, 149, 237)),
+            ];
+            for (show, name, quantity, color) in calculated {
+                let Some(quantity) = quantity.filter(|_| show) else {
+                    continue;
+                };
+                let tooltip = format!(
+                    "{} (calc): {} {} ({})",
+                    name,
+                    fmt_blood_value(quantity.value),
+                    quantity.unit.symbol(),
+                    date_short
+                );
+                points.push(ViewChartPoint {
+                    x,
+                    y: quantity.value,
+                    label: tooltip,
+                    color,
+                    flagged: false,
+                });
+                all_values.push(quantity.value);
+                has_data = true;
+            }
+        }
         if show_fudge_factor {
             if let Some(fudge_factor) = inferred_fudge_factor(test) {
                 let efficacy_percent = fudge_factor * 100.0;
@@ -314,6 +434,7 @@ pub fn compute_view_chart_state(
                     y: efficacy_percent,
                     label: tooltip,
                     color: RGBColor(255, 99, 132),
+                    flagged: false,
                 });
                 all_values.push(efficacy_percent);
                 has_data = true;
@@ -321,6 +442,36 @@ pub fn compute_view_chart_state(
         }
     }
 
+    if !lab_result.is_empty() {
+        let series = lab_result_series(data, lab_result);
+        for point in series.points.iter().filter(|p| p.date >= start_time) {
+            let flagged = point.flag.is_some_and(|flag| flag.is_out_of_range());
+            let mut label = format!(
+                "{}: {} {} ({})",
+                series.name,
+                fmt_blood_value(point.value),
+                series.unit,
+                short_date(point.date)
+            );
+            if flagged {
+                label.push_str(if point.flag == Some(RangeFlag::Low) {
+                    " · low"
+                } else {
+                    " · high"
+                });
+            }
+            points.push(ViewChartPoint {
+                x: x_for_date(point.date),
+                y: point.value,
+                label,
+                flagged,
+                color: RGBColor(46, 139, 87),
+            });
+            all_values.push(point.value);
+            has_data = true;
+        }
+    }
+
     let mut dosage_points = Vec::new();
     if show_medications {
         for dose in data.dosageHistory.iter() {
@@ -328,7 +479,10 @@ pub fn compute_view_chart_state(
                 DosageHistoryEntry::InjectableEstradiol { date, .. }
                 | DosageHistoryEntry::OralEstradiol { date, .. }
                 | DosageHistoryEntry::Antiandrogen { date, .. }
-                | DosageHistoryEntry::Progesterone { date, .. } => *date,
+                | DosageHistoryEntry::Progesterone { date, .. }
+                | DosageHistoryEntry::Patch { date, .. }
+                | DosageHistoryEntry::Gel { date, .. }
+                | DosageHistoryEntry::Medication { date, .. } => *date,
             };
             if date < start_time {
                 continue;
@@ -337,45 +491,57 @@ pub fn compute_view_chart_state(
                 DosageHistoryEntry::InjectableEstradiol {
                     kind, dose, unit, ..
                 } => (
-                    format!(
-                        "Injection: {:?}, {:.2} {}",
-                        kind,
-                        dose,
-                        hormone_unit_label(unit)
-                    ),
+                    format!("Injection: {:?}, {:.2} {}", kind, dose, unit.symbol()),
                     (*dose * 20.0).min(300.0),
                     RGBColor(0, 114, 178),
                 ),
                 DosageHistoryEntry::OralEstradiol {
                     kind, dose, unit, ..
                 } => (
-                    format!(
-                        "Oral E: {:?}, {:.2} {}",
-                        kind,
-                        dose,
-                        hormone_unit_label(unit)
-                    ),
+                    format!("Oral E: {:?}, {:.2} {}", kind, dose, unit.symbol()),
                     (*dose * 10.0).min(200.0),
                     RGBColor(46, 139, 87),
                 ),
                 DosageHistoryEntry::Antiandrogen {
                     kind, dose, unit, ..
                 } => (
-                    format!("AA: {:?}, {:.2} {}", kind, dose, hormone_unit_label(unit)),
+                    format!("AA: {:?}, {:.2} {}", kind, dose, unit.symbol()),
                     (*dose * 10.0).min(200.0),
                     RGBColor(255, 140, 0),
                 ),
                 DosageHistoryEntry::Progesterone {
                     kind, dose, unit, ..
                 } => (
+                    format!("Progesterone: {:?}, {:.2} {}", kind, dose, unit.symbol()),
+                    (*dose).min(400.0),
+                    RGBColor(255, 215, 0),
+                ),
+                DosageHistoryEntry::Patch {
+                    strength,
+                    patchQuantity,
+                    ..
+                } => {
+                    let total = patch_total_strength(*strength, *patchQuantity);
+                    (
+                        format!("Patch: {:.0} µg/day", total),
+                        (total * 2.0).min(300.0),
+                        RGBColor(204, 121, 167),
+                    )
+                }
+                DosageHistoryEntry::Gel { dose, unit, .. } => (
+                    format!("Gel: {:.2} {}", dose, unit.symbol()),
+                    (*dose * 20.0).min(300.0),
+                    RGBColor(86, 180, 233),
+                ),
+                entry @ DosageHistoryEntry::Medication { dose, unit, .. } => (
                     format!(
-                        "Progesterone: {:?}, {:.2} {}",
-                        kind,
+                        "{}: {:.2} {}",
+                        entry_medication_name(data, entry),
                         dose,
-                        hormone_unit_label(unit)
+                        unit.symbol()
                     ),
-                    (*dose).min(400.0),
-                    RGBColor(255, 215, 0),
+                    (*dose * 10.0).min(200.0),
+                    RGBColor(148, 103, 189),
                 ),
             };
             let x = if use_days {
@@ -389,6 +555,7 @@ pub fn compute_view_chart_state(
                 y: value,
                 label: format!("{} ({})", label, date_label),
                 color,
+                flagged: false,
             });
 
             all_values.push(value);
@@ -396,6 +563,37 @@ pub fn compute_view_chart_state(
         }
     }
 
+    let e2_band: Vec<(f64, f64, f64)> = if show_e2 {
+        e2_band
+            .iter()
+            .map(|(date, lower, upper)| {
+                (
+                    x_for_date(*date),
+                    convert_estradiol_to_display(*lower, &HormoneUnits::E2PgMl, &display_unit),
+                    convert_estradiol_to_display(*upper, &HormoneUnits::E2PgMl, &display_unit),
+                )
+            })
+            .collect()
+    } else {
+        Vec::new()
+    };
+    all_values.extend(e2_band.iter().map(|(_, _, upper)| *upper));
+
+    let (model_lines, load_lines) = medication_model_lines(
+        data,
+        start_time,
+        now,
+        use_days,
+        first_dose,
+        show_prog_model,
+        show_aa_model,
+    );
+    for (_, line) in &model_lines {
+        all_values.extend(line.iter().map(|(_, y)| *y));
+        has_data = true;
+    }
+    has_data |= !load_lines.is_empty();
+
     if all_values.is_empty() {
         all_values.push(0.0);
         all_values.push(1.0);
@@ -439,12 +637,142 @@ pub fn compute_view_chart_state(
         y_label: "Levels".to_string(),
         points,
         dosage_points,
+        e2_band,
+        model_lines,
+        load_lines,
         first_dose,
         use_days,
         has_data,
     }
 }
 
+impl ViewChartState {
+    pub fn has_secondary_axis(&self) -> bool {
+        !self.load_lines.is_empty()
+    }
+}
+
+const MODEL_LINE_POINTS: usize = 400;
+
+// Progesterone in ng/mL, and antiandrogens as % of their peak body load:
+// their mg amounts are not concentrations and differ by orders of magnitude.
+fn medication_model_lines(
+    data: &HrtData,
+    start_time: i64,
+    now: i64,
+    use_days: bool,
+    first_dose: Option<i64>,
+    show_prog_model: bool,
+    show_aa_model: bool,
+) -> (Vec<ModelLine>, Vec<LoadLine>) {
+    let day_ms = 24.0 * 60.0 * 60.0 * 1000.0;
+    let from = match first_dose {
+        Some(first) => start_time.max(first),
+        None => return (Vec::new(), Vec::new()),
+    };
+    if from >= now {
+        return (Vec::new(), Vec::new());
+    }
+    let to_line = |dates: Vec<i64>, values: Vec<f64>| -> Vec<(f64, f64)> {
+        dates
+            .iter()
+            .zip(values)
+            .map(|(date, y)| {
+                let x = if use_days {
+                    (*date - first_dose.unwrap_or(*date)) as f64 / day_ms
+                } else {
+                    *date as f64
+                };
+                (x, y)
+            })
+            .collect()
+    };
+    let has_entry = |pred: &dyn Fn(&DosageHistoryEntry) -> bool| {
+        data.dosageHistory.iter().any(pred)
+    };
+
+    let mut lines = Vec::new();
+    if show_prog_model
+        && has_entry(&|entry| matches!(entry, DosageHistoryEntry::Progesterone { .. }))
+    {
+        let dates = progesterone_sample_times(&data.dosageHistory, from, now, MODEL_LINE_POINTS);
+        let values = progesterone_series(&data.dosageHistory, &dates);
+        lines.push((RGBColor(186, 85, 211), to_line(dates, values)));
+    }
+    let mut loads = Vec::new();
+    if show_aa_model {
+        let kinds = [
+            (Antiandrogens::CPA, "CPA", RGBColor(255, 140, 0)),
+            (
+                Antiandrogens::Spiro,
+                "Spironolactone",
+                RGBColor(255, 99, 71),
+            ),
+            (Antiandrogens::Bica, "Bicalutamide", RGBColor(218, 165, 32)),
+            (
+                Antiandrogens::Finasteride,
+                "Finasteride",
+                RGBColor(205, 133, 63),
+            ),
+        ];
+        for (kind, name, color) in kinds {
+            if has_entry(
+                &|entry| matches!(entry, DosageHistoryEntry::Antiandrogen { kind: k, .. } if *k == kind),
+            ) {
+                let dates = antiandrogen_sample_times(
+                    &data.dosageHistory,
+                    &kind,
+                    from,
+                    now,
+                    MODEL_LINE_POINTS,
+                );
+                let values = antiandrogen_series(&data.dosageHistory, &kind, &dates);
+                let peak = values.iter().cloned().fold(0.0, f64::max);
+                if peak > 0.0 {
+                    let percent = values.iter().map(|v| v / peak * 100.0).collect();
+                    loads.push((name, color, to_line(dates, percent)));
+                }
+            }
+        }
+    }
+    (lines, loads)
+}
+
+/// Predicted estradiol range (date, lower, upper) in pg/mL from `start_time`
+/// to `now`, scaled by the latest fudge factor.
+pub fn predicted_e2_band(data: &HrtData, start_time: i64, now: i64) -> Vec<(i64, f64, f64)> {
+    let day_ms = 24.0 * 60.0 * 60.0 * 1000.0;
+    let events = estradiol_dose_events(data);
+    let Some(first_e2) = events.first().map(|(date, _, _)| *date) else {
+        return Vec::new();
+    };
+    let fudge = data
+        .bloodTests
+        .iter()
+        .filter(|test| test.estradiol.is_some())
+        .max_by_key(|test| test.date)
+        .and_then(inferred_fudge_factor)
+        .unwrap_or(1.0);
+
+    let times: Vec<f64> = events
+        .iter()
+        .map(|(date, _, _)| (*date - first_e2) as f64 / day_ms)
+        .collect();
+    let doses: Vec<f64> = events.iter().map(|(_, dose, _)| *dose).collect();
+    let models: Vec<_> = events.into_iter().map(|(_, _, model)| model).collect();
+    let dates = band_dates(first_e2, start_time, now);
+    let ts: Vec<f64> = dates
+        .iter()
+        .map(|date| (*date - first_e2) as f64 / day_ms)
+        .collect();
+    let band = cached_e2_band(&ts, &doses, &times, &models);
+    dates
+        .into_iter()
+        .zip(band.lower.iter().zip(&band.upper))
+        .map(|(date, (lower, upper))| (date, lower * fudge, upper * fudge))
+        .collect()
+}
+
 #[allow(clippy::too_many_arguments)]
 pub fn find_nearest_point(
     points: &[ViewChartPoint],
@@ -507,13 +835,19 @@ pub fn draw_view_chart(canvas_id: &str, state: &ViewChartState, zoom: ViewZoom)
 
     let x_min = zoom.x_min.unwrap_or(state.domain_min);
     let x_max = zoom.x_max.unwrap_or(state.domain_max);
+    let secondary_axis = state.has_secondary_axis();
     let mut chart = match ChartBuilder::on(&backend)
         .margin(CHART_MARGIN as i32)
         .x_label_area_size(CHART_X_LABEL as i32)
         .y_label_area_size(CHART_Y_LABEL as i32)
+        .right_y_label_area_size(if secondary_axis {
+            CHART_Y_LABEL as i32
+        } else {
+            0
+        })
         .build_cartesian_2d(x_min..x_max, state.y_min..state.y_max)
     {
-        Ok(chart) => chart,
+        Ok(chart) => chart.set_secondary_coord(x_min..x_max, 0.0..105.0),
         Err(_) => return,
     };
 
@@ -543,8 +877,8 @@ pub fn draw_view_chart(canvas_id: &str, state: &ViewChartState, zoom: ViewZoom)
         .x_labels(6)
         .y_labels(6)
         .x_label_formatter(&x_label_formatter)
-        .label_style(label_style)
-        .axis_desc_style(axis_desc_style)
+        .label_style(label_style.clone())
+        .axis_desc_style(axis_desc_style.clone())
         .axis_style(RGBColor(96, 86, 120))
         .bold_line_style(bold_grid)
         .light_line_style(light_grid)
@@ -552,6 +886,72 @@ pub fn draw_view_chart(canvas_id: &str, state: &ViewChartState, zoom: ViewZoom)
         .y_desc(state.y_label.clone())
         .draw()
         .ok();
+    if secondary_axis {
+        chart
+            .configure_secondary_axes()
+            .y_labels(6)
+            .y_label_formatter(&|value: &f64| format!("{value:.0}%"))
+            .label_style(label_style.clone())
+            .axis_desc_style(axis_desc_style)
+            .axis_style(RGBColor(96, 86, 120))
+            .y_desc("Antiandrogen load (% of peak)")
+            .draw()
+            .ok();
+    }
+
+    if state.e2_band.len() > 1 {
+        let outline = state
+            .e2_band
+            .iter()
+            .map(|(x, lower, _)| (*x, *lower))
+            .chain(state.e2_band.iter().rev().map(|(x, _, upper)| (*x, *upper)))
+            .collect::<Vec<_>>();
+        chart
+            .draw_series(std::iter::once(Polygon::new(
+                outline,
+                RGBAColor(70, 130, 180, 0.16).filled(),
+            )))
+            .ok();
+    }
+
+    for (color, line) in &state.model_lines {
+        if line.len() > 1 {
+            let style = ShapeStyle::from(color).stroke_width(2);
+            chart
+                .draw_series(std::iter::once(DashedPathElement::new(
+                    line.clone(),
+                    6,
+                    4,
+                    style,
+                )))
+                .ok();
+        }
+    }
+
+    for (name, color, line) in &state.load_lines {
+        if line.len() > 1 {
+            let style = ShapeStyle::from(color).stroke_width(2);
+            if let Ok(anno) = chart.draw_secondary_series(std::iter::once(DashedPathElement::new(
+                line.clone(),
+                6,
+                4,
+                style,
+            ))) {
+                anno.label(*name)
+                    .legend(move |(x, y)| PathElement::new(vec![(x, y), (x + 18, y)], style));
+            }
+        }
+    }
+    if secondary_axis {
+        chart
+            .configure_series_labels()
+            .position(SeriesLabelPosition::UpperRight)
+            .label_font(label_style)
+            .background_style(RGBAColor(15, 17, 26, 0.8))
+            .border_style(RGBColor(96, 86, 120))
+            .draw()
+            .ok();
+    }
 
     let mut line_map: std::collections::HashMap<RGBColor, Vec<(f64, f64)>> =
         std::collections::HashMap::new();
@@ -574,6 +974,15 @@ pub fn draw_view_chart(canvas_id: &str, state: &ViewChartState, zoom: ViewZoom)
                 point.color.filled(),
             )))
             .ok();
+        if point.flagged {
+            chart
+                .draw_series(std::iter::once(Circle::new(
+                    (point.x, point.y),
+                    15,
+                    RGBColor(255, 177, 196).stroke_width(3),
+                )))
+                .ok();
+        }
     }
 
     for point in &state.dosage_points {
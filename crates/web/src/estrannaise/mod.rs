@@ -1,22 +1,72 @@
-use hrt_shared::estrannaise::e2_multidose_3c;
+use std::cell::RefCell;
+
+use hrt_shared::estrannaise::{
+    e2_multidose_band, e2_multidose_series, estradiol_dose_events, injectable_schedule_model,
+    oral_e2_fraction, oral_model, washout_days, E2Band, UNCERTAINTY_COVERAGE, UNCERTAINTY_SAMPLES,
+};
+use hrt_shared::logic::fit_pk_parameters;
+use hrt_shared::plans::projected_regimens;
+use hrt_shared::transdermal::{gel_dose_mg, patch_total_strength, worn_patch_model};
 use hrt_shared::types::{
-    BloodTest, EstrannaiseModel, HormoneUnits, HrtData, InjectableEstradiols, Settings,
+    BloodTest, EstrannaiseModel, HormoneUnits, HrtData, RegimenSchedule, Settings,
 };
+use hrt_shared::whatif::{apply_dose_adjustments, suggest_catch_up, DoseAdjustment};
 
 use crate::charts::estrannaise::{EstrannaisePoint, EstrannaiseSeries};
-use crate::utils::{
-    convert_estradiol_to_display, estradiol_conversion_factor, fmt_date_label, hormone_unit_label,
-};
+use crate::utils::{convert_estradiol_to_display, estradiol_conversion_factor, fmt_date_label};
+
+const BAND_POINTS: i64 = 150;
+const BAND_STEP_MS: i64 = 6 * 60 * 60 * 1000;
 
-fn map_estrannaise_model(kind: &InjectableEstradiols) -> Option<EstrannaiseModel> {
-    match kind {
-        InjectableEstradiols::Benzoate => Some(EstrannaiseModel::EbIm),
-        InjectableEstradiols::Valerate => Some(EstrannaiseModel::EvIm),
-        InjectableEstradiols::Enanthate => Some(EstrannaiseModel::EEnIm),
-        InjectableEstradiols::Cypionate => Some(EstrannaiseModel::EcIm),
-        InjectableEstradiols::Undecylate => Some(EstrannaiseModel::EUnIm),
-        InjectableEstradiols::PolyestradiolPhosphate => None,
+type BandInputs = (Vec<f64>, Vec<f64>, Vec<f64>, Vec<EstrannaiseModel>);
+
+thread_local! {
+    static BAND_CACHE: RefCell<Option<(BandInputs, E2Band)>> = const { RefCell::new(None) };
+}
+
+/// About `BAND_POINTS` dates from `from` to `to` on a grid of whole 6 h steps
+/// from `anchor`. The grid only changes when the span outgrows the step, so
+/// redraws as time passes hit the band cache.
+pub fn band_dates(anchor: i64, from: i64, to: i64) -> Vec<i64> {
+    if from > to || anchor > to {
+        return Vec::new();
     }
+    let span_steps = (to - anchor) / BAND_STEP_MS + 1;
+    let step = BAND_STEP_MS * ((span_steps + BAND_POINTS - 1) / BAND_POINTS).max(1);
+    let first = (from.max(anchor) - anchor + step - 1) / step;
+    (first..)
+        .map(|i| anchor + i * step)
+        .take_while(|date| *date <= to)
+        .collect()
+}
+
+/// `e2_multidose_band` at unit conversion factor. The last result is kept and
+/// reused while the inputs are unchanged.
+pub fn cached_e2_band(
+    ts: &[f64],
+    doses: &[f64],
+    times: &[f64],
+    models: &[EstrannaiseModel],
+) -> E2Band {
+    let inputs = (ts.to_vec(), doses.to_vec(), times.to_vec(), models.to_vec());
+    BAND_CACHE.with(|cache| {
+        if let Some((cached, band)) = cache.borrow().as_ref() {
+            if *cached == inputs {
+                return band.clone();
+            }
+        }
+        let band = e2_multidose_band(
+            ts,
+            doses,
+            times,
+            models,
+            1.0,
+            UNCERTAINTY_SAMPLES,
+            UNCERTAINTY_COVERAGE,
+        );
+        *cache.borrow_mut() = Some((inputs, band.clone()));
+        band
+    })
 }
 
 fn extract_fudge_series(tests: &[BloodTest]) -> Vec<(i64, f64)> {
@@ -74,16 +124,52 @@ fn step_fudge(series: &[(i64, f64)], target: i64) -> f64 {
     series[series.len() - 1].1
 }
 
+fn forecast_schedule(
+    data: &HrtData,
+    schedule: &RegimenSchedule,
+) -> Option<(Option<i64>, f64, f64, EstrannaiseModel, f64)> {
+    match schedule {
+        RegimenSchedule::InjectableEstradiol(s) => injectable_schedule_model(data, s)
+            .map(|model| (s.nextDoseDate, s.dose, s.frequency, model, 1.0)),
+        RegimenSchedule::OralEstradiol(s) => oral_e2_fraction(&s.kind).map(|fraction| {
+            (
+                s.nextDoseDate,
+                s.dose,
+                s.frequency,
+                oral_model(s.route.as_ref()),
+                fraction,
+            )
+        }),
+        RegimenSchedule::Patch(s) => Some((
+            s.nextDoseDate,
+            patch_total_strength(s.strength, s.patchQuantity),
+            s.frequency,
+            worn_patch_model(s.wearDays.unwrap_or(s.frequency)),
+            1.0,
+        )),
+        RegimenSchedule::Gel(s) => Some((
+            s.nextDoseDate,
+            gel_dose_mg(s.dose, s.pumps, s.mgPerPump),
+            s.frequency,
+            EstrannaiseModel::E2Gel,
+            1.0,
+        )),
+        RegimenSchedule::Antiandrogen(_) | RegimenSchedule::Progesterone(_) => None,
+    }
+}
+
 #[allow(clippy::too_many_arguments)]
 pub fn compute_estrannaise_series(
     data: &HrtData,
     settings: &Settings,
     axis_mode: &str,
     forecast_enabled: bool,
+    show_uncertainty: bool,
     forecast_weeks: i64,
     forecast_dose_override: Option<f64>,
     forecast_freq_override: Option<f64>,
     stepped_fudge_override: Option<f64>,
+    adjustments: &[DoseAdjustment],
 ) -> EstrannaiseSeries {
     let display_unit = settings
         .displayEstradiolUnit
@@ -92,22 +178,7 @@ pub fn compute_estrannaise_series(
     let conversion = estradiol_conversion_factor(&display_unit);
     let stepped_fudge_override =
         stepped_fudge_override.filter(|value| value.is_finite() && *value > 0.0);
-    let dose_history: Vec<_> = data
-        .dosageHistory
-        .iter()
-        .filter_map(|d| match d {
-            hrt_shared::types::DosageHistoryEntry::InjectableEstradiol {
-                date,
-                kind,
-                dose,
-                unit: _,
-                ..
-            } => Some((*date, kind.clone(), *dose)),
-            _ => None,
-        })
-        .collect();
-    let mut dose_history = dose_history;
-    dose_history.sort_by_key(|(date, _, _)| *date);
+    let dose_history = estradiol_dose_events(data);
 
     let earliest_date = if !dose_history.is_empty() {
         Some(dose_history.first().map(|(date, _, _)| *date).unwrap())
@@ -118,43 +189,81 @@ pub fn compute_estrannaise_series(
         return EstrannaiseSeries::default();
     };
 
-    let last_dose = dose_history
-        .last()
-        .map(|(date, _, _)| *date)
-        .unwrap_or(first_dose);
+    // Long-acting depots like PEP stay visible well past the last dose.
+    let washout_end = dose_history
+        .iter()
+        .map(|(date, _, model)| date + (washout_days(model) * 24.0 * 60.0 * 60.0 * 1000.0) as i64)
+        .max()
+        .unwrap_or(first_dose + 30 * 24 * 60 * 60 * 1000);
     let start_date = first_dose;
-    let base_end = (last_dose + 30 * 24 * 60 * 60 * 1000).max(js_sys::Date::now() as i64);
+    let base_end = washout_end.max(js_sys::Date::now() as i64);
     let forecast_weeks = forecast_weeks.clamp(4, 8);
     let forecast_end =
         base_end.max(js_sys::Date::now() as i64 + forecast_weeks * 7 * 24 * 60 * 60 * 1000);
 
-    let schedule = data.injectableEstradiol.clone();
+    // (next dose, dose, frequency, model, mg of E2 per mg of drug, end) for
+    // each current estradiol regimen, followed by the steps of any dose
+    // plans. What-if overrides and adjustments apply to the first one; the
+    // others are forecast as scheduled.
+    #[allow(clippy::type_complexity)]
+    let mut schedules: Vec<(Option<i64>, f64, f64, EstrannaiseModel, f64, Option<i64>)> =
+        projected_regimens(data)
+            .iter()
+            .filter_map(|regimen| {
+                let (next, dose, freq, model, scale) = forecast_schedule(data, &regimen.schedule)?;
+                Some((
+                    Some(next.unwrap_or(regimen.startDate).max(regimen.startDate)),
+                    dose,
+                    freq,
+                    model,
+                    scale,
+                    regimen.endDate,
+                ))
+            })
+            .collect();
+    if schedules.is_empty() {
+        if let Some((_, _, model)) = dose_history.last() {
+            if let (Some(dose), Some(freq)) = (forecast_dose_override, forecast_freq_override) {
+                schedules.push((None, dose, freq, model.clone(), 1.0, None));
+            }
+        }
+    }
     let forecast_start = js_sys::Date::now() as i64;
-    let forecast_start_date = schedule
-        .as_ref()
-        .and_then(|s| s.nextDoseDate)
-        .unwrap_or(forecast_start)
-        .max(forecast_start);
-    let forecast_dose = forecast_dose_override.or_else(|| schedule.as_ref().map(|s| s.dose));
-    let forecast_freq = forecast_freq_override.or_else(|| schedule.as_ref().map(|s| s.frequency));
-    let forecast_type = schedule
-        .as_ref()
-        .map(|s| s.kind.clone())
-        .or_else(|| dose_history.last().map(|(_, kind, _)| kind.clone()));
+    let forecast_scale = schedules.first().map(|s| s.4).unwrap_or(1.0);
 
     let mut forecast_doses = Vec::new();
+    let mut concurrent_doses = Vec::new();
     if forecast_enabled {
-        if let (Some(dose), Some(freq), Some(kind)) = (forecast_dose, forecast_freq, forecast_type)
-        {
-            let mut t = forecast_start_date;
-            while t <= forecast_end {
-                forecast_doses.push((t, kind.clone(), dose));
-                t += (freq * 24.0 * 60.0 * 60.0 * 1000.0) as i64;
+        for (idx, (next, dose, freq, model, scale, end)) in schedules.iter().enumerate() {
+            let (dose, freq, doses) = if idx == 0 {
+                (
+                    forecast_dose_override.unwrap_or(*dose),
+                    forecast_freq_override.unwrap_or(*freq),
+                    &mut forecast_doses,
+                )
+            } else {
+                (*dose, *freq, &mut concurrent_doses)
+            };
+            let step = (freq * 24.0 * 60.0 * 60.0 * 1000.0) as i64;
+            if step <= 0 {
+                continue;
+            }
+            let mut t = next.unwrap_or(forecast_start).max(forecast_start);
+            let end = end.map_or(forecast_end, |end| (end - 1).min(forecast_end));
+            while t <= end {
+                doses.push((t, dose * scale, model.clone()));
+                t += step;
             }
         }
     }
+    // Recorded doses plus the regimens the what-if does not touch.
+    let background_doses: Vec<_> = dose_history
+        .iter()
+        .cloned()
+        .chain(concurrent_doses)
+        .collect();
 
-    let mut all_doses = dose_history.clone();
+    let mut all_doses = background_doses.clone();
     all_doses.extend(forecast_doses.clone());
     all_doses.sort_by_key(|(date, _, _)| *date);
 
@@ -167,23 +276,41 @@ pub fn compute_estrannaise_series(
     let step_ms = 6 * 60 * 60 * 1000;
     let mut blended = Vec::new();
     let mut stepped = Vec::new();
+    let mut fitted = Vec::new();
+    let mut whatif = Vec::new();
+    let fit = fit_pk_parameters(data);
     let mut y_values = Vec::new();
 
     let mut time_map = Vec::new();
     let mut dose_map = Vec::new();
     let mut model_map = Vec::new();
-    for (date, kind, dose) in &all_doses {
-        if let Some(model) = map_estrannaise_model(kind) {
-            time_map.push((*date - start_date) as f64 / (24.0 * 60.0 * 60.0 * 1000.0));
-            dose_map.push(*dose);
-            model_map.push(model);
-        }
+    for (date, dose, model) in &all_doses {
+        time_map.push((*date - start_date) as f64 / (24.0 * 60.0 * 60.0 * 1000.0));
+        dose_map.push(*dose);
+        model_map.push(model.clone());
     }
 
     if !model_map.is_empty() {
-        let mut t = start_date;
-        while t <= forecast_end {
-            let day_value = (t - start_date) as f64 / (24.0 * 60.0 * 60.0 * 1000.0);
+        let dates: Vec<i64> = (start_date..=forecast_end).step_by(step_ms as usize).collect();
+        let days: Vec<f64> = dates
+            .iter()
+            .map(|t| (t - start_date) as f64 / (24.0 * 60.0 * 60.0 * 1000.0))
+            .collect();
+        // Levels are linear in the conversion factor, so one unit-factor pass
+        // serves both the blended and stepped lines.
+        let base = e2_multidose_series(&days, &dose_map, &time_map, &model_map, 1.0, false);
+        let fitted_values = fit.as_ref().map(|fit| {
+            let shifted: Vec<f64> = days.iter().map(|day| day - fit.time_shift_days).collect();
+            e2_multidose_series(
+                &shifted,
+                &dose_map,
+                &time_map,
+                &model_map,
+                fit.scale * conversion,
+                false,
+            )
+        });
+        for (idx, (&t, &day_value)) in dates.iter().zip(&days).enumerate() {
             let blended_fudge = blend_fudge(&series, t);
             let step_fudge = if let Some(override_value) = stepped_fudge_override {
                 override_value
@@ -192,22 +319,8 @@ pub fn compute_estrannaise_series(
             } else {
                 step_fudge(&series, t)
             };
-            let blended_val = e2_multidose_3c(
-                day_value,
-                &dose_map,
-                &time_map,
-                &model_map,
-                blended_fudge * conversion,
-                false,
-            );
-            let stepped_val = e2_multidose_3c(
-                day_value,
-                &dose_map,
-                &time_map,
-                &model_map,
-                step_fudge * conversion,
-                false,
-            );
+            let blended_val = base[idx] * blended_fudge * conversion;
+            let stepped_val = base[idx] * step_fudge * conversion;
             let x = if axis_mode == "days" {
                 day_value
             } else {
@@ -226,20 +339,95 @@ pub fn compute_estrannaise_series(
             });
             y_values.push(blended_val);
             y_values.push(stepped_val);
-            t += step_ms;
+            if let Some(fitted_val) = fitted_values.as_ref().map(|values| values[idx]) {
+                fitted.push(EstrannaisePoint {
+                    x,
+                    y: fitted_val,
+                    label: format!("Fitted: {:.1} ({})", fitted_val, label),
+                });
+                y_values.push(fitted_val);
+            }
+        }
+
+        if !adjustments.is_empty() && !forecast_doses.is_empty() {
+            let modified: Vec<_> = background_doses
+                .iter()
+                .cloned()
+                .chain(apply_dose_adjustments(&forecast_doses, adjustments))
+                .collect();
+            let times: Vec<f64> = modified
+                .iter()
+                .map(|(date, _, _)| (*date - start_date) as f64 / (24.0 * 60.0 * 60.0 * 1000.0))
+                .collect();
+            let doses: Vec<f64> = modified.iter().map(|(_, dose, _)| *dose).collect();
+            let models: Vec<_> = modified.iter().map(|(_, _, model)| model.clone()).collect();
+            let values = e2_multidose_series(&days, &doses, &times, &models, 1.0, false);
+            for ((&t, point), value) in dates.iter().zip(&blended).zip(values) {
+                if t < forecast_start {
+                    continue;
+                }
+                let y = value * blend_fudge(&series, t) * conversion;
+                let label = fmt_date_label(t, axis_mode, Some(start_date));
+                whatif.push(EstrannaisePoint {
+                    x: point.x,
+                    y,
+                    label: format!("What-if: {:.1} ({})", y, label),
+                });
+                y_values.push(y);
+            }
         }
     }
 
+    let catch_up = suggest_catch_up(
+        &background_doses,
+        &forecast_doses,
+        adjustments,
+        blend_fudge(&series, forecast_start) * conversion,
+    )
+    .map(|mut suggestion| {
+        // Back from mg of estradiol to mg of the scheduled drug.
+        suggestion.scheduledDose /= forecast_scale;
+        suggestion.suggestedDose = (suggestion.suggestedDose / forecast_scale * 100.0).round() / 100.0;
+        suggestion
+    });
+    let planned_doses = forecast_doses
+        .iter()
+        .map(|(date, dose, _)| (*date, dose / forecast_scale))
+        .collect();
+
+    // Band around the blended line; the fudge factor scales it linearly.
+    let band: Vec<(f64, f64, f64)> = if show_uncertainty && !model_map.is_empty() {
+        let dates = band_dates(start_date, start_date, forecast_end);
+        let ts: Vec<f64> = dates
+            .iter()
+            .map(|t| (t - start_date) as f64 / (24.0 * 60.0 * 60.0 * 1000.0))
+            .collect();
+        let raw = cached_e2_band(&ts, &dose_map, &time_map, &model_map);
+        dates
+            .iter()
+            .zip(&ts)
+            .zip(raw.lower.iter().zip(&raw.upper))
+            .map(|((&t, &day_value), (lower, upper))| {
+                let x = if axis_mode == "days" {
+                    day_value
+                } else {
+                    t as f64
+                };
+                let scale = blend_fudge(&series, t) * conversion;
+                (x, lower * scale, upper * scale)
+            })
+            .collect()
+    } else {
+        Vec::new()
+    };
+    y_values.extend(band.iter().map(|(_, _, upper)| *upper));
+
     let blood: Vec<EstrannaisePoint> = data
         .bloodTests
         .iter()
-        .filter_map(|test| {
-            test.estradiolLevel
-                .map(|value| (test.date, value, test.estradiolUnit.clone()))
-        })
-        .map(|(date, value, unit)| {
-            let raw_unit = unit.unwrap_or(display_unit.clone());
-            let plot_val = convert_estradiol_to_display(value, &raw_unit, &display_unit);
+        .filter_map(|test| test.estradiol.as_ref().map(|level| (test.date, level)))
+        .map(|(date, level)| {
+            let plot_val = convert_estradiol_to_display(level.value, &level.unit, &display_unit);
             let x = if axis_mode == "days" {
                 (date - first_dose) as f64 / (24.0 * 60.0 * 60.0 * 1000.0)
             } else {
@@ -296,6 +484,12 @@ pub fn compute_estrannaise_series(
     EstrannaiseSeries {
         blended,
         stepped,
+        fitted,
+        fit,
+        band,
+        whatif,
+        planned_doses,
+        catch_up,
         blood,
         forecast,
         step_split_x: if forecast_enabled && stepped_fudge_override.is_none() {
@@ -317,8 +511,28 @@ pub fn compute_estrannaise_series(
         } else {
             "Date".to_string()
         },
-        y_label: format!("E2 ({})", hormone_unit_label(&display_unit)),
+        y_label: format!("E2 ({})", display_unit.symbol()),
         first_dose: Some(first_dose),
         use_days: axis_mode == "days",
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{band_dates, BAND_POINTS, BAND_STEP_MS};
+
+    #[test]
+    fn band_dates_stay_on_a_stable_grid() {
+        let day = 24 * 60 * 60 * 1000;
+        let dates = band_dates(0, 0, 3 * 365 * day);
+        assert!(dates.len() as i64 <= BAND_POINTS + 1);
+        assert!(dates.iter().all(|date| date % BAND_STEP_MS == 0));
+        // A few hours later the same grid is reused.
+        assert_eq!(band_dates(0, 0, 3 * 365 * day + 3 * 60 * 60 * 1000), dates);
+
+        let window = band_dates(0, 30 * day + 1, 60 * day);
+        assert!(window.first().is_some_and(|first| *first > 30 * day));
+        assert!(window.last().is_some_and(|last| *last <= 60 * day));
+        assert!(band_dates(0, 10, 5).is_empty());
+    }
+}
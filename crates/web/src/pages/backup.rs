@@ -8,6 +8,7 @@ use crate::layout::page_layout;
 use crate::store;
 use crate::store::use_store;
 use crate::utils::parse_decimal;
+use hrt_shared::schedules::migrate_schedules_to_regimens;
 use hrt_shared::types::{HormoneUnits, Settings};
 
 #[component]
@@ -41,6 +42,24 @@ pub fn BackupPage() -> impl IntoView {
         }
     });
 
+    let todos_url = create_memo({
+        let ics_secret = ics_secret;
+        move |_| {
+            let base = store::api_base();
+            let secret = ics_secret.get();
+            if secret.trim().is_empty() {
+                format!("{}/api/todos", base)
+            } else {
+                format!("{}/api/todos/{}/", base, urlencoding::encode(secret.trim()))
+            }
+        }
+    });
+
+    let on_copy_todos = move |_| {
+        let url = todos_url.get();
+        let _ = window().navigator().clipboard().write_text(&url);
+    };
+
     let on_copy_ics = move |_| {
         let url = ics_url.get();
         let _ = window().navigator().clipboard().write_text(&url);
@@ -101,9 +120,10 @@ pub fn BackupPage() -> impl IntoView {
                     } else {
                         payload.clone()
                     };
-                    if let Ok(parsed) =
+                    if let Ok(mut parsed) =
                         serde_json::from_value::<hrt_shared::types::HrtData>(data_value)
                     {
+                        migrate_schedules_to_regimens(&mut parsed);
                         store.data.set(parsed);
                         store.mark_dirty();
                     }
@@ -333,6 +353,18 @@ pub fn BackupPage() -> impl IntoView {
                         </div>
                     </div>
 
+                    <div class="card">
+                        <h3>"Dose Check-off"</h3>
+                        <p class="muted">
+                            "Upcoming doses as tasks. With an ICS secret set, add this URL to a CalDAV app as a task list; checking a dose off there logs it. Without a secret it is a read-only feed."
+                        </p>
+                        <input type="text" readonly prop:value=move || todos_url.get() />
+                        <div class="primary-actions">
+                            <a href=move || todos_url.get() target="_blank" rel="noopener noreferrer">"Open"</a>
+                            <button type="button" on:click=on_copy_todos>"Copy"</button>
+                        </div>
+                    </div>
+
                     <div class="card">
                         <h3>"Backup"</h3>
                         <p class="muted">"Export your full data + settings bundle for safekeeping."</p>
@@ -13,27 +13,16 @@ use web_sys::{FileReader, FormData, HtmlInputElement};
 
 use crate::layout::page_layout;
 use crate::store::use_store;
-use crate::utils::{compute_fudge_factor, hormone_unit_label, parse_decimal, parse_hormone_unit};
+use crate::utils::{
+    calculated_level_notes, compute_fudge_factor, convert_estradiol_to_display, fmt_lab_result,
+    parse_decimal,
+};
+use hrt_shared::lab_results::{canonical_lab_name, find_lab_analyte, LabResult, LAB_ANALYTES};
 use hrt_shared::logic::predict_e2_pg_ml;
+use hrt_shared::quantity::{BloodAnalyte, Quantity};
+use hrt_shared::ranges::RangeFlag;
 use hrt_shared::types::{BloodTest, HormoneUnits};
 
-#[derive(Clone, PartialEq)]
-struct UnitOption {
-    label: String,
-}
-
-const UNIT_OPTIONS: [HormoneUnits; 9] = [
-    HormoneUnits::E2PgMl,
-    HormoneUnits::E2PmolL,
-    HormoneUnits::TNgDl,
-    HormoneUnits::TNmolL,
-    HormoneUnits::Mg,
-    HormoneUnits::NgMl,
-    HormoneUnits::MIuMl,
-    HormoneUnits::MIuL,
-    HormoneUnits::UL,
-];
-
 #[derive(Clone, Debug)]
 struct OcrValue {
     value: String,
@@ -50,6 +39,7 @@ struct OcrExtraction {
     prolactin: Option<OcrValue>,
     shbg: Option<OcrValue>,
     fai: Option<OcrValue>,
+    lab_results: Vec<LabResult>,
     sample_date: Option<String>,
 }
 
@@ -86,6 +76,7 @@ fn apply_ocr_extraction(
     shbg_level: RwSignal<String>,
     shbg_unit: RwSignal<String>,
     free_androgen_index: RwSignal<String>,
+    lab_results: RwSignal<Vec<LabResult>>,
     test_date_time: RwSignal<String>,
 ) -> usize {
     let mut filled = 0;
@@ -146,9 +137,22 @@ fn apply_ocr_extraction(
         free_androgen_index.set(value.value);
         filled += 1;
     }
+    filled += extracted.lab_results.len();
+    for result in extracted.lab_results {
+        upsert_lab_result(lab_results, result);
+    }
     filled
 }
 
+/// Adds `result`, replacing an earlier result for the same analyte.
+fn upsert_lab_result(lab_results: RwSignal<Vec<LabResult>>, result: LabResult) {
+    lab_results.update(|results| {
+        let name = canonical_lab_name(&result.name);
+        results.retain(|existing| !canonical_lab_name(&existing.name).eq_ignore_ascii_case(&name));
+        results.push(result);
+    });
+}
+
 #[wasm_bindgen]
 extern "C" {
     #[wasm_bindgen(js_namespace = Tesseract, js_name = recognize)]
@@ -189,7 +193,11 @@ fn parse_optional(value: &str) -> Option<f64> {
 }
 
 fn unit_or_default(value: &str, fallback: HormoneUnits) -> HormoneUnits {
-    parse_hormone_unit(value).unwrap_or(fallback)
+    HormoneUnits::from_symbol(value).unwrap_or(fallback)
+}
+
+fn level(value: Option<f64>, unit: HormoneUnits) -> Option<Quantity> {
+    value.map(|value| Quantity::new(value, unit))
 }
 
 fn parse_ocr_number(token: &str) -> Option<String> {
@@ -265,6 +273,120 @@ fn extract_ocr_value(text: &str, labels: &[&str]) -> Option<OcrValue> {
     None
 }
 
+fn normalize_lab_unit(token: &str) -> Option<String> {
+    if let Some(unit) = normalize_ocr_unit(token) {
+        return Some(unit);
+    }
+    let cleaned = token
+        .trim()
+        .trim_matches(|c: char| c == ',' || c == ';' || c == '(' || c == ')')
+        .to_lowercase()
+        .replace('μ', "µ");
+    let unit = match cleaned.as_str() {
+        "%" => "%",
+        "mmol/l" => "mmol/L",
+        "umol/l" | "µmol/l" => "µmol/L",
+        "mg/dl" => "mg/dL",
+        "g/dl" => "g/dL",
+        "g/l" => "g/L",
+        "mmol/mol" => "mmol/mol",
+        "l/l" => "L/L",
+        "ml/min/1.73m2" | "ml/min/1.73m²" => "mL/min/1.73m²",
+        _ => return None,
+    };
+    Some(unit.to_string())
+}
+
+fn parse_ocr_range(token: &str) -> Option<(f64, f64)> {
+    let (low, high) = token.split_once(['-', '–'])?;
+    let low = parse_ocr_number(low)?.parse().ok()?;
+    let high = parse_ocr_number(high)?.parse().ok()?;
+    Some((low, high))
+}
+
+fn parse_ocr_flag(token: &str) -> Option<RangeFlag> {
+    match token.trim_matches(|c: char| !c.is_ascii_alphabetic()) {
+        "h" | "hi" | "high" => Some(RangeFlag::High),
+        "l" | "lo" | "low" => Some(RangeFlag::Low),
+        _ => None,
+    }
+}
+
+/// Reads "value [unit] [low - high] [H/L]" after an analyte label. The unit,
+/// range and flag must be on the same line as the value.
+fn parse_lab_result_window(window: &str, name: &str) -> Option<LabResult> {
+    let window: String = window.chars().take(220).collect();
+    let (value, rest) = window.lines().find_map(|line| {
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        tokens.iter().enumerate().find_map(|(index, token)| {
+            let value: f64 = parse_ocr_number(token)?.parse().ok()?;
+            let rest: Vec<&str> = tokens.iter().skip(index + 1).take(8).copied().collect();
+            Some((value, rest))
+        })
+    })?;
+    let mut result = LabResult {
+        name: name.to_string(),
+        value,
+        ..LabResult::default()
+    };
+    for (offset, token) in rest.iter().enumerate() {
+        if result.unit.is_none() && offset < 3 {
+            if let Some(unit) = normalize_lab_unit(token) {
+                result.unit = Some(unit);
+                continue;
+            }
+        }
+        if result.referenceLow.is_none() {
+            let spaced = match rest.get(offset..offset + 3) {
+                Some([low, "-" | "–", high]) => parse_ocr_range(&format!("{low}-{high}")),
+                _ => None,
+            };
+            if let Some((low, high)) = parse_ocr_range(token).or(spaced) {
+                result.referenceLow = Some(low);
+                result.referenceHigh = Some(high);
+                continue;
+            }
+        }
+        if result.flag.is_none() {
+            result.flag = parse_ocr_flag(token);
+        }
+    }
+    Some(result)
+}
+
+fn extract_lab_results(text: &str) -> Vec<LabResult> {
+    let lower = text.to_lowercase();
+    let bounded = |c: Option<char>| c.is_none_or(|c| !c.is_alphanumeric());
+    let mut matches = Vec::new();
+    for (analyte_index, analyte) in LAB_ANALYTES.iter().enumerate() {
+        for alias in analyte.aliases {
+            for (start, _) in lower.match_indices(alias) {
+                let end = start + alias.len();
+                if bounded(lower[..start].chars().next_back()) && bounded(lower[end..].chars().next()) {
+                    matches.push((start, end, analyte_index));
+                }
+            }
+        }
+    }
+    // "hemoglobin" inside "hemoglobin a1c" belongs to the longer label.
+    let covered = |start: usize, end: usize| {
+        matches
+            .iter()
+            .any(|&(s, e, _)| s <= start && end <= e && e - s > end - start)
+    };
+    LAB_ANALYTES
+        .iter()
+        .enumerate()
+        .filter_map(|(analyte_index, analyte)| {
+            let &(_, end, _) = matches
+                .iter()
+                .filter(|&&(start, end, index)| index == analyte_index && !covered(start, end))
+                .min_by_key(|&&(start, _, _)| start)?;
+            parse_lab_result_window(&lower[end..], analyte.name)
+        })
+        .collect()
+}
+
 fn month_from_name(name: &str) -> Option<u32> {
     match name {
         "jan" | "january" => Some(1),
@@ -374,6 +496,7 @@ fn extract_ocr_values(text: &str) -> OcrExtraction {
         prolactin: extract_ocr_value(&cleaned, &["prolactin"]),
         shbg: extract_ocr_value(&cleaned, &["sex hormone binding globulin", "shbg"]),
         fai: extract_ocr_value(&cleaned, &["free androgen index", "fai"]),
+        lab_results: extract_lab_results(&cleaned),
         sample_date: extract_sample_date(&cleaned),
     }
 }
@@ -381,13 +504,6 @@ fn extract_ocr_values(text: &str) -> OcrExtraction {
 #[component]
 pub fn CreateBloodTest() -> impl IntoView {
     let store = use_store();
-    let unit_options = UNIT_OPTIONS
-        .iter()
-        .map(|unit| UnitOption {
-            label: hormone_unit_label(unit).to_string(),
-        })
-        .collect::<Vec<_>>();
-
     let test_date_time = create_rw_signal(to_local_input_value(Date::now() as i64));
     let estradiol_level = create_rw_signal("0".to_string());
     let default_e2_unit = store
@@ -395,23 +511,27 @@ pub fn CreateBloodTest() -> impl IntoView {
         .get()
         .displayEstradiolUnit
         .unwrap_or(HormoneUnits::E2PmolL);
-    let estradiol_unit = create_rw_signal(hormone_unit_label(&default_e2_unit).to_string());
+    let estradiol_unit = create_rw_signal(default_e2_unit.symbol().to_string());
     let estrannaise_number = create_rw_signal("0".to_string());
-    let estrannaise_unit = create_rw_signal(hormone_unit_label(&HormoneUnits::E2PgMl).to_string());
+    let estrannaise_unit = create_rw_signal(HormoneUnits::E2PgMl.symbol().to_string());
     let test_level = create_rw_signal("0".to_string());
-    let test_unit = create_rw_signal(hormone_unit_label(&HormoneUnits::TNmolL).to_string());
+    let test_unit = create_rw_signal(HormoneUnits::TNmolL.symbol().to_string());
     let progesterone_level = create_rw_signal("0".to_string());
-    let progesterone_unit = create_rw_signal(hormone_unit_label(&HormoneUnits::TNmolL).to_string());
+    let progesterone_unit = create_rw_signal(HormoneUnits::TNmolL.symbol().to_string());
     let fsh_level = create_rw_signal("0".to_string());
-    let fsh_unit = create_rw_signal(hormone_unit_label(&HormoneUnits::UL).to_string());
+    let fsh_unit = create_rw_signal(HormoneUnits::UL.symbol().to_string());
     let lh_level = create_rw_signal("0".to_string());
-    let lh_unit = create_rw_signal(hormone_unit_label(&HormoneUnits::UL).to_string());
+    let lh_unit = create_rw_signal(HormoneUnits::UL.symbol().to_string());
     let prolactin_level = create_rw_signal("0".to_string());
-    let prolactin_unit = create_rw_signal(hormone_unit_label(&HormoneUnits::MIuL).to_string());
+    let prolactin_unit = create_rw_signal(HormoneUnits::MIuL.symbol().to_string());
     let shbg_level = create_rw_signal("0".to_string());
-    let shbg_unit = create_rw_signal(hormone_unit_label(&HormoneUnits::TNmolL).to_string());
+    let shbg_unit = create_rw_signal(HormoneUnits::TNmolL.symbol().to_string());
     let free_androgen_index = create_rw_signal("0".to_string());
+    let albumin_level = create_rw_signal(String::new());
+    let lab_results = create_rw_signal(Vec::<LabResult>::new());
     let notes = create_rw_signal(String::new());
+    let lab = create_rw_signal(String::new());
+    let store_data = store.data;
     let ocr_busy = create_rw_signal(false);
     let ocr_status = create_rw_signal(String::new());
     let ocr_error = create_rw_signal(None::<String>);
@@ -577,6 +697,7 @@ pub fn CreateBloodTest() -> impl IntoView {
                         shbg_level,
                         shbg_unit,
                         free_androgen_index,
+                        lab_results,
                         test_date_time,
                     );
                     if filled == 0 {
@@ -741,6 +862,7 @@ pub fn CreateBloodTest() -> impl IntoView {
                             shbg_level,
                             shbg_unit,
                             free_androgen_index,
+                            lab_results,
                             test_date_time,
                         );
                     }
@@ -798,6 +920,7 @@ pub fn CreateBloodTest() -> impl IntoView {
             let prolactin_value = parse_optional(&prolactin_level.get());
             let shbg_value = parse_optional(&shbg_level.get());
             let free_androgen_value = parse_optional(&free_androgen_index.get());
+            let albumin_value = parse_optional(&albumin_level.get()).filter(|v| *v > 0.0);
 
             let default_e2_unit = store
                 .settings
@@ -816,18 +939,10 @@ pub fn CreateBloodTest() -> impl IntoView {
             let estrannaise_unit_value =
                 unit_or_default(&estrannaise_unit.get(), HormoneUnits::E2PgMl);
             let measured_e2 = estradiol_value.map(|value| {
-                if estradiol_unit_value == HormoneUnits::E2PmolL {
-                    value / 3.671
-                } else {
-                    value
-                }
+                convert_estradiol_to_display(value, &estradiol_unit_value, &HormoneUnits::E2PgMl)
             });
             let predicted_input = estrannaise_value.map(|value| {
-                if estrannaise_unit_value == HormoneUnits::E2PmolL {
-                    value / 3.671
-                } else {
-                    value
-                }
+                convert_estradiol_to_display(value, &estrannaise_unit_value, &HormoneUnits::E2PgMl)
             });
             let predicted_model = predict_e2_pg_ml(&store.data.get(), date);
             let fudge_factor =
@@ -837,21 +952,15 @@ pub fn CreateBloodTest() -> impl IntoView {
 
             let entry = BloodTest {
                 date,
-                estradiolLevel: estradiol_value,
-                testLevel: test_value,
-                estradiolUnit: Some(estradiol_unit_value),
-                testUnit: Some(test_unit_value),
-                progesteroneLevel: progesterone_value,
-                progesteroneUnit: Some(progesterone_unit_value),
-                fshLevel: fsh_value,
-                fshUnit: Some(fsh_unit_value),
-                lhLevel: lh_value,
-                lhUnit: Some(lh_unit_value),
-                prolactinLevel: prolactin_value,
-                prolactinUnit: Some(prolactin_unit_value),
-                shbgLevel: shbg_value,
-                shbgUnit: Some(shbg_unit_value),
+                estradiol: level(estradiol_value, estradiol_unit_value),
+                testosterone: level(test_value, test_unit_value),
+                progesterone: level(progesterone_value, progesterone_unit_value),
+                fsh: level(fsh_value, fsh_unit_value),
+                lh: level(lh_value, lh_unit_value),
+                prolactin: level(prolactin_value, prolactin_unit_value),
+                shbg: level(shbg_value, shbg_unit_value),
                 freeAndrogenIndex: free_androgen_value,
+                albuminLevel: albumin_value,
                 estrannaiseNumber: predicted_input,
                 fudgeFactor: fudge_factor,
                 notes: if notes.get().trim().is_empty() {
@@ -865,6 +974,8 @@ pub fn CreateBloodTest() -> impl IntoView {
                 } else {
                     Some(pdf_files)
                 },
+                lab: Some(lab.get().trim().to_string()).filter(|value| !value.is_empty()),
+                labResults: lab_results.get(),
             };
 
             let mut removed_pdf_files = Vec::new();
@@ -906,13 +1017,13 @@ pub fn CreateBloodTest() -> impl IntoView {
         }
     };
 
-    let render_unit_options = move || {
-        unit_options
-            .clone()
-            .into_iter()
-            .map(|option| {
-                let label = option.label.clone();
-                view! { <option value=label.clone()>{label}</option> }
+    let render_unit_options = move |analyte: BloodAnalyte| {
+        HormoneUnits::ALL
+            .iter()
+            .filter(|unit| analyte.supports(unit))
+            .map(|unit| {
+                let label = unit.symbol();
+                view! { <option value=label>{label}</option> }
             })
             .collect_view()
     };
@@ -1063,7 +1174,7 @@ pub fn CreateBloodTest() -> impl IntoView {
                                     on:change=move |ev| estradiol_unit.set(event_target_value(&ev))
                                     prop:value=move || estradiol_unit.get()
                                 >
-                                    {render_unit_options()}
+                                    {render_unit_options(BloodAnalyte::Estradiol)}
                                 </select>
                             </label>
                         </div>
@@ -1106,7 +1217,7 @@ pub fn CreateBloodTest() -> impl IntoView {
                                     on:change=move |ev| test_unit.set(event_target_value(&ev))
                                     prop:value=move || test_unit.get()
                                 >
-                                    {render_unit_options()}
+                                    {render_unit_options(BloodAnalyte::Testosterone)}
                                 </select>
                             </label>
                         </div>
@@ -1127,7 +1238,7 @@ pub fn CreateBloodTest() -> impl IntoView {
                                     on:change=move |ev| progesterone_unit.set(event_target_value(&ev))
                                     prop:value=move || progesterone_unit.get()
                                 >
-                                    {render_unit_options()}
+                                    {render_unit_options(BloodAnalyte::Progesterone)}
                                 </select>
                             </label>
                         </div>
@@ -1148,7 +1259,7 @@ pub fn CreateBloodTest() -> impl IntoView {
                                     on:change=move |ev| fsh_unit.set(event_target_value(&ev))
                                     prop:value=move || fsh_unit.get()
                                 >
-                                    {render_unit_options()}
+                                    {render_unit_options(BloodAnalyte::Fsh)}
                                 </select>
                             </label>
                         </div>
@@ -1169,7 +1280,7 @@ pub fn CreateBloodTest() -> impl IntoView {
                                     on:change=move |ev| lh_unit.set(event_target_value(&ev))
                                     prop:value=move || lh_unit.get()
                                 >
-                                    {render_unit_options()}
+                                    {render_unit_options(BloodAnalyte::Lh)}
                                 </select>
                             </label>
                         </div>
@@ -1190,7 +1301,7 @@ pub fn CreateBloodTest() -> impl IntoView {
                                     on:change=move |ev| prolactin_unit.set(event_target_value(&ev))
                                     prop:value=move || prolactin_unit.get()
                                 >
-                                    {render_unit_options()}
+                                    {render_unit_options(BloodAnalyte::Prolactin)}
                                 </select>
                             </label>
                         </div>
@@ -1211,7 +1322,7 @@ pub fn CreateBloodTest() -> impl IntoView {
                                     on:change=move |ev| shbg_unit.set(event_target_value(&ev))
                                     prop:value=move || shbg_unit.get()
                                 >
-                                    {render_unit_options()}
+                                    {render_unit_options(BloodAnalyte::Shbg)}
                                 </select>
                             </label>
                         </div>
@@ -1226,10 +1337,72 @@ pub fn CreateBloodTest() -> impl IntoView {
                                     prop:value=move || free_androgen_index.get()
                                 />
                             </label>
-                            <div></div>
+                            <label>
+                                "Albumin (g/dL)"
+                                <input
+                                    type="text"
+                                    step="any"
+                                    placeholder="4.3"
+                                    on:input=move |ev| albumin_level.set(event_target_value(&ev))
+                                    prop:value=move || albumin_level.get()
+                                />
+                            </label>
                         </div>
+                        <p class="muted">
+                            {move || {
+                                let preview = BloodTest {
+                                    estradiol: level(
+                                        parse_optional(&estradiol_level.get()),
+                                        unit_or_default(&estradiol_unit.get(), HormoneUnits::E2PgMl),
+                                    ),
+                                    testosterone: level(
+                                        parse_optional(&test_level.get()),
+                                        unit_or_default(&test_unit.get(), HormoneUnits::TNmolL),
+                                    ),
+                                    shbg: level(
+                                        parse_optional(&shbg_level.get()),
+                                        unit_or_default(&shbg_unit.get(), HormoneUnits::TNmolL),
+                                    ),
+                                    freeAndrogenIndex: parse_optional(&free_androgen_index.get()),
+                                    albuminLevel: parse_optional(&albumin_level.get()),
+                                    ..BloodTest::default()
+                                };
+                                let notes = calculated_level_notes(&preview);
+                                if notes.is_empty() {
+                                    "Enter T and SHBG to calculate FAI and free T.".to_string()
+                                } else {
+                                    notes.join(" · ")
+                                }
+                            }}
+                        </p>
+                    </div>
+
+                    <div class="form-section">
+                        <h3>"Other results"</h3>
+                        {lab_results_editor(lab_results)}
                     </div>
 
+                    <label>
+                        "Lab"
+                        <input
+                            type="text"
+                            list="lab-names"
+                            placeholder="Used for the lab's reference ranges"
+                            on:input=move |ev| lab.set(event_target_value(&ev))
+                            prop:value=move || lab.get()
+                        />
+                        <datalist id="lab-names">
+                            {move || {
+                                store_data
+                                    .get()
+                                    .labRanges
+                                    .into_iter()
+                                    .map(|entry| view! { <option value=entry.lab></option> })
+                                    .collect_view()
+                            }}
+                        </datalist>
+                    </label>
+
                     <label>
                         "Notes"
                         <textarea
@@ -1252,3 +1425,161 @@ pub fn CreateBloodTest() -> impl IntoView {
         .into_view(),
     )
 }
+
+/// Editable list of the results without a dedicated field. Shared with the
+/// blood test edit form on the View page.
+pub(super) fn lab_results_editor(lab_results: RwSignal<Vec<LabResult>>) -> impl IntoView {
+    let name = create_rw_signal(String::new());
+    let value = create_rw_signal(String::new());
+    let unit = create_rw_signal(String::new());
+    let reference_low = create_rw_signal(String::new());
+    let reference_high = create_rw_signal(String::new());
+    let flag = create_rw_signal(String::new());
+    let error = create_rw_signal(None::<String>);
+
+    let on_name_change = move |ev| {
+        let next = event_target_value(&ev);
+        if unit.get().trim().is_empty() {
+            if let Some(analyte) = find_lab_analyte(&next) {
+                unit.set(analyte.unit.to_string());
+            }
+        }
+        name.set(next);
+    };
+
+    let on_add = move |_| {
+        let result_name = canonical_lab_name(&name.get());
+        if result_name.is_empty() {
+            error.set(Some("Enter the analyte name.".to_string()));
+            return;
+        }
+        let Some(result_value) = parse_decimal(&value.get()) else {
+            error.set(Some(format!("Enter a value for {result_name}.")));
+            return;
+        };
+        let result = LabResult {
+            name: result_name,
+            value: result_value,
+            unit: Some(unit.get().trim().to_string()).filter(|unit| !unit.is_empty()),
+            referenceLow: parse_decimal(&reference_low.get()),
+            referenceHigh: parse_decimal(&reference_high.get()),
+            flag: match flag.get().as_str() {
+                "low" => Some(RangeFlag::Low),
+                "normal" => Some(RangeFlag::InRange),
+                "high" => Some(RangeFlag::High),
+                _ => None,
+            },
+        };
+        upsert_lab_result(lab_results, result);
+        for field in [name, value, unit, reference_low, reference_high, flag] {
+            field.set(String::new());
+        }
+        error.set(None);
+    };
+
+    view! {
+        <Show when=move || !lab_results.get().is_empty()>
+            <ul class="history-list">
+                {move || {
+                    lab_results
+                        .get()
+                        .into_iter()
+                        .enumerate()
+                        .map(|(index, result)| {
+                            let flagged = result.effective_flag().is_some_and(|flag| flag.is_out_of_range());
+                            view! {
+                                <li class="history-item">
+                                    <span class:range-flag=flagged>{fmt_lab_result(&result)}</span>
+                                    <button
+                                        type="button"
+                                        class="action-button"
+                                        on:click=move |_| lab_results.update(|results| {
+                                            if index < results.len() {
+                                                results.remove(index);
+                                            }
+                                        })
+                                    >
+                                        "Remove"
+                                    </button>
+                                </li>
+                            }
+                        })
+                        .collect_view()
+                }}
+            </ul>
+        </Show>
+        <div class="inline-equal">
+            <label>
+                "Analyte"
+                <input
+                    type="text"
+                    list="lab-analyte-names"
+                    placeholder="e.g. Potassium"
+                    on:change=on_name_change
+                    prop:value=move || name.get()
+                />
+                <datalist id="lab-analyte-names">
+                    {LAB_ANALYTES
+                        .iter()
+                        .map(|analyte| view! { <option value=analyte.name></option> })
+                        .collect_view()}
+                </datalist>
+            </label>
+            <label>
+                "Value"
+                <input
+                    type="text"
+                    step="any"
+                    on:input=move |ev| value.set(event_target_value(&ev))
+                    prop:value=move || value.get()
+                />
+            </label>
+        </div>
+        <div class="inline-equal">
+            <label>
+                "Unit"
+                <input
+                    type="text"
+                    on:input=move |ev| unit.set(event_target_value(&ev))
+                    prop:value=move || unit.get()
+                />
+            </label>
+            <label>
+                "Lab flag"
+                <select
+                    on:change=move |ev| flag.set(event_target_value(&ev))
+                    prop:value=move || flag.get()
+                >
+                    <option value="">"From range"</option>
+                    <option value="low">"Low"</option>
+                    <option value="normal">"Normal"</option>
+                    <option value="high">"High"</option>
+                </select>
+            </label>
+        </div>
+        <div class="inline-equal">
+            <label>
+                "Reference low"
+                <input
+                    type="text"
+                    step="any"
+                    on:input=move |ev| reference_low.set(event_target_value(&ev))
+                    prop:value=move || reference_low.get()
+                />
+            </label>
+            <label>
+                "Reference high"
+                <input
+                    type="text"
+                    step="any"
+                    on:input=move |ev| reference_high.set(event_target_value(&ev))
+                    prop:value=move || reference_high.get()
+                />
+            </label>
+        </div>
+        <button type="button" on:click=on_add>"Add result"</button>
+        <Show when=move || error.get().is_some()>
+            <p class="muted">{move || error.get().unwrap_or_default()}</p>
+        </Show>
+    }
+}
@@ -8,16 +8,24 @@ use std::rc::Rc;
 use wasm_bindgen::JsValue;
 
 use crate::layout::page_layout;
-use crate::store::use_store;
+use crate::store::{use_store, AppStore};
 use crate::utils::{
-    hormone_unit_label, injectable_dose_from_iu, injectable_iu_from_dose, parse_decimal,
-    parse_decimal_or_nan, parse_hormone_unit,
+    browser_time_zone, fmt_date_label, fmt_regimen, injectable_dose_from_iu,
+    injectable_iu_from_dose, parse_decimal, parse_decimal_or_nan,
 };
 use hrt_shared::logic::backfill_scheduled_doses;
+use hrt_shared::medications::{validate_medication, BUILTIN_PK_MODELS};
+use hrt_shared::recurrence::{
+    format_day_list, format_time_of_day, parse_day_list, parse_times_of_day, WEEKDAY_LABELS,
+};
+use hrt_shared::schedules::{current_injectable, current_regimens, end_regimen, replace_regimen};
+use hrt_shared::transdermal::{gel_dose_mg, next_patch_site};
 use hrt_shared::types::{
-    AntiandrogenSchedule, Antiandrogens, DosageHistoryEntry, HormoneUnits, InjectableEstradiols,
-    InjectableSchedule, InjectionSites, OralEstradiols, OralSchedule, ProgesteroneRoutes,
-    ProgesteroneSchedule, Progesterones, SyringeKinds,
+    AntiandrogenSchedule, Antiandrogens, CustomPkRoute, DosageHistoryEntry, DosePlan, GelSchedule,
+    HormoneUnits, HrtData, InjectableEstradiols, InjectableSchedule, InjectionSites, Medication,
+    MedicationClass, MedicationRoute, OralEstradiolRoutes, OralEstradiols, OralSchedule,
+    PatchSchedule, PatchSites, PlanStep, ProgesteroneRoutes, ProgesteroneSchedule, Progesterones,
+    Recurrence, RecurrenceCycle, Regimen, RegimenSchedule, SyringeKinds,
 };
 
 const INJECTABLE_OPTIONS: [InjectableEstradiols; 6] = [
@@ -35,6 +43,9 @@ const ORAL_OPTIONS: [OralEstradiols; 3] = [
     OralEstradiols::Premarin,
 ];
 
+const ORAL_ROUTE_OPTIONS: [OralEstradiolRoutes; 2] =
+    [OralEstradiolRoutes::Oral, OralEstradiolRoutes::Sublingual];
+
 const ANTIANDROGEN_OPTIONS: [Antiandrogens; 4] = [
     Antiandrogens::CPA,
     Antiandrogens::Spiro,
@@ -47,18 +58,6 @@ const PROGESTERONE_OPTIONS: [Progesterones; 1] = [Progesterones::Micronized];
 const PROGESTERONE_ROUTE_OPTIONS: [ProgesteroneRoutes; 2] =
     [ProgesteroneRoutes::Oral, ProgesteroneRoutes::Boofed];
 
-const HORMONE_UNITS: [HormoneUnits; 9] = [
-    HormoneUnits::E2PgMl,
-    HormoneUnits::E2PmolL,
-    HormoneUnits::TNgDl,
-    HormoneUnits::TNmolL,
-    HormoneUnits::Mg,
-    HormoneUnits::NgMl,
-    HormoneUnits::MIuMl,
-    HormoneUnits::MIuL,
-    HormoneUnits::UL,
-];
-
 const INJECTION_SITES: [InjectionSites; 12] = [
     InjectionSites::StomachRight,
     InjectionSites::StomachLeft,
@@ -101,6 +100,13 @@ fn oral_label(kind: &OralEstradiols) -> &'static str {
     }
 }
 
+fn oral_route_label(route: &OralEstradiolRoutes) -> &'static str {
+    match route {
+        OralEstradiolRoutes::Oral => "Oral",
+        OralEstradiolRoutes::Sublingual => "Sublingual",
+    }
+}
+
 fn antiandrogen_label(kind: &Antiandrogens) -> &'static str {
     match kind {
         Antiandrogens::CPA => "Cyproterone Acetate",
@@ -166,6 +172,14 @@ fn oral_from_label(value: &str) -> OralEstradiols {
         .unwrap_or(OralEstradiols::Valerate)
 }
 
+fn oral_route_from_label(value: &str) -> OralEstradiolRoutes {
+    ORAL_ROUTE_OPTIONS
+        .iter()
+        .find(|route| oral_route_label(route) == value)
+        .cloned()
+        .unwrap_or(OralEstradiolRoutes::Oral)
+}
+
 fn antiandrogen_from_label(value: &str) -> Antiandrogens {
     ANTIANDROGEN_OPTIONS
         .iter()
@@ -248,6 +262,246 @@ fn parse_optional_num(value: &str) -> Option<f64> {
     parse_decimal(value)
 }
 
+fn pill_stock_fields(
+    value: &str,
+    existing_stock: Option<f64>,
+    existing_date: Option<i64>,
+) -> (Option<f64>, Option<i64>) {
+    let stock = parse_optional_num(value).filter(|v| *v >= 0.0);
+    match stock {
+        Some(count) if existing_stock == Some(count) => (stock, existing_date),
+        Some(_) => (stock, Some(Date::now() as i64)),
+        None => (None, None),
+    }
+}
+
+fn edited_schedule<'a>(data: &'a HrtData, id: &Option<String>) -> Option<&'a RegimenSchedule> {
+    let id = id.as_deref()?;
+    data.regimens
+        .iter()
+        .find(|regimen| regimen.id == id)
+        .map(|regimen| &regimen.schedule)
+}
+
+/// Saves over the edited regimen, or next to it when `concurrent` is set.
+fn save_regimen(
+    data: &mut HrtData,
+    edited: &Option<String>,
+    schedule: RegimenSchedule,
+    recurrence: Option<Recurrence>,
+    concurrent: bool,
+    now: i64,
+) -> String {
+    let id = match edited {
+        Some(id) if !concurrent => id.as_str(),
+        _ => "",
+    };
+    replace_regimen(data, id, schedule, recurrence, now)
+}
+
+/// Recurring schedules keep `frequency` as their average interval, for the
+/// stock and level projections that only understand intervals.
+fn schedule_frequency(input: &str, recurrence: &Option<Recurrence>) -> f64 {
+    recurrence
+        .as_ref()
+        .and_then(Recurrence::mean_interval_days)
+        .unwrap_or_else(|| parse_num(input).max(1.0))
+}
+
+/// Form state for one section's repeat rule.
+#[derive(Clone, Copy)]
+struct RecurrenceFields {
+    weekdays: RwSignal<Vec<u8>>,
+    times: RwSignal<String>,
+    month_days: RwSignal<String>,
+    cycle_on: RwSignal<String>,
+    cycle_off: RwSignal<String>,
+    cycle_start: RwSignal<String>,
+}
+
+impl RecurrenceFields {
+    fn new() -> Self {
+        Self {
+            weekdays: create_rw_signal(Vec::new()),
+            times: create_rw_signal(String::new()),
+            month_days: create_rw_signal(String::new()),
+            cycle_on: create_rw_signal(String::new()),
+            cycle_off: create_rw_signal(String::new()),
+            cycle_start: create_rw_signal(String::new()),
+        }
+    }
+
+    fn load(&self, recurrence: Option<&Recurrence>) {
+        let rule = recurrence.cloned().unwrap_or_default();
+        self.weekdays.set(rule.weekdays);
+        self.times.set(
+            rule.timesOfDay
+                .into_iter()
+                .map(format_time_of_day)
+                .collect::<Vec<_>>()
+                .join(", "),
+        );
+        self.month_days.set(format_day_list(&rule.monthDays));
+        self.cycle_on.set(
+            rule.cycle
+                .as_ref()
+                .map(|c| c.onDays.to_string())
+                .unwrap_or_default(),
+        );
+        self.cycle_off.set(
+            rule.cycle
+                .as_ref()
+                .map(|c| c.offDays.to_string())
+                .unwrap_or_default(),
+        );
+        self.cycle_start.set(
+            rule.cycle
+                .map(|c| to_local_input_value(c.startDate))
+                .unwrap_or_default(),
+        );
+    }
+
+    /// `Ok(None)` when no rule is filled in.
+    fn build(&self) -> Result<Option<Recurrence>, String> {
+        let times_of_day = parse_times_of_day(&self.times.get())
+            .ok_or("Times of day should look like 08:00, 20:00.")?;
+        let month_days = parse_day_list(&self.month_days.get(), 31)
+            .ok_or("Days of the month should look like 1-14, 20.")?;
+        let on = self.cycle_on.get();
+        let off = self.cycle_off.get();
+        let cycle = if on.trim().is_empty() && off.trim().is_empty() {
+            None
+        } else {
+            let on_days = on
+                .trim()
+                .parse::<u32>()
+                .ok()
+                .filter(|days| *days > 0)
+                .ok_or("Days on should be a whole number of days.")?;
+            let off_days = if off.trim().is_empty() {
+                0
+            } else {
+                off.trim()
+                    .parse::<u32>()
+                    .map_err(|_| "Days off should be a whole number of days.")?
+            };
+            Some(RecurrenceCycle {
+                onDays: on_days,
+                offDays: off_days,
+                startDate: parse_optional_datetime(&self.cycle_start.get())
+                    .unwrap_or_else(|| Date::now() as i64),
+            })
+        };
+        let mut recurrence = Recurrence {
+            weekdays: self.weekdays.get(),
+            monthDays: month_days,
+            timesOfDay: times_of_day,
+            cycle,
+            timeZone: None,
+        };
+        if recurrence == Recurrence::default() {
+            return Ok(None);
+        }
+        recurrence.timeZone = browser_time_zone();
+        if recurrence.mean_interval_days().is_none() {
+            return Err("The repeat rule never falls on a day.".to_string());
+        }
+        Ok(Some(recurrence))
+    }
+}
+
+fn recurrence_editor(fields: RecurrenceFields) -> impl IntoView {
+    view! {
+        <details>
+            <summary>"Repeat rule"</summary>
+            <p class="muted">
+                "Doses fall on days matching every rule filled in, once per time of day. Leave it empty to repeat by frequency."
+            </p>
+            <div class="chart-toolbar">
+                {WEEKDAY_LABELS
+                    .iter()
+                    .enumerate()
+                    .map(|(index, label)| {
+                        let day = index as u8;
+                        view! {
+                            <label>
+                                <input
+                                    type="checkbox"
+                                    on:change=move |ev| {
+                                        let checked = event_target_checked(&ev);
+                                        fields.weekdays.update(|days| {
+                                            days.retain(|d| *d != day);
+                                            if checked {
+                                                days.push(day);
+                                                days.sort_unstable();
+                                            }
+                                        });
+                                    }
+                                    prop:checked=move || fields.weekdays.with(|days| days.contains(&day))
+                                />
+                                {*label}
+                            </label>
+                        }
+                    })
+                    .collect_view()}
+            </div>
+            <label>
+                "Times of day"
+                <input
+                    type="text"
+                    placeholder="08:00, 20:00"
+                    on:input=move |ev| fields.times.set(event_target_value(&ev))
+                    prop:value=move || fields.times.get()
+                />
+            </label>
+            <label>
+                "Days of the month"
+                <input
+                    type="text"
+                    placeholder="1-14"
+                    on:input=move |ev| fields.month_days.set(event_target_value(&ev))
+                    prop:value=move || fields.month_days.get()
+                />
+            </label>
+            <div class="inline-equal">
+                <label>
+                    "Days on"
+                    <input
+                        type="text"
+                        on:input=move |ev| fields.cycle_on.set(event_target_value(&ev))
+                        prop:value=move || fields.cycle_on.get()
+                    />
+                </label>
+                <label>
+                    "Days off"
+                    <input
+                        type="text"
+                        on:input=move |ev| fields.cycle_off.set(event_target_value(&ev))
+                        prop:value=move || fields.cycle_off.get()
+                    />
+                </label>
+                <label>
+                    "Cycle start"
+                    <input
+                        type="datetime-local"
+                        on:input=move |ev| fields.cycle_start.set(event_target_value(&ev))
+                        prop:value=move || fields.cycle_start.get()
+                    />
+                </label>
+            </div>
+        </details>
+    }
+}
+
+fn end_edited_regimen(data: &mut HrtData, edited: &mut Option<String>, concurrent: bool, now: i64) {
+    if concurrent {
+        return;
+    }
+    if let Some(id) = edited.take() {
+        end_regimen(data, &id, now);
+    }
+}
+
 fn fmt(value: f64, decimals: usize) -> String {
     if !value.is_finite() {
         return "—".to_string();
@@ -267,9 +521,9 @@ fn fmt_iu_from_ml(ml: f64) -> String {
 }
 
 fn hormone_unit_labels() -> Vec<String> {
-    HORMONE_UNITS
+    HormoneUnits::ALL
         .iter()
-        .map(|unit| hormone_unit_label(unit).to_string())
+        .map(|unit| unit.symbol().to_string())
         .collect()
 }
 
@@ -300,10 +554,12 @@ fn dosage_editor_page(schedule_only: bool) -> impl IntoView {
     let injectable_type =
         create_rw_signal(injectable_label(&InjectableEstradiols::Benzoate).to_string());
     let oral_type = create_rw_signal(oral_label(&OralEstradiols::Valerate).to_string());
+    let oral_route = create_rw_signal(oral_route_label(&OralEstradiolRoutes::Oral).to_string());
     let estrogen_dose = create_rw_signal("0".to_string());
-    let estrogen_unit = create_rw_signal(hormone_unit_label(&HormoneUnits::Mg).to_string());
+    let estrogen_unit = create_rw_signal(HormoneUnits::Mg.symbol().to_string());
     let injection_frequency = create_rw_signal("7".to_string());
     let oral_frequency = create_rw_signal("1".to_string());
+    let oral_pill_stock = create_rw_signal(String::new());
     let estrogen_next_date = create_rw_signal(String::new());
 
     let inj_conv_dose_mg = create_rw_signal("4".to_string());
@@ -348,26 +604,52 @@ fn dosage_editor_page(schedule_only: bool) -> impl IntoView {
 
     let aa_type = create_rw_signal(String::new());
     let aa_dose = create_rw_signal("0".to_string());
-    let aa_unit = create_rw_signal(hormone_unit_label(&HormoneUnits::Mg).to_string());
+    let aa_unit = create_rw_signal(HormoneUnits::Mg.symbol().to_string());
     let aa_frequency = create_rw_signal("1".to_string());
     let aa_next_date = create_rw_signal(String::new());
+    let aa_pill_stock = create_rw_signal(String::new());
 
     let prog_type = create_rw_signal(String::new());
     let prog_dose = create_rw_signal("0".to_string());
-    let prog_unit = create_rw_signal(hormone_unit_label(&HormoneUnits::Mg).to_string());
+    let prog_unit = create_rw_signal(HormoneUnits::Mg.symbol().to_string());
     let prog_route =
         create_rw_signal(progesterone_route_label(&ProgesteroneRoutes::Oral).to_string());
     let prog_frequency = create_rw_signal("1".to_string());
     let prog_next_date = create_rw_signal(String::new());
+    let prog_pill_stock = create_rw_signal(String::new());
+
+    let patch_enabled = create_rw_signal(false);
+    let patch_strength = create_rw_signal("100".to_string());
+    let patch_quantity = create_rw_signal("1".to_string());
+    let patch_frequency = create_rw_signal("3.5".to_string());
+    let patch_wear_days = create_rw_signal(String::new());
+    let patch_next_date = create_rw_signal(String::new());
+    let patch_site = create_rw_signal(String::new());
+
+    let gel_enabled = create_rw_signal(false);
+    let gel_dose = create_rw_signal("1".to_string());
+    let gel_pumps = create_rw_signal(String::new());
+    let gel_mg_per_pump = create_rw_signal(String::new());
+    let gel_frequency = create_rw_signal("1".to_string());
+    let gel_next_date = create_rw_signal(String::new());
 
     let record_estrogen = create_rw_signal(true);
     let record_aa = create_rw_signal(false);
     let record_prog = create_rw_signal(false);
+    let record_patch = create_rw_signal(false);
+    let record_gel = create_rw_signal(false);
+    let record_other = create_rw_signal(false);
+    let other_medication_id = create_rw_signal(String::new());
+    let other_dose = create_rw_signal("0".to_string());
+    let other_unit = create_rw_signal(HormoneUnits::Mg.symbol().to_string());
+    let other_note = create_rw_signal(String::new());
     let bonus_dose = create_rw_signal(false);
 
     let estrogen_note = create_rw_signal(String::new());
     let aa_note = create_rw_signal(String::new());
     let prog_note = create_rw_signal(String::new());
+    let patch_note = create_rw_signal(String::new());
+    let gel_note = create_rw_signal(String::new());
     let estrogen_pill_qty = create_rw_signal("1".to_string());
     let prog_pill_qty = create_rw_signal("1".to_string());
 
@@ -378,6 +660,21 @@ fn dosage_editor_page(schedule_only: bool) -> impl IntoView {
 
     let selected_vial_id = create_rw_signal(String::new());
     let selected_sub_vial_id = create_rw_signal(String::new());
+    let pk_model_id = create_rw_signal(String::new());
+    let route_pk_models = move || {
+        let route = if estrogen_method.get() == "injection" {
+            CustomPkRoute::Injection
+        } else {
+            CustomPkRoute::Oral
+        };
+        store
+            .data
+            .get()
+            .pkModels
+            .into_iter()
+            .filter(|model| model.route == route)
+            .collect::<Vec<_>>()
+    };
     let estrogen_dose_in_iu = create_rw_signal(false);
 
     let injectable_dose_field_in_iu = create_memo({
@@ -399,10 +696,8 @@ fn dosage_editor_page(schedule_only: bool) -> impl IntoView {
             } else {
                 Some(&selected_vial)
             };
-            let schedule_vial_id = data_value
-                .injectableEstradiol
-                .as_ref()
-                .and_then(|cfg| cfg.vialId.as_ref());
+            let schedule_vial_id =
+                current_injectable(&data_value).and_then(|cfg| cfg.vialId.as_ref());
             injectable_dose_from_iu(&data_value, 1.0, selected_vial_id, schedule_vial_id).is_some()
         }
     });
@@ -431,10 +726,8 @@ fn dosage_editor_page(schedule_only: bool) -> impl IntoView {
             } else {
                 Some(&selected_vial)
             };
-            let schedule_vial_id = data_value
-                .injectableEstradiol
-                .as_ref()
-                .and_then(|cfg| cfg.vialId.as_ref());
+            let schedule_vial_id =
+                current_injectable(&data_value).and_then(|cfg| cfg.vialId.as_ref());
             let converted = if target_iu_mode {
                 injectable_iu_from_dose(
                     &data_value,
@@ -484,10 +777,8 @@ fn dosage_editor_page(schedule_only: bool) -> impl IntoView {
             } else {
                 Some(&selected_vial)
             };
-            let schedule_vial_id = data_value
-                .injectableEstradiol
-                .as_ref()
-                .and_then(|cfg| cfg.vialId.as_ref());
+            let schedule_vial_id =
+                current_injectable(&data_value).and_then(|cfg| cfg.vialId.as_ref());
             injectable_dose_from_iu(&data_value, dose_value, selected_vial_id, schedule_vial_id)
         }
     });
@@ -497,6 +788,20 @@ fn dosage_editor_page(schedule_only: bool) -> impl IntoView {
     let schedule_feedback = create_rw_signal(false);
     let feedback_timeout: Rc<RefCell<Option<Timeout>>> = Rc::new(RefCell::new(None));
 
+    // Ids of the regimens being edited; `None` saves as a new regimen.
+    let estrogen_regimen_id = create_rw_signal(None::<String>);
+    let aa_regimen_id = create_rw_signal(None::<String>);
+    let prog_regimen_id = create_rw_signal(None::<String>);
+    let patch_regimen_id = create_rw_signal(None::<String>);
+    let gel_regimen_id = create_rw_signal(None::<String>);
+    let add_concurrent = create_rw_signal(false);
+    let estrogen_recurrence = RecurrenceFields::new();
+    let aa_recurrence = RecurrenceFields::new();
+    let prog_recurrence = RecurrenceFields::new();
+    let patch_recurrence = RecurrenceFields::new();
+    let gel_recurrence = RecurrenceFields::new();
+    let schedule_error = create_rw_signal(None::<String>);
+
     let initialized = create_rw_signal(false);
     create_effect({
         let store = store.clone();
@@ -508,7 +813,15 @@ fn dosage_editor_page(schedule_only: bool) -> impl IntoView {
                 return;
             }
             let data = store.data.get();
-            if let Some(inj) = data.injectableEstradiol.as_ref() {
+            let estrogen = current_regimens(&data).find(|regimen| {
+                matches!(
+                    regimen.schedule,
+                    RegimenSchedule::InjectableEstradiol(_) | RegimenSchedule::OralEstradiol(_)
+                )
+            });
+            estrogen_regimen_id.set(estrogen.map(|regimen| regimen.id.clone()));
+            estrogen_recurrence.load(estrogen.and_then(|regimen| regimen.recurrence.as_ref()));
+            if let Some(inj) = estrogen.and_then(|regimen| regimen.schedule.as_injectable()) {
                 estrogen_method.set("injection".to_string());
                 injectable_type.set(injectable_label(&inj.kind).to_string());
                 let dose_in_iu = mode.get() == "record"
@@ -537,7 +850,7 @@ fn dosage_editor_page(schedule_only: bool) -> impl IntoView {
                 };
                 estrogen_dose.set(dose_label);
                 estrogen_dose_in_iu.set(dose_in_iu);
-                estrogen_unit.set(hormone_unit_label(&HormoneUnits::Mg).to_string());
+                estrogen_unit.set(HormoneUnits::Mg.symbol().to_string());
                 injection_frequency.set(format!("{:.2}", inj.frequency));
                 estrogen_next_date.set(
                     inj.nextDoseDate
@@ -549,13 +862,18 @@ fn dosage_editor_page(schedule_only: bool) -> impl IntoView {
                 syringe_kind.set(inj.syringeKind.clone().unwrap_or_default());
                 needle_length.set(inj.needleLength.clone().unwrap_or_default());
                 needle_gauge.set(inj.needleGauge.clone().unwrap_or_default());
-            } else if let Some(oral) = data.oralEstradiol.as_ref() {
+            } else if let Some(oral) = estrogen.and_then(|regimen| regimen.schedule.as_oral()) {
                 estrogen_method.set("oral".to_string());
                 oral_type.set(oral_label(&oral.kind).to_string());
+                oral_route.set(
+                    oral_route_label(oral.route.as_ref().unwrap_or(&OralEstradiolRoutes::Oral))
+                        .to_string(),
+                );
                 estrogen_dose.set(fmt(oral.dose, 3));
                 estrogen_dose_in_iu.set(false);
-                estrogen_unit.set(hormone_unit_label(&oral.unit).to_string());
+                estrogen_unit.set(oral.unit.symbol().to_string());
                 oral_frequency.set(format!("{:.2}", oral.frequency));
+                oral_pill_stock.set(oral.pillStock.map(|v| fmt(v, 2)).unwrap_or_default());
                 estrogen_next_date.set(
                     oral.nextDoseDate
                         .map(to_local_input_value)
@@ -568,11 +886,16 @@ fn dosage_editor_page(schedule_only: bool) -> impl IntoView {
                 needle_gauge.set(String::new());
             }
 
-            if let Some(aa) = data.antiandrogen.as_ref() {
+            let aa_regimen = current_regimens(&data)
+                .find(|regimen| regimen.schedule.as_antiandrogen().is_some());
+            aa_regimen_id.set(aa_regimen.map(|regimen| regimen.id.clone()));
+            aa_recurrence.load(aa_regimen.and_then(|regimen| regimen.recurrence.as_ref()));
+            if let Some(aa) = aa_regimen.and_then(|regimen| regimen.schedule.as_antiandrogen()) {
                 aa_type.set(antiandrogen_label(&aa.kind).to_string());
                 aa_dose.set(fmt(aa.dose, 3));
-                aa_unit.set(hormone_unit_label(&aa.unit).to_string());
+                aa_unit.set(aa.unit.symbol().to_string());
                 aa_frequency.set(format!("{:.2}", aa.frequency));
+                aa_pill_stock.set(aa.pillStock.map(|v| fmt(v, 2)).unwrap_or_default());
                 aa_next_date.set(
                     aa.nextDoseDate
                         .map(to_local_input_value)
@@ -580,18 +903,59 @@ fn dosage_editor_page(schedule_only: bool) -> impl IntoView {
                 );
             }
 
-            if let Some(prog) = data.progesterone.as_ref() {
+            let prog_regimen = current_regimens(&data)
+                .find(|regimen| regimen.schedule.as_progesterone().is_some());
+            prog_regimen_id.set(prog_regimen.map(|regimen| regimen.id.clone()));
+            prog_recurrence.load(prog_regimen.and_then(|regimen| regimen.recurrence.as_ref()));
+            if let Some(prog) = prog_regimen.and_then(|regimen| regimen.schedule.as_progesterone())
+            {
                 prog_type.set(progesterone_label(&prog.kind).to_string());
                 prog_dose.set(fmt(prog.dose, 3));
-                prog_unit.set(hormone_unit_label(&prog.unit).to_string());
+                prog_unit.set(prog.unit.symbol().to_string());
                 prog_route.set(progesterone_route_label(&prog.route).to_string());
                 prog_frequency.set(format!("{:.2}", prog.frequency));
+                prog_pill_stock.set(prog.pillStock.map(|v| fmt(v, 2)).unwrap_or_default());
                 prog_next_date.set(
                     prog.nextDoseDate
                         .map(to_local_input_value)
                         .unwrap_or_default(),
                 );
             }
+            let patch_regimen =
+                current_regimens(&data).find(|regimen| regimen.schedule.as_patch().is_some());
+            patch_regimen_id.set(patch_regimen.map(|regimen| regimen.id.clone()));
+            patch_recurrence.load(patch_regimen.and_then(|regimen| regimen.recurrence.as_ref()));
+            if let Some(patch) = patch_regimen.and_then(|regimen| regimen.schedule.as_patch()) {
+                patch_enabled.set(true);
+                patch_strength.set(fmt(patch.strength, 1));
+                patch_quantity.set(fmt(patch.patchQuantity.unwrap_or(1.0), 1));
+                patch_frequency.set(format!("{:.2}", patch.frequency));
+                patch_wear_days.set(patch.wearDays.map(|v| fmt(v, 2)).unwrap_or_default());
+                patch_next_date.set(
+                    patch
+                        .nextDoseDate
+                        .map(to_local_input_value)
+                        .unwrap_or_default(),
+                );
+            }
+            patch_site.set(next_patch_site(&data).label().to_string());
+
+            let gel_regimen =
+                current_regimens(&data).find(|regimen| regimen.schedule.as_gel().is_some());
+            gel_regimen_id.set(gel_regimen.map(|regimen| regimen.id.clone()));
+            gel_recurrence.load(gel_regimen.and_then(|regimen| regimen.recurrence.as_ref()));
+            if let Some(gel) = gel_regimen.and_then(|regimen| regimen.schedule.as_gel()) {
+                gel_enabled.set(true);
+                gel_dose.set(fmt(gel.dose, 3));
+                gel_pumps.set(gel.pumps.map(|v| fmt(v, 1)).unwrap_or_default());
+                gel_mg_per_pump.set(gel.mgPerPump.map(|v| fmt(v, 3)).unwrap_or_default());
+                gel_frequency.set(format!("{:.2}", gel.frequency));
+                gel_next_date.set(
+                    gel.nextDoseDate
+                        .map(to_local_input_value)
+                        .unwrap_or_default(),
+                );
+            }
             initialized.set(true);
         }
     });
@@ -661,7 +1025,7 @@ fn dosage_editor_page(schedule_only: bool) -> impl IntoView {
             let estrogen_unit_value = if estrogen_method_value == "injection" {
                 HormoneUnits::Mg
             } else {
-                parse_hormone_unit(&estrogen_unit.get()).unwrap_or(HormoneUnits::Mg)
+                HormoneUnits::from_symbol(&estrogen_unit.get()).unwrap_or(HormoneUnits::Mg)
             };
             let estrogen_dose_value =
                 if estrogen_method_value == "injection" && estrogen_dose_in_iu.get() {
@@ -672,10 +1036,8 @@ fn dosage_editor_page(schedule_only: bool) -> impl IntoView {
                     } else {
                         Some(&selected_vial)
                     };
-                    let schedule_vial_id = data_value
-                        .injectableEstradiol
-                        .as_ref()
-                        .and_then(|cfg| cfg.vialId.as_ref());
+                    let schedule_vial_id =
+                        current_injectable(&data_value).and_then(|cfg| cfg.vialId.as_ref());
                     injectable_dose_from_iu(
                         &data_value,
                         estrogen_input_dose,
@@ -732,16 +1094,23 @@ fn dosage_editor_page(schedule_only: bool) -> impl IntoView {
                                     Some(needle_gauge.get())
                                 },
                                 photos: None,
+                                pkModelId: if pk_model_id.get().is_empty() {
+                                    None
+                                } else {
+                                    Some(pk_model_id.get())
+                                },
                             };
                             data.dosageHistory.push(record);
                         } else {
                             let kind = oral_from_label(&oral_type.get());
+                            let route = oral_route_from_label(&oral_route.get());
                             let pill_qty = parse_optional_num(&estrogen_pill_qty.get())
                                 .filter(|value| *value > 0.0);
                             let record = DosageHistoryEntry::OralEstradiol {
                                 date: record_ms,
                                 id: None,
                                 kind,
+                                route: Some(route),
                                 dose: estrogen_dose_value,
                                 unit: estrogen_unit_value.clone(),
                                 pillQuantity: pill_qty,
@@ -750,6 +1119,11 @@ fn dosage_editor_page(schedule_only: bool) -> impl IntoView {
                                 } else {
                                     Some(estrogen_note.get())
                                 },
+                                pkModelId: if pk_model_id.get().is_empty() {
+                                    None
+                                } else {
+                                    Some(pk_model_id.get())
+                                },
                             };
                             data.dosageHistory.push(record);
                         }
@@ -762,7 +1136,8 @@ fn dosage_editor_page(schedule_only: bool) -> impl IntoView {
                             id: None,
                             kind,
                             dose: parse_num(&aa_dose.get()),
-                            unit: parse_hormone_unit(&aa_unit.get()).unwrap_or(HormoneUnits::Mg),
+                            unit: HormoneUnits::from_symbol(&aa_unit.get())
+                                .unwrap_or(HormoneUnits::Mg),
                             note: if aa_note.get().trim().is_empty() {
                                 None
                             } else {
@@ -782,7 +1157,8 @@ fn dosage_editor_page(schedule_only: bool) -> impl IntoView {
                             kind,
                             route: progesterone_route_from_label(&prog_route.get()),
                             dose: parse_num(&prog_dose.get()),
-                            unit: parse_hormone_unit(&prog_unit.get()).unwrap_or(HormoneUnits::Mg),
+                            unit: HormoneUnits::from_symbol(&prog_unit.get())
+                                .unwrap_or(HormoneUnits::Mg),
                             pillQuantity: pill_qty,
                             note: if prog_note.get().trim().is_empty() {
                                 None
@@ -792,19 +1168,105 @@ fn dosage_editor_page(schedule_only: bool) -> impl IntoView {
                         };
                         data.dosageHistory.push(record);
                     }
+
+                    if record_patch.get() {
+                        let record = DosageHistoryEntry::Patch {
+                            date: record_ms,
+                            id: None,
+                            strength: parse_num(&patch_strength.get()),
+                            patchQuantity: parse_optional_num(&patch_quantity.get())
+                                .filter(|value| *value > 0.0),
+                            wearDays: parse_optional_num(&patch_wear_days.get())
+                                .or_else(|| parse_optional_num(&patch_frequency.get()))
+                                .filter(|value| *value > 0.0),
+                            removedAt: None,
+                            site: PatchSites::from_label(&patch_site.get()),
+                            note: if patch_note.get().trim().is_empty() {
+                                None
+                            } else {
+                                Some(patch_note.get())
+                            },
+                        };
+                        data.dosageHistory.push(record);
+                    }
+
+                    if record_gel.get() {
+                        let pumps =
+                            parse_optional_num(&gel_pumps.get()).filter(|value| *value > 0.0);
+                        let record = DosageHistoryEntry::Gel {
+                            date: record_ms,
+                            id: None,
+                            dose: gel_dose_mg(
+                                parse_num(&gel_dose.get()),
+                                pumps,
+                                parse_optional_num(&gel_mg_per_pump.get()),
+                            ),
+                            unit: HormoneUnits::Mg,
+                            pumps,
+                            note: if gel_note.get().trim().is_empty() {
+                                None
+                            } else {
+                                Some(gel_note.get())
+                            },
+                        };
+                        data.dosageHistory.push(record);
+                    }
+
+                    if record_other.get() && !other_medication_id.get().is_empty() {
+                        let record = DosageHistoryEntry::Medication {
+                            date: record_ms,
+                            id: None,
+                            medicationId: other_medication_id.get(),
+                            dose: parse_num(&other_dose.get()),
+                            unit: HormoneUnits::from_symbol(&other_unit.get())
+                                .unwrap_or(HormoneUnits::Mg),
+                            note: if other_note.get().trim().is_empty() {
+                                None
+                            } else {
+                                Some(other_note.get())
+                            },
+                        };
+                        data.dosageHistory.push(record);
+                    }
                 });
                 store.mark_dirty();
                 navigate("/view", Default::default());
                 return;
             }
 
+            let rules = [
+                estrogen_recurrence,
+                aa_recurrence,
+                prog_recurrence,
+                patch_recurrence,
+                gel_recurrence,
+            ]
+            .map(|fields| fields.build());
+            if let Some(err) = rules.iter().find_map(|rule| rule.as_ref().err()) {
+                schedule_error.set(Some(err.clone()));
+                return;
+            }
+            schedule_error.set(None);
+            let [estrogen_rule, aa_rule, prog_rule, patch_rule, gel_rule] =
+                rules.map(Result::unwrap_or_default);
+
+            let now = Date::now() as i64;
+            let concurrent = add_concurrent.get();
+            let mut edited = [
+                estrogen_regimen_id.get(),
+                aa_regimen_id.get(),
+                prog_regimen_id.get(),
+                patch_regimen_id.get(),
+                gel_regimen_id.get(),
+            ];
             store.data.update(|data| {
+                let [estrogen_id, aa_id, prog_id, patch_id, gel_id] = &mut edited;
                 if estrogen_method_value == "injection" {
                     let schedule = InjectableSchedule {
                         kind: injectable_from_label(&injectable_type.get()),
                         dose: estrogen_dose_value,
                         unit: estrogen_unit_value.clone(),
-                        frequency: parse_num(&injection_frequency.get()).max(1.0),
+                        frequency: schedule_frequency(&injection_frequency.get(), &estrogen_rule),
                         vialId: if selected_vial_id.get().is_empty() {
                             None
                         } else {
@@ -832,49 +1294,155 @@ fn dosage_editor_page(schedule_only: bool) -> impl IntoView {
                         },
                         nextDoseDate: parse_optional_datetime(&estrogen_next_date.get()),
                     };
-                    data.injectableEstradiol = Some(schedule);
-                    data.oralEstradiol = None;
+                    *estrogen_id = Some(save_regimen(
+                        data,
+                        estrogen_id,
+                        RegimenSchedule::InjectableEstradiol(schedule),
+                        estrogen_rule.clone(),
+                        concurrent,
+                        now,
+                    ));
                 } else {
+                    let existing =
+                        edited_schedule(data, estrogen_id).and_then(RegimenSchedule::as_oral);
+                    let (pill_stock, pill_stock_date) = pill_stock_fields(
+                        &oral_pill_stock.get(),
+                        existing.and_then(|s| s.pillStock),
+                        existing.and_then(|s| s.pillStockDate),
+                    );
                     let schedule = OralSchedule {
                         kind: oral_from_label(&oral_type.get()),
+                        route: Some(oral_route_from_label(&oral_route.get())),
                         dose: estrogen_dose_value,
                         unit: estrogen_unit_value.clone(),
-                        frequency: parse_num(&oral_frequency.get()).max(1.0),
+                        frequency: schedule_frequency(&oral_frequency.get(), &estrogen_rule),
                         nextDoseDate: parse_optional_datetime(&estrogen_next_date.get()),
+                        pillStock: pill_stock,
+                        pillStockDate: pill_stock_date,
                     };
-                    data.oralEstradiol = Some(schedule);
-                    data.injectableEstradiol = None;
+                    *estrogen_id = Some(save_regimen(
+                        data,
+                        estrogen_id,
+                        RegimenSchedule::OralEstradiol(schedule),
+                        estrogen_rule.clone(),
+                        concurrent,
+                        now,
+                    ));
                 }
 
                 if aa_type.get().trim().is_empty() {
-                    data.antiandrogen = None;
+                    end_edited_regimen(data, aa_id, concurrent, now);
                 } else {
+                    let existing =
+                        edited_schedule(data, aa_id).and_then(RegimenSchedule::as_antiandrogen);
+                    let (pill_stock, pill_stock_date) = pill_stock_fields(
+                        &aa_pill_stock.get(),
+                        existing.and_then(|s| s.pillStock),
+                        existing.and_then(|s| s.pillStockDate),
+                    );
                     let schedule = AntiandrogenSchedule {
                         kind: antiandrogen_from_label(&aa_type.get()),
                         dose: parse_num(&aa_dose.get()),
-                        unit: parse_hormone_unit(&aa_unit.get()).unwrap_or(HormoneUnits::Mg),
-                        frequency: parse_num(&aa_frequency.get()).max(1.0),
+                        unit: HormoneUnits::from_symbol(&aa_unit.get()).unwrap_or(HormoneUnits::Mg),
+                        frequency: schedule_frequency(&aa_frequency.get(), &aa_rule),
                         nextDoseDate: parse_optional_datetime(&aa_next_date.get()),
+                        pillStock: pill_stock,
+                        pillStockDate: pill_stock_date,
                     };
-                    data.antiandrogen = Some(schedule);
+                    *aa_id = Some(save_regimen(
+                        data,
+                        aa_id,
+                        RegimenSchedule::Antiandrogen(schedule),
+                        aa_rule.clone(),
+                        concurrent,
+                        now,
+                    ));
                 }
 
                 if prog_type.get().trim().is_empty() {
-                    data.progesterone = None;
+                    end_edited_regimen(data, prog_id, concurrent, now);
                 } else {
+                    let existing =
+                        edited_schedule(data, prog_id).and_then(RegimenSchedule::as_progesterone);
+                    let (pill_stock, pill_stock_date) = pill_stock_fields(
+                        &prog_pill_stock.get(),
+                        existing.and_then(|s| s.pillStock),
+                        existing.and_then(|s| s.pillStockDate),
+                    );
                     let schedule = ProgesteroneSchedule {
                         kind: progesterone_from_label(&prog_type.get()),
                         route: progesterone_route_from_label(&prog_route.get()),
                         dose: parse_num(&prog_dose.get()),
-                        unit: parse_hormone_unit(&prog_unit.get()).unwrap_or(HormoneUnits::Mg),
-                        frequency: parse_num(&prog_frequency.get()).max(1.0),
+                        unit: HormoneUnits::from_symbol(&prog_unit.get())
+                            .unwrap_or(HormoneUnits::Mg),
+                        frequency: schedule_frequency(&prog_frequency.get(), &prog_rule),
                         nextDoseDate: parse_optional_datetime(&prog_next_date.get()),
+                        pillStock: pill_stock,
+                        pillStockDate: pill_stock_date,
                     };
-                    data.progesterone = Some(schedule);
+                    *prog_id = Some(save_regimen(
+                        data,
+                        prog_id,
+                        RegimenSchedule::Progesterone(schedule),
+                        prog_rule.clone(),
+                        concurrent,
+                        now,
+                    ));
+                }
+
+                if patch_enabled.get() {
+                    let schedule = PatchSchedule {
+                        strength: parse_num(&patch_strength.get()),
+                        patchQuantity: parse_optional_num(&patch_quantity.get())
+                            .filter(|value| *value > 0.0),
+                        frequency: schedule_frequency(&patch_frequency.get(), &patch_rule),
+                        wearDays: parse_optional_num(&patch_wear_days.get())
+                            .filter(|value| *value > 0.0),
+                        nextDoseDate: parse_optional_datetime(&patch_next_date.get()),
+                    };
+                    *patch_id = Some(save_regimen(
+                        data,
+                        patch_id,
+                        RegimenSchedule::Patch(schedule),
+                        patch_rule.clone(),
+                        concurrent,
+                        now,
+                    ));
+                } else {
+                    end_edited_regimen(data, patch_id, concurrent, now);
+                }
+
+                if gel_enabled.get() {
+                    let schedule = GelSchedule {
+                        dose: parse_num(&gel_dose.get()),
+                        unit: HormoneUnits::Mg,
+                        pumps: parse_optional_num(&gel_pumps.get()).filter(|value| *value > 0.0),
+                        mgPerPump: parse_optional_num(&gel_mg_per_pump.get())
+                            .filter(|value| *value > 0.0),
+                        frequency: schedule_frequency(&gel_frequency.get(), &gel_rule),
+                        nextDoseDate: parse_optional_datetime(&gel_next_date.get()),
+                    };
+                    *gel_id = Some(save_regimen(
+                        data,
+                        gel_id,
+                        RegimenSchedule::Gel(schedule),
+                        gel_rule.clone(),
+                        concurrent,
+                        now,
+                    ));
+                } else {
+                    end_edited_regimen(data, gel_id, concurrent, now);
                 }
 
                 backfill_scheduled_doses(data);
             });
+            let [estrogen_id, aa_id, prog_id, patch_id, gel_id] = edited;
+            estrogen_regimen_id.set(estrogen_id);
+            aa_regimen_id.set(aa_id);
+            prog_regimen_id.set(prog_id);
+            patch_regimen_id.set(patch_id);
+            gel_regimen_id.set(gel_id);
+            add_concurrent.set(false);
 
             store.mark_dirty();
             store.save();
@@ -890,6 +1458,32 @@ fn dosage_editor_page(schedule_only: bool) -> impl IntoView {
         }
     };
 
+    // Keeps the form from saving over a regimen ended from the list below.
+    let end_edited = move |id: String| {
+        if estrogen_regimen_id.get_untracked().as_deref() == Some(id.as_str()) {
+            estrogen_regimen_id.set(None);
+        }
+        if aa_regimen_id.get_untracked().as_deref() == Some(id.as_str()) {
+            aa_regimen_id.set(None);
+            aa_type.set(String::new());
+        }
+        if prog_regimen_id.get_untracked().as_deref() == Some(id.as_str()) {
+            prog_regimen_id.set(None);
+            prog_type.set(String::new());
+        }
+        if patch_regimen_id.get_untracked().as_deref() == Some(id.as_str()) {
+            patch_regimen_id.set(None);
+            patch_enabled.set(false);
+        }
+        if gel_regimen_id.get_untracked().as_deref() == Some(id.as_str()) {
+            gel_regimen_id.set(None);
+            gel_enabled.set(false);
+        }
+    };
+
+    let store_other = store.clone();
+    let store_catalog = store.clone();
+    let store_regimens = store.clone();
     page_layout(
         if schedule_only {
             "Edit schedule"
@@ -1115,6 +1709,25 @@ fn dosage_editor_page(schedule_only: bool) -> impl IntoView {
                             </label>
                         </Show>
 
+                        <Show when=move || {
+                            mode.get() == "record" && record_estrogen.get() && !route_pk_models().is_empty()
+                        }>
+                            <label>
+                                "PK model"
+                                <select
+                                    on:change=move |ev| pk_model_id.set(event_target_value(&ev))
+                                    prop:value=move || pk_model_id.get()
+                                >
+                                    <option value="">"Default (vial or ester)"</option>
+                                    <For
+                                        each=route_pk_models
+                                        key=|model| model.id.clone()
+                                        children=move |model| view! { <option value=model.id.clone()>{model.name.clone()}</option> }
+                                    />
+                                </select>
+                            </label>
+                        </Show>
+
                         <Show when=move || estrogen_method.get() == "injection">
                             <label>
                                 "Vial (optional)"
@@ -1312,6 +1925,18 @@ fn dosage_editor_page(schedule_only: bool) -> impl IntoView {
                                         }
                                     />
                                 </label>
+                                {recurrence_editor(estrogen_recurrence)}
+                                <Show when=move || estrogen_method.get() != "injection">
+                                    <label>
+                                        "Pills on hand (optional)"
+                                        <input
+                                            type="text"
+                                            step="any"
+                                            on:input=move |ev| oral_pill_stock.set(event_target_value(&ev))
+                                            prop:value=move || oral_pill_stock.get()
+                                        />
+                                    </label>
+                                </Show>
                             </Show>
 
                             <label>
@@ -1362,6 +1987,27 @@ fn dosage_editor_page(schedule_only: bool) -> impl IntoView {
                                 </p>
                             </Show>
 
+                            <Show when=move || estrogen_method.get() != "injection">
+                                <label>
+                                    "Route"
+                                    <select
+                                        on:change=move |ev| oral_route.set(event_target_value(&ev))
+                                        prop:value=move || oral_route.get()
+                                    >
+                                        <For
+                                            each=move || {
+                                                ORAL_ROUTE_OPTIONS
+                                                    .iter()
+                                                    .map(|route| oral_route_label(route).to_string())
+                                                    .collect::<Vec<_>>()
+                                            }
+                                            key=|label| label.clone()
+                                            children=move |label| view! { <option value=label.clone()>{label}</option> }
+                                        />
+                                    </select>
+                                </label>
+                            </Show>
+
                             <Show when=move || estrogen_method.get() != "injection">
                                 <label>
                                     "Unit"
@@ -1448,6 +2094,16 @@ fn dosage_editor_page(schedule_only: bool) -> impl IntoView {
                                         prop:value=move || aa_frequency.get()
                                     />
                                 </label>
+                                {recurrence_editor(aa_recurrence)}
+                                <label>
+                                    "Pills on hand (optional)"
+                                    <input
+                                        type="text"
+                                        step="any"
+                                        on:input=move |ev| aa_pill_stock.set(event_target_value(&ev))
+                                        prop:value=move || aa_pill_stock.get()
+                                    />
+                                </label>
                             </Show>
 
                             <Show when=move || !aa_type.get().is_empty()>
@@ -1559,6 +2215,16 @@ fn dosage_editor_page(schedule_only: bool) -> impl IntoView {
                                             prop:value=move || prog_frequency.get()
                                         />
                                     </label>
+                                    {recurrence_editor(prog_recurrence)}
+                                    <label>
+                                        "Pills on hand (optional)"
+                                        <input
+                                            type="text"
+                                            step="any"
+                                            on:input=move |ev| prog_pill_stock.set(event_target_value(&ev))
+                                            prop:value=move || prog_pill_stock.get()
+                                        />
+                                    </label>
                                 </Show>
                                 <label>
                                     "Dose"
@@ -1604,21 +2270,911 @@ fn dosage_editor_page(schedule_only: bool) -> impl IntoView {
                         </div>
                     </section>
 
+                    <section class="card dose-card">
+                        <h3>"Estradiol Patch"</h3>
+                        <Show when=move || mode.get() == "record">
+                            <label class="toggle toggle-wide">
+                                <input
+                                    type="checkbox"
+                                    on:change=move |ev| record_patch.set(event_target_checked(&ev))
+                                    prop:checked=move || record_patch.get()
+                                />
+                                <span class="toggle-track" aria-hidden="true"></span>
+                                <span class="toggle-label">"Record Patch Application"</span>
+                            </label>
+                        </Show>
+                        <Show when=move || mode.get() == "schedule">
+                            <label class="toggle toggle-wide">
+                                <input
+                                    type="checkbox"
+                                    on:change=move |ev| patch_enabled.set(event_target_checked(&ev))
+                                    prop:checked=move || patch_enabled.get()
+                                />
+                                <span class="toggle-track" aria-hidden="true"></span>
+                                <span class="toggle-label">"Use Patches"</span>
+                            </label>
+                        </Show>
+                        <Show when=move || {
+                            if mode.get() == "record" { record_patch.get() } else { patch_enabled.get() }
+                        }>
+                            <div class="calc-grid">
+                                <label>
+                                    "Strength (µg/day)"
+                                    <input
+                                        type="text"
+                                        step="any"
+                                        on:input=move |ev| patch_strength.set(event_target_value(&ev))
+                                        prop:value=move || patch_strength.get()
+                                    />
+                                </label>
+                                <label>
+                                    "Patches"
+                                    <input
+                                        type="text"
+                                        min="1"
+                                        step="any"
+                                        on:input=move |ev| patch_quantity.set(event_target_value(&ev))
+                                        prop:value=move || patch_quantity.get()
+                                    />
+                                </label>
+                                <label>
+                                    "Wear (days)"
+                                    <input
+                                        type="text"
+                                        step="any"
+                                        placeholder=move || patch_frequency.get()
+                                        on:input=move |ev| patch_wear_days.set(event_target_value(&ev))
+                                        prop:value=move || patch_wear_days.get()
+                                    />
+                                </label>
+                                <Show when=move || mode.get() == "schedule">
+                                    <label>
+                                        "Change every (days)"
+                                        <input
+                                            type="text"
+                                            step="any"
+                                            on:input=move |ev| patch_frequency.set(event_target_value(&ev))
+                                            prop:value=move || patch_frequency.get()
+                                        />
+                                    </label>
+                                    {recurrence_editor(patch_recurrence)}
+                                    <label>
+                                        "Next Change Date"
+                                        <input
+                                            type="datetime-local"
+                                            on:input=move |ev| patch_next_date.set(event_target_value(&ev))
+                                            prop:value=move || patch_next_date.get()
+                                        />
+                                    </label>
+                                </Show>
+                                <Show when=move || mode.get() == "record">
+                                    <label>
+                                        "Site"
+                                        <select
+                                            on:change=move |ev| patch_site.set(event_target_value(&ev))
+                                            prop:value=move || patch_site.get()
+                                        >
+                                            <option value="">"Select site"</option>
+                                            {PatchSites::ALL
+                                                .iter()
+                                                .map(|site| {
+                                                    let label = site.label();
+                                                    view! { <option value=label>{label}</option> }
+                                                })
+                                                .collect_view()}
+                                        </select>
+                                    </label>
+                                </Show>
+                            </div>
+                            <Show when=move || mode.get() == "schedule">
+                                <p class="muted">"Use 3.5 days for twice-weekly changes."</p>
+                            </Show>
+                            <Show when=move || mode.get() == "record">
+                                <label>
+                                    "Note (optional)"
+                                    <textarea
+                                        rows="2"
+                                        on:input=move |ev| patch_note.set(event_target_value(&ev))
+                                        prop:value=move || patch_note.get()
+                                    ></textarea>
+                                </label>
+                            </Show>
+                        </Show>
+                    </section>
+
+                    <section class="card dose-card">
+                        <h3>"Estradiol Gel"</h3>
+                        <Show when=move || mode.get() == "record">
+                            <label class="toggle toggle-wide">
+                                <input
+                                    type="checkbox"
+                                    on:change=move |ev| record_gel.set(event_target_checked(&ev))
+                                    prop:checked=move || record_gel.get()
+                                />
+                                <span class="toggle-track" aria-hidden="true"></span>
+                                <span class="toggle-label">"Record Gel Dose"</span>
+                            </label>
+                        </Show>
+                        <Show when=move || mode.get() == "schedule">
+                            <label class="toggle toggle-wide">
+                                <input
+                                    type="checkbox"
+                                    on:change=move |ev| gel_enabled.set(event_target_checked(&ev))
+                                    prop:checked=move || gel_enabled.get()
+                                />
+                                <span class="toggle-track" aria-hidden="true"></span>
+                                <span class="toggle-label">"Use Gel"</span>
+                            </label>
+                        </Show>
+                        <Show when=move || {
+                            if mode.get() == "record" { record_gel.get() } else { gel_enabled.get() }
+                        }>
+                            <div class="calc-grid">
+                                <label>
+                                    "Dose (mg)"
+                                    <input
+                                        type="text"
+                                        step="any"
+                                        on:input=move |ev| gel_dose.set(event_target_value(&ev))
+                                        prop:value=move || gel_dose.get()
+                                    />
+                                </label>
+                                <label>
+                                    "Pumps (optional)"
+                                    <input
+                                        type="text"
+                                        step="any"
+                                        on:input=move |ev| gel_pumps.set(event_target_value(&ev))
+                                        prop:value=move || gel_pumps.get()
+                                    />
+                                </label>
+                                <label>
+                                    "mg per pump (optional)"
+                                    <input
+                                        type="text"
+                                        step="any"
+                                        on:input=move |ev| gel_mg_per_pump.set(event_target_value(&ev))
+                                        prop:value=move || gel_mg_per_pump.get()
+                                    />
+                                </label>
+                                <Show when=move || mode.get() == "schedule">
+                                    <label>
+                                        "Frequency (days)"
+                                        <input
+                                            type="text"
+                                            step="any"
+                                            on:input=move |ev| gel_frequency.set(event_target_value(&ev))
+                                            prop:value=move || gel_frequency.get()
+                                        />
+                                    </label>
+                                    {recurrence_editor(gel_recurrence)}
+                                    <label>
+                                        "Next Dose Date"
+                                        <input
+                                            type="datetime-local"
+                                            on:input=move |ev| gel_next_date.set(event_target_value(&ev))
+                                            prop:value=move || gel_next_date.get()
+                                        />
+                                    </label>
+                                </Show>
+                            </div>
+                            <Show when=move || {
+                                parse_optional_num(&gel_pumps.get()).is_some()
+                                    && parse_optional_num(&gel_mg_per_pump.get()).is_some()
+                            }>
+                                <p class="muted">
+                                    {move || {
+                                        let total = gel_dose_mg(
+                                            parse_num(&gel_dose.get()),
+                                            parse_optional_num(&gel_pumps.get()),
+                                            parse_optional_num(&gel_mg_per_pump.get()),
+                                        );
+                                        format!("Total = {} mg", fmt(total, 3))
+                                    }}
+                                </p>
+                            </Show>
+                            <Show when=move || mode.get() == "record">
+                                <label>
+                                    "Note (optional)"
+                                    <textarea
+                                        rows="2"
+                                        on:input=move |ev| gel_note.set(event_target_value(&ev))
+                                        prop:value=move || gel_note.get()
+                                    ></textarea>
+                                </label>
+                            </Show>
+                        </Show>
+                    </section>
+
+                    <Show when=move || mode.get() == "record">
+                        <section class="card dose-card">
+                            <h3>"Other medication"</h3>
+                            <label class="toggle toggle-wide">
+                                <input
+                                    type="checkbox"
+                                    on:change=move |ev| record_other.set(event_target_checked(&ev))
+                                    prop:checked=move || record_other.get()
+                                />
+                                <span class="toggle-track" aria-hidden="true"></span>
+                                <span class="toggle-label">"Record Other Medication Dose"</span>
+                            </label>
+                            <Show when=move || record_other.get()>
+                                <Show
+                                    when=move || !store_other.data.get().medications.is_empty()
+                                    fallback=|| view! { <p class="muted">"Add a medication to the catalog below first."</p> }
+                                >
+                                    <div class="calc-grid">
+                                        <label>
+                                            "Medication"
+                                            <select
+                                                on:change=move |ev| {
+                                                    let id = event_target_value(&ev);
+                                                    if let Some(med) = store_other
+                                                        .data
+                                                        .get()
+                                                        .medications
+                                                        .iter()
+                                                        .find(|med| med.id == id)
+                                                    {
+                                                        other_unit.set(med.unit.symbol().to_string());
+                                                    }
+                                                    other_medication_id.set(id);
+                                                }
+                                                prop:value=move || other_medication_id.get()
+                                            >
+                                                <option value="">"Select"</option>
+                                                <For
+                                                    each=move || store_other.data.get().medications
+                                                    key=|med| med.id.clone()
+                                                    children=move |med| view! { <option value=med.id.clone()>{med.name.clone()}</option> }
+                                                />
+                                            </select>
+                                        </label>
+                                        <label>
+                                            "Dose"
+                                            <input
+                                                type="text"
+                                                step="any"
+                                                on:input=move |ev| other_dose.set(event_target_value(&ev))
+                                                prop:value=move || other_dose.get()
+                                            />
+                                        </label>
+                                        <label>
+                                            "Unit"
+                                            <select
+                                                on:change=move |ev| other_unit.set(event_target_value(&ev))
+                                                prop:value=move || other_unit.get()
+                                            >
+                                                <For
+                                                    each=move || hormone_unit_labels()
+                                                    key=|label| label.clone()
+                                                    children=move |label| view! { <option value=label.clone()>{label}</option> }
+                                                />
+                                            </select>
+                                        </label>
+                                    </div>
+                                    <label>
+                                        "Note (optional)"
+                                        <textarea
+                                            rows="2"
+                                            on:input=move |ev| other_note.set(event_target_value(&ev))
+                                            prop:value=move || other_note.get()
+                                        ></textarea>
+                                    </label>
+                                </Show>
+                            </Show>
+                        </section>
+                    </Show>
+
+                    <Show when=move || schedule_only>
+                        <label class="toggle toggle-wide">
+                            <input
+                                type="checkbox"
+                                on:change=move |ev| add_concurrent.set(event_target_checked(&ev))
+                                prop:checked=move || add_concurrent.get()
+                            />
+                            <span class="toggle-track" aria-hidden="true"></span>
+                            <span class="toggle-label">"Add as new concurrent regimens"</span>
+                        </label>
+                    </Show>
+
                     <div class="form-actions">
                         <button type="submit">
                             {move || if mode.get() == "record" { "Record dosage" } else { "Save schedule" }}
                         </button>
+                        <Show when=move || schedule_error.get().is_some()>
+                            <p class="muted">{move || schedule_error.get().unwrap_or_default()}</p>
+                        </Show>
                         <Show when=move || schedule_feedback.get()>
                             <p class="muted">"Schedule saved!"</p>
                         </Show>
                     </div>
                 </form>
+
+                <Show when=move || !schedule_only>
+                    {medication_catalog_card(store_catalog.clone())}
+                </Show>
+                <Show when=move || schedule_only>
+                    {regimens_card(store_regimens.clone(), end_edited)}
+                    {plans_card(store_regimens.clone())}
+                </Show>
             </div>
         }
         .into_view(),
     )
 }
 
+fn medication_catalog_card(store: AppStore) -> impl IntoView {
+    let name = create_rw_signal(String::new());
+    let class = create_rw_signal(MedicationClass::Other.label().to_string());
+    let route = create_rw_signal(MedicationRoute::Oral.label().to_string());
+    let unit = create_rw_signal(HormoneUnits::Mg.symbol().to_string());
+    let pk_model = create_rw_signal(String::new());
+    let error = create_rw_signal(None::<String>);
+
+    let add_medication = {
+        let store = store.clone();
+        move |_| {
+            let medication = Medication {
+                id: format!("med-{}", Date::now() as i64),
+                name: name.get().trim().to_string(),
+                class: MedicationClass::from_label(&class.get()).unwrap_or(MedicationClass::Other),
+                route: MedicationRoute::from_label(&route.get()).unwrap_or(MedicationRoute::Other),
+                unit: HormoneUnits::from_symbol(&unit.get()).unwrap_or(HormoneUnits::Mg),
+                pkModelId: if pk_model.get().is_empty() {
+                    None
+                } else {
+                    Some(pk_model.get())
+                },
+            };
+            if let Err(err) = validate_medication(&medication) {
+                error.set(Some(err));
+                return;
+            }
+            error.set(None);
+            store.data.update(|data| data.medications.push(medication));
+            store.mark_dirty();
+            store.save();
+            name.set(String::new());
+        }
+    };
+
+    let data = store.data;
+    let pk_model_options = move || {
+        let mut options: Vec<(String, String)> = data
+            .get()
+            .pkModels
+            .into_iter()
+            .map(|model| (model.id, model.name))
+            .collect();
+        options.extend(
+            BUILTIN_PK_MODELS
+                .iter()
+                .map(|model| (model.to_string(), model.to_string())),
+        );
+        options
+    };
+    let pk_model_name = move |id: &Option<String>| {
+        let Some(id) = id else {
+            return "—".to_string();
+        };
+        data.get()
+            .pkModels
+            .iter()
+            .find(|model| &model.id == id)
+            .map(|model| model.name.clone())
+            .unwrap_or_else(|| id.clone())
+    };
+
+    view! {
+        <div class="card">
+            <h3>"Medication catalog"</h3>
+            <p class="muted">
+                "Medications without a dedicated section, e.g. GnRH agonists, dutasteride, raloxifene or gel. Estrogens with a PK model are included in E2 predictions."
+            </p>
+            <Show when=move || !data.get().medications.is_empty()>
+                <table class="table">
+                    <thead>
+                        <tr>
+                            <th>"Name"</th>
+                            <th>"Class"</th>
+                            <th>"Route"</th>
+                            <th>"Unit"</th>
+                            <th>"PK model"</th>
+                            <th></th>
+                        </tr>
+                    </thead>
+                    <tbody>
+                        {
+                            let store = store.clone();
+                            move || {
+                                data.get()
+                                    .medications
+                                    .into_iter()
+                                    .map(|med| {
+                                        let id = med.id.clone();
+                                        let store = store.clone();
+                                        view! {
+                                            <tr>
+                                                <td>{med.name.clone()}</td>
+                                                <td>{med.class.label()}</td>
+                                                <td>{med.route.label()}</td>
+                                                <td>{med.unit.symbol()}</td>
+                                                <td>{pk_model_name(&med.pkModelId)}</td>
+                                                <td>
+                                                    <button type="button" on:click=move |_| {
+                                                        store.data.update(|data| data.medications.retain(|med| med.id != id));
+                                                        store.mark_dirty();
+                                                        store.save();
+                                                    }>
+                                                        "Delete"
+                                                    </button>
+                                                </td>
+                                            </tr>
+                                        }
+                                    })
+                                    .collect_view()
+                            }
+                        }
+                    </tbody>
+                </table>
+            </Show>
+            <div class="chart-toolbar">
+                <div class="chart-toolbar-group">
+                    <label class="muted">"Name"</label>
+                    <input
+                        type="text"
+                        on:input=move |ev| name.set(event_target_value(&ev))
+                        prop:value=move || name.get()
+                    />
+                </div>
+                <div class="chart-toolbar-group">
+                    <label class="muted">"Class"</label>
+                    <select
+                        on:change=move |ev| class.set(event_target_value(&ev))
+                        prop:value=move || class.get()
+                    >
+                        {MedicationClass::ALL
+                            .iter()
+                            .map(|class| {
+                                let label = class.label();
+                                view! { <option value=label>{label}</option> }
+                            })
+                            .collect_view()}
+                    </select>
+                </div>
+                <div class="chart-toolbar-group">
+                    <label class="muted">"Route"</label>
+                    <select
+                        on:change=move |ev| route.set(event_target_value(&ev))
+                        prop:value=move || route.get()
+                    >
+                        {MedicationRoute::ALL
+                            .iter()
+                            .map(|route| {
+                                let label = route.label();
+                                view! { <option value=label>{label}</option> }
+                            })
+                            .collect_view()}
+                    </select>
+                </div>
+                <div class="chart-toolbar-group">
+                    <label class="muted">"Default unit"</label>
+                    <select
+                        on:change=move |ev| unit.set(event_target_value(&ev))
+                        prop:value=move || unit.get()
+                    >
+                        {hormone_unit_labels()
+                            .into_iter()
+                            .map(|label| view! { <option value=label.clone()>{label}</option> })
+                            .collect_view()}
+                    </select>
+                </div>
+                <div class="chart-toolbar-group">
+                    <label class="muted">"PK model"</label>
+                    <select
+                        on:change=move |ev| pk_model.set(event_target_value(&ev))
+                        prop:value=move || pk_model.get()
+                    >
+                        <option value="">"None"</option>
+                        {move || {
+                            pk_model_options()
+                                .into_iter()
+                                .map(|(id, label)| view! { <option value=id>{label}</option> })
+                                .collect_view()
+                        }}
+                    </select>
+                </div>
+                <div class="chart-toolbar-group">
+                    <button type="button" on:click=add_medication>"Add medication"</button>
+                </div>
+            </div>
+            <Show when=move || error.get().is_some()>
+                <p class="muted">{move || error.get().unwrap_or_default()}</p>
+            </Show>
+        </div>
+    }
+}
+
+fn regimens_card(store: AppStore, end_edited: impl Fn(String) + Copy + 'static) -> impl IntoView {
+    let data = store.data;
+    let settings = store.settings;
+    let end_now = {
+        let store = store.clone();
+        move |id: String| {
+            store.data.update(|data| {
+                end_regimen(data, &id, Date::now() as i64);
+            });
+            end_edited(id);
+            store.mark_dirty();
+            store.save();
+        }
+    };
+    let delete = {
+        let store = store.clone();
+        move |id: String| {
+            store
+                .data
+                .update(|data| data.regimens.retain(|regimen| regimen.id != id));
+            store.mark_dirty();
+            store.save();
+        }
+    };
+    let regimen_rows = move |current: bool| {
+        let data_value = data.get();
+        let use_iu = settings.get().displayInjectableInIU.unwrap_or(false);
+        let mut regimens: Vec<_> = data_value
+            .regimens
+            .iter()
+            .filter(|regimen| regimen.is_current() == current)
+            .cloned()
+            .collect();
+        regimens.sort_by_key(|regimen| std::cmp::Reverse(regimen.startDate));
+        regimens
+            .into_iter()
+            .map(|regimen| {
+                let (title, detail) = fmt_regimen(&data_value, &regimen, use_iu);
+                let period = match regimen.endDate {
+                    Some(end) => format!(
+                        "{} – {}",
+                        fmt_date_label(regimen.startDate, "date", None),
+                        fmt_date_label(end, "date", None)
+                    ),
+                    None => format!("since {}", fmt_date_label(regimen.startDate, "date", None)),
+                };
+                let id = regimen.id.clone();
+                let end_now = end_now.clone();
+                let delete = delete.clone();
+                view! {
+                    <div class="chart-toolbar">
+                        <div class="chart-toolbar-group">
+                            <p>
+                                <strong>{format!("{title}: ")}</strong>
+                                {detail}
+                                <span class="muted">{format!(" ({period})")}</span>
+                            </p>
+                        </div>
+                        <div class="chart-toolbar-group">
+                            {if current {
+                                view! {
+                                    <button type="button" on:click=move |_| end_now(id.clone())>
+                                        "End"
+                                    </button>
+                                }
+                            } else {
+                                view! {
+                                    <button type="button" on:click=move |_| delete(id.clone())>
+                                        "Delete"
+                                    </button>
+                                }
+                            }}
+                        </div>
+                    </div>
+                }
+            })
+            .collect_view()
+    };
+    let past_rows = regimen_rows.clone();
+    let has_past =
+        move || data.with(|data| data.regimens.iter().any(|regimen| !regimen.is_current()));
+
+    view! {
+        <section class="card">
+            <h3>"Regimens"</h3>
+            <p class="muted">
+                "Changing a dose ends the current regimen and starts a new one. Ended regimens stay in the history."
+            </p>
+            {move || regimen_rows(true)}
+            <Show when=has_past>
+                <h4>"Past regimens"</h4>
+                {
+                    let past_rows = past_rows.clone();
+                    move || past_rows(false)
+                }
+            </Show>
+        </section>
+    }
+}
+
+fn plans_card(store: AppStore) -> impl IntoView {
+    let data = store.data;
+    let settings = store.settings;
+    let plan_name = create_rw_signal(String::new());
+    let plan_target = create_rw_signal(String::new());
+    let step_date = create_rw_signal(String::new());
+    let step_dose = create_rw_signal(String::new());
+    let step_frequency = create_rw_signal(String::new());
+    let step_stop = create_rw_signal(false);
+    let draft_steps = create_rw_signal(Vec::<PlanStep>::new());
+    let plan_error = create_rw_signal(None::<String>);
+
+    let target_schedule = move || {
+        let target = plan_target.get();
+        data.with(|data| {
+            current_regimens(data)
+                .find(|regimen| regimen.id == target)
+                .map(|regimen| regimen.schedule.clone())
+        })
+    };
+    let step_label = move |step: &PlanStep| match &step.schedule {
+        Some(schedule) => {
+            let regimen = Regimen {
+                id: String::new(),
+                startDate: step.startDate,
+                endDate: None,
+                schedule: schedule.clone(),
+                recurrence: step.recurrence.clone(),
+            };
+            let use_iu = settings.get().displayInjectableInIU.unwrap_or(false);
+            fmt_regimen(&data.get(), &regimen, use_iu).1
+        }
+        None => "Stop".to_string(),
+    };
+
+    let add_step = move |_| {
+        plan_error.set(None);
+        let Some(schedule) = target_schedule() else {
+            plan_error.set(Some("Choose the regimen this plan changes.".to_string()));
+            return;
+        };
+        if step_date.get().trim().is_empty() {
+            plan_error.set(Some("Pick a start date for the step.".to_string()));
+            return;
+        }
+        let start = parse_datetime_local(&step_date.get());
+        let schedule = if step_stop.get() {
+            None
+        } else {
+            let dose = parse_decimal(&step_dose.get()).filter(|dose| *dose > 0.0);
+            let frequency = parse_decimal(&step_frequency.get()).filter(|freq| *freq > 0.0);
+            let (Some(dose), Some(frequency)) = (dose, frequency) else {
+                plan_error.set(Some("Enter a dose and frequency, or stop.".to_string()));
+                return;
+            };
+            let mut schedule = schedule.with_dosing(dose, frequency);
+            schedule.set_next_dose_date(start);
+            Some(schedule)
+        };
+        draft_steps.update(|steps| {
+            steps.retain(|step| step.startDate != start);
+            steps.push(PlanStep {
+                startDate: start,
+                schedule,
+                recurrence: None,
+                applied: false,
+            });
+            steps.sort_by_key(|step| step.startDate);
+        });
+        step_date.set(String::new());
+        step_stop.set(false);
+    };
+
+    let save_plan = {
+        let store = store.clone();
+        move |_| {
+            plan_error.set(None);
+            let name = plan_name.get().trim().to_string();
+            if name.is_empty() {
+                plan_error.set(Some("Name the plan.".to_string()));
+                return;
+            }
+            if target_schedule().is_none() {
+                plan_error.set(Some("Choose the regimen this plan changes.".to_string()));
+                return;
+            }
+            let steps = draft_steps.get();
+            if steps.is_empty() {
+                plan_error.set(Some("Add at least one step.".to_string()));
+                return;
+            }
+            let plan = DosePlan {
+                id: format!("plan-{}", Date::now() as i64),
+                name,
+                regimenId: Some(plan_target.get()),
+                steps,
+            };
+            store.data.update(|data| data.plans.push(plan));
+            plan_name.set(String::new());
+            draft_steps.set(Vec::new());
+            store.mark_dirty();
+            store.save();
+        }
+    };
+
+    let delete_plan = {
+        let store = store.clone();
+        move |id: String| {
+            store
+                .data
+                .update(|data| data.plans.retain(|plan| plan.id != id));
+            store.mark_dirty();
+            store.save();
+        }
+    };
+
+    let plan_rows = move || {
+        data.get()
+            .plans
+            .into_iter()
+            .map(|plan| {
+                let delete_plan = delete_plan.clone();
+                let id = plan.id.clone();
+                let steps = plan
+                    .steps
+                    .iter()
+                    .map(|step| {
+                        let status = if step.applied { "started" } else { "pending" };
+                        view! {
+                            <li>
+                                {format!(
+                                    "{}: {} ",
+                                    fmt_date_label(step.startDate, "date", None),
+                                    step_label(step)
+                                )}
+                                <span class="muted">{format!("({status})")}</span>
+                            </li>
+                        }
+                    })
+                    .collect_view();
+                view! {
+                    <div>
+                        <div class="chart-toolbar">
+                            <div class="chart-toolbar-group">
+                                <strong>{plan.name.clone()}</strong>
+                            </div>
+                            <div class="chart-toolbar-group">
+                                <button type="button" on:click=move |_| delete_plan(id.clone())>
+                                    "Delete"
+                                </button>
+                            </div>
+                        </div>
+                        <ul>{steps}</ul>
+                    </div>
+                }
+            })
+            .collect_view()
+    };
+
+    view! {
+        <section class="card">
+            <h3>"Dose plans"</h3>
+            <p class="muted">
+                "Plan a titration or taper ahead of time. Each step replaces the regimen on its start date."
+            </p>
+            {plan_rows}
+            <h4>"New plan"</h4>
+            <label>
+                "Name"
+                <input
+                    type="text"
+                    on:input=move |ev| plan_name.set(event_target_value(&ev))
+                    prop:value=move || plan_name.get()
+                />
+            </label>
+            <label>
+                "Regimen"
+                <select
+                    on:change=move |ev| plan_target.set(event_target_value(&ev))
+                    prop:value=move || plan_target.get()
+                >
+                    <option value="">"Choose a regimen"</option>
+                    {move || {
+                        let data_value = data.get();
+                        let use_iu = settings.get().displayInjectableInIU.unwrap_or(false);
+                        current_regimens(&data_value)
+                            .map(|regimen| {
+                                let (title, detail) = fmt_regimen(&data_value, regimen, use_iu);
+                                view! {
+                                    <option value=regimen.id.clone()>
+                                        {format!("{title}: {detail}")}
+                                    </option>
+                                }
+                            })
+                            .collect_view()
+                    }}
+                </select>
+            </label>
+            <ul>
+                {move || {
+                    draft_steps
+                        .get()
+                        .iter()
+                        .map(|step| {
+                            let start = step.startDate;
+                            view! {
+                                <li>
+                                    {format!(
+                                        "{}: {} ",
+                                        fmt_date_label(start, "date", None),
+                                        step_label(step)
+                                    )}
+                                    <button
+                                        type="button"
+                                        on:click=move |_| {
+                                            draft_steps.update(|steps| {
+                                                steps.retain(|step| step.startDate != start)
+                                            })
+                                        }
+                                    >
+                                        "Remove"
+                                    </button>
+                                </li>
+                            }
+                        })
+                        .collect_view()
+                }}
+            </ul>
+            <label>
+                "Step starts"
+                <input
+                    type="datetime-local"
+                    on:input=move |ev| step_date.set(event_target_value(&ev))
+                    prop:value=move || step_date.get()
+                />
+            </label>
+            <label>
+                <input
+                    type="checkbox"
+                    on:change=move |ev| step_stop.set(event_target_checked(&ev))
+                    prop:checked=move || step_stop.get()
+                />
+                "Stop the medication"
+            </label>
+            <Show when=move || !step_stop.get()>
+                <label>
+                    "Dose"
+                    <input
+                        type="text"
+                        inputmode="decimal"
+                        on:input=move |ev| step_dose.set(event_target_value(&ev))
+                        prop:value=move || step_dose.get()
+                    />
+                </label>
+                <label>
+                    "Frequency (days)"
+                    <input
+                        type="text"
+                        inputmode="decimal"
+                        on:input=move |ev| step_frequency.set(event_target_value(&ev))
+                        prop:value=move || step_frequency.get()
+                    />
+                </label>
+            </Show>
+            <div class="form-actions">
+                <button type="button" on:click=add_step>
+                    "Add step"
+                </button>
+                <button type="button" on:click=save_plan>
+                    "Save plan"
+                </button>
+                <Show when=move || plan_error.get().is_some()>
+                    <p class="muted">{move || plan_error.get().unwrap_or_default()}</p>
+                </Show>
+            </div>
+        </section>
+    }
+}
+
 #[component]
 pub fn CreateDosage() -> impl IntoView {
     dosage_editor_page(false)
@@ -14,17 +14,68 @@ use crate::estrannaise::compute_estrannaise_series;
 use crate::layout::page_layout;
 use crate::store::use_store;
 use crate::utils::{
-    compute_fudge_factor, fmt_blood_value, fmt_date_label, fmt_decimal, hormone_unit_label,
-    injectable_dose_from_iu, parse_decimal,
+    compute_fudge_factor, fmt_blood_value, fmt_date_label, fmt_decimal, injectable_dose_from_iu,
+    parse_decimal,
 };
-use hrt_shared::logic::predict_e2_pg_ml;
-use hrt_shared::types::{BloodTest, HormoneUnits};
+use hrt_shared::estrannaise::{
+    injectable_model, validate_custom_pk_model, UNCERTAINTY_COVERAGE,
+};
+use hrt_shared::logic::{fit_pk_parameters, predict_e2_pg_ml};
+use hrt_shared::quantity::BloodAnalyte;
+use hrt_shared::regimen::{e2_unit_factor, optimize_regimen, RegimenOption, RegimenRequest};
+use hrt_shared::schedules::current_injectable;
+use hrt_shared::types::{
+    BloodTest, CustomPkModel, CustomPkRoute, HormoneUnits, InjectableEstradiols,
+};
+use hrt_shared::whatif::DoseAdjustment;
+
+const WHATIF_ROWS: usize = 6;
+const WHATIF_ACTIONS: &[(&str, &str)] = &[
+    ("planned", "As planned"),
+    ("skip", "Skip"),
+    ("late1", "1 day late"),
+    ("late2", "2 days late"),
+    ("late3", "3 days late"),
+    ("early1", "1 day early"),
+    ("double", "Double up"),
+    ("half", "Half dose"),
+];
+
+fn whatif_adjustment(index: usize, action: &str) -> Option<DoseAdjustment> {
+    match action {
+        "skip" => Some(DoseAdjustment::Skip { index }),
+        "late1" => Some(DoseAdjustment::Shift { index, days: 1.0 }),
+        "late2" => Some(DoseAdjustment::Shift { index, days: 2.0 }),
+        "late3" => Some(DoseAdjustment::Shift { index, days: 3.0 }),
+        "early1" => Some(DoseAdjustment::Shift { index, days: -1.0 }),
+        "double" => Some(DoseAdjustment::Scale { index, factor: 2.0 }),
+        "half" => Some(DoseAdjustment::Scale { index, factor: 0.5 }),
+        _ => None,
+    }
+}
+
+const OPTIMIZER_ESTERS: &[(InjectableEstradiols, &str)] = &[
+    (InjectableEstradiols::Benzoate, "Benzoate"),
+    (InjectableEstradiols::Valerate, "Valerate"),
+    (InjectableEstradiols::Enanthate, "Enanthate"),
+    (InjectableEstradiols::Cypionate, "Cypionate"),
+    (InjectableEstradiols::Undecylate, "Undecylate"),
+    (InjectableEstradiols::PolyestradiolPhosphate, "Polyestradiol Phosphate"),
+];
+
+fn custom_route_label(route: &CustomPkRoute) -> &'static str {
+    match route {
+        CustomPkRoute::Injection => "Injection",
+        CustomPkRoute::Oral => "Oral",
+    }
+}
 
 #[component]
 pub fn EstrannaisePage() -> impl IntoView {
     let store = use_store();
     let x_axis_mode = create_rw_signal("date".to_string());
     let forecast_enabled = create_rw_signal(true);
+    let show_uncertainty = create_rw_signal(false);
     let forecast_weeks = create_rw_signal(8_i64);
     let forecast_dose_override = create_rw_signal(String::new());
     let forecast_frequency_override = create_rw_signal(String::new());
@@ -32,6 +83,41 @@ pub fn EstrannaisePage() -> impl IntoView {
     let estrannaise_zoom = create_rw_signal(ViewZoom::default());
     let estrannaise_tooltip = create_rw_signal(None::<ChartTooltip>);
 
+    let initial_unit = store
+        .settings
+        .get_untracked()
+        .displayEstradiolUnit
+        .unwrap_or(HormoneUnits::E2PmolL);
+    let initial_factor = e2_unit_factor(&initial_unit).unwrap_or(1.0);
+    let target_input = |pg_ml: f64| fmt_decimal(pg_ml * initial_factor, 0);
+    let optimizer_ester = create_rw_signal(
+        store
+            .data
+            .with_untracked(|data| current_injectable(data).map(|cfg| cfg.kind.clone()))
+            .and_then(|kind| {
+                OPTIMIZER_ESTERS
+                    .iter()
+                    .find(|(ester, _)| *ester == kind)
+                    .map(|(_, label)| label.to_string())
+            })
+            .unwrap_or_else(|| "Valerate".to_string()),
+    );
+    let optimizer_trough_min = create_rw_signal(target_input(100.0));
+    let optimizer_trough_max = create_rw_signal(target_input(200.0));
+    let optimizer_peak_min = create_rw_signal(target_input(150.0));
+    let optimizer_peak_max = create_rw_signal(target_input(400.0));
+    let optimizer_intervals = create_rw_signal("3.5, 5, 7, 10, 14".to_string());
+    let optimizer_results = create_rw_signal(Vec::<RegimenOption>::new());
+    let optimizer_error = create_rw_signal(None::<String>);
+    let whatif_actions = create_rw_signal(vec!["planned".to_string(); WHATIF_ROWS]);
+    let custom_name = create_rw_signal(String::new());
+    let custom_route = create_rw_signal(custom_route_label(&CustomPkRoute::Injection).to_string());
+    let custom_d = create_rw_signal(String::new());
+    let custom_k1 = create_rw_signal(String::new());
+    let custom_k2 = create_rw_signal(String::new());
+    let custom_k3 = create_rw_signal(String::new());
+    let custom_error = create_rw_signal(None::<String>);
+
     let blood_test_options = create_memo({
         let settings = store.settings;
         move |_| {
@@ -40,11 +126,11 @@ pub fn EstrannaisePage() -> impl IntoView {
                 .get()
                 .displayEstradiolUnit
                 .unwrap_or(HormoneUnits::E2PmolL);
-            let fallback_label = hormone_unit_label(&display_unit);
+            let fallback_label = display_unit.symbol();
             let mut tests: Vec<&BloodTest> = data_value
                 .bloodTests
                 .iter()
-                .filter(|test| test.estradiolLevel.is_some())
+                .filter(|test| test.estradiol.is_some())
                 .collect();
             tests.sort_by_key(|test| test.date);
             tests.reverse();
@@ -52,15 +138,10 @@ pub fn EstrannaisePage() -> impl IntoView {
                 .into_iter()
                 .map(|test| {
                     let date_label = fmt_date_label(test.date, "date", None);
-                    let value_label = test
-                        .estradiolLevel
-                        .map(fmt_blood_value)
-                        .unwrap_or_else(|| "-".to_string());
-                    let unit_label = test
-                        .estradiolUnit
-                        .as_ref()
-                        .map(hormone_unit_label)
-                        .unwrap_or(fallback_label);
+                    let (value_label, unit_label) = match &test.estradiol {
+                        Some(level) => (fmt_blood_value(level.value), level.unit.symbol()),
+                        None => ("-".to_string(), fallback_label),
+                    };
                     (
                         test.date.to_string(),
                         format!("{date_label} · E2 {value_label} {unit_label}"),
@@ -86,7 +167,7 @@ pub fn EstrannaisePage() -> impl IntoView {
             if let Some(fudge) = test.fudgeFactor {
                 return Some(fudge);
             }
-            let measured = measured_e2_pg_ml(test);
+            let measured = test.level_in(BloodAnalyte::Estradiol, HormoneUnits::E2PgMl);
             let predicted = predict_e2_pg_ml(&data_value, test.date);
             compute_fudge_factor(measured, predicted)
         }
@@ -99,10 +180,8 @@ pub fn EstrannaisePage() -> impl IntoView {
                 return false;
             }
             let data_value = store.data.get();
-            let schedule_vial_id = data_value
-                .injectableEstradiol
-                .as_ref()
-                .and_then(|cfg| cfg.vialId.as_ref());
+            let schedule_vial_id =
+                current_injectable(&data_value).and_then(|cfg| cfg.vialId.as_ref());
             injectable_dose_from_iu(&data_value, 1.0, schedule_vial_id, schedule_vial_id).is_some()
         }
     });
@@ -111,10 +190,8 @@ pub fn EstrannaisePage() -> impl IntoView {
         let store = store.clone();
         move |_| {
             let data_value = store.data.get();
-            let schedule_vial_id = data_value
-                .injectableEstradiol
-                .as_ref()
-                .and_then(|cfg| cfg.vialId.clone())?;
+            let schedule_vial_id =
+                current_injectable(&data_value).and_then(|cfg| cfg.vialId.clone())?;
             let vial = data_value
                 .vials
                 .iter()
@@ -131,6 +208,35 @@ pub fn EstrannaisePage() -> impl IntoView {
     });
 
     let concentration_input = create_rw_signal(String::new());
+
+    let add_custom_model = {
+        let store = store.clone();
+        move |_| {
+            let parse = |signal: RwSignal<String>| parse_decimal(&signal.get()).unwrap_or(f64::NAN);
+            let route = if custom_route.get() == custom_route_label(&CustomPkRoute::Oral) {
+                CustomPkRoute::Oral
+            } else {
+                CustomPkRoute::Injection
+            };
+            let model = CustomPkModel {
+                id: format!("pk-{}", js_sys::Date::now() as i64),
+                name: custom_name.get().trim().to_string(),
+                route,
+                d: parse(custom_d),
+                k1: parse(custom_k1),
+                k2: parse(custom_k2),
+                k3: parse(custom_k3),
+            };
+            if let Err(err) = validate_custom_pk_model(&model) {
+                custom_error.set(Some(err));
+                return;
+            }
+            custom_error.set(None);
+            store.data.update(|data| data.pkModels.push(model));
+            store.mark_dirty();
+            custom_name.set(String::new());
+        }
+    };
     create_effect({
         let concentration_target_vial = concentration_target_vial;
         move |_| {
@@ -178,16 +284,100 @@ pub fn EstrannaisePage() -> impl IntoView {
     };
     let apply_concentration_update = StoredValue::new(Rc::new(apply_concentration_update));
 
+    let run_optimizer = {
+        let store = store.clone();
+        move |_| {
+            let data_value = store.data.get_untracked();
+            let unit = store
+                .settings
+                .get_untracked()
+                .displayEstradiolUnit
+                .unwrap_or(HormoneUnits::E2PmolL);
+            let ester = optimizer_ester.get_untracked();
+            let Some(model) = OPTIMIZER_ESTERS
+                .iter()
+                .find(|(_, label)| *label == ester)
+                .and_then(|(kind, _)| injectable_model(kind))
+            else {
+                optimizer_error.set(Some("Choose an ester.".to_string()));
+                return;
+            };
+            let (Some(trough_min), Some(trough_max), Some(peak_min), Some(peak_max)) = (
+                parse_decimal(&optimizer_trough_min.get_untracked()),
+                parse_decimal(&optimizer_trough_max.get_untracked()),
+                parse_decimal(&optimizer_peak_min.get_untracked()),
+                parse_decimal(&optimizer_peak_max.get_untracked()),
+            ) else {
+                optimizer_error.set(Some("Enter numeric trough and peak targets.".to_string()));
+                return;
+            };
+            let intervals: Vec<f64> = optimizer_intervals
+                .get_untracked()
+                .split(',')
+                .filter_map(|part| parse_decimal(part.trim()))
+                .filter(|value| *value > 0.0)
+                .collect();
+            if intervals.is_empty() {
+                optimizer_error.set(Some("Enter at least one interval in days.".to_string()));
+                return;
+            }
+            let fudge_factor = selected_fudge_factor
+                .get_untracked()
+                .or_else(|| fit_pk_parameters(&data_value).map(|fit| fit.scale));
+            let request = RegimenRequest {
+                model,
+                troughMin: trough_min,
+                troughMax: trough_max,
+                peakMin: peak_min,
+                peakMax: peak_max,
+                unit: Some(unit),
+                intervals,
+                fudgeFactor: fudge_factor,
+                doseStep: None,
+                maxOptions: None,
+            };
+            match optimize_regimen(&request) {
+                Ok(options) => {
+                    optimizer_results.set(options);
+                    optimizer_error.set(None);
+                }
+                Err(err) => {
+                    optimizer_results.set(Vec::new());
+                    optimizer_error.set(Some(err));
+                }
+            }
+        }
+    };
+
+    let use_regimen_option = {
+        let store = store.clone();
+        move |option: &RegimenOption| {
+            let dose_input = if forecast_dose_in_iu.get_untracked() {
+                let data_value = store.data.get_untracked();
+                let schedule_vial_id =
+                    current_injectable(&data_value).and_then(|cfg| cfg.vialId.as_ref());
+                injectable_dose_from_iu(&data_value, 1.0, schedule_vial_id, schedule_vial_id)
+                    .filter(|mg_per_iu| *mg_per_iu > 0.0)
+                    .map(|mg_per_iu| fmt_decimal(option.dose / mg_per_iu, 1))
+                    .unwrap_or_default()
+            } else {
+                fmt_decimal(option.dose, 3)
+            };
+            forecast_dose_override.set(dose_input);
+            forecast_frequency_override.set(fmt_decimal(option.frequency, 2));
+            forecast_enabled.set(true);
+        }
+    };
+    let use_regimen_option = StoredValue::new(Rc::new(use_regimen_option));
+
     let estrannaise_series = create_memo({
         let settings = store.settings;
         move |_| {
             let data_value = store.data.get();
             let settings_value = settings.get();
             let dose_override = if forecast_dose_in_iu.get() {
-                let schedule_vial_id = data_value
-                    .injectableEstradiol
-                    .as_ref()
-                    .and_then(|cfg| cfg.vialId.as_ref());
+                let schedule_vial_id =
+                    current_injectable(&data_value).and_then(|cfg| cfg.vialId.as_ref());
                 parse_decimal(&forecast_dose_override.get()).and_then(|iu| {
                     injectable_dose_from_iu(&data_value, iu, schedule_vial_id, schedule_vial_id)
                 })
@@ -200,10 +390,17 @@ pub fn EstrannaisePage() -> impl IntoView {
                 &settings_value,
                 &x_axis_mode.get(),
                 forecast_enabled.get(),
+                show_uncertainty.get(),
                 forecast_weeks.get(),
                 dose_override,
                 freq_override,
                 selected_fudge_factor.get(),
+                &whatif_actions
+                    .get()
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(index, action)| whatif_adjustment(index, action))
+                    .collect::<Vec<_>>(),
             )
         }
     });
@@ -242,7 +439,7 @@ pub fn EstrannaisePage() -> impl IntoView {
                 series.y_max,
             );
             let mut best: Option<(ChartTooltip, f64)> = None;
-            for set in [&series.blended, &series.stepped, &series.blood] {
+            for set in [&series.blended, &series.stepped, &series.fitted, &series.blood] {
                 if let Some(candidate) = find_nearest_estrannaise_point(
                     set,
                     x_min,
@@ -446,6 +643,14 @@ pub fn EstrannaisePage() -> impl IntoView {
                             prop:checked=move || forecast_enabled.get()
                         />
                     </div>
+                    <div class="chart-toolbar-group">
+                        <label class="muted">"Uncertainty"</label>
+                        <input
+                            type="checkbox"
+                            on:change=move |ev| show_uncertainty.set(event_target_checked(&ev))
+                            prop:checked=move || show_uncertainty.get()
+                        />
+                    </div>
                     <div class="chart-toolbar-group">
                         <label class="muted">"Weeks"</label>
                         <select on:change=move |ev| forecast_weeks.set(event_target_value(&ev).parse::<i64>().unwrap_or(8))>
@@ -589,8 +794,32 @@ pub fn EstrannaisePage() -> impl IntoView {
                     <div class="chart-note muted">
                         <p>"* Blue line blends fudge factor between blood tests."</p>
                         <p>"* Pink dashed line steps to each test's fudge factor."</p>
+                        <Show when=move || estrannaise_series.get().fit.is_some()>
+                            <p>{move || {
+                                estrannaise_series
+                                    .get()
+                                    .fit
+                                    .map(|fit| format!(
+                                        "* Green line is fitted to all {} blood test(s): scale {:.2}, shift {:+.1} h, RMSE {:.1} pg/mL.",
+                                        fit.samples,
+                                        fit.scale,
+                                        fit.time_shift_days * 24.0,
+                                        fit.rmse_pg_ml,
+                                    ))
+                                    .unwrap_or_default()
+                            }}</p>
+                        </Show>
+                        <Show when=move || !estrannaise_series.get().whatif.is_empty()>
+                            <p>"* Dashed purple line applies the what-if dose changes below."</p>
+                        </Show>
                         <p>"* Orange points show measured E2 in display units."</p>
                         <p>"* Shaded region is forecasted schedule window."</p>
+                        <Show when=move || !estrannaise_series.get().band.is_empty()>
+                            <p>{format!(
+                                "* Blue band is the {:.0}% range from population variability in absorption and clearance.",
+                                UNCERTAINTY_COVERAGE * 100.0
+                            )}</p>
+                        </Show>
                         <Show when=move || selected_fudge_factor.get().is_some()>
                             <p>{move || {
                                 let value = selected_fudge_factor.get().unwrap_or(1.0);
@@ -599,22 +828,320 @@ pub fn EstrannaisePage() -> impl IntoView {
                         </Show>
                     </div>
                 </div>
+
+                <div class="card">
+                    <div class="view-header">
+                        <div>
+                            <h3>"What-if Simulator"</h3>
+                            <p class="muted">
+                                "Skip, delay or double upcoming doses to see the dashed purple curve against the plan."
+                            </p>
+                        </div>
+                        <button on:click=move |_| whatif_actions.set(vec!["planned".to_string(); WHATIF_ROWS])>
+                            "Reset"
+                        </button>
+                    </div>
+                    <Show
+                        when=move || !estrannaise_series.get().planned_doses.is_empty()
+                        fallback=move || view! {
+                            <p class="muted">"Turn on Forecast with an estradiol schedule to simulate upcoming doses."</p>
+                        }
+                    >
+                        <table class="table">
+                            <thead>
+                                <tr>
+                                    <th>"Planned"</th>
+                                    <th>"Dose (mg)"</th>
+                                    <th>"What if"</th>
+                                </tr>
+                            </thead>
+                            <tbody>
+                                {move || {
+                                    estrannaise_series
+                                        .get()
+                                        .planned_doses
+                                        .into_iter()
+                                        .take(WHATIF_ROWS)
+                                        .enumerate()
+                                        .map(|(index, (date, dose))| {
+                                            view! {
+                                                <tr>
+                                                    <td>{fmt_date_label(date, "date", None)}</td>
+                                                    <td>{fmt_decimal(dose, 3)}</td>
+                                                    <td>
+                                                        <select
+                                                            on:change=move |ev| {
+                                                                let value = event_target_value(&ev);
+                                                                whatif_actions.update(|actions| actions[index] = value);
+                                                            }
+                                                            prop:value=move || whatif_actions.get()[index].clone()
+                                                        >
+                                                            {WHATIF_ACTIONS
+                                                                .iter()
+                                                                .map(|(value, label)| view! { <option value=*value>{*label}</option> })
+                                                                .collect_view()}
+                                                        </select>
+                                                    </td>
+                                                </tr>
+                                            }
+                                        })
+                                        .collect_view()
+                                }}
+                            </tbody>
+                        </table>
+                        <Show when=move || estrannaise_series.get().catch_up.is_some()>
+                            <p>
+                                {move || {
+                                    let unit = store
+                                        .settings
+                                        .get()
+                                        .displayEstradiolUnit
+                                        .unwrap_or(HormoneUnits::E2PmolL)
+                                        .symbol();
+                                    estrannaise_series
+                                        .get()
+                                        .catch_up
+                                        .map(|suggestion| {
+                                            let mut text = format!(
+                                                "Catch-up: take {} mg instead of {} mg on {} to bring the next trough to {} {} (planned {} {}).",
+                                                fmt_decimal(suggestion.suggestedDose, 2),
+                                                fmt_decimal(suggestion.scheduledDose, 2),
+                                                fmt_date_label(suggestion.date, "date", None),
+                                                fmt_decimal(suggestion.simulatedTrough, 0),
+                                                unit,
+                                                fmt_decimal(suggestion.baselineTrough, 0),
+                                                unit,
+                                            );
+                                            if suggestion.peakLimited {
+                                                text.push_str(" Limited so the peak stays under the planned peak.");
+                                            }
+                                            text
+                                        })
+                                        .unwrap_or_default()
+                                }}
+                            </p>
+                        </Show>
+                    </Show>
+                </div>
+
+                <div class="card">
+                    <h3>"Regimen Optimizer"</h3>
+                    <p class="muted">
+                        "Finds steady-state dose and interval options for a target trough and peak, using the selected test's fudge factor or the fitted curve."
+                    </p>
+                    <div class="chart-toolbar">
+                        <div class="chart-toolbar-group">
+                            <label class="muted">"Ester"</label>
+                            <select
+                                on:change=move |ev| optimizer_ester.set(event_target_value(&ev))
+                                prop:value=move || optimizer_ester.get()
+                            >
+                                {OPTIMIZER_ESTERS
+                                    .iter()
+                                    .map(|(_, label)| view! { <option value=*label>{*label}</option> })
+                                    .collect_view()}
+                            </select>
+                        </div>
+                        <div class="chart-toolbar-group">
+                            <label class="muted">
+                                {move || format!("Trough ({})", store.settings.get().displayEstradiolUnit.unwrap_or(HormoneUnits::E2PmolL).symbol())}
+                            </label>
+                            <input
+                                type="text"
+                                class="chart-input"
+                                on:input=move |ev| optimizer_trough_min.set(event_target_value(&ev))
+                                prop:value=move || optimizer_trough_min.get()
+                            />
+                            <span class="muted">"to"</span>
+                            <input
+                                type="text"
+                                class="chart-input"
+                                on:input=move |ev| optimizer_trough_max.set(event_target_value(&ev))
+                                prop:value=move || optimizer_trough_max.get()
+                            />
+                        </div>
+                        <div class="chart-toolbar-group">
+                            <label class="muted">"Peak"</label>
+                            <input
+                                type="text"
+                                class="chart-input"
+                                on:input=move |ev| optimizer_peak_min.set(event_target_value(&ev))
+                                prop:value=move || optimizer_peak_min.get()
+                            />
+                            <span class="muted">"to"</span>
+                            <input
+                                type="text"
+                                class="chart-input"
+                                on:input=move |ev| optimizer_peak_max.set(event_target_value(&ev))
+                                prop:value=move || optimizer_peak_max.get()
+                            />
+                        </div>
+                        <div class="chart-toolbar-group">
+                            <label class="muted">"Intervals (days)"</label>
+                            <input
+                                type="text"
+                                on:input=move |ev| optimizer_intervals.set(event_target_value(&ev))
+                                prop:value=move || optimizer_intervals.get()
+                            />
+                        </div>
+                        <div class="chart-toolbar-group">
+                            <button on:click=run_optimizer>"Find regimens"</button>
+                        </div>
+                    </div>
+                    <Show when=move || optimizer_error.get().is_some()>
+                        <p class="muted">{move || optimizer_error.get().unwrap_or_default()}</p>
+                    </Show>
+                    <Show when=move || !optimizer_results.get().is_empty()>
+                        <table class="table">
+                            <thead>
+                                <tr>
+                                    <th>"Dose (mg)"</th>
+                                    <th>"Every (days)"</th>
+                                    <th>"Trough"</th>
+                                    <th>"Peak"</th>
+                                    <th>"Average"</th>
+                                    <th>"In target"</th>
+                                    <th></th>
+                                </tr>
+                            </thead>
+                            <tbody>
+                                {move || {
+                                    optimizer_results
+                                        .get()
+                                        .into_iter()
+                                        .map(|option| {
+                                            let row = option.clone();
+                                            view! {
+                                                <tr>
+                                                    <td>{fmt_decimal(option.dose, 3)}</td>
+                                                    <td>{fmt_decimal(option.frequency, 2)}</td>
+                                                    <td>{fmt_decimal(option.trough, 0)}</td>
+                                                    <td>{fmt_decimal(option.peak, 0)}</td>
+                                                    <td>{fmt_decimal(option.average, 0)}</td>
+                                                    <td>{if option.withinTarget { "Yes" } else { "No" }}</td>
+                                                    <td>
+                                                        <button on:click=move |_| {
+                                                            let use_regimen_option = use_regimen_option.get_value();
+                                                            use_regimen_option(&row)
+                                                        }>
+                                                            "Forecast"
+                                                        </button>
+                                                    </td>
+                                                </tr>
+                                            }
+                                        })
+                                        .collect_view()
+                                }}
+                            </tbody>
+                        </table>
+                    </Show>
+                </div>
+
+                <div class="card">
+                    <h3>"Custom PK Models"</h3>
+                    <p class="muted">
+                        "Three-compartment parameters (D, k1, k2, k3 per day) as used by estrannaise. Select a model on a dose or vial to use it in predictions."
+                    </p>
+                    <Show when=move || !store.data.get().pkModels.is_empty()>
+                        <table class="table">
+                            <thead>
+                                <tr>
+                                    <th>"Name"</th>
+                                    <th>"Route"</th>
+                                    <th>"D"</th>
+                                    <th>"k1"</th>
+                                    <th>"k2"</th>
+                                    <th>"k3"</th>
+                                    <th></th>
+                                </tr>
+                            </thead>
+                            <tbody>
+                                {
+                                    let store = store.clone();
+                                    move || {
+                                        store
+                                            .data
+                                            .get()
+                                            .pkModels
+                                            .into_iter()
+                                            .map(|model| {
+                                                let id = model.id.clone();
+                                                let store = store.clone();
+                                                view! {
+                                                    <tr>
+                                                        <td>{model.name.clone()}</td>
+                                                        <td>{custom_route_label(&model.route)}</td>
+                                                        <td>{fmt_decimal(model.d, 4)}</td>
+                                                        <td>{fmt_decimal(model.k1, 4)}</td>
+                                                        <td>{fmt_decimal(model.k2, 4)}</td>
+                                                        <td>{fmt_decimal(model.k3, 4)}</td>
+                                                        <td>
+                                                            <button on:click=move |_| {
+                                                                store.data.update(|data| data.pkModels.retain(|model| model.id != id));
+                                                                store.mark_dirty();
+                                                            }>
+                                                                "Delete"
+                                                            </button>
+                                                        </td>
+                                                    </tr>
+                                                }
+                                            })
+                                            .collect_view()
+                                    }
+                                }
+                            </tbody>
+                        </table>
+                    </Show>
+                    <div class="chart-toolbar">
+                        <div class="chart-toolbar-group">
+                            <label class="muted">"Name"</label>
+                            <input
+                                type="text"
+                                on:input=move |ev| custom_name.set(event_target_value(&ev))
+                                prop:value=move || custom_name.get()
+                            />
+                        </div>
+                        <div class="chart-toolbar-group">
+                            <label class="muted">"Route"</label>
+                            <select
+                                on:change=move |ev| custom_route.set(event_target_value(&ev))
+                                prop:value=move || custom_route.get()
+                            >
+                                {[CustomPkRoute::Injection, CustomPkRoute::Oral]
+                                    .iter()
+                                    .map(|route| {
+                                        let label = custom_route_label(route);
+                                        view! { <option value=label>{label}</option> }
+                                    })
+                                    .collect_view()}
+                            </select>
+                        </div>
+                        {[("D", custom_d), ("k1", custom_k1), ("k2", custom_k2), ("k3", custom_k3)]
+                            .into_iter()
+                            .map(|(label, signal)| {
+                                view! {
+                                    <div class="chart-toolbar-group">
+                                        <label class="muted">{label}</label>
+                                        <input
+                                            type="text"
+                                            class="chart-input"
+                                            on:input=move |ev| signal.set(event_target_value(&ev))
+                                            prop:value=move || signal.get()
+                                        />
+                                    </div>
+                                }
+                            })
+                            .collect_view()}
+                        <div class="chart-toolbar-group">
+                            <button on:click=add_custom_model>"Add model"</button>
+                        </div>
+                    </div>
+                    <Show when=move || custom_error.get().is_some()>
+                        <p class="muted">{move || custom_error.get().unwrap_or_default()}</p>
+                    </Show>
+                </div>
             </div>
         }
         .into_view(),
     )
 }
-fn measured_e2_pg_ml(test: &BloodTest) -> Option<f64> {
-    let value = test.estradiolLevel?;
-    let unit = test.estradiolUnit.clone().unwrap_or(HormoneUnits::E2PgMl);
-    let measured = if unit == HormoneUnits::E2PmolL {
-        value / 3.671
-    } else {
-        value
-    };
-    if measured.is_finite() {
-        Some(measured)
-    } else {
-        None
-    }
-}
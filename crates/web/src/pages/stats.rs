@@ -1,9 +1,20 @@
+use chrono::{Datelike, Duration};
 use leptos::*;
 use leptos_router::A;
 
 use crate::layout::page_layout;
-use crate::store::use_store;
-use crate::utils::injectable_iu_from_dose;
+use crate::store::{use_store, AppStore};
+use crate::utils::{
+    fmt_blood_value, fmt_date_label, fmt_reference_range, fmt_regimen, injectable_iu_from_dose,
+    parse_decimal, range_flag_note,
+};
+use hrt_shared::adherence::{adherence_report, DoseStatus};
+use hrt_shared::quantity::BloodAnalyte;
+use hrt_shared::ranges::{
+    default_target_ranges, range_summary, target_range, LabRanges, ReferenceRange,
+};
+use hrt_shared::recurrence::local_date;
+use hrt_shared::schedules::current_injectable;
 use hrt_shared::types::{DosageHistoryEntry, HormoneUnits, ProgesteroneRoutes};
 
 #[component]
@@ -23,7 +34,10 @@ pub fn StatsPage() -> impl IntoView {
                 DosageHistoryEntry::InjectableEstradiol { date, .. }
                 | DosageHistoryEntry::OralEstradiol { date, .. }
                 | DosageHistoryEntry::Antiandrogen { date, .. }
-                | DosageHistoryEntry::Progesterone { date, .. } => *date,
+                | DosageHistoryEntry::Progesterone { date, .. }
+                | DosageHistoryEntry::Patch { date, .. }
+                | DosageHistoryEntry::Gel { date, .. }
+                | DosageHistoryEntry::Medication { date, .. } => *date,
             })
             .min();
         min_date.map(|value| ((js_sys::Date::now() as i64 - value) / DAY_MS).max(0))
@@ -53,12 +67,10 @@ pub fn StatsPage() -> impl IntoView {
         injectable_records()
             .iter()
             .map(|d| match d {
-                DosageHistoryEntry::InjectableEstradiol { dose, unit, .. } => {
-                    if *unit == HormoneUnits::Mg {
-                        *dose
-                    } else {
-                        0.0
-                    }
+                DosageHistoryEntry::InjectableEstradiol { dose, unit, .. }
+                    if *unit == HormoneUnits::Mg =>
+                {
+                    *dose
                 }
                 _ => 0.0,
             })
@@ -67,10 +79,7 @@ pub fn StatsPage() -> impl IntoView {
 
     let total_injectable_iu = move || {
         let data_value = data.get();
-        let schedule_vial_id = data_value
-            .injectableEstradiol
-            .as_ref()
-            .and_then(|cfg| cfg.vialId.as_ref());
+        let schedule_vial_id = current_injectable(&data_value).and_then(|cfg| cfg.vialId.as_ref());
         injectable_records()
             .iter()
             .filter_map(|entry| match entry {
@@ -105,12 +114,10 @@ pub fn StatsPage() -> impl IntoView {
                         dose * qty
                     }
                 }
-                DosageHistoryEntry::InjectableEstradiol { dose, unit, .. } => {
-                    if *unit == HormoneUnits::Mg {
-                        *dose
-                    } else {
-                        0.0
-                    }
+                DosageHistoryEntry::InjectableEstradiol { dose, unit, .. }
+                    if *unit == HormoneUnits::Mg =>
+                {
+                    *dose
                 }
                 _ => 0.0,
             })
@@ -746,8 +753,527 @@ pub fn StatsPage() -> impl IntoView {
                         </div>
                     </Show>
                 </div>
+
+                {adherence_card(store.clone())}
+                {regimen_history_card(store.clone())}
+                {reference_ranges_card(store.clone())}
             </div>
         }
         .into_view(),
     )
 }
+
+/// Weeks shown in the adherence heatmap.
+const HEATMAP_WEEKS: i64 = 12;
+
+fn adherence_card(store: AppStore) -> impl IntoView {
+    let data = store.data;
+    let settings = store.settings;
+    let report = create_memo(move |_| adherence_report(&data.get(), js_sys::Date::now() as i64));
+    let pct = |value: Option<f64>| value.map_or("–".to_string(), |value| format!("{value:.0}%"));
+
+    let heatmap = move || {
+        let days = report.with(|report| report.by_day());
+        let today = local_date(js_sys::Date::now() as i64);
+        let first = today
+            - Duration::days(
+                today.weekday().num_days_from_monday() as i64 + (HEATMAP_WEEKS - 1) * 7,
+            );
+        (0..HEATMAP_WEEKS * 7)
+            .map(|offset| first + Duration::days(offset))
+            .map(|day| {
+                let counts = days.get(&day).copied().unwrap_or_default();
+                let taken = counts.onTime + counts.late;
+                let level = if day > today || taken + counts.missed == 0 {
+                    "heatmap-cell"
+                } else if counts.missed == 0 && counts.late == 0 {
+                    "heatmap-cell on-time"
+                } else if counts.missed == 0 {
+                    "heatmap-cell late"
+                } else if taken > 0 {
+                    "heatmap-cell partial"
+                } else {
+                    "heatmap-cell missed"
+                };
+                let title = format!(
+                    "{}: {} on time, {} late, {} missed",
+                    day.format("%Y-%m-%d"),
+                    counts.onTime,
+                    counts.late,
+                    counts.missed
+                );
+                view! { <span class=level title=title></span> }
+            })
+            .collect_view()
+    };
+
+    view! {
+        <div class="card">
+            <h3>"Adherence"</h3>
+            <Show
+                when=move || report.with(|report| !report.slots.is_empty())
+                fallback=move || view! { <p class="muted">"No scheduled doses to compare yet."</p> }
+            >
+                <p>
+                    "Taken: " <strong>{move || pct(report.with(|report| report.taken_percent()))}</strong>
+                    " · On time: " <strong>{move || pct(report.with(|report| report.on_time_percent()))}</strong>
+                </p>
+                <p class="muted">
+                    {move || {
+                        report.with(|report| {
+                            format!(
+                                "{} on time, {} late, {} missed, {} extra",
+                                report.count(DoseStatus::OnTime),
+                                report.count(DoseStatus::Late),
+                                report.count(DoseStatus::Missed),
+                                report.extra
+                            )
+                        })
+                    }}
+                </p>
+                <p>
+                    {move || {
+                        let (current, longest) = report.with(|report| report.streaks());
+                        format!("Current streak: {current} doses · Longest: {longest} doses")
+                    }}
+                </p>
+                <div class="adherence-heatmap">{heatmap}</div>
+                <table class="table">
+                    <thead>
+                        <tr>
+                            <th>"Timing"</th>
+                            <th>"Doses"</th>
+                        </tr>
+                    </thead>
+                    <tbody>
+                        {move || {
+                            report
+                                .with(|report| report.lateness_buckets())
+                                .into_iter()
+                                .map(|(label, count)| {
+                                    view! {
+                                        <tr>
+                                            <td>{label}</td>
+                                            <td>{count}</td>
+                                        </tr>
+                                    }
+                                })
+                                .collect_view()
+                        }}
+                    </tbody>
+                </table>
+                <Show when=move || report.with(|report| report.count(DoseStatus::Missed) > 0)>
+                    <h4>"Recently missed"</h4>
+                    <ul>
+                        {move || {
+                            let data_value = data.get();
+                            let use_iu = settings.get().displayInjectableInIU.unwrap_or(false);
+                            report.with(|report| {
+                                report
+                                    .missed()
+                                    .into_iter()
+                                    .take(5)
+                                    .map(|slot| {
+                                        let title = data_value
+                                            .regimens
+                                            .iter()
+                                            .find(|regimen| regimen.id == slot.regimenId)
+                                            .map(|regimen| fmt_regimen(&data_value, regimen, use_iu).0)
+                                            .unwrap_or("Dose");
+                                        view! {
+                                            <li>
+                                                {format!("{title}: due {}", fmt_date_label(slot.due, "date", None))}
+                                            </li>
+                                        }
+                                    })
+                                    .collect_view()
+                            })
+                        }}
+                    </ul>
+                </Show>
+            </Show>
+        </div>
+    }
+}
+
+fn regimen_history_card(store: AppStore) -> impl IntoView {
+    let data = store.data;
+    let settings = store.settings;
+
+    view! {
+        <div class="card">
+            <h3>"Regimen History"</h3>
+            <Show
+                when=move || !data.with(|data| data.regimens.is_empty())
+                fallback=move || view! { <p class="muted">"No regimens recorded yet."</p> }
+            >
+                <table class="table">
+                    <thead>
+                        <tr>
+                            <th>"Medication"</th>
+                            <th>"Schedule"</th>
+                            <th>"Started"</th>
+                            <th>"Ended"</th>
+                        </tr>
+                    </thead>
+                    <tbody>
+                        {move || {
+                            let data_value = data.get();
+                            let use_iu = settings.get().displayInjectableInIU.unwrap_or(false);
+                            let mut regimens = data_value.regimens.clone();
+                            regimens.sort_by_key(|regimen| std::cmp::Reverse(regimen.startDate));
+                            regimens
+                                .into_iter()
+                                .map(|regimen| {
+                                    let (title, detail) =
+                                        fmt_regimen(&data_value, &regimen, use_iu);
+                                    let ended = regimen
+                                        .endDate
+                                        .map(|end| fmt_date_label(end, "date", None))
+                                        .unwrap_or_else(|| "Current".to_string());
+                                    view! {
+                                        <tr>
+                                            <td>{title}</td>
+                                            <td>{detail}</td>
+                                            <td>{fmt_date_label(regimen.startDate, "date", None)}</td>
+                                            <td>{ended}</td>
+                                        </tr>
+                                    }
+                                })
+                                .collect_view()
+                        }}
+                    </tbody>
+                </table>
+            </Show>
+        </div>
+    }
+}
+
+fn analyte_from_label(label: &str) -> Option<BloodAnalyte> {
+    BloodAnalyte::ALL
+        .into_iter()
+        .find(|analyte| analyte.label() == label)
+}
+
+fn reference_ranges_card(store: AppStore) -> impl IntoView {
+    let data = store.data;
+    let form_analyte = create_rw_signal(BloodAnalyte::Estradiol.label().to_string());
+    let form_low = create_rw_signal(String::new());
+    let form_high = create_rw_signal(String::new());
+    let form_unit = create_rw_signal(HormoneUnits::E2PgMl.symbol().to_string());
+    let form_lab = create_rw_signal(String::new());
+    let form_error = create_rw_signal(None::<String>);
+
+    let form_range = move || -> Result<ReferenceRange, String> {
+        let analyte = analyte_from_label(&form_analyte.get()).ok_or("Pick an analyte")?;
+        let unit = HormoneUnits::from_symbol(&form_unit.get())
+            .filter(|unit| analyte.supports(unit))
+            .ok_or_else(|| format!("Pick a unit that {} can be measured in", analyte.label()))?;
+        let bound = |raw: String, name: &str| -> Result<Option<f64>, String> {
+            if raw.trim().is_empty() {
+                return Ok(None);
+            }
+            parse_decimal(&raw)
+                .filter(|value| *value >= 0.0)
+                .map(Some)
+                .ok_or_else(|| format!("Invalid {name} bound"))
+        };
+        let low = bound(form_low.get(), "low")?;
+        let high = bound(form_high.get(), "high")?;
+        if let (Some(low), Some(high)) = (low, high) {
+            if low > high {
+                return Err("Low bound is above the high bound".to_string());
+            }
+        }
+        Ok(ReferenceRange::new(analyte, low, high, unit))
+    };
+
+    let save_target = {
+        let store = store.clone();
+        move |_| match form_range() {
+            Ok(range) => {
+                data.update(|d| {
+                    d.targetRanges.retain(|r| r.analyte != range.analyte);
+                    d.targetRanges.push(range);
+                });
+                store.mark_dirty();
+                form_error.set(None);
+            }
+            Err(err) => form_error.set(Some(err)),
+        }
+    };
+
+    let save_lab_range = {
+        let store = store.clone();
+        move |_| {
+            let lab = form_lab.get().trim().to_string();
+            if lab.is_empty() {
+                form_error.set(Some("Enter the lab name".to_string()));
+                return;
+            }
+            let range = match form_range() {
+                Ok(range) if !range.is_open() => range,
+                Ok(_) => {
+                    form_error.set(Some("Enter a low or high bound".to_string()));
+                    return;
+                }
+                Err(err) => {
+                    form_error.set(Some(err));
+                    return;
+                }
+            };
+            data.update(|d| {
+                let index = d
+                    .labRanges
+                    .iter()
+                    .position(|entry| entry.lab.eq_ignore_ascii_case(&lab))
+                    .unwrap_or_else(|| {
+                        d.labRanges.push(LabRanges {
+                            lab: lab.clone(),
+                            ranges: Vec::new(),
+                        });
+                        d.labRanges.len() - 1
+                    });
+                let ranges = &mut d.labRanges[index].ranges;
+                ranges.retain(|r| r.analyte != range.analyte);
+                ranges.push(range);
+            });
+            store.mark_dirty();
+            form_error.set(None);
+        }
+    };
+    let target_store = store.clone();
+    let lab_store = store;
+
+    view! {
+        <div class="card">
+            <h3>"Blood Test Ranges"</h3>
+            <p class="muted">
+                "Levels are checked against your HRT targets and, when a blood test names its lab, that lab's reference range."
+            </p>
+            <Show
+                when=move || !range_summary(&data.get()).is_empty()
+                fallback=move || view! { <p class="muted">"No blood tests recorded yet."</p> }
+            >
+                <table class="table">
+                    <thead>
+                        <tr>
+                            <th>"Analyte"</th>
+                            <th>"Target"</th>
+                            <th>"Tests"</th>
+                            <th>"Below"</th>
+                            <th>"In target"</th>
+                            <th>"Above"</th>
+                            <th>"Outside lab range"</th>
+                            <th>"Latest"</th>
+                        </tr>
+                    </thead>
+                    <tbody>
+                        {move || {
+                            let data_value = data.get();
+                            range_summary(&data_value)
+                                .into_iter()
+                                .map(|summary| {
+                                    let target = target_range(&data_value, summary.analyte)
+                                        .map(|range| fmt_reference_range(&range))
+                                        .unwrap_or_else(|| "-".to_string());
+                                    let latest = summary.latest.as_ref().map(|latest| {
+                                        let value = format!(
+                                            "{} {}",
+                                            fmt_blood_value(latest.level.value),
+                                            latest.level.unit.symbol()
+                                        );
+                                        match range_flag_note(latest) {
+                                            Some(note) => view! {
+                                                <span class="range-flag" title=note>{value}</span>
+                                            }
+                                            .into_view(),
+                                            None => value.into_view(),
+                                        }
+                                    });
+                                    view! {
+                                        <tr>
+                                            <td>{summary.analyte.label()}</td>
+                                            <td>{target}</td>
+                                            <td>{summary.tests}</td>
+                                            <td>{summary.low}</td>
+                                            <td>{summary.inRange}</td>
+                                            <td>{summary.high}</td>
+                                            <td>{summary.outsideReference}</td>
+                                            <td>{latest}</td>
+                                        </tr>
+                                    }
+                                })
+                                .collect_view()
+                        }}
+                    </tbody>
+                </table>
+            </Show>
+
+            <h4>"Custom targets"</h4>
+            <p class="muted">
+                {format!(
+                    "Defaults: {}. A custom target without bounds turns the default off.",
+                    default_target_ranges()
+                        .iter()
+                        .map(|range| format!("{} {}", range.analyte.label(), fmt_reference_range(range)))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )}
+            </p>
+            <Show when=move || !data.get().targetRanges.is_empty()>
+                <ul class="history-list">
+                    {
+                        let store = target_store.clone();
+                        move || {
+                            data.get()
+                                .targetRanges
+                                .into_iter()
+                                .map(|range| {
+                                    let store = store.clone();
+                                    let analyte = range.analyte;
+                                    let label = if range.is_open() {
+                                        "off".to_string()
+                                    } else {
+                                        fmt_reference_range(&range)
+                                    };
+                                    view! {
+                                        <li class="history-item">
+                                            <div>{format!("{}: {}", analyte.label(), label)}</div>
+                                            <button
+                                                type="button"
+                                                class="action-button"
+                                                on:click=move |_| {
+                                                    data.update(|d| d.targetRanges.retain(|r| r.analyte != analyte));
+                                                    store.mark_dirty();
+                                                }
+                                            >
+                                                "Use default"
+                                            </button>
+                                        </li>
+                                    }
+                                })
+                                .collect_view()
+                        }
+                    }
+                </ul>
+            </Show>
+
+            <h4>"Lab reference ranges"</h4>
+            <Show
+                when=move || !data.get().labRanges.is_empty()
+                fallback=move || view! { <p class="muted">"No lab ranges yet."</p> }
+            >
+                <ul class="history-list">
+                    {
+                        let store = lab_store.clone();
+                        move || {
+                            data.get()
+                                .labRanges
+                                .into_iter()
+                                .map(|entry| {
+                                    let store = store.clone();
+                                    let lab = entry.lab.clone();
+                                    let ranges = entry
+                                        .ranges
+                                        .iter()
+                                        .map(|range| format!("{} {}", range.analyte.label(), fmt_reference_range(range)))
+                                        .collect::<Vec<_>>()
+                                        .join(", ");
+                                    view! {
+                                        <li class="history-item">
+                                            <div>
+                                                <div class="history-date">{entry.lab}</div>
+                                                <div class="history-meta">{ranges}</div>
+                                            </div>
+                                            <button
+                                                type="button"
+                                                class="action-button"
+                                                on:click=move |_| {
+                                                    data.update(|d| d.labRanges.retain(|r| r.lab != lab));
+                                                    store.mark_dirty();
+                                                }
+                                            >
+                                                "Delete"
+                                            </button>
+                                        </li>
+                                    }
+                                })
+                                .collect_view()
+                        }
+                    }
+                </ul>
+            </Show>
+
+            <div class="chart-toolbar">
+                <div class="chart-toolbar-group">
+                    <label class="muted">"Analyte"</label>
+                    <select
+                        on:change=move |ev| form_analyte.set(event_target_value(&ev))
+                        prop:value=move || form_analyte.get()
+                    >
+                        {BloodAnalyte::ALL
+                            .iter()
+                            .map(|analyte| view! { <option value=analyte.label()>{analyte.label()}</option> })
+                            .collect_view()}
+                    </select>
+                </div>
+                <div class="chart-toolbar-group">
+                    <label class="muted">"Low"</label>
+                    <input
+                        type="text"
+                        class="chart-input"
+                        on:input=move |ev| form_low.set(event_target_value(&ev))
+                        prop:value=move || form_low.get()
+                    />
+                </div>
+                <div class="chart-toolbar-group">
+                    <label class="muted">"High"</label>
+                    <input
+                        type="text"
+                        class="chart-input"
+                        on:input=move |ev| form_high.set(event_target_value(&ev))
+                        prop:value=move || form_high.get()
+                    />
+                </div>
+                <div class="chart-toolbar-group">
+                    <label class="muted">"Unit"</label>
+                    <select
+                        on:change=move |ev| form_unit.set(event_target_value(&ev))
+                        prop:value=move || form_unit.get()
+                    >
+                        {move || {
+                            let analyte = analyte_from_label(&form_analyte.get())
+                                .unwrap_or(BloodAnalyte::Estradiol);
+                            HormoneUnits::ALL
+                                .iter()
+                                .filter(|unit| analyte.supports(unit))
+                                .map(|unit| {
+                                    let label = unit.symbol();
+                                    view! { <option value=label>{label}</option> }
+                                })
+                                .collect_view()
+                        }}
+                    </select>
+                </div>
+                <div class="chart-toolbar-group">
+                    <button on:click=save_target>"Save as target"</button>
+                </div>
+                <div class="chart-toolbar-group">
+                    <label class="muted">"Lab"</label>
+                    <input
+                        type="text"
+                        on:input=move |ev| form_lab.set(event_target_value(&ev))
+                        prop:value=move || form_lab.get()
+                    />
+                </div>
+                <div class="chart-toolbar-group">
+                    <button on:click=save_lab_range>"Save lab range"</button>
+                </div>
+            </div>
+            <Show when=move || form_error.get().is_some()>
+                <p class="error">{move || form_error.get().unwrap_or_default()}</p>
+            </Show>
+        </div>
+    }
+}
@@ -9,7 +9,7 @@ use std::rc::Rc;
 use crate::layout::page_layout;
 use crate::store::use_store;
 use crate::utils::{fmt_decimal, injectable_iu_from_dose, parse_date_or_now, parse_decimal};
-use hrt_shared::types::{DosageHistoryEntry, InjectableEstradiols, SubVial, Vial};
+use hrt_shared::types::{CustomPkRoute, DosageHistoryEntry, InjectableEstradiols, SubVial, Vial};
 
 const ESTER_OPTIONS: [InjectableEstradiols; 6] = [
     InjectableEstradiols::Benzoate,
@@ -31,6 +31,37 @@ fn ester_label(kind: &InjectableEstradiols) -> &'static str {
     }
 }
 
+fn pk_model_select(pk_model_id: RwSignal<String>) -> impl IntoView {
+    let store = use_store();
+    let models = move || {
+        store
+            .data
+            .get()
+            .pkModels
+            .into_iter()
+            .filter(|model| model.route == CustomPkRoute::Injection)
+            .collect::<Vec<_>>()
+    };
+    view! {
+        <Show when=move || !models().is_empty()>
+            <label>
+                "PK model"
+                <select
+                    on:change=move |ev| pk_model_id.set(event_target_value(&ev))
+                    prop:value=move || pk_model_id.get()
+                >
+                    <option value="">"Default (from ester)"</option>
+                    <For
+                        each=models
+                        key=|model| model.id.clone()
+                        children=move |model| view! { <option value=model.id.clone()>{model.name.clone()}</option> }
+                    />
+                </select>
+            </label>
+        </Show>
+    }
+}
+
 fn format_date(ms: i64) -> String {
     Local
         .timestamp_millis_opt(ms)
@@ -577,10 +608,12 @@ pub fn VialsCreatePage() -> impl IntoView {
     let batch_number = create_rw_signal(String::new());
     let source = create_rw_signal(String::new());
     let concentration = create_rw_signal(String::new());
+    let volume = create_rw_signal(String::new());
     let created_date = create_rw_signal(Local::now().format("%Y-%m-%d").to_string());
     let use_by = create_rw_signal(String::new());
     let first_sub_number = create_rw_signal(String::new());
     let first_sub_iu = create_rw_signal(String::new());
+    let pk_model_id = create_rw_signal(String::new());
 
     let on_submit = move |ev: leptos::ev::SubmitEvent| {
         ev.prevent_default();
@@ -595,6 +628,7 @@ pub fn VialsCreatePage() -> impl IntoView {
         let created_at = parse_date_or_now(&created_date.get());
         let use_by_ms = parse_optional_date(&use_by.get());
         let concentration_value = parse_decimal(&concentration.get()).filter(|v| *v > 0.0);
+        let volume_value = parse_decimal(&volume.get()).filter(|v| *v > 0.0);
         let first_sub_initial = parse_decimal(&first_sub_iu.get()).filter(|v| *v >= 0.0);
         let mut sub_vials = Vec::new();
         if !first_sub_number.get().trim().is_empty() {
@@ -638,8 +672,14 @@ pub fn VialsCreatePage() -> impl IntoView {
             isSpent: Some(false),
             spentAt: None,
             useBy: use_by_ms,
+            volumeMl: volume_value,
             createdAt: created_at,
             subVials: sub_vials,
+            pkModelId: if pk_model_id.get().is_empty() {
+                None
+            } else {
+                Some(pk_model_id.get())
+            },
         };
 
         store.data.update(|data| {
@@ -739,6 +779,17 @@ pub fn VialsCreatePage() -> impl IntoView {
                         prop:value=move || concentration.get()
                     />
                 </label>
+                <label>
+                    "Volume (mL, optional)"
+                    <input
+                        type="text"
+                        step="any"
+                        min="0"
+                        placeholder="e.g., 10"
+                        on:input=move |ev| volume.set(event_target_value(&ev))
+                        prop:value=move || volume.get()
+                    />
+                </label>
                 <label>
                     "Manufacturer / Source"
                     <input
@@ -748,6 +799,7 @@ pub fn VialsCreatePage() -> impl IntoView {
                         prop:value=move || source.get()
                     />
                 </label>
+                {pk_model_select(pk_model_id)}
                 <label>
                     "First sub-vial/cartridge number (optional)"
                     <input
@@ -792,10 +844,12 @@ pub fn VialsDetailPage() -> impl IntoView {
     let batch_number = create_rw_signal(String::new());
     let source = create_rw_signal(String::new());
     let concentration = create_rw_signal(String::new());
+    let volume = create_rw_signal(String::new());
     let created_date = create_rw_signal(String::new());
     let use_by = create_rw_signal(String::new());
     let is_spent = create_rw_signal(false);
     let spent_date = create_rw_signal(String::new());
+    let pk_model_id = create_rw_signal(String::new());
 
     create_effect(move |_| {
         if let Some(entry) = vial() {
@@ -810,10 +864,12 @@ pub fn VialsDetailPage() -> impl IntoView {
                     .map(|v| format!("{:.2}", v))
                     .unwrap_or_default(),
             );
+            volume.set(entry.volumeMl.map(|v| format!("{:.2}", v)).unwrap_or_default());
             created_date.set(format_date(entry.createdAt));
             use_by.set(entry.useBy.map(format_date).unwrap_or_default());
             is_spent.set(entry.isSpent.unwrap_or(false));
             spent_date.set(entry.spentAt.map(format_date).unwrap_or_default());
+            pk_model_id.set(entry.pkModelId.unwrap_or_default());
             custom_ester.set(String::new());
         }
     });
@@ -838,6 +894,7 @@ pub fn VialsDetailPage() -> impl IntoView {
         };
         let use_by_ms = parse_optional_date(&use_by.get());
         let concentration_value = parse_decimal(&concentration.get()).filter(|v| *v > 0.0);
+        let volume_value = parse_decimal(&volume.get()).filter(|v| *v > 0.0);
         let spent_at = if is_spent.get() {
             let spent_value = spent_date.get();
             let value = if spent_value.trim().is_empty() {
@@ -877,10 +934,16 @@ pub fn VialsDetailPage() -> impl IntoView {
                     Some(source.get())
                 };
                 target.concentrationMgPerMl = concentration_value;
+                target.volumeMl = volume_value;
                 target.createdAt = created_at;
                 target.useBy = use_by_ms;
                 target.isSpent = Some(is_spent.get());
                 target.spentAt = spent_at;
+                target.pkModelId = if pk_model_id.get().is_empty() {
+                    None
+                } else {
+                    Some(pk_model_id.get())
+                };
             }
         });
         store.mark_dirty();
@@ -936,6 +999,16 @@ pub fn VialsDetailPage() -> impl IntoView {
                             prop:value=move || concentration.get()
                         />
                     </label>
+                    <label>
+                        "Volume (mL, optional)"
+                        <input
+                            type="text"
+                            step="any"
+                            min="0"
+                            on:input=move |ev| volume.set(event_target_value(&ev))
+                            prop:value=move || volume.get()
+                        />
+                    </label>
                     <label>
                         "Suspension oil"
                         <input
@@ -984,6 +1057,7 @@ pub fn VialsDetailPage() -> impl IntoView {
                             prop:value=move || source.get()
                         />
                     </label>
+                    {pk_model_select(pk_model_id)}
                     <label class="inline">
                         <span>"Spent"</span>
                         <input
@@ -14,30 +14,44 @@ mod scheduling;
 mod types;
 
 use self::helpers::{
-    bloodtest_pdf_url, dosage_entry_date, dosage_entry_matches_key, dosage_photo_view,
-    hormone_unit_labels, injection_site_from_label, injection_site_label, length_unit_label,
-    measurement_key, measurement_matches_target, parse_date_only, parse_datetime_local,
-    parse_optional_num, parse_weight_unit, progesterone_route_label, syringe_kind_label,
-    to_local_input_value, update_photo_note, weight_unit_label,
+    analyte_unit_labels, bloodtest_pdf_url, dosage_entry_date, dosage_entry_matches_key,
+    dosage_photo_view, hormone_unit_labels, injection_site_from_label, injection_site_label,
+    length_unit_label, level_form_fields, measurement_key, measurement_matches_target,
+    parse_date_only, parse_datetime_local, parse_optional_num, parse_weight_unit,
+    progesterone_route_label, syringe_kind_label, to_local_input_value, update_photo_note,
+    weight_unit_label,
 };
 use self::scheduling::{generate_estrannaise_url, get_next_scheduled_candidate};
-use self::types::{
-    RegimenKey, UploadResponse, DAY_MS, INJECTION_SITE_OPTIONS, SYRINGE_KIND_OPTIONS,
-};
+use self::types::{UploadResponse, DAY_MS, INJECTION_SITE_OPTIONS, SYRINGE_KIND_OPTIONS};
 
-use crate::charts::view::{compute_view_chart_state, draw_view_chart, find_nearest_point};
+use crate::charts::view::{
+    compute_view_chart_state, draw_view_chart, find_nearest_point, predicted_e2_band,
+};
 use crate::charts::{
-    chart_padding, clamp_zoom, compute_chart_bounds, ChartTooltip, DragState, ViewZoom,
+    chart_padding_with, clamp_zoom, compute_chart_bounds, ChartTooltip, DragState, ViewZoom,
 };
+use super::create_blood_test::lab_results_editor;
 use crate::layout::page_layout;
 use crate::store::use_store;
 use crate::utils::{
-    compute_fudge_factor, fmt_blood_value, fmt_date_label, fmt_decimal, format_injectable_dose,
-    hormone_unit_label, injectable_dose_from_iu, parse_hormone_unit, parse_length_unit,
+    calculated_level_notes, compute_fudge_factor, convert_estradiol_to_display, fmt_blood_value,
+    fmt_date_label, fmt_decimal, fmt_lab_result, fmt_regimen, format_injectable_dose,
+    injectable_dose_from_iu, parse_length_unit, range_flag_note,
+};
+use hrt_shared::lab_results::{recorded_lab_names, LabResult};
+use hrt_shared::logic::{predict_e2_pg_ml, record_scheduled_dose, snap_to_next_injection_boundary};
+use hrt_shared::medications::{entry_medication, entry_medication_name};
+use hrt_shared::quantity::{BloodAnalyte, Quantity};
+use hrt_shared::ranges::assess_blood_test;
+use hrt_shared::regimen::{e2_unit_factor, injectable_steady_state};
+use hrt_shared::safety::{active_safety_alerts, SafetyAlert};
+use hrt_shared::schedules::{current_injectable, current_regimens};
+use hrt_shared::transdermal::{
+    next_patch_site, patch_removal_date, patch_total_strength, worn_patches,
 };
-use hrt_shared::logic::{predict_e2_pg_ml, snap_to_next_injection_boundary};
 use hrt_shared::types::{
-    DiaryEntry, DosageHistoryEntry, DosagePhoto, HormoneUnits, HrtData, ProgesteroneRoutes,
+    DiaryEntry, DosageHistoryEntry, DosagePhoto, HormoneUnits, HrtData, PatchSites,
+    ProgesteroneRoutes,
 };
 
 #[component]
@@ -66,6 +80,8 @@ pub fn ViewPage() -> impl IntoView {
     let editing_needle_length = create_rw_signal(String::new());
     let editing_needle_gauge = create_rw_signal(String::new());
     let editing_bonus = create_rw_signal(false);
+    let editing_patch_site = create_rw_signal(String::new());
+    let editing_removed_at = create_rw_signal(String::new());
     let upload_busy = create_rw_signal(false);
     let photo_input_ref: NodeRef<html::Input> = create_node_ref();
     let confirm_delete = create_rw_signal(None::<String>);
@@ -91,7 +107,10 @@ pub fn ViewPage() -> impl IntoView {
     let edit_blood_shbg = create_rw_signal(String::new());
     let edit_blood_shbg_unit = create_rw_signal(String::new());
     let edit_blood_fai = create_rw_signal(String::new());
+    let edit_blood_albumin = create_rw_signal(String::new());
     let edit_blood_notes = create_rw_signal(String::new());
+    let edit_blood_lab = create_rw_signal(String::new());
+    let edit_blood_results = create_rw_signal(Vec::<LabResult>::new());
     let edit_blood_pdf_files = create_rw_signal(Vec::<String>::new());
 
     let edit_measurement_id = create_rw_signal(None::<String>);
@@ -131,15 +150,31 @@ pub fn ViewPage() -> impl IntoView {
     let show_e2 = create_rw_signal(true);
     let show_t = create_rw_signal(true);
     let show_prog = create_rw_signal(false);
+    let show_prog_model = create_rw_signal(false);
+    let show_aa_model = create_rw_signal(false);
     let show_fsh = create_rw_signal(false);
     let show_lh = create_rw_signal(false);
     let show_prolactin = create_rw_signal(false);
     let show_shbg = create_rw_signal(false);
     let show_fai = create_rw_signal(false);
+    let show_free_t = create_rw_signal(false);
+    let show_bio_t = create_rw_signal(false);
+    let show_free_e2 = create_rw_signal(false);
+    let chart_lab_result = create_rw_signal(String::new());
     let show_fudge_factor = create_rw_signal(true);
+    let show_e2_band = create_rw_signal(false);
     let view_zoom = create_rw_signal(ViewZoom::default());
     let view_tooltip = create_rw_signal(None::<ChartTooltip>);
 
+    // Only computed while shown, and kept across unrelated toggles.
+    let view_e2_band = create_memo(move |_| {
+        if !(show_e2_band.get() && show_e2.get()) {
+            return Vec::new();
+        }
+        let now = Date::now() as i64;
+        predicted_e2_band(&data.get(), now - time_range_days.get() * DAY_MS, now)
+    });
+
     let view_chart_state = create_memo({
         let settings = store.settings;
         move |_| {
@@ -154,12 +189,19 @@ pub fn ViewPage() -> impl IntoView {
                 show_e2.get(),
                 show_t.get(),
                 show_prog.get(),
+                show_prog_model.get(),
+                show_aa_model.get(),
                 show_fsh.get(),
                 show_lh.get(),
                 show_prolactin.get(),
                 show_shbg.get(),
                 show_fai.get(),
+                show_free_t.get(),
+                show_bio_t.get(),
+                show_free_e2.get(),
+                &chart_lab_result.get(),
                 show_fudge_factor.get(),
+                &view_e2_band.get(),
             )
         }
     });
@@ -185,19 +227,34 @@ pub fn ViewPage() -> impl IntoView {
         tests.last().map(|(_, value)| *value)
     });
 
-    let estrannaise_url = create_memo(move |_| {
+    let injectable_steady_state_label = create_memo(move |_| {
         let data_value = data.get();
-        generate_estrannaise_url(&data_value, latest_fudge_factor.get())
+        let display_unit = store.settings
+            .get()
+            .displayEstradiolUnit
+            .unwrap_or(HormoneUnits::E2PmolL);
+        let factor =
+            latest_fudge_factor.get().unwrap_or(1.0) * e2_unit_factor(&display_unit).unwrap_or(1.0);
+        let state = injectable_steady_state(&data_value, factor)?;
+        Some(format!(
+            "trough {}, peak {}, average {} {} · peak {} h after dose · fluctuation {}%",
+            fmt_decimal(state.trough, 0),
+            fmt_decimal(state.peak, 0),
+            fmt_decimal(state.average, 0),
+            display_unit.symbol(),
+            fmt_decimal(state.timeToPeak * 24.0, 0),
+            fmt_decimal(state.fluctuation * 100.0, 0),
+        ))
     });
 
-    let has_any_regimen = create_memo(move |_| {
+    let estrannaise_url = create_memo(move |_| {
         let data_value = data.get();
-        data_value.injectableEstradiol.is_some()
-            || data_value.oralEstradiol.is_some()
-            || data_value.antiandrogen.is_some()
-            || data_value.progesterone.is_some()
+        generate_estrannaise_url(&data_value, latest_fudge_factor.get())
     });
 
+    let has_any_regimen =
+        create_memo(move |_| data.with(|data_value| current_regimens(data_value).next().is_some()));
+
     let next_scheduled_candidate = create_memo(move |_| {
         let data_value = data.get();
         let use_iu = store.settings.get().displayInjectableInIU.unwrap_or(false);
@@ -206,19 +263,19 @@ pub fn ViewPage() -> impl IntoView {
 
     let sorted_notes = create_memo(move |_| {
         let mut notes = data.get().notes.clone();
-        notes.sort_by(|a, b| b.date.cmp(&a.date));
+        notes.sort_by_key(|b| std::cmp::Reverse(b.date));
         notes
     });
 
     let sorted_blood_tests = create_memo(move |_| {
         let mut tests = data.get().bloodTests.clone();
-        tests.sort_by(|a, b| b.date.cmp(&a.date));
+        tests.sort_by_key(|b| std::cmp::Reverse(b.date));
         tests
     });
 
     let sorted_measurements = create_memo(move |_| {
         let mut items = data.get().measurements.clone();
-        items.sort_by(|a, b| b.date.cmp(&a.date));
+        items.sort_by_key(|b| std::cmp::Reverse(b.date));
         items
     });
 
@@ -248,10 +305,8 @@ pub fn ViewPage() -> impl IntoView {
                 return Some(dose_value);
             }
             let data_value = store.data.get();
-            let schedule_vial_id = data_value
-                .injectableEstradiol
-                .as_ref()
-                .and_then(|cfg| cfg.vialId.as_ref());
+            let schedule_vial_id =
+                current_injectable(&data_value).and_then(|cfg| cfg.vialId.as_ref());
             let selected_vial = editing_vial_id.get();
             let selected_vial_id = if selected_vial.trim().is_empty() {
                 None
@@ -370,73 +425,8 @@ pub fn ViewPage() -> impl IntoView {
                 return;
             };
             let now = Date::now() as i64;
-            store.data.update(|data| match candidate.med_type {
-                RegimenKey::InjectableEstradiol => {
-                    if let Some(cfg) = data.injectableEstradiol.as_mut() {
-                        let record = DosageHistoryEntry::InjectableEstradiol {
-                            date: now,
-                            id: None,
-                            kind: cfg.kind.clone(),
-                            dose: cfg.dose,
-                            unit: cfg.unit.clone(),
-                            note: None,
-                            bonusDose: None,
-                            injectionSite: None,
-                            vialId: cfg.vialId.clone(),
-                            subVialId: cfg.subVialId.clone(),
-                            syringeKind: cfg.syringeKind.clone(),
-                            needleLength: cfg.needleLength.clone(),
-                            needleGauge: cfg.needleGauge.clone(),
-                            photos: None,
-                        };
-                        data.dosageHistory.push(record);
-                        if cfg.frequency.is_finite() && cfg.frequency > 0.0 {
-                            cfg.nextDoseDate = Some(now + (cfg.frequency * DAY_MS as f64) as i64);
-                        }
-                    }
-                }
-                RegimenKey::OralEstradiol => {
-                    if let Some(cfg) = data.oralEstradiol.as_ref() {
-                        let record = DosageHistoryEntry::OralEstradiol {
-                            date: now,
-                            id: None,
-                            kind: cfg.kind.clone(),
-                            dose: cfg.dose,
-                            unit: cfg.unit.clone(),
-                            pillQuantity: Some(1.0),
-                            note: None,
-                        };
-                        data.dosageHistory.push(record);
-                    }
-                }
-                RegimenKey::Antiandrogen => {
-                    if let Some(cfg) = data.antiandrogen.as_ref() {
-                        let record = DosageHistoryEntry::Antiandrogen {
-                            date: now,
-                            id: None,
-                            kind: cfg.kind.clone(),
-                            dose: cfg.dose,
-                            unit: cfg.unit.clone(),
-                            note: None,
-                        };
-                        data.dosageHistory.push(record);
-                    }
-                }
-                RegimenKey::Progesterone => {
-                    if let Some(cfg) = data.progesterone.as_ref() {
-                        let record = DosageHistoryEntry::Progesterone {
-                            date: now,
-                            id: None,
-                            kind: cfg.kind.clone(),
-                            route: cfg.route.clone(),
-                            dose: cfg.dose,
-                            unit: cfg.unit.clone(),
-                            pillQuantity: Some(1.0),
-                            note: None,
-                        };
-                        data.dosageHistory.push(record);
-                    }
-                }
+            store.data.update(|data| {
+                record_scheduled_dose(data, &candidate.regimen_id, now, None);
             });
             store.mark_dirty();
             store.save();
@@ -466,7 +456,7 @@ pub fn ViewPage() -> impl IntoView {
             let zoom = view_zoom.get();
             let x_min = zoom.x_min.unwrap_or(state.domain_min);
             let x_max = zoom.x_max.unwrap_or(state.domain_max);
-            let padding = chart_padding();
+            let padding = chart_padding_with(state.has_secondary_axis());
             let (width, height, domain_span, y_span) = compute_chart_bounds(
                 rect.width(),
                 rect.height(),
@@ -592,7 +582,7 @@ pub fn ViewPage() -> impl IntoView {
             let zoom = view_zoom.get();
             let x_min = zoom.x_min.unwrap_or(state.domain_min);
             let x_max = zoom.x_max.unwrap_or(state.domain_max);
-            let padding = chart_padding();
+            let padding = chart_padding_with(state.has_secondary_axis());
             let (width, _, domain_span, _) = compute_chart_bounds(
                 rect.width(),
                 rect.height(),
@@ -662,6 +652,8 @@ pub fn ViewPage() -> impl IntoView {
     };
     let is_injectable = move || editing_med_type.get() == "injectableEstradiol";
     let is_prog = move || editing_med_type.get() == "progesterone";
+    let is_patch = move || editing_med_type.get() == "patch";
+    let is_gel = move || editing_med_type.get() == "gel";
     let is_pill = move || {
         let med = editing_med_type.get();
         med == "oralEstradiol" || med == "progesterone"
@@ -671,6 +663,8 @@ pub fn ViewPage() -> impl IntoView {
         "oralEstradiol" => "Oral Estradiol",
         "antiandrogen" => "Antiandrogen",
         "progesterone" => "Progesterone",
+        "patch" => "Estradiol Patch",
+        "gel" => "Estradiol Gel",
         _ => "",
     };
 
@@ -709,7 +703,7 @@ pub fn ViewPage() -> impl IntoView {
             let unit_value = if editing_med_type.get() == "injectableEstradiol" {
                 HormoneUnits::Mg
             } else {
-                parse_hormone_unit(&editing_unit.get()).unwrap_or(HormoneUnits::Mg)
+                HormoneUnits::from_symbol(&editing_unit.get()).unwrap_or(HormoneUnits::Mg)
             };
             let pill_qty = parse_optional_num(&editing_pill_qty.get()).filter(|v| *v > 0.0);
             let route_value =
@@ -720,10 +714,8 @@ pub fn ViewPage() -> impl IntoView {
                 };
 
             store_edit.data.update(|d| {
-                let schedule_vial_id_for_iu = d
-                    .injectableEstradiol
-                    .as_ref()
-                    .and_then(|cfg| cfg.vialId.clone());
+                let schedule_vial_id_for_iu =
+                    current_injectable(d).and_then(|cfg| cfg.vialId.clone());
                 let iu_conversion_data = HrtData {
                     vials: d.vials.clone(),
                     ..Default::default()
@@ -819,6 +811,13 @@ pub fn ViewPage() -> impl IntoView {
                                 unit,
                                 note,
                                 ..
+                            }
+                            | DosageHistoryEntry::Medication {
+                                date,
+                                dose,
+                                unit,
+                                note,
+                                ..
                             } => {
                                 *date = date_value;
                                 *dose = dose_input_value;
@@ -841,6 +840,40 @@ pub fn ViewPage() -> impl IntoView {
                                 *pillQuantity = pill_qty;
                                 *route = route_value.clone();
                             }
+                            DosageHistoryEntry::Patch {
+                                date,
+                                strength,
+                                patchQuantity,
+                                removedAt,
+                                site,
+                                note,
+                                ..
+                            } => {
+                                *date = date_value;
+                                *strength = dose_input_value;
+                                *patchQuantity = pill_qty;
+                                *removedAt = if editing_removed_at.get().trim().is_empty() {
+                                    None
+                                } else {
+                                    Some(parse_datetime_local(&editing_removed_at.get()))
+                                };
+                                *site = PatchSites::from_label(&editing_patch_site.get());
+                                *note = note_value.clone();
+                            }
+                            DosageHistoryEntry::Gel {
+                                date,
+                                dose,
+                                unit,
+                                pumps,
+                                note,
+                                ..
+                            } => {
+                                *date = date_value;
+                                *dose = dose_input_value;
+                                *unit = unit_value.clone();
+                                *pumps = pill_qty;
+                                *note = note_value.clone();
+                            }
                         }
                     }
                 }
@@ -1058,6 +1091,46 @@ pub fn ViewPage() -> impl IntoView {
                     </div>
                 </div>
 
+                {move || {
+                    let now = Date::now() as i64;
+                    let alerts = active_safety_alerts(&data.get(), now);
+                    (!alerts.is_empty()).then(|| view! {
+                        <div class="card">
+                            <h3>"Safety Monitoring"</h3>
+                            <ul class="history-list">
+                                {alerts
+                                    .into_iter()
+                                    .map(|alert| {
+                                        let when = match &alert {
+                                            SafetyAlert::MonitoringDue { due, .. } if *due < now => {
+                                                format!("Overdue since {}", fmt_date_label(*due, "date", None))
+                                            }
+                                            SafetyAlert::MonitoringDue { due, .. } => {
+                                                format!("Due {}", fmt_date_label(*due, "date", None))
+                                            }
+                                            SafetyAlert::OutOfRange { date, .. } => {
+                                                format!("Blood test {}", fmt_date_label(*date, "date", None))
+                                            }
+                                        };
+                                        let flagged = matches!(alert, SafetyAlert::OutOfRange { .. });
+                                        view! {
+                                            <li class="history-item">
+                                                <div>
+                                                    <div class="history-date" class:range-flag=flagged>{alert.summary()}</div>
+                                                    <div class="history-meta">
+                                                        <span>{alert.description()}</span>
+                                                        <span class="muted">{when}</span>
+                                                    </div>
+                                                </div>
+                                            </li>
+                                        }
+                                    })
+                                    .collect_view()}
+                            </ul>
+                        </div>
+                    })
+                }}
+
                 <div class="card">
                     <div class="card-header">
                         <h3>"Current Regimen"</h3>
@@ -1102,73 +1175,49 @@ pub fn ViewPage() -> impl IntoView {
                                 {move || latest_fudge_factor.get().unwrap_or(0.0)}
                             </p>
                         </Show>
-                        <Show when=move || store.data.get().injectableEstradiol.is_some()>
-                            <p>
-                                <strong>"Injectable Estradiol: "</strong>
-                                {move || {
-                                    let data_value = store.data.get();
-                                    let use_iu = store.settings.get().displayInjectableInIU.unwrap_or(false);
-                                    data_value
-                                        .injectableEstradiol
-                                        .as_ref()
-                                        .map(|cfg| {
-                                            let dose_label = format_injectable_dose(
-                                                &data_value,
-                                                cfg.dose,
-                                                &cfg.unit,
-                                                cfg.vialId.as_ref(),
-                                                cfg.vialId.as_ref(),
-                                                use_iu,
-                                            );
-                                            format!(
-                                                "{:?}, {dose_label} every {:.1} days",
-                                                cfg.kind, cfg.frequency
-                                            )
-                                        })
-                                        .unwrap_or_default()
-                                }}
-                            </p>
-                        </Show>
-                        <Show when=move || store.data.get().oralEstradiol.is_some()>
-                            <p>
-                                <strong>"Oral Estradiol: "</strong>
-                                {move || store
-                                    .data
-                                    .get()
-                                    .oralEstradiol
-                                    .as_ref()
-                                    .map(|cfg| format!("{:?}, {:.2} {:?} every {:.1} days", cfg.kind, cfg.dose, cfg.unit, cfg.frequency))
-                                    .unwrap_or_default()}
-                            </p>
-                        </Show>
-                        <Show when=move || store.data.get().antiandrogen.is_some()>
+                        {move || {
+                            let data_value = store.data.get();
+                            let use_iu = store.settings.get().displayInjectableInIU.unwrap_or(false);
+                            current_regimens(&data_value)
+                                .map(|regimen| {
+                                    let (title, detail) = fmt_regimen(&data_value, regimen, use_iu);
+                                    view! {
+                                        <p>
+                                            <strong>{format!("{title}: ")}</strong>
+                                            {detail}
+                                        </p>
+                                    }
+                                })
+                                .collect_view()
+                        }}
+                        <Show when=move || injectable_steady_state_label.get().is_some()>
                             <p>
-                                <strong>"Antiandrogen: "</strong>
-                                {move || store
-                                    .data
-                                    .get()
-                                    .antiandrogen
-                                    .as_ref()
-                                    .map(|cfg| format!("{:?}, {:.2} {:?} every {:.1} days", cfg.kind, cfg.dose, cfg.unit, cfg.frequency))
-                                    .unwrap_or_default()}
+                                <strong>"Steady state: "</strong>
+                                {move || injectable_steady_state_label.get().unwrap_or_default()}
                             </p>
                         </Show>
-                        <Show when=move || store.data.get().progesterone.is_some()>
+                        <Show when=move || {
+                            current_regimens(&store.data.get()).any(|regimen| regimen.schedule.as_patch().is_some())
+                        }>
                             <p>
-                                <strong>"Progesterone: "</strong>
-                                {move || store
-                                    .data
-                                    .get()
-                                    .progesterone
-                                    .as_ref()
-                                    .map(|cfg| format!("{:?} ({:?}), {:.2} {:?} every {:.1} days", cfg.kind, cfg.route, cfg.dose, cfg.unit, cfg.frequency))
-                                    .unwrap_or_default()}
+                                <strong>"Next patch site: "</strong>
+                                {move || next_patch_site(&store.data.get()).label()}
                             </p>
+                            {move || {
+                                let data_value = store.data.get();
+                                worn_patches(&data_value, Date::now() as i64)
+                                    .into_iter()
+                                    .map(|(_, removal)| removal)
+                                    .min()
+                                    .map(|removal| view! {
+                                        <p>
+                                            <strong>"Remove current patch: "</strong>
+                                            {fmt_date_label(removal, "date", None)}
+                                        </p>
+                                    })
+                            }}
                         </Show>
-                        <Show when=move || store.data.get().injectableEstradiol.is_none()
-                            && store.data.get().oralEstradiol.is_none()
-                            && store.data.get().antiandrogen.is_none()
-                            && store.data.get().progesterone.is_none()>
+                        <Show when=move || !has_any_regimen.get()>
                             <p class="muted">"No regimen set up. You can set one on the dosage page."</p>
                         </Show>
                     </div>
@@ -1246,6 +1295,29 @@ pub fn ViewPage() -> impl IntoView {
                         <button class:active=move || show_fai.get() on:click=move |_| show_fai.set(!show_fai.get())>
                             "FAI"
                         </button>
+                        <button class:active=move || show_free_t.get() on:click=move |_| show_free_t.set(!show_free_t.get())>
+                            "Free T"
+                        </button>
+                        <button class:active=move || show_bio_t.get() on:click=move |_| show_bio_t.set(!show_bio_t.get())>
+                            "Bio T"
+                        </button>
+                        <button class:active=move || show_free_e2.get() on:click=move |_| show_free_e2.set(!show_free_e2.get())>
+                            "Free E2"
+                        </button>
+                        <Show when=move || !recorded_lab_names(&data.get()).is_empty()>
+                            <select
+                                on:change=move |ev| chart_lab_result.set(event_target_value(&ev))
+                                prop:value=move || chart_lab_result.get()
+                            >
+                                <option value="">"Other result…"</option>
+                                {move || {
+                                    recorded_lab_names(&data.get())
+                                        .into_iter()
+                                        .map(|name| view! { <option value=name.clone()>{name}</option> })
+                                        .collect_view()
+                                }}
+                            </select>
+                        </Show>
                         <button
                             class:active=move || show_fudge_factor.get()
                             on:click=move |_| show_fudge_factor.set(!show_fudge_factor.get())
@@ -1260,6 +1332,28 @@ pub fn ViewPage() -> impl IntoView {
                             {move || if show_medications.get() { "Medication Dosages (on)" } else { "Medication Dosages" }}
                         </button>
                     </div>
+
+                    <div class="chart-toolbar view-model-group">
+                        <span class="muted">"Show Models:"</span>
+                        <button
+                            class:active=move || show_prog_model.get()
+                            on:click=move |_| show_prog_model.set(!show_prog_model.get())
+                        >
+                            "Progesterone (ng/mL)"
+                        </button>
+                        <button
+                            class:active=move || show_aa_model.get()
+                            on:click=move |_| show_aa_model.set(!show_aa_model.get())
+                        >
+                            "Antiandrogens (% of peak load)"
+                        </button>
+                        <button
+                            class:active=move || show_e2_band.get()
+                            on:click=move |_| show_e2_band.set(!show_e2_band.get())
+                        >
+                            "E2 uncertainty"
+                        </button>
+                    </div>
                 </div>
 
                 <div class="chart-card chart-interactive">
@@ -1445,14 +1539,6 @@ pub fn ViewPage() -> impl IntoView {
                                     key=|entry| entry.date
                                     children=move |entry| {
                                         let entry_date = entry.date;
-                                        let e2_default_label = {
-                                            let unit = store
-                                                .settings
-                                                .get()
-                                                .displayEstradiolUnit
-                                                .unwrap_or(HormoneUnits::E2PmolL);
-                                            hormone_unit_label(&unit)
-                                        };
                                         let date_label =
                                             move || fmt_date_label(entry_date, &x_axis_mode.get(), first_dose_date.get());
                                         let on_edit = {
@@ -1461,153 +1547,82 @@ pub fn ViewPage() -> impl IntoView {
                                                 let date_text = to_local_input_value(entry.date);
                                                 edit_blood_date.set(Some(entry.date));
                                                 edit_blood_date_text.set(date_text);
-                                                edit_blood_e2.set(entry.estradiolLevel.map(fmt_blood_value).unwrap_or_default());
                                                 let e2_default = store
                                                     .settings
                                                     .get()
                                                     .displayEstradiolUnit
                                                     .unwrap_or(HormoneUnits::E2PmolL);
-                                                edit_blood_e2_unit.set(
-                                                    entry
-                                                        .estradiolUnit
-                                                        .as_ref()
-                                                        .map(|u| hormone_unit_label(u).to_string())
-                                                        .unwrap_or_else(|| hormone_unit_label(&e2_default).to_string()),
-                                                );
+                                                let (value, unit) = level_form_fields(&entry.estradiol, &e2_default);
+                                                edit_blood_e2.set(value);
+                                                edit_blood_e2_unit.set(unit);
                                                 edit_blood_estrannaise.set(entry.estrannaiseNumber.map(fmt_blood_value).unwrap_or_default());
                                                 edit_blood_estrannaise_unit.set("pg/mL".to_string());
-                                                edit_blood_t.set(entry.testLevel.map(fmt_blood_value).unwrap_or_default());
-                                                edit_blood_t_unit.set(
-                                                    entry
-                                                        .testUnit
-                                                        .as_ref()
-                                                        .map(|u| hormone_unit_label(u).to_string())
-                                                        .unwrap_or_else(|| "nmol/L".to_string()),
-                                                );
-                                                edit_blood_prog.set(entry.progesteroneLevel.map(fmt_blood_value).unwrap_or_default());
-                                                edit_blood_prog_unit.set(
-                                                    entry
-                                                        .progesteroneUnit
-                                                        .as_ref()
-                                                        .map(|u| hormone_unit_label(u).to_string())
-                                                        .unwrap_or_else(|| "nmol/L".to_string()),
-                                                );
-                                                edit_blood_fsh.set(entry.fshLevel.map(fmt_blood_value).unwrap_or_default());
-                                                edit_blood_fsh_unit.set(
-                                                    entry
-                                                        .fshUnit
-                                                        .as_ref()
-                                                        .map(|u| hormone_unit_label(u).to_string())
-                                                        .unwrap_or_else(|| "U/L".to_string()),
-                                                );
-                                                edit_blood_lh.set(entry.lhLevel.map(fmt_blood_value).unwrap_or_default());
-                                                edit_blood_lh_unit.set(
-                                                    entry
-                                                        .lhUnit
-                                                        .as_ref()
-                                                        .map(|u| hormone_unit_label(u).to_string())
-                                                        .unwrap_or_else(|| "U/L".to_string()),
-                                                );
-                                                edit_blood_prolactin.set(entry.prolactinLevel.map(fmt_blood_value).unwrap_or_default());
-                                                edit_blood_prolactin_unit.set(
-                                                    entry
-                                                        .prolactinUnit
-                                                        .as_ref()
-                                                        .map(|u| hormone_unit_label(u).to_string())
-                                                        .unwrap_or_else(|| "mIU/L".to_string()),
-                                                );
-                                                edit_blood_shbg.set(entry.shbgLevel.map(fmt_blood_value).unwrap_or_default());
-                                                edit_blood_shbg_unit.set(entry.shbgUnit.as_ref().map(|u| hormone_unit_label(u).to_string()).unwrap_or_else(|| "nmol/L".to_string()));
+                                                let (value, unit) = level_form_fields(&entry.testosterone, &HormoneUnits::TNmolL);
+                                                edit_blood_t.set(value);
+                                                edit_blood_t_unit.set(unit);
+                                                let (value, unit) = level_form_fields(&entry.progesterone, &HormoneUnits::TNmolL);
+                                                edit_blood_prog.set(value);
+                                                edit_blood_prog_unit.set(unit);
+                                                let (value, unit) = level_form_fields(&entry.fsh, &HormoneUnits::UL);
+                                                edit_blood_fsh.set(value);
+                                                edit_blood_fsh_unit.set(unit);
+                                                let (value, unit) = level_form_fields(&entry.lh, &HormoneUnits::UL);
+                                                edit_blood_lh.set(value);
+                                                edit_blood_lh_unit.set(unit);
+                                                let (value, unit) = level_form_fields(&entry.prolactin, &HormoneUnits::MIuL);
+                                                edit_blood_prolactin.set(value);
+                                                edit_blood_prolactin_unit.set(unit);
+                                                let (value, unit) = level_form_fields(&entry.shbg, &HormoneUnits::TNmolL);
+                                                edit_blood_shbg.set(value);
+                                                edit_blood_shbg_unit.set(unit);
                                                 edit_blood_fai.set(entry.freeAndrogenIndex.map(fmt_blood_value).unwrap_or_default());
+                                                edit_blood_albumin.set(entry.albuminLevel.map(fmt_blood_value).unwrap_or_default());
                                                 edit_blood_notes.set(entry.notes.clone().unwrap_or_default());
+                                                edit_blood_lab.set(entry.lab.clone().unwrap_or_default());
+                                                edit_blood_results.set(entry.labResults.clone());
                                                 edit_blood_pdf_files.set(
                                                     entry.pdfFiles.clone().unwrap_or_default(),
                                                 );
                                             }
                                         };
                                         let pdf_files = StoredValue::new(entry.pdfFiles.clone().unwrap_or_default());
+                                        let range_notes = {
+                                            let entry = entry.clone();
+                                            let data = store.data;
+                                            move || {
+                                                assess_blood_test(&data.get(), &entry)
+                                                    .iter()
+                                                    .filter_map(range_flag_note)
+                                                    .collect::<Vec<_>>()
+                                            }
+                                        };
+                                        let lab_name = entry.lab.clone();
+                                        let calculated_notes = calculated_level_notes(&entry);
+                                        let level_texts: Vec<String> = [
+                                            ("E2", &entry.estradiol),
+                                            ("T", &entry.testosterone),
+                                            ("Prog", &entry.progesterone),
+                                            ("FSH", &entry.fsh),
+                                            ("LH", &entry.lh),
+                                            ("PRL", &entry.prolactin),
+                                            ("SHBG", &entry.shbg),
+                                        ]
+                                        .into_iter()
+                                        .filter_map(|(label, level)| {
+                                            let level = level.as_ref()?;
+                                            Some(format!("{label}: {} {}", fmt_blood_value(level.value), level.unit.symbol()))
+                                        })
+                                        .collect();
+                                        let lab_results = entry.labResults.clone();
                                         view! {
                                             <li class="history-item">
                                                 <div>
                                                     <div class="history-date">{date_label}</div>
                                                     <div class="history-meta history-meta-inline">
-                                                        <Show when=move || entry.estradiolLevel.is_some()>
-                                                            <span>{format!(
-                                                                "E2: {} {}",
-                                                                fmt_blood_value(entry.estradiolLevel.unwrap_or_default()),
-                                                                entry
-                                                                    .estradiolUnit
-                                                                    .as_ref()
-                                                                    .map(|u| hormone_unit_label(u))
-                                                                    .unwrap_or(e2_default_label)
-                                                            )}</span>
-                                                        </Show>
-                                                        <Show when=move || entry.testLevel.is_some()>
-                                                            <span>{format!(
-                                                                "T: {} {}",
-                                                                fmt_blood_value(entry.testLevel.unwrap_or_default()),
-                                                                entry
-                                                                    .testUnit
-                                                                    .as_ref()
-                                                                    .map(|u| hormone_unit_label(u))
-                                                                    .unwrap_or("nmol/L")
-                                                            )}</span>
-                                                        </Show>
-                                                        <Show when=move || entry.progesteroneLevel.is_some()>
-                                                            <span>{format!(
-                                                                "Prog: {} {}",
-                                                                fmt_blood_value(entry.progesteroneLevel.unwrap_or_default()),
-                                                                entry
-                                                                    .progesteroneUnit
-                                                                    .as_ref()
-                                                                    .map(|u| hormone_unit_label(u))
-                                                                    .unwrap_or("nmol/L")
-                                                            )}</span>
-                                                        </Show>
-                                                        <Show when=move || entry.fshLevel.is_some()>
-                                                            <span>{format!(
-                                                                "FSH: {} {}",
-                                                                fmt_blood_value(entry.fshLevel.unwrap_or_default()),
-                                                                entry
-                                                                    .fshUnit
-                                                                    .as_ref()
-                                                                    .map(|u| hormone_unit_label(u))
-                                                                    .unwrap_or("U/L")
-                                                            )}</span>
-                                                        </Show>
-                                                        <Show when=move || entry.lhLevel.is_some()>
-                                                            <span>{format!(
-                                                                "LH: {} {}",
-                                                                fmt_blood_value(entry.lhLevel.unwrap_or_default()),
-                                                                entry
-                                                                    .lhUnit
-                                                                    .as_ref()
-                                                                    .map(|u| hormone_unit_label(u))
-                                                                    .unwrap_or("U/L")
-                                                            )}</span>
-                                                        </Show>
-                                                        <Show when=move || entry.prolactinLevel.is_some()>
-                                                            <span>{format!(
-                                                                "PRL: {} {}",
-                                                                fmt_blood_value(entry.prolactinLevel.unwrap_or_default()),
-                                                                entry
-                                                                    .prolactinUnit
-                                                                    .as_ref()
-                                                                    .map(|u| hormone_unit_label(u))
-                                                                    .unwrap_or("mIU/L")
-                                                            )}</span>
-                                                        </Show>
-                                                        <Show when=move || entry.shbgLevel.is_some()>
-                                                            <span>{format!(
-                                                                "SHBG: {} {}",
-                                                                fmt_blood_value(entry.shbgLevel.unwrap_or_default()),
-                                                                entry
-                                                                    .shbgUnit
-                                                                    .as_ref()
-                                                                    .map(|u| hormone_unit_label(u))
-                                                                    .unwrap_or("nmol/L")
-                                                            )}</span>
-                                                        </Show>
+                                                        {level_texts
+                                                            .into_iter()
+                                                            .map(|text| view! { <span>{text}</span> })
+                                                            .collect_view()}
                                                         <Show when=move || entry.freeAndrogenIndex.is_some()>
                                                             <span>{format!(
                                                                 "FAI: {}",
@@ -1620,7 +1635,40 @@ pub fn ViewPage() -> impl IntoView {
                                                         <Show when=move || !pdf_files.get_value().is_empty()>
                                                             <span>{format!("PDFs: {}", pdf_files.get_value().len())}</span>
                                                         </Show>
+                                                        {lab_name.map(|lab| view! { <span class="muted">{format!("Lab: {}", lab)}</span> })}
                                                     </div>
+                                                    {(!calculated_notes.is_empty()).then(|| view! {
+                                                        <div class="history-meta history-meta-inline">
+                                                            {calculated_notes
+                                                                .into_iter()
+                                                                .map(|note| view! { <span class="muted">{note}</span> })
+                                                                .collect_view()}
+                                                        </div>
+                                                    })}
+                                                    {(!lab_results.is_empty()).then(|| view! {
+                                                        <div class="history-meta history-meta-inline">
+                                                            {lab_results
+                                                                .iter()
+                                                                .map(|result| {
+                                                                    let flagged = result
+                                                                        .effective_flag()
+                                                                        .is_some_and(|flag| flag.is_out_of_range());
+                                                                    view! { <span class:range-flag=flagged>{fmt_lab_result(result)}</span> }
+                                                                })
+                                                                .collect_view()}
+                                                        </div>
+                                                    })}
+                                                    {move || {
+                                                        let notes = range_notes();
+                                                        (!notes.is_empty()).then(|| view! {
+                                                            <div class="history-meta history-meta-inline">
+                                                                {notes
+                                                                    .into_iter()
+                                                                    .map(|note| view! { <span class="range-flag">{note}</span> })
+                                                                    .collect_view()}
+                                                            </div>
+                                                        })
+                                                    }}
                                                     <Show when=move || !pdf_files.get_value().is_empty()>
                                                         <div class="history-meta history-meta-inline">
                                                             {pdf_files
@@ -1802,7 +1850,10 @@ pub fn ViewPage() -> impl IntoView {
                                         DosageHistoryEntry::InjectableEstradiol { date, id, .. }
                                         | DosageHistoryEntry::OralEstradiol { date, id, .. }
                                         | DosageHistoryEntry::Antiandrogen { date, id, .. }
-                                        | DosageHistoryEntry::Progesterone { date, id, .. } => id
+                                        | DosageHistoryEntry::Progesterone { date, id, .. }
+                                        | DosageHistoryEntry::Patch { date, id, .. }
+                                        | DosageHistoryEntry::Gel { date, id, .. }
+                                        | DosageHistoryEntry::Medication { date, id, .. } => id
                                             .clone()
                                             .unwrap_or_else(|| date.to_string()),
                                     }
@@ -1814,7 +1865,10 @@ pub fn ViewPage() -> impl IntoView {
                                             DosageHistoryEntry::InjectableEstradiol { date, id, .. }
                                             | DosageHistoryEntry::OralEstradiol { date, id, .. }
                                             | DosageHistoryEntry::Antiandrogen { date, id, .. }
-                                            | DosageHistoryEntry::Progesterone { date, id, .. } => {
+                                            | DosageHistoryEntry::Progesterone { date, id, .. }
+                                            | DosageHistoryEntry::Patch { date, id, .. }
+                                            | DosageHistoryEntry::Gel { date, id, .. }
+                                            | DosageHistoryEntry::Medication { date, id, .. } => {
                                                 id.clone().unwrap_or_else(|| date.to_string())
                                             }
                                         };
@@ -1852,7 +1906,10 @@ pub fn ViewPage() -> impl IntoView {
                                                                 DosageHistoryEntry::InjectableEstradiol { id, date, .. }
                                                                 | DosageHistoryEntry::OralEstradiol { id, date, .. }
                                                                 | DosageHistoryEntry::Antiandrogen { id, date, .. }
-                                                                | DosageHistoryEntry::Progesterone { id, date, .. } => {
+                                                                | DosageHistoryEntry::Progesterone { id, date, .. }
+                                                                | DosageHistoryEntry::Patch { id, date, .. }
+                                                                | DosageHistoryEntry::Gel { id, date, .. }
+                                                                | DosageHistoryEntry::Medication { id, date, .. } => {
                                                                     if id.is_none() {
                                                                         *id = Some(format!(
                                                                             "dose-{}-{}",
@@ -1879,25 +1936,36 @@ pub fn ViewPage() -> impl IntoView {
                                                     } => fmt_decimal(*dose, 3),
                                                     DosageHistoryEntry::OralEstradiol { dose, .. }
                                                     | DosageHistoryEntry::Antiandrogen { dose, .. }
-                                                    | DosageHistoryEntry::Progesterone { dose, .. } => {
+                                                    | DosageHistoryEntry::Progesterone { dose, .. }
+                                                    | DosageHistoryEntry::Gel { dose, .. }
+                                                    | DosageHistoryEntry::Medication { dose, .. } => {
                                                         fmt_decimal(*dose, 3)
                                                     }
+                                                    DosageHistoryEntry::Patch { strength, .. } => {
+                                                        fmt_decimal(*strength, 1)
+                                                    }
                                                 });
                                                 editing_unit.set(match &entry {
-                                                    DosageHistoryEntry::InjectableEstradiol { .. } => {
-                                                        hormone_unit_label(&HormoneUnits::Mg).to_string()
+                                                    DosageHistoryEntry::InjectableEstradiol { .. }
+                                                    | DosageHistoryEntry::Patch { .. } => {
+                                                        HormoneUnits::Mg.symbol().to_string()
                                                     }
                                                     DosageHistoryEntry::OralEstradiol { unit, .. }
                                                     | DosageHistoryEntry::Antiandrogen { unit, .. }
-                                                    | DosageHistoryEntry::Progesterone { unit, .. } => {
-                                                        hormone_unit_label(unit).to_string()
+                                                    | DosageHistoryEntry::Progesterone { unit, .. }
+                                                    | DosageHistoryEntry::Gel { unit, .. }
+                                                    | DosageHistoryEntry::Medication { unit, .. } => {
+                                                        unit.symbol().to_string()
                                                     }
                                                 });
                                                 editing_note.set(match &entry {
                                                     DosageHistoryEntry::InjectableEstradiol { note, .. }
                                                     | DosageHistoryEntry::OralEstradiol { note, .. }
                                                     | DosageHistoryEntry::Antiandrogen { note, .. }
-                                                    | DosageHistoryEntry::Progesterone { note, .. } => {
+                                                    | DosageHistoryEntry::Progesterone { note, .. }
+                                                    | DosageHistoryEntry::Patch { note, .. }
+                                                    | DosageHistoryEntry::Gel { note, .. }
+                                                    | DosageHistoryEntry::Medication { note, .. } => {
                                                         note.clone().unwrap_or_default()
                                                     }
                                                 });
@@ -1914,6 +1982,11 @@ pub fn ViewPage() -> impl IntoView {
                                                     DosageHistoryEntry::Progesterone { .. } => {
                                                         "progesterone".to_string()
                                                     }
+                                                    DosageHistoryEntry::Patch { .. } => "patch".to_string(),
+                                                    DosageHistoryEntry::Gel { .. } => "gel".to_string(),
+                                                    DosageHistoryEntry::Medication { .. } => {
+                                                        "medication".to_string()
+                                                    }
                                                 });
                                                 editing_route.set(progesterone_route_label(&ProgesteroneRoutes::Oral).to_string());
                                                 editing_pill_qty.set(String::new());
@@ -1924,6 +1997,8 @@ pub fn ViewPage() -> impl IntoView {
                                                 editing_needle_length.set(String::new());
                                                 editing_needle_gauge.set(String::new());
                                                 editing_bonus.set(false);
+                                                editing_patch_site.set(String::new());
+                                                editing_removed_at.set(String::new());
                                                 match &entry {
                                                     DosageHistoryEntry::InjectableEstradiol {
                                                         bonusDose,
@@ -1956,6 +2031,14 @@ pub fn ViewPage() -> impl IntoView {
                                                         editing_pill_qty.set(pillQuantity.map(|v| v.to_string()).unwrap_or_default());
                                                         editing_route.set(progesterone_route_label(route).to_string());
                                                     }
+                                                    DosageHistoryEntry::Patch { patchQuantity, removedAt, site, .. } => {
+                                                        editing_pill_qty.set(patchQuantity.map(|v| v.to_string()).unwrap_or_default());
+                                                        editing_removed_at.set(removedAt.map(to_local_input_value).unwrap_or_default());
+                                                        editing_patch_site.set(site.as_ref().map(PatchSites::label).unwrap_or("").to_string());
+                                                    }
+                                                    DosageHistoryEntry::Gel { pumps, .. } => {
+                                                        editing_pill_qty.set(pumps.map(|v| v.to_string()).unwrap_or_default());
+                                                    }
                                                     _ => {}
                                                 }
                                             }
@@ -1964,9 +2047,7 @@ pub fn ViewPage() -> impl IntoView {
                                             DosageHistoryEntry::InjectableEstradiol { kind, dose, unit, bonusDose, injectionSite, vialId, subVialId, syringeKind, needleLength, needleGauge, note, .. } => {
                                                 let use_iu = store.settings.get().displayInjectableInIU.unwrap_or(false);
                                                 let data_value = store.data.get();
-                                                let schedule_vial_id = data_value
-                                                    .injectableEstradiol
-                                                    .as_ref()
+                                                let schedule_vial_id = current_injectable(&data_value)
                                                     .and_then(|cfg| cfg.vialId.as_ref());
                                                 let dose_label = format_injectable_dose(
                                                     &data_value,
@@ -2060,6 +2141,53 @@ pub fn ViewPage() -> impl IntoView {
                                                 }
                                                 (summary, details, meta)
                                             }
+                                            DosageHistoryEntry::Patch { date, strength, patchQuantity, wearDays, removedAt, site, note, .. } => {
+                                                let total = patch_total_strength(*strength, *patchQuantity);
+                                                let summary = format!("Patch · {} µg/day", fmt_decimal(total, 1));
+                                                let mut details = Vec::new();
+                                                if let Some(qty) = patchQuantity.filter(|q| *q != 1.0) {
+                                                    details.push(format!("{} µg/day × {qty:.0}", fmt_decimal(*strength, 1)));
+                                                }
+                                                let removal = patch_removal_date(*date, *wearDays, *removedAt);
+                                                details.push(if removedAt.is_some() {
+                                                    format!("Removed {}", fmt_date_label(removal, "date", None))
+                                                } else {
+                                                    format!("Remove by {}", fmt_date_label(removal, "date", None))
+                                                });
+                                                if let Some(site) = site {
+                                                    details.push(format!("Site: {}", site.label()));
+                                                }
+                                                let mut meta = Vec::new();
+                                                if let Some(note) = note {
+                                                    meta.push(format!("Note: {note}"));
+                                                }
+                                                (summary, details, meta)
+                                            }
+                                            DosageHistoryEntry::Gel { dose, unit, pumps, note, .. } => {
+                                                let summary = "Gel · Estradiol".to_string();
+                                                let mut details = vec![format!("{} {}", fmt_decimal(*dose, 3), unit.symbol())];
+                                                if let Some(pumps) = pumps {
+                                                    details.push(format!("{} pumps", fmt_decimal(*pumps, 1)));
+                                                }
+                                                let mut meta = Vec::new();
+                                                if let Some(note) = note {
+                                                    meta.push(format!("Note: {note}"));
+                                                }
+                                                (summary, details, meta)
+                                            }
+                                            DosageHistoryEntry::Medication { dose, unit, note, .. } => {
+                                                let data_value = store.data.get();
+                                                let summary = match entry_medication(&data_value, &entry) {
+                                                    Some(med) => format!("{} · {}", med.class.label(), med.name),
+                                                    None => entry_medication_name(&data_value, &entry),
+                                                };
+                                                let details = vec![format!("{} {}", fmt_decimal(*dose, 3), unit.symbol())];
+                                                let mut meta = Vec::new();
+                                                if let Some(note) = note {
+                                                    meta.push(format!("Note: {note}"));
+                                                }
+                                                (summary, details, meta)
+                                            }
                                         };
                                         let detail_lines = StoredValue::new(details.clone());
                                         let meta_lines = StoredValue::new(meta.clone());
@@ -2122,7 +2250,9 @@ pub fn ViewPage() -> impl IntoView {
                         <div class="inline-equal">
                             <label>
                                 {move || {
-                                    if editing_med_type.get() != "injectableEstradiol" {
+                                    if editing_med_type.get() == "patch" {
+                                        "Strength (µg/day)"
+                                    } else if editing_med_type.get() != "injectableEstradiol" {
                                         "Dose"
                                     } else if editing_dose_in_iu.get() {
                                         "Dose (IU)"
@@ -2137,13 +2267,13 @@ pub fn ViewPage() -> impl IntoView {
                                     prop:value=move || editing_dose.get()
                                 />
                             </label>
-                            <Show when=move || editing_med_type.get() != "injectableEstradiol">
+                            <Show when=move || !is_injectable() && !is_patch()>
                                 <label>
                                     "Unit"
                                     <select
                                         on:change=move |ev| {
                                             let value = event_target_value(&ev);
-                                            if parse_hormone_unit(&value) != Some(HormoneUnits::Mg) {
+                                            if HormoneUnits::from_symbol(&value) != Some(HormoneUnits::Mg) {
                                                 editing_dose_in_iu.set(false);
                                             }
                                             editing_unit.set(value);
@@ -2225,6 +2355,53 @@ pub fn ViewPage() -> impl IntoView {
                                 }}
                             </p>
                         </Show>
+                        <Show when=is_patch>
+                            <div class="inline-equal">
+                                <label>
+                                    "Patches"
+                                    <input
+                                        type="text"
+                                        min="1"
+                                        step="any"
+                                        on:input=move |ev| editing_pill_qty.set(event_target_value(&ev))
+                                        prop:value=move || editing_pill_qty.get()
+                                    />
+                                </label>
+                                <label>
+                                    "Site"
+                                    <select
+                                        on:change=move |ev| editing_patch_site.set(event_target_value(&ev))
+                                        prop:value=move || editing_patch_site.get()
+                                    >
+                                        <option value="">"Select site"</option>
+                                        {PatchSites::ALL
+                                            .iter()
+                                            .map(|site| {
+                                                let label = site.label();
+                                                view! { <option value=label>{label}</option> }
+                                            })
+                                            .collect_view()}
+                                    </select>
+                                </label>
+                            </div>
+                            <label>"Removed at (optional)"</label>
+                            <input
+                                type="datetime-local"
+                                on:input=move |ev| editing_removed_at.set(event_target_value(&ev))
+                                prop:value=move || editing_removed_at.get()
+                            />
+                        </Show>
+                        <Show when=is_gel>
+                            <label>
+                                "Pumps (optional)"
+                                <input
+                                    type="text"
+                                    step="any"
+                                    on:input=move |ev| editing_pill_qty.set(event_target_value(&ev))
+                                    prop:value=move || editing_pill_qty.get()
+                                />
+                            </label>
+                        </Show>
                         <label>"Note (optional)"</label>
                         <textarea
                             rows="3"
@@ -2498,7 +2675,7 @@ pub fn ViewPage() -> impl IntoView {
                                     on:change=move |ev| edit_blood_e2_unit.set(event_target_value(&ev))
                                     prop:value=move || edit_blood_e2_unit.get()
                                 >
-                                    {hormone_unit_labels()
+                                    {analyte_unit_labels(BloodAnalyte::Estradiol)
                                         .into_iter()
                                         .map(|label| {
                                             let display = label.clone();
@@ -2545,7 +2722,7 @@ pub fn ViewPage() -> impl IntoView {
                                     on:change=move |ev| edit_blood_t_unit.set(event_target_value(&ev))
                                     prop:value=move || edit_blood_t_unit.get()
                                 >
-                                    {hormone_unit_labels()
+                                    {analyte_unit_labels(BloodAnalyte::Testosterone)
                                         .into_iter()
                                         .map(|label| {
                                             let display = label.clone();
@@ -2571,7 +2748,7 @@ pub fn ViewPage() -> impl IntoView {
                                     on:change=move |ev| edit_blood_prog_unit.set(event_target_value(&ev))
                                     prop:value=move || edit_blood_prog_unit.get()
                                 >
-                                    {hormone_unit_labels()
+                                    {analyte_unit_labels(BloodAnalyte::Progesterone)
                                         .into_iter()
                                         .map(|label| {
                                             let display = label.clone();
@@ -2597,7 +2774,7 @@ pub fn ViewPage() -> impl IntoView {
                                     on:change=move |ev| edit_blood_fsh_unit.set(event_target_value(&ev))
                                     prop:value=move || edit_blood_fsh_unit.get()
                                 >
-                                    {hormone_unit_labels()
+                                    {analyte_unit_labels(BloodAnalyte::Fsh)
                                         .into_iter()
                                         .map(|label| {
                                             let display = label.clone();
@@ -2623,7 +2800,7 @@ pub fn ViewPage() -> impl IntoView {
                                     on:change=move |ev| edit_blood_lh_unit.set(event_target_value(&ev))
                                     prop:value=move || edit_blood_lh_unit.get()
                                 >
-                                    {hormone_unit_labels()
+                                    {analyte_unit_labels(BloodAnalyte::Lh)
                                         .into_iter()
                                         .map(|label| {
                                             let display = label.clone();
@@ -2649,7 +2826,7 @@ pub fn ViewPage() -> impl IntoView {
                                     on:change=move |ev| edit_blood_prolactin_unit.set(event_target_value(&ev))
                                     prop:value=move || edit_blood_prolactin_unit.get()
                                 >
-                                    {hormone_unit_labels()
+                                    {analyte_unit_labels(BloodAnalyte::Prolactin)
                                         .into_iter()
                                         .map(|label| {
                                             let display = label.clone();
@@ -2675,7 +2852,7 @@ pub fn ViewPage() -> impl IntoView {
                                     on:change=move |ev| edit_blood_shbg_unit.set(event_target_value(&ev))
                                     prop:value=move || edit_blood_shbg_unit.get()
                                 >
-                                    {hormone_unit_labels()
+                                    {analyte_unit_labels(BloodAnalyte::Shbg)
                                         .into_iter()
                                         .map(|label| {
                                             let display = label.clone();
@@ -2695,8 +2872,26 @@ pub fn ViewPage() -> impl IntoView {
                                     prop:value=move || edit_blood_fai.get()
                                 />
                             </label>
-                            <div></div>
+                            <label>
+                                "Albumin (g/dL)"
+                                <input
+                                    type="text"
+                                    step="any"
+                                    on:input=move |ev| edit_blood_albumin.set(event_target_value(&ev))
+                                    prop:value=move || edit_blood_albumin.get()
+                                />
+                            </label>
                         </div>
+                        <label>"Other results"</label>
+                        {lab_results_editor(edit_blood_results)}
+                        <label>
+                            "Lab"
+                            <input
+                                type="text"
+                                on:input=move |ev| edit_blood_lab.set(event_target_value(&ev))
+                                prop:value=move || edit_blood_lab.get()
+                            />
+                        </label>
                         <label>"Notes"</label>
                         <textarea
                             rows="3"
@@ -2798,49 +2993,49 @@ pub fn ViewPage() -> impl IntoView {
                                     let prolactin_value = parse_optional_num(&edit_blood_prolactin.get());
                                     let shbg_value = parse_optional_num(&edit_blood_shbg.get());
                                     let fai_value = parse_optional_num(&edit_blood_fai.get());
+                                    let albumin_value =
+                                        parse_optional_num(&edit_blood_albumin.get()).filter(|v| *v > 0.0);
                                     let e2_default = store
                                         .settings
                                         .get()
                                         .displayEstradiolUnit
                                         .unwrap_or(HormoneUnits::E2PmolL);
                                     let e2_unit =
-                                        parse_hormone_unit(&edit_blood_e2_unit.get())
+                                        HormoneUnits::from_symbol(&edit_blood_e2_unit.get())
                                             .unwrap_or(e2_default);
                                     let estrannaise_unit =
-                                        parse_hormone_unit(&edit_blood_estrannaise_unit.get())
+                                        HormoneUnits::from_symbol(&edit_blood_estrannaise_unit.get())
                                             .unwrap_or(HormoneUnits::E2PgMl);
-                                    let t_unit = parse_hormone_unit(&edit_blood_t_unit.get())
+                                    let t_unit = HormoneUnits::from_symbol(&edit_blood_t_unit.get())
                                         .unwrap_or(HormoneUnits::TNmolL);
                                     let prog_unit =
-                                        parse_hormone_unit(&edit_blood_prog_unit.get())
+                                        HormoneUnits::from_symbol(&edit_blood_prog_unit.get())
                                             .unwrap_or(HormoneUnits::TNmolL);
                                     let fsh_unit =
-                                        parse_hormone_unit(&edit_blood_fsh_unit.get())
+                                        HormoneUnits::from_symbol(&edit_blood_fsh_unit.get())
                                             .unwrap_or(HormoneUnits::UL);
                                     let lh_unit =
-                                        parse_hormone_unit(&edit_blood_lh_unit.get())
+                                        HormoneUnits::from_symbol(&edit_blood_lh_unit.get())
                                             .unwrap_or(HormoneUnits::UL);
                                     let prolactin_unit =
-                                        parse_hormone_unit(&edit_blood_prolactin_unit.get())
+                                        HormoneUnits::from_symbol(&edit_blood_prolactin_unit.get())
                                             .unwrap_or(HormoneUnits::MIuL);
                                     let shbg_unit =
-                                        parse_hormone_unit(&edit_blood_shbg_unit.get())
+                                        HormoneUnits::from_symbol(&edit_blood_shbg_unit.get())
                                             .unwrap_or(HormoneUnits::TNmolL);
                                     let notes = edit_blood_notes.get();
+                                    let lab = edit_blood_lab.get().trim().to_string();
+                                    let lab_results = edit_blood_results.get();
                                     let updated_pdf_files = edit_blood_pdf_files.get();
                                     let measured_e2 = e2_value.map(|value| {
-                                        if e2_unit == HormoneUnits::E2PmolL {
-                                            value / 3.671
-                                        } else {
-                                            value
-                                        }
+                                        convert_estradiol_to_display(value, &e2_unit, &HormoneUnits::E2PgMl)
                                     });
                                     let predicted_input = estrannaise_value.map(|value| {
-                                        if estrannaise_unit == HormoneUnits::E2PmolL {
-                                            value / 3.671
-                                        } else {
-                                            value
-                                        }
+                                        convert_estradiol_to_display(
+                                            value,
+                                            &estrannaise_unit,
+                                            &HormoneUnits::E2PgMl,
+                                        )
                                     });
                                     let predicted_model = predict_e2_pg_ml(&store.data.get(), snapped_date);
                                     let fudge_factor =
@@ -2861,21 +3056,15 @@ pub fn ViewPage() -> impl IntoView {
                                                     .cloned()
                                                     .collect();
                                                 entry.date = snapped_date;
-                                                entry.estradiolLevel = e2_value;
-                                                entry.testLevel = t_value;
-                                                entry.estradiolUnit = Some(e2_unit.clone());
-                                                entry.testUnit = Some(t_unit.clone());
-                                                entry.progesteroneLevel = prog_value;
-                                                entry.progesteroneUnit = Some(prog_unit.clone());
-                                                entry.fshLevel = fsh_value;
-                                                entry.fshUnit = Some(fsh_unit.clone());
-                                                entry.lhLevel = lh_value;
-                                                entry.lhUnit = Some(lh_unit.clone());
-                                                entry.prolactinLevel = prolactin_value;
-                                                entry.prolactinUnit = Some(prolactin_unit.clone());
-                                                entry.shbgLevel = shbg_value;
-                                                entry.shbgUnit = Some(shbg_unit.clone());
+                                                entry.estradiol = e2_value.map(|value| Quantity::new(value, e2_unit.clone()));
+                                                entry.testosterone = t_value.map(|value| Quantity::new(value, t_unit.clone()));
+                                                entry.progesterone = prog_value.map(|value| Quantity::new(value, prog_unit.clone()));
+                                                entry.fsh = fsh_value.map(|value| Quantity::new(value, fsh_unit.clone()));
+                                                entry.lh = lh_value.map(|value| Quantity::new(value, lh_unit.clone()));
+                                                entry.prolactin = prolactin_value.map(|value| Quantity::new(value, prolactin_unit.clone()));
+                                                entry.shbg = shbg_value.map(|value| Quantity::new(value, shbg_unit.clone()));
                                                 entry.freeAndrogenIndex = fai_value;
+                                                entry.albuminLevel = albumin_value;
                                                 entry.estrannaiseNumber = predicted_input;
                                                 entry.fudgeFactor = fudge_factor;
                                                 entry.notes = if notes.trim().is_empty() {
@@ -2883,6 +3072,8 @@ pub fn ViewPage() -> impl IntoView {
                                                 } else {
                                                     Some(notes.clone())
                                                 };
+                                                entry.lab = Some(lab.clone()).filter(|value| !value.is_empty());
+                                                entry.labResults = lab_results.clone();
                                                 entry.pdfFiles = if updated_pdf_files.is_empty() {
                                                     None
                                                 } else {
@@ -1,7 +1,8 @@
 use js_sys::Date;
 use wasm_bindgen::JsValue;
 
-use crate::utils::{hormone_unit_label, parse_decimal};
+use crate::utils::{fmt_blood_value, parse_decimal};
+use hrt_shared::quantity::{BloodAnalyte, Quantity};
 use hrt_shared::types::{
     DosageHistoryEntry, DosagePhoto, HormoneUnits, InjectionSites, LengthUnit, Measurement,
     ProgesteroneRoutes, SyringeKinds, WeightUnit,
@@ -134,20 +135,30 @@ pub(super) fn syringe_kind_label(kind: &SyringeKinds) -> &'static str {
 }
 
 pub(super) fn hormone_unit_labels() -> Vec<String> {
-    [
-        HormoneUnits::E2PgMl,
-        HormoneUnits::E2PmolL,
-        HormoneUnits::TNgDl,
-        HormoneUnits::TNmolL,
-        HormoneUnits::Mg,
-        HormoneUnits::NgMl,
-        HormoneUnits::MIuMl,
-        HormoneUnits::MIuL,
-        HormoneUnits::UL,
-    ]
-    .iter()
-    .map(|unit| hormone_unit_label(unit).to_string())
-    .collect()
+    HormoneUnits::ALL
+        .iter()
+        .map(|unit| unit.symbol().to_string())
+        .collect()
+}
+
+pub(super) fn analyte_unit_labels(analyte: BloodAnalyte) -> Vec<String> {
+    HormoneUnits::ALL
+        .iter()
+        .filter(|unit| analyte.supports(unit))
+        .map(|unit| unit.symbol().to_string())
+        .collect()
+}
+
+/// Form text for a stored level and its unit; empty with `unit` selected
+/// when nothing was recorded.
+pub(super) fn level_form_fields(level: &Option<Quantity>, unit: &HormoneUnits) -> (String, String) {
+    match level {
+        Some(level) => (
+            fmt_blood_value(level.value),
+            level.unit.symbol().to_string(),
+        ),
+        None => (String::new(), unit.symbol().to_string()),
+    }
 }
 
 pub(super) fn progesterone_route_label(route: &ProgesteroneRoutes) -> &'static str {
@@ -203,7 +214,10 @@ pub(super) fn dosage_entry_date(entry: &DosageHistoryEntry) -> i64 {
         DosageHistoryEntry::InjectableEstradiol { date, .. }
         | DosageHistoryEntry::OralEstradiol { date, .. }
         | DosageHistoryEntry::Antiandrogen { date, .. }
-        | DosageHistoryEntry::Progesterone { date, .. } => *date,
+        | DosageHistoryEntry::Progesterone { date, .. }
+        | DosageHistoryEntry::Patch { date, .. }
+        | DosageHistoryEntry::Gel { date, .. }
+        | DosageHistoryEntry::Medication { date, .. } => *date,
     }
 }
 
@@ -212,7 +226,10 @@ pub(super) fn dosage_entry_matches_key(entry: &DosageHistoryEntry, key: &str) ->
         DosageHistoryEntry::InjectableEstradiol { date, id, .. }
         | DosageHistoryEntry::OralEstradiol { date, id, .. }
         | DosageHistoryEntry::Antiandrogen { date, id, .. }
-        | DosageHistoryEntry::Progesterone { date, id, .. } => id
+        | DosageHistoryEntry::Progesterone { date, id, .. }
+        | DosageHistoryEntry::Patch { date, id, .. }
+        | DosageHistoryEntry::Gel { date, id, .. }
+        | DosageHistoryEntry::Medication { date, id, .. } => id
             .as_ref()
             .map(|value| value == key)
             .unwrap_or_else(|| date.to_string() == key),
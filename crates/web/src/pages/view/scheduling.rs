@@ -1,8 +1,14 @@
 use js_sys::Date;
 
-use super::types::{NextDoseCandidate, RegimenKey, DAY_MS};
+use super::types::{NextDoseCandidate, DAY_MS};
 use crate::utils::format_injectable_dose;
-use hrt_shared::types::{DosageHistoryEntry, HrtData, InjectableEstradiols};
+use hrt_shared::schedules::{
+    current_injectable, current_regimens, last_regimen_dose, next_regimen_dose,
+};
+use hrt_shared::transdermal::{gel_dose_mg, next_patch_site, patch_total_strength};
+use hrt_shared::types::{
+    DosageHistoryEntry, HrtData, InjectableEstradiols, Regimen, RegimenSchedule,
+};
 
 fn injectable_model_id(kind: &InjectableEstradiols) -> Option<i64> {
     match kind {
@@ -11,85 +17,38 @@ fn injectable_model_id(kind: &InjectableEstradiols) -> Option<i64> {
         InjectableEstradiols::Enanthate => Some(2),
         InjectableEstradiols::Cypionate => Some(3),
         InjectableEstradiols::Undecylate => Some(4),
+        // estrannai.se has no PEP model; PEP is charted locally only.
         InjectableEstradiols::PolyestradiolPhosphate => None,
     }
 }
 
-fn get_last_dose_date_for_type(data: &HrtData, med_type: RegimenKey) -> Option<i64> {
-    let mut dates = Vec::new();
-    for entry in &data.dosageHistory {
-        match (med_type, entry) {
-            (
-                RegimenKey::InjectableEstradiol,
-                DosageHistoryEntry::InjectableEstradiol {
-                    date, bonusDose, ..
-                },
-            ) => {
-                if !bonusDose.unwrap_or(false) {
-                    dates.push(*date);
-                }
-            }
-            (RegimenKey::OralEstradiol, DosageHistoryEntry::OralEstradiol { date, .. })
-            | (RegimenKey::Antiandrogen, DosageHistoryEntry::Antiandrogen { date, .. })
-            | (RegimenKey::Progesterone, DosageHistoryEntry::Progesterone { date, .. }) => {
-                dates.push(*date);
-            }
-            _ => {}
-        }
+fn get_next_scheduled_date_for(data: &HrtData, regimen: &Regimen) -> i64 {
+    if regimen.recurrence.is_some() {
+        return next_regimen_dose(data, regimen, regimen.startDate)
+            .unwrap_or_else(|| Date::now() as i64);
     }
-    dates.into_iter().max()
-}
-
-fn get_next_scheduled_date_for(data: &HrtData, med_type: RegimenKey) -> Option<i64> {
-    let now = Date::now() as i64;
-    match med_type {
-        RegimenKey::InjectableEstradiol => {
-            let cfg = data.injectableEstradiol.as_ref()?;
-            let last = get_last_dose_date_for_type(data, RegimenKey::InjectableEstradiol);
-            if let (Some(next), Some(last)) = (cfg.nextDoseDate, last) {
-                if next > last {
-                    return Some(next);
-                }
+    let schedule = &regimen.schedule;
+    let last = last_regimen_dose(data, schedule);
+    // Injections and patches keep a manually moved next date.
+    if matches!(
+        schedule,
+        RegimenSchedule::InjectableEstradiol(_) | RegimenSchedule::Patch(_)
+    ) {
+        if let (Some(next), Some(last)) = (schedule.next_dose_date(), last) {
+            if next > last {
+                return next;
             }
-            if let Some(last) = last {
-                return Some(last + (cfg.frequency * DAY_MS as f64) as i64);
-            }
-            Some(now)
-        }
-        RegimenKey::OralEstradiol => {
-            let cfg = data.oralEstradiol.as_ref()?;
-            let last = get_last_dose_date_for_type(data, RegimenKey::OralEstradiol);
-            if let Some(last) = last {
-                return Some(last + (cfg.frequency * DAY_MS as f64) as i64);
-            }
-            Some(now)
-        }
-        RegimenKey::Antiandrogen => {
-            let cfg = data.antiandrogen.as_ref()?;
-            let last = get_last_dose_date_for_type(data, RegimenKey::Antiandrogen);
-            if let Some(last) = last {
-                return Some(last + (cfg.frequency * DAY_MS as f64) as i64);
-            }
-            Some(now)
-        }
-        RegimenKey::Progesterone => {
-            let cfg = data.progesterone.as_ref()?;
-            let last = get_last_dose_date_for_type(data, RegimenKey::Progesterone);
-            if let Some(last) = last {
-                return Some(last + (cfg.frequency * DAY_MS as f64) as i64);
-            }
-            Some(now)
         }
     }
+    match last {
+        Some(last) => last + (schedule.frequency() * DAY_MS as f64) as i64,
+        None => Date::now() as i64,
+    }
 }
 
-pub(super) fn get_next_scheduled_candidate(
-    data: &HrtData,
-    use_iu: bool,
-) -> Option<NextDoseCandidate> {
-    let mut options: Vec<(RegimenKey, i64, String)> = Vec::new();
-    if let Some(cfg) = data.injectableEstradiol.as_ref() {
-        if let Some(date) = get_next_scheduled_date_for(data, RegimenKey::InjectableEstradiol) {
+fn scheduled_dose_label(data: &HrtData, schedule: &RegimenSchedule, use_iu: bool) -> String {
+    match schedule {
+        RegimenSchedule::InjectableEstradiol(cfg) => {
             let dose_label = format_injectable_dose(
                 data,
                 cfg.dose,
@@ -98,49 +57,46 @@ pub(super) fn get_next_scheduled_candidate(
                 cfg.vialId.as_ref(),
                 use_iu,
             );
-            options.push((
-                RegimenKey::InjectableEstradiol,
-                date,
-                format!("Injection: {:?}, {dose_label}", cfg.kind),
-            ));
-        }
-    }
-    if let Some(cfg) = data.oralEstradiol.as_ref() {
-        if let Some(date) = get_next_scheduled_date_for(data, RegimenKey::OralEstradiol) {
-            options.push((
-                RegimenKey::OralEstradiol,
-                date,
-                format!(
-                    "Oral Estradiol: {:?}, {:.2} {:?}",
-                    cfg.kind, cfg.dose, cfg.unit
-                ),
-            ));
-        }
-    }
-    if let Some(cfg) = data.antiandrogen.as_ref() {
-        if let Some(date) = get_next_scheduled_date_for(data, RegimenKey::Antiandrogen) {
-            options.push((
-                RegimenKey::Antiandrogen,
-                date,
-                format!(
-                    "Antiandrogen: {:?}, {:.2} {:?}",
-                    cfg.kind, cfg.dose, cfg.unit
-                ),
-            ));
-        }
-    }
-    if let Some(cfg) = data.progesterone.as_ref() {
-        if let Some(date) = get_next_scheduled_date_for(data, RegimenKey::Progesterone) {
-            options.push((
-                RegimenKey::Progesterone,
-                date,
-                format!(
-                    "Progesterone ({:?}): {:?}, {:.2} {:?}",
-                    cfg.route, cfg.kind, cfg.dose, cfg.unit
-                ),
-            ));
+            format!("Injection: {:?}, {dose_label}", cfg.kind)
         }
+        RegimenSchedule::OralEstradiol(cfg) => format!(
+            "Oral Estradiol: {:?}, {:.2} {:?}",
+            cfg.kind, cfg.dose, cfg.unit
+        ),
+        RegimenSchedule::Antiandrogen(cfg) => format!(
+            "Antiandrogen: {:?}, {:.2} {:?}",
+            cfg.kind, cfg.dose, cfg.unit
+        ),
+        RegimenSchedule::Progesterone(cfg) => format!(
+            "Progesterone ({:?}): {:?}, {:.2} {:?}",
+            cfg.route, cfg.kind, cfg.dose, cfg.unit
+        ),
+        RegimenSchedule::Patch(cfg) => format!(
+            "Patch: {:.0} µg/day, {}",
+            patch_total_strength(cfg.strength, cfg.patchQuantity),
+            next_patch_site(data).label()
+        ),
+        RegimenSchedule::Gel(cfg) => format!(
+            "Gel: {:.2} {:?}",
+            gel_dose_mg(cfg.dose, cfg.pumps, cfg.mgPerPump),
+            cfg.unit
+        ),
     }
+}
+
+pub(super) fn get_next_scheduled_candidate(
+    data: &HrtData,
+    use_iu: bool,
+) -> Option<NextDoseCandidate> {
+    let mut options: Vec<(String, i64, String)> = current_regimens(data)
+        .map(|regimen| {
+            (
+                regimen.id.clone(),
+                get_next_scheduled_date_for(data, regimen),
+                scheduled_dose_label(data, &regimen.schedule, use_iu),
+            )
+        })
+        .collect();
     if options.is_empty() {
         return None;
     }
@@ -150,19 +106,19 @@ pub(super) fn get_next_scheduled_candidate(
         .collect();
     if !future.is_empty() {
         future.sort_by_key(|(_, date, _)| *date);
-        let (med_type, _, label) = future[0].clone();
-        return Some(NextDoseCandidate { med_type, label });
+        let (regimen_id, _, label) = future[0].clone();
+        return Some(NextDoseCandidate { regimen_id, label });
     }
-    options.sort_by(|a, b| b.1.cmp(&a.1));
-    let (med_type, _, label) = options[0].clone();
-    Some(NextDoseCandidate { med_type, label })
+    options.sort_by_key(|option| std::cmp::Reverse(option.1));
+    let (regimen_id, _, label) = options[0].clone();
+    Some(NextDoseCandidate { regimen_id, label })
 }
 
 pub(super) fn generate_estrannaise_url(
     data: &HrtData,
     fudge_factor: Option<f64>,
 ) -> Option<String> {
-    let regimen = data.injectableEstradiol.as_ref();
+    let regimen = current_injectable(data);
     let mut historical: Vec<(i64, InjectableEstradiols, f64)> = data
         .dosageHistory
         .iter()
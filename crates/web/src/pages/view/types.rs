@@ -2,17 +2,9 @@ use serde::Deserialize;
 
 use hrt_shared::types::{InjectionSites, SyringeKinds};
 
-#[derive(Clone, Copy, PartialEq, Eq)]
-pub(super) enum RegimenKey {
-    InjectableEstradiol,
-    OralEstradiol,
-    Antiandrogen,
-    Progesterone,
-}
-
 #[derive(Clone, PartialEq)]
 pub(super) struct NextDoseCandidate {
-    pub(super) med_type: RegimenKey,
+    pub(super) regimen_id: String,
     pub(super) label: String,
 }
 
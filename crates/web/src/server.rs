@@ -1,5 +1,5 @@
 use axum::extract::OriginalUri;
-use axum::routing::{get, post};
+use axum::routing::{get, post, put};
 use axum::{
     body::Body,
     http::{Request, Response, StatusCode},
@@ -57,8 +57,14 @@ fn api_proxy_router(backend_addr: String) -> Router {
         .route("/data", get(proxy_handler).post(proxy_handler))
         .route("/settings", get(proxy_handler).post(proxy_handler))
         .route("/convert", post(proxy_handler))
+        .route("/convert/batch", post(proxy_handler))
+        .route("/convert/units", get(proxy_handler))
+        .route("/regimen/optimize", post(proxy_handler))
         .route("/ics", get(proxy_handler))
         .route("/ics/:secret", get(proxy_handler))
+        .route("/todos", get(proxy_handler))
+        .route("/todos/:secret", get(proxy_handler))
+        .route("/todos/:secret/:resource", put(proxy_handler))
         .route("/dosage-photo/:entry_id", post(proxy_handler))
         .route(
             "/dosage-photo/:entry_id/:filename",
@@ -2,6 +2,8 @@ use gloo_net::http::Request;
 use gloo_timers::callback::Timeout;
 use gloo_timers::future::TimeoutFuture;
 use hrt_shared::logic::{backfill_scheduled_doses, migrate_blood_tests_fudge_factor};
+use hrt_shared::plans::apply_due_plan_steps;
+use hrt_shared::schedules::migrate_schedules_to_regimens;
 use hrt_shared::types::{HormoneUnits, HrtData, Settings};
 use leptos::*;
 use serde_json::Value;
@@ -53,6 +55,8 @@ impl AppStore {
                 Ok(resp) => match resp.json::<HrtData>().await {
                     Ok(mut loaded) => {
                         migrate_blood_tests_fudge_factor(&mut loaded);
+                        migrate_schedules_to_regimens(&mut loaded);
+                        apply_due_plan_steps(&mut loaded, js_sys::Date::now() as i64);
                         backfill_scheduled_doses(&mut loaded);
                         ensure_measurement_ids(&mut loaded);
                         data.set(loaded);
@@ -112,6 +116,9 @@ impl AppStore {
             return;
         }
 
+        self.data.update(|data| {
+            apply_due_plan_steps(data, js_sys::Date::now() as i64);
+        });
         let settings_value = self.settings.get();
         if settings_value.enableAutoBackfill {
             self.data.update(backfill_scheduled_doses);
@@ -1,5 +1,13 @@
 use chrono::{Local, TimeZone};
-use hrt_shared::types::{HormoneUnits, HrtData, LengthUnit};
+use hrt_shared::free_hormones::{calculate_levels, DEFAULT_ALBUMIN_G_DL};
+use hrt_shared::inventory::SYRINGE_UNITS_PER_ML;
+use hrt_shared::lab_results::LabResult;
+use hrt_shared::quantity::Quantity;
+use hrt_shared::ranges::{LevelAssessment, RangeFlag, ReferenceRange};
+use hrt_shared::transdermal::{gel_dose_mg, patch_total_strength};
+use hrt_shared::types::{
+    BloodTest, Hormone, HormoneUnits, HrtData, LengthUnit, Regimen, RegimenSchedule,
+};
 use js_sys::Date;
 
 pub fn parse_date_or_now(value: &str) -> i64 {
@@ -21,19 +29,13 @@ pub fn parse_date_or_now(value: &str) -> i64 {
     date.get_time() as i64
 }
 
-pub fn parse_hormone_unit(value: &str) -> Option<HormoneUnits> {
-    match value {
-        "pg/mL" => Some(HormoneUnits::E2PgMl),
-        "pmol/L" => Some(HormoneUnits::E2PmolL),
-        "ng/dL" => Some(HormoneUnits::TNgDl),
-        "nmol/L" => Some(HormoneUnits::TNmolL),
-        "mg" => Some(HormoneUnits::Mg),
-        "ng/mL" => Some(HormoneUnits::NgMl),
-        "mIU/mL" => Some(HormoneUnits::MIuMl),
-        "mIU/L" => Some(HormoneUnits::MIuL),
-        "U/L" => Some(HormoneUnits::UL),
-        _ => None,
-    }
+/// The browser's IANA time zone, e.g. "Europe/Berlin".
+pub fn browser_time_zone() -> Option<String> {
+    let options = js_sys::Intl::DateTimeFormat::new(&js_sys::Array::new(), &js_sys::Object::new())
+        .resolved_options();
+    js_sys::Reflect::get(&options, &"timeZone".into())
+        .ok()?
+        .as_string()
 }
 
 pub fn parse_length_unit(value: &str) -> Option<LengthUnit> {
@@ -108,18 +110,11 @@ pub fn parse_decimal_or_nan(value: &str) -> f64 {
     parse_decimal(value).unwrap_or(f64::NAN)
 }
 
-pub fn hormone_unit_label(unit: &HormoneUnits) -> &'static str {
-    match unit {
-        HormoneUnits::E2PmolL => "pmol/L",
-        HormoneUnits::E2PgMl => "pg/mL",
-        HormoneUnits::TNgDl => "ng/dL",
-        HormoneUnits::TNmolL => "nmol/L",
-        HormoneUnits::Mg => "mg",
-        HormoneUnits::NgMl => "ng/mL",
-        HormoneUnits::MIuMl => "mIU/mL",
-        HormoneUnits::MIuL => "mIU/L",
-        HormoneUnits::UL => "U/L",
-    }
+// Incompatible units (e.g. a level mistakenly saved as mg) plot unconverted.
+fn convert_level(value: f64, hormone: Hormone, unit: &HormoneUnits, to: HormoneUnits) -> f64 {
+    Quantity::new(value, unit.clone())
+        .value_in(hormone, to)
+        .unwrap_or(value)
 }
 
 pub fn convert_estradiol_to_display(
@@ -127,57 +122,31 @@ pub fn convert_estradiol_to_display(
     unit: &HormoneUnits,
     display_unit: &HormoneUnits,
 ) -> f64 {
-    if display_unit == &HormoneUnits::E2PmolL {
-        if unit == &HormoneUnits::E2PmolL {
-            value
-        } else {
-            value * 3.6713
-        }
-    } else if unit == &HormoneUnits::E2PmolL {
-        value / 3.6713
-    } else {
-        value
-    }
+    convert_level(value, Hormone::Estradiol, unit, display_unit.clone())
 }
 
 pub fn estradiol_conversion_factor(display_unit: &HormoneUnits) -> f64 {
-    if display_unit == &HormoneUnits::E2PmolL {
-        3.6713
-    } else {
-        1.0
-    }
+    convert_estradiol_to_display(1.0, &HormoneUnits::E2PgMl, display_unit)
 }
 
 pub fn convert_testosterone_to_ng_dl(value: f64, unit: &HormoneUnits) -> f64 {
-    if unit == &HormoneUnits::TNmolL {
-        value * 28.818
-    } else {
-        value
-    }
+    convert_level(value, Hormone::Testosterone, unit, HormoneUnits::TNgDl)
 }
 
 pub fn convert_fsh_to_miu_ml(value: f64, unit: &HormoneUnits) -> f64 {
-    match unit {
-        HormoneUnits::MIuL => value / 1000.0,
-        HormoneUnits::UL => value,
-        _ => value,
-    }
+    convert_level(value, Hormone::FollicleStimulatingHormone, unit, HormoneUnits::MIuMl)
 }
 
 pub fn convert_lh_to_miu_ml(value: f64, unit: &HormoneUnits) -> f64 {
-    match unit {
-        HormoneUnits::MIuL => value / 1000.0,
-        HormoneUnits::UL => value,
-        _ => value,
-    }
+    convert_level(value, Hormone::LuteinisingHormone, unit, HormoneUnits::MIuMl)
+}
+
+pub fn convert_prolactin_to_miu_l(value: f64, unit: &HormoneUnits) -> f64 {
+    convert_level(value, Hormone::Prolactin, unit, HormoneUnits::MIuL)
 }
 
 pub fn convert_progesterone_to_ng_ml(value: f64, unit: &HormoneUnits) -> f64 {
-    if unit == &HormoneUnits::TNmolL {
-        value * 0.31
-    } else {
-        value
-    }
+    convert_level(value, Hormone::Progesterone, unit, HormoneUnits::NgMl)
 }
 
 pub fn fmt_decimal(value: f64, max_decimals: usize) -> String {
@@ -195,6 +164,116 @@ pub fn fmt_blood_value(value: f64) -> String {
     fmt_decimal(value, 4)
 }
 
+pub fn fmt_reference_range(range: &ReferenceRange) -> String {
+    let unit = range.unit.symbol();
+    match (range.low, range.high) {
+        (Some(low), Some(high)) => {
+            format!("{}–{} {}", fmt_blood_value(low), fmt_blood_value(high), unit)
+        }
+        (Some(low), None) => format!("≥ {} {}", fmt_blood_value(low), unit),
+        (None, Some(high)) => format!("≤ {} {}", fmt_blood_value(high), unit),
+        (None, None) => "-".to_string(),
+    }
+}
+
+/// Describes the out-of-range flags of one level, e.g. "T above target
+/// (≤ 50 ng/dL)", or `None` when it is in range.
+pub fn range_flag_note(assessment: &LevelAssessment) -> Option<String> {
+    let label = assessment.analyte.label();
+    let mut parts = Vec::new();
+    if let Some(check) = assessment.target.as_ref() {
+        let side = match check.flag {
+            RangeFlag::Low => Some("below"),
+            RangeFlag::High => Some("above"),
+            RangeFlag::InRange => None,
+        };
+        if let Some(side) = side {
+            parts.push(format!(
+                "{} {} target ({})",
+                label,
+                side,
+                fmt_reference_range(&check.range)
+            ));
+        }
+    }
+    if let Some(check) = assessment.reference.as_ref() {
+        let side = match check.flag {
+            RangeFlag::Low => Some("low"),
+            RangeFlag::High => Some("high"),
+            RangeFlag::InRange => None,
+        };
+        if let Some(side) = side {
+            parts.push(format!(
+                "{} {} for lab ({})",
+                label,
+                side,
+                fmt_reference_range(&check.range)
+            ));
+        }
+    }
+    (!parts.is_empty()).then(|| parts.join("; "))
+}
+
+/// e.g. "Potassium: 5.6 mmol/L (3.5–5.1, high)".
+pub fn fmt_lab_result(result: &LabResult) -> String {
+    let mut text = format!("{}: {}", result.name, fmt_blood_value(result.value));
+    if let Some(unit) = result.unit.as_deref().filter(|unit| !unit.is_empty()) {
+        text.push(' ');
+        text.push_str(unit);
+    }
+    let range = match (result.referenceLow, result.referenceHigh) {
+        (Some(low), Some(high)) => Some(format!("{}–{}", fmt_blood_value(low), fmt_blood_value(high))),
+        (Some(low), None) => Some(format!("≥ {}", fmt_blood_value(low))),
+        (None, Some(high)) => Some(format!("≤ {}", fmt_blood_value(high))),
+        (None, None) => None,
+    };
+    let flag = match result.effective_flag() {
+        Some(RangeFlag::Low) => Some("low"),
+        Some(RangeFlag::High) => Some("high"),
+        _ => None,
+    };
+    match (range, flag) {
+        (Some(range), Some(flag)) => text.push_str(&format!(" ({range}, {flag})")),
+        (Some(range), None) => text.push_str(&format!(" ({range})")),
+        (None, Some(flag)) => text.push_str(&format!(" ({flag})")),
+        (None, None) => {}
+    }
+    text
+}
+
+/// The FAI typed in for `test`, or the one calculated from T and SHBG.
+pub fn effective_fai(test: &BloodTest) -> Option<f64> {
+    test.freeAndrogenIndex
+        .filter(|fai| *fai > 0.0)
+        .or_else(|| calculate_levels(test).freeAndrogenIndex)
+}
+
+/// Calculated free hormone levels of `test` as display lines. The FAI is only
+/// listed when none was entered.
+pub fn calculated_level_notes(test: &BloodTest) -> Vec<String> {
+    let levels = calculate_levels(test);
+    let quantity = |q: &Quantity| format!("{} {}", fmt_decimal(q.value, 2), q.unit.symbol());
+    let mut notes = Vec::new();
+    if test.freeAndrogenIndex.filter(|fai| *fai > 0.0).is_none() {
+        if let Some(fai) = levels.freeAndrogenIndex {
+            notes.push(format!("FAI (calc): {}", fmt_decimal(fai, 2)));
+        }
+    }
+    if let Some(free_t) = levels.freeTestosterone.as_ref() {
+        notes.push(format!("Free T (calc): {}", quantity(free_t)));
+    }
+    if let Some(bio_t) = levels.bioavailableTestosterone.as_ref() {
+        notes.push(format!("Bioavailable T (calc): {}", quantity(bio_t)));
+    }
+    if let Some(free_e2) = levels.freeEstradiol.as_ref() {
+        notes.push(format!("Free E2 (calc): {}", quantity(free_e2)));
+    }
+    if levels.albuminAssumed && !notes.is_empty() {
+        notes.push(format!("Albumin assumed {DEFAULT_ALBUMIN_G_DL} g/dL"));
+    }
+    notes
+}
+
 fn vial_concentration_mg_ml(data: &HrtData, vial_id: &str) -> Option<f64> {
     data.vials
         .iter()
@@ -226,7 +305,7 @@ pub fn injectable_iu_from_dose(
     let conc = injectable_concentration_mg_ml(data, vial_id, schedule_vial_id);
     let conc = conc?;
     let ml = dose / conc;
-    let iu = ml * 100.0;
+    let iu = ml * SYRINGE_UNITS_PER_ML;
     if iu.is_finite() && iu > 0.0 {
         Some(iu)
     } else {
@@ -244,7 +323,7 @@ pub fn injectable_dose_from_iu(
         return None;
     }
     let conc = injectable_concentration_mg_ml(data, vial_id, schedule_vial_id)?;
-    let ml = iu / 100.0;
+    let ml = iu / SYRINGE_UNITS_PER_ML;
     let dose = ml * conc;
     if dose.is_finite() && dose > 0.0 {
         Some(dose)
@@ -265,12 +344,85 @@ pub fn format_injectable_dose(
         return "-".to_string();
     }
     if !use_iu || *unit != HormoneUnits::Mg {
-        return format!("{} {}", fmt_decimal(dose, 3), hormone_unit_label(unit));
+        return format!("{} {}", fmt_decimal(dose, 3), unit.symbol());
     }
     if let Some(iu) = injectable_iu_from_dose(data, dose, unit, vial_id, schedule_vial_id) {
         return format!("{} IU ({} mg)", iu.round() as i64, fmt_decimal(dose, 3));
     }
-    format!("{} {}", fmt_decimal(dose, 3), hormone_unit_label(unit))
+    format!("{} {}", fmt_decimal(dose, 3), unit.symbol())
+}
+
+/// Heading and dosing line for a regimen, as shown on the View and Stats pages.
+pub fn fmt_regimen(data: &HrtData, regimen: &Regimen, use_iu: bool) -> (&'static str, String) {
+    let cadence = |frequency: f64| match &regimen.recurrence {
+        Some(recurrence) => recurrence.describe(),
+        None => format!("every {:.1} days", frequency),
+    };
+    match &regimen.schedule {
+        RegimenSchedule::InjectableEstradiol(cfg) => {
+            let dose_label = format_injectable_dose(
+                data,
+                cfg.dose,
+                &cfg.unit,
+                cfg.vialId.as_ref(),
+                cfg.vialId.as_ref(),
+                use_iu,
+            );
+            (
+                "Injectable Estradiol",
+                format!("{:?}, {dose_label} {}", cfg.kind, cadence(cfg.frequency)),
+            )
+        }
+        RegimenSchedule::OralEstradiol(cfg) => (
+            "Oral Estradiol",
+            format!(
+                "{:?}, {:.2} {:?} {}",
+                cfg.kind,
+                cfg.dose,
+                cfg.unit,
+                cadence(cfg.frequency)
+            ),
+        ),
+        RegimenSchedule::Antiandrogen(cfg) => (
+            "Antiandrogen",
+            format!(
+                "{:?}, {:.2} {:?} {}",
+                cfg.kind,
+                cfg.dose,
+                cfg.unit,
+                cadence(cfg.frequency)
+            ),
+        ),
+        RegimenSchedule::Progesterone(cfg) => (
+            "Progesterone",
+            format!(
+                "{:?} ({:?}), {:.2} {:?} {}",
+                cfg.kind,
+                cfg.route,
+                cfg.dose,
+                cfg.unit,
+                cadence(cfg.frequency)
+            ),
+        ),
+        RegimenSchedule::Patch(cfg) => (
+            "Patch",
+            format!(
+                "{:.0} µg/day, changed {}, worn {:.1} days",
+                patch_total_strength(cfg.strength, cfg.patchQuantity),
+                cadence(cfg.frequency),
+                cfg.wearDays.unwrap_or(cfg.frequency)
+            ),
+        ),
+        RegimenSchedule::Gel(cfg) => (
+            "Gel",
+            format!(
+                "{:.2} {:?} {}",
+                gel_dose_mg(cfg.dose, cfg.pumps, cfg.mgPerPump),
+                cfg.unit,
+                cadence(cfg.frequency)
+            ),
+        ),
+    }
 }
 
 pub fn compute_fudge_factor(
@@ -369,8 +521,10 @@ mod tests {
             isSpent: None,
             spentAt: None,
             useBy: None,
+            volumeMl: None,
             createdAt: 0,
             subVials: Vec::new(),
+            pkModelId: None,
         });
         let vial_id = "vial-1".to_string();
         let vial = Some(&vial_id);
@@ -392,21 +546,6 @@ mod tests {
         assert!(injectable_dose_from_iu(&data, 10.0, vial, None).is_none());
     }
 
-    #[test]
-    fn parse_hormone_unit_all_variants() {
-        use super::parse_hormone_unit;
-        assert_eq!(parse_hormone_unit("pg/mL"), Some(HormoneUnits::E2PgMl));
-        assert_eq!(parse_hormone_unit("pmol/L"), Some(HormoneUnits::E2PmolL));
-        assert_eq!(parse_hormone_unit("ng/dL"), Some(HormoneUnits::TNgDl));
-        assert_eq!(parse_hormone_unit("nmol/L"), Some(HormoneUnits::TNmolL));
-        assert_eq!(parse_hormone_unit("mg"), Some(HormoneUnits::Mg));
-        assert_eq!(parse_hormone_unit("ng/mL"), Some(HormoneUnits::NgMl));
-        assert_eq!(parse_hormone_unit("mIU/mL"), Some(HormoneUnits::MIuMl));
-        assert_eq!(parse_hormone_unit("mIU/L"), Some(HormoneUnits::MIuL));
-        assert_eq!(parse_hormone_unit("U/L"), Some(HormoneUnits::UL));
-        assert_eq!(parse_hormone_unit("invalid"), None);
-    }
-
     #[test]
     fn parse_length_unit_variants() {
         use super::parse_length_unit;
@@ -416,16 +555,6 @@ mod tests {
         assert_eq!(parse_length_unit("m"), None);
     }
 
-    #[test]
-    fn hormone_unit_label_roundtrips() {
-        use super::{hormone_unit_label, parse_hormone_unit};
-        let units = ["pg/mL", "pmol/L", "ng/dL", "nmol/L", "mg", "ng/mL", "mIU/mL", "mIU/L", "U/L"];
-        for label in units {
-            let parsed = parse_hormone_unit(label).unwrap();
-            assert_eq!(hormone_unit_label(&parsed), label);
-        }
-    }
-
     #[test]
     fn fmt_decimal_basic() {
         use super::fmt_decimal;
@@ -476,15 +605,20 @@ mod tests {
     #[test]
     fn estradiol_conversion_factor_values() {
         use super::estradiol_conversion_factor;
-        assert_eq!(estradiol_conversion_factor(&HormoneUnits::E2PmolL), 3.6713);
+        // 1000 / 272.38 g/mol = 3.67134..., the old hard-coded 3.6713 to four
+        // decimals.
+        let factor = estradiol_conversion_factor(&HormoneUnits::E2PmolL);
+        assert_eq!((factor * 1e4).round() / 1e4, 3.6713);
         assert_eq!(estradiol_conversion_factor(&HormoneUnits::E2PgMl), 1.0);
     }
 
     #[test]
     fn convert_testosterone_to_ng_dl_from_nmol() {
         use super::convert_testosterone_to_ng_dl;
+        // 288.431 g/mol / 10, as /api/convert computes it. The old 28.818
+        // assumed a molar mass of 288.18, which matches no testosterone form.
         let result = convert_testosterone_to_ng_dl(1.0, &HormoneUnits::TNmolL);
-        assert!((result - 28.818).abs() < 0.001);
+        assert!((result - 28.843).abs() < 0.001, "got {result}");
     }
 
     #[test]
@@ -511,11 +645,84 @@ mod tests {
         assert_eq!(convert_lh_to_miu_ml(3000.0, &HormoneUnits::MIuL), 3.0);
     }
 
+    #[test]
+    fn convert_prolactin_to_miu_l_from_ng_ml() {
+        use super::convert_prolactin_to_miu_l;
+        let result = convert_prolactin_to_miu_l(10.0, &HormoneUnits::NgMl);
+        assert!((result - 212.0).abs() < 0.001);
+        assert_eq!(convert_prolactin_to_miu_l(212.0, &HormoneUnits::MIuL), 212.0);
+    }
+
+    #[test]
+    fn range_flag_note_describes_flags() {
+        use super::range_flag_note;
+        use hrt_shared::quantity::{BloodAnalyte, Quantity};
+        use hrt_shared::ranges::{LevelAssessment, RangeCheck, RangeFlag, ReferenceRange};
+        let range =
+            ReferenceRange::new(BloodAnalyte::Testosterone, None, Some(50.0), HormoneUnits::TNgDl);
+        let mut assessment = LevelAssessment {
+            analyte: BloodAnalyte::Testosterone,
+            level: Quantity::new(80.0, HormoneUnits::TNgDl),
+            target: Some(RangeCheck { range: range.clone(), flag: RangeFlag::High }),
+            reference: None,
+        };
+        assert_eq!(
+            range_flag_note(&assessment).as_deref(),
+            Some("Testosterone above target (≤ 50 ng/dL)")
+        );
+        assessment.target = Some(RangeCheck { range, flag: RangeFlag::InRange });
+        assert_eq!(range_flag_note(&assessment), None);
+    }
+
+    #[test]
+    fn fmt_lab_result_includes_range_and_flag() {
+        use super::fmt_lab_result;
+        use hrt_shared::lab_results::LabResult;
+        let mut result = LabResult {
+            name: "Potassium".to_string(),
+            value: 5.6,
+            unit: Some("mmol/L".to_string()),
+            referenceLow: Some(3.5),
+            referenceHigh: Some(5.1),
+            flag: None,
+        };
+        assert_eq!(fmt_lab_result(&result), "Potassium: 5.6 mmol/L (3.5–5.1, high)");
+        result.referenceLow = None;
+        result.value = 4.0;
+        assert_eq!(fmt_lab_result(&result), "Potassium: 4 mmol/L (≤ 5.1)");
+        result.unit = None;
+        result.referenceHigh = None;
+        assert_eq!(fmt_lab_result(&result), "Potassium: 4");
+    }
+
+    #[test]
+    fn calculated_level_notes_skip_entered_fai() {
+        use super::{calculated_level_notes, effective_fai, Quantity};
+        use hrt_shared::types::BloodTest;
+        let mut test = BloodTest {
+            testosterone: Some(Quantity::new(1.0, HormoneUnits::TNmolL)),
+            shbg: Some(Quantity::new(50.0, HormoneUnits::TNmolL)),
+            ..BloodTest::default()
+        };
+        let notes = calculated_level_notes(&test);
+        assert_eq!(notes[0], "FAI (calc): 2");
+        assert!(notes[1].starts_with("Free T (calc): "), "{notes:?}");
+        assert_eq!(notes.last().unwrap(), "Albumin assumed 4.3 g/dL");
+        assert_eq!(effective_fai(&test), Some(2.0));
+
+        test.freeAndrogenIndex = Some(3.0);
+        test.albuminLevel = Some(4.0);
+        let notes = calculated_level_notes(&test);
+        assert!(notes.iter().all(|note| !note.starts_with("FAI") && !note.starts_with("Albumin")));
+        assert_eq!(effective_fai(&test), Some(3.0));
+    }
+
     #[test]
     fn convert_progesterone_to_ng_ml_from_nmol() {
         use super::convert_progesterone_to_ng_ml;
+        // 314.469 g/mol / 1000; the old factor was this rounded to 0.31.
         let result = convert_progesterone_to_ng_ml(10.0, &HormoneUnits::TNmolL);
-        assert!((result - 3.1).abs() < 0.001);
+        assert!((result - 3.1447).abs() < 0.001, "got {result}");
     }
 
     #[test]
@@ -609,8 +816,10 @@ mod tests {
             isSpent: None,
             spentAt: None,
             useBy: None,
+            volumeMl: None,
             createdAt: 0,
             subVials: Vec::new(),
+            pkModelId: None,
         });
         let vid = "v1".to_string();
         let result = format_injectable_dose(&data, 4.0, &HormoneUnits::Mg, Some(&vid), None, true);
@@ -648,8 +857,10 @@ mod tests {
             isSpent: None,
             spentAt: None,
             useBy: None,
+            volumeMl: None,
             createdAt: 0,
             subVials: Vec::new(),
+            pkModelId: None,
         });
         let missing = "nonexistent".to_string();
         let schedule_id = "schedule-vial".to_string();